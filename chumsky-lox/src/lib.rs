@@ -10,19 +10,44 @@
 //!
 //! let input = r#"print "Hello, world!";"#;
 //! let result = parse_program(input);
-//! // Note: currently has linker issues on macOS
 //! ```
 
 mod parser;
 
-pub use parser::parse_program;
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use parser::{parse_program, parse_program_diagnostic, parse_program_with_max_depth};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "chumsky";
-pub const PARSER_VERSION: &str = "1.0.0-alpha.8";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "Parser combinator focused on excellent error messages - rewritten for 1.0.0-alpha";
 
+/// This parser behind the common `LoxParser` interface
+pub struct ChumskyParser;
+
+impl LoxParser for ChumskyParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: false,
+            spans: false,
+            error_recovery: false,
+            streaming: false,
+            extensions: LanguageOptions::STRICT,
+            lexical: parser::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parse_program_with_max_depth(source, options.max_depth).map_err(|error| ParseError::syntax(*error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,7 +90,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -75,4 +100,19 @@ mod tests {
             _ => panic!("Expected variable declaration"),
         }
     }
+
+    #[test]
+    fn test_unary_operand_stops_at_binary_operators() {
+        let result = parse_program("print -1 + 2;").unwrap();
+        let minus_one = Expr::Unary { operator: UnaryOp::Minus, operand: Box::new(Expr::Literal(Value::Number(1.0))) };
+        assert_eq!(result.statements[0], Stmt::Print(Expr::Binary {
+            left: Box::new(minus_one),
+            operator: BinaryOp::Add,
+            right: Box::new(Expr::Literal(Value::Number(2.0))),
+        }));
+
+        // A long flat sum of negations nests no deeper than one of its terms
+        let input = format!("print {};", vec!["-1"; 20_000].join(" + "));
+        assert!(parse_program(&input).is_ok());
+    }
 }
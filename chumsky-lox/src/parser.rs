@@ -1,10 +1,14 @@
 //! Chumsky-based parser for the Lox language (1.0.0-alpha.8)
 
 use chumsky::prelude::*;
-use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+use lox_ast::{BinaryOp, Diagnostic, Expr, LanguageOptions, LexOptions, Program, Stmt, UnaryOp, Value, hints, is_keyword, lexical};
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+
+/// Errors carry the span they are about, so they can become diagnostics
+type Extra<'src> = extra::Err<Rich<'src, char>>;
 
 /// Parse whitespace and comments
-fn whitespace<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
+fn whitespace<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
     let comment = just("//")
         .then(any().and_is(just('\n').not()).repeated())
         .ignored();
@@ -17,15 +21,43 @@ fn whitespace<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
     .ignored()
 }
 
-/// Parse an identifier
-fn identifier<'src>() -> impl Parser<'src, &'src str, String> + Clone {
-    text::ident()
+/// Characters that may start an identifier
+const IDENT_START: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_";
+
+/// Characters that may continue an identifier
+const IDENT_CONTINUE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_0123456789";
+
+/// Parse a run of identifier characters. `text::ident` rejects its input with
+/// `try_map`, which drops the error an earlier alternative recorded further
+/// along, and `filter` reports one character late, so this uses `one_of`,
+/// which reports at the character and keeps the furthest error
+fn word<'src>() -> impl Parser<'src, &'src str, &'src str, Extra<'src>> + Clone {
+    one_of(IDENT_START).then(one_of(IDENT_CONTINUE).repeated()).to_slice()
+}
+
+/// Parse a run of decimal digits, with `one_of` for the same reason as `word`
+fn digits<'src>() -> impl Parser<'src, &'src str, &'src str, Extra<'src>> + Clone {
+    one_of("0123456789").repeated().at_least(1).to_slice()
+}
+
+/// Parse `keyword`, but not as the start of a longer identifier
+fn keyword<'src>(keyword: &'static str) -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
+    just(keyword).then_ignore(one_of(IDENT_CONTINUE).not()).ignored()
+}
+
+/// Parse an identifier that is not a reserved word
+fn identifier<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> + Clone {
+    let reserved = word().filter(|word: &&str| is_keyword(word));
+    // Looking ahead for a reserved word reports it from its start, not its end
+    reserved
+        .not()
+        .ignore_then(word())
         .map(|s: &str| s.to_string())
         .padded_by(whitespace())
 }
 
 /// Parse a string literal
-fn string_literal<'src>() -> impl Parser<'src, &'src str, String> + Clone {
+fn string_literal<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> + Clone {
     just('"')
         .ignore_then(
             any().and_is(just('"').not())
@@ -38,30 +70,30 @@ fn string_literal<'src>() -> impl Parser<'src, &'src str, String> + Clone {
 }
 
 /// Parse a number literal
-fn number_literal<'src>() -> impl Parser<'src, &'src str, f64> + Clone {
-    text::int(10)
-        .then(just('.').then(text::digits(10)).or_not())
+fn number_literal<'src>() -> impl Parser<'src, &'src str, f64, Extra<'src>> + Clone {
+    digits()
+        .then(just('.').then(digits()).or_not())
         .to_slice()
         .map(|s: &str| s.parse().unwrap())
         .padded_by(whitespace())
 }
 
 /// Parse a boolean literal
-fn boolean_literal<'src>() -> impl Parser<'src, &'src str, bool> + Clone {
+fn boolean_literal<'src>() -> impl Parser<'src, &'src str, bool, Extra<'src>> + Clone {
     choice((
-        just("true").to(true),
-        just("false").to(false),
+        keyword("true").to(true),
+        keyword("false").to(false),
     ))
     .padded_by(whitespace())
 }
 
 /// Parse nil literal
-fn nil_literal<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
-    just("nil").ignored().padded_by(whitespace())
+fn nil_literal<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
+    keyword("nil").ignored().padded_by(whitespace())
 }
 
 /// Parse a literal value
-fn literal<'src>() -> impl Parser<'src, &'src str, Value> + Clone {
+fn literal<'src>() -> impl Parser<'src, &'src str, Value, Extra<'src>> + Clone {
     choice((
         nil_literal().to(Value::Nil),
         boolean_literal().map(Value::Bool),
@@ -71,7 +103,7 @@ fn literal<'src>() -> impl Parser<'src, &'src str, Value> + Clone {
 }
 
 /// Forward declaration for recursive parsing
-fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
+fn expression<'src>() -> impl Parser<'src, &'src str, Expr, Extra<'src>> + Clone {
     recursive(|expr| {
         let atom = choice((
             literal().map(Expr::Literal),
@@ -81,23 +113,24 @@ fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
                 .map(|e| Expr::Grouping(Box::new(e))),
         ));
 
-        let unary = choice((
+        // The operand is another unary expression, so `-1 + 2` negates only the `1`
+        let unary = recursive(|unary| choice((
             just('!')
                 .padded_by(whitespace())
-                .ignore_then(expr.clone())
+                .ignore_then(unary.clone())
                 .map(|operand| Expr::Unary {
                     operator: UnaryOp::Not,
                     operand: Box::new(operand),
                 }),
             just('-')
                 .padded_by(whitespace())
-                .ignore_then(expr.clone())
+                .ignore_then(unary)
                 .map(|operand| Expr::Unary {
                     operator: UnaryOp::Minus,
                     operand: Box::new(operand),
                 }),
             atom,
-        ));
+        )));
 
         let factor = unary.clone().foldl(
             choice((
@@ -158,7 +191,7 @@ fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
         );
 
         let logical_and = equality.clone().foldl(
-            just("and")
+            keyword("and")
                 .padded_by(whitespace())
                 .to(BinaryOp::And)
                 .then(equality)
@@ -171,7 +204,7 @@ fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
         );
 
         let logical_or = logical_and.clone().foldl(
-            just("or")
+            keyword("or")
                 .padded_by(whitespace())
                 .to(BinaryOp::Or)
                 .then(logical_and)
@@ -183,7 +216,8 @@ fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
             },
         );
 
-        let assignment = choice((
+        // Assignment
+        choice((
             identifier()
                 .then_ignore(just('=').padded_by(whitespace()))
                 .then(expr.clone())
@@ -192,15 +226,13 @@ fn expression<'src>() -> impl Parser<'src, &'src str, Expr> + Clone {
                     value: Box::new(value),
                 }),
             logical_or,
-        ));
-
-        assignment
+        ))
     })
 }
 
 /// Parse a print statement
-fn print_stmt<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
-    just("print")
+fn print_stmt<'src>() -> impl Parser<'src, &'src str, Stmt, Extra<'src>> + Clone {
+    keyword("print")
         .padded_by(whitespace())
         .ignore_then(expression())
         .then_ignore(just(';').padded_by(whitespace()))
@@ -208,8 +240,8 @@ fn print_stmt<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
 }
 
 /// Parse a variable declaration
-fn var_declaration<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
-    just("var")
+fn var_declaration<'src>() -> impl Parser<'src, &'src str, Stmt, Extra<'src>> + Clone {
+    keyword("var")
         .padded_by(whitespace())
         .ignore_then(identifier())
         .then(
@@ -219,18 +251,18 @@ fn var_declaration<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
                 .or_not()
         )
         .then_ignore(just(';').padded_by(whitespace()))
-        .map(|(name, initializer)| Stmt::VarDeclaration { name, initializer })
+        .map(|(name, initializer)| Stmt::VarDeclaration { name, initializer, type_annotation: None, doc: None })
 }
 
 /// Parse an expression statement
-fn expr_stmt<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
+fn expr_stmt<'src>() -> impl Parser<'src, &'src str, Stmt, Extra<'src>> + Clone {
     expression()
         .then_ignore(just(';').padded_by(whitespace()))
         .map(Stmt::Expression)
 }
 
 /// Parse a statement
-fn statement<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
+fn statement<'src>() -> impl Parser<'src, &'src str, Stmt, Extra<'src>> + Clone {
     choice((
         print_stmt(),
         var_declaration(),
@@ -239,23 +271,50 @@ fn statement<'src>() -> impl Parser<'src, &'src str, Stmt> + Clone {
 }
 
 /// Parse a program (list of statements)
-fn program<'src>() -> impl Parser<'src, &'src str, Program> + Clone {
+fn program<'src>() -> impl Parser<'src, &'src str, Program, Extra<'src>> + Clone {
     whitespace()
         .ignore_then(statement().repeated().collect::<Vec<_>>())
         .then_ignore(end())
         .map(Program::new)
 }
 
+/// The lexical extensions this parser implements: none
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: false,
+    number_extensions: false,
+    block_comments: false,
+    doc_comments: false,
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 256 * 1024;
+
 /// Parse a complete Lox program from a string
 pub fn parse_program(input: &str) -> Result<Program, String> {
-    match program().parse(input).into_result() {
-        Ok(program) => Ok(program),
-        Err(errors) => {
-            let error_messages: Vec<String> = errors
-                .into_iter()
-                .map(|e| format!("{:?}", e))
-                .collect();
-            Err(error_messages.join("; "))
+    parse_program_diagnostic(input).map_err(|error| error.describe(input))
+}
+
+/// Parse a complete program, reporting the first failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<Program, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        let errors = match program().parse(input).into_result() {
+            Ok(program) => return Ok(program),
+            Err(errors) => errors,
+        };
+        if let Err(lex) = lexical::validate(input, &LEX_OPTIONS) {
+            return Err(Box::new(lex.into()));
         }
-    }
-}
\ No newline at end of file
+        let position = errors.first().map_or(input.len(), |error| error.span().start);
+        let diagnostic =
+            Diagnostic::unexpected(input, position, &LEX_OPTIONS).with_unclosed_delimiter(input, &LEX_OPTIONS);
+        Err(Box::new(hints::explain(input, diagnostic, &LEX_OPTIONS, &LanguageOptions::STRICT)))
+    })
+}
@@ -1,5 +1,5 @@
 use combine::{
-    between, choice, many, many1, optional, token,
+    attempt, between, choice, many, many1, not_followed_by, optional, unexpected_any, value,
     parser::{
         char::{char, digit, letter, space, string},
         combinator::no_partial,
@@ -9,19 +9,48 @@ use combine::{
     stream::Stream,
     Parser, ParseError, EasyParser,
 };
-use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+use lox_ast::{BinaryOp, Diagnostic, Expr, LanguageOptions, LexOptions, Program, Stmt, UnaryOp, Value, hints, lexical};
+use lox_ast::lexical::KEYWORDS;
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+
+/// The lexical extensions this parser implements: none
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: false,
+    number_extensions: false,
+    block_comments: false,
+    doc_comments: false,
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 128 * 1024;
 
 pub fn parse_program(input: &str) -> Result<Program, String> {
-    match program().easy_parse(input) {
-        Ok((program, remaining)) => {
-            if remaining.is_empty() {
-                Ok(program)
-            } else {
-                Err(format!("Unexpected remaining input: {}", remaining))
-            }
+    parse_program_diagnostic(input).map_err(|error| error.describe(input))
+}
+
+/// Parse a complete program, reporting a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<Program, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        let position = match program().easy_parse(input) {
+            Ok((program, "")) => return Ok(program),
+            Ok((_, remaining)) => input.len() - remaining.len(),
+            Err(e) => e.position.translate_position(input),
+        };
+        if let Err(lex) = lexical::validate(input, &LEX_OPTIONS) {
+            return Err(Box::new(lex.into()));
         }
-        Err(e) => Err(format!("Parse error: {}", e)),
-    }
+        let diagnostic =
+            Diagnostic::unexpected(input, position, &LEX_OPTIONS).with_unclosed_delimiter(input, &LEX_OPTIONS);
+        Err(Box::new(hints::explain(input, diagnostic, &LEX_OPTIONS, &LanguageOptions::STRICT)))
+    })
 }
 
 // Whitespace and comments
@@ -44,7 +73,7 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     (
-        string("//"),
+        attempt(string("//")),
         many::<Vec<_>, _, _>(combine::satisfy(|c| c != '\n' && c != '\r')),
         optional(char('\n').or(char('\r'))),
     )
@@ -52,7 +81,7 @@ where
 }
 
 // Identifiers
-fn identifier<Input>() -> impl Parser<Input, Output = String>
+fn word<Input>() -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
@@ -69,17 +98,39 @@ where
         })
 }
 
+// A reserved word, which is never an identifier
+fn reserved_word<Input>() -> impl Parser<Input, Output = &'static str>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt(word().then(|word| match KEYWORDS.iter().find(|keyword| **keyword == word) {
+        Some(keyword) => value(*keyword).left(),
+        None => unexpected_any("identifier").right(),
+    }))
+}
+
+// An identifier; checking for a reserved word first leaves the error at its start
+fn identifier<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    not_followed_by(reserved_word()).with(word())
+}
+
 // Keywords
 fn keyword<Input>(word: &'static str) -> impl Parser<Input, Output = ()>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    string(word)
-        .skip(combine::not_followed_by(
+    attempt(
+        string(word).skip(combine::not_followed_by(
             letter().or(digit()).or(char('_'))
-        ))
-        .map(|_| ())
+        )),
+    )
+    .map(|_| ())
 }
 
 // Literals
@@ -145,7 +196,13 @@ where
         boolean_literal().map(|b| Expr::Literal(Value::Bool(b))),
         nil_literal().map(|_| Expr::Literal(Value::Nil)),
         identifier().map(Expr::Variable),
-        between(char('(').skip(ws()), char(')'), expression()),
+        // Recursing through a function parser keeps the return types finite
+        between(
+            char('(').skip(ws()),
+            char(')'),
+            parser(|input: &mut Input| expression().parse_stream(input).into_result()),
+        )
+        .map(|expr| Expr::Grouping(Box::new(expr))),
     ))
 }
 
@@ -161,7 +218,7 @@ where
     ));
 
     choice((
-        (unary_op.skip(ws()), primary())
+        (unary_op.skip(ws()), parser(|input: &mut Input| unary().parse_stream(input).into_result()))
             .map(|(op, expr)| Expr::Unary {
                 operator: op,
                 operand: Box::new(expr),
@@ -215,8 +272,9 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     let op = choice((
-        string(">=").skip(ws()).map(|_| BinaryOp::GreaterEqual),
-        string("<=").skip(ws()).map(|_| BinaryOp::LessEqual),
+        // On a lone `<` or `>`, `string` fails after consuming it, so back out to try `char`
+        attempt(string(">=")).skip(ws()).map(|_| BinaryOp::GreaterEqual),
+        attempt(string("<=")).skip(ws()).map(|_| BinaryOp::LessEqual),
         char('>').skip(ws()).map(|_| BinaryOp::Greater),
         char('<').skip(ws()).map(|_| BinaryOp::Less),
     ));
@@ -236,8 +294,9 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     let op = choice((
-        string("==").skip(ws()).map(|_| BinaryOp::Equal),
-        string("!=").skip(ws()).map(|_| BinaryOp::NotEqual),
+        // `string` consumes the `=` of an assignment or the `!` of a negation before failing
+        attempt(string("==")).skip(ws()).map(|_| BinaryOp::Equal),
+        attempt(string("!=")).skip(ws()).map(|_| BinaryOp::NotEqual),
     ));
 
     chainl1(comparison().skip(ws()), op.map(|op| {
@@ -287,12 +346,13 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     choice((
+        // Only the `name =` is backtracked over, so an error in the value is
+        // reported where it is; the value may be another, right-associative, assignment
         (
-            identifier().skip(ws()),
-            char('=').skip(ws()),
-            logical_or(),
+            attempt(identifier().skip(ws()).skip(char('=')).skip(not_followed_by(char('=')))).skip(ws()),
+            parser(|input: &mut Input| assignment().parse_stream(input).into_result()),
         )
-            .map(|(name, _, value)| Expr::Assignment {
+            .map(|(name, value)| Expr::Assignment {
                 name,
                 value: Box::new(value),
             }),
@@ -333,7 +393,7 @@ where
         optional(char('=').skip(ws()).with(expression())).skip(ws()),
         char(';'),
     )
-        .map(|(_, name, initializer, _)| Stmt::VarDeclaration { name, initializer })
+        .map(|(_, name, initializer, _)| Stmt::VarDeclaration { name, initializer, type_annotation: None, doc: None })
 }
 
 fn expression_statement<Input>() -> impl Parser<Input, Output = Stmt>
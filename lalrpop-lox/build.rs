@@ -2,4 +2,16 @@ extern crate lalrpop;
 
 fn main() {
     lalrpop::process_root().unwrap();
+
+    // The version of lalrpop the manifest requires, which `version()` reports.
+    // No rerun directive: lalrpop prints none for the grammar, so the script
+    // reruns whenever anything in the package, the manifest included, changes
+    let manifest = std::fs::read_to_string("Cargo.toml").expect("the manifest is readable");
+    let requirement = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("lalrpop = "))
+        .map(|entry| entry.split_once("version").map_or(entry, |(_, rest)| rest))
+        .and_then(|entry| entry.split('"').nth(1))
+        .expect("the manifest depends on lalrpop");
+    println!("cargo:rustc-env=LIBRARY_VERSION={}", requirement);
 }
@@ -0,0 +1,210 @@
+//! Hand-written lexer feeding the LALRPOP grammar
+//!
+//! LALRPOP's built-in lexer only understands regular expressions, which cannot
+//! express escape sequences as an opt-in feature or report where an unterminated
+//! string began, so literals are scanned with the shared rules in `lox_ast::lexical`.
+
+use lox_ast::{LanguageOptions, LexOptions};
+use lox_ast::lexical::{self, is_identifier_continue, is_identifier_start};
+
+/// Lexical extensions enabled through this crate's cargo features
+pub const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+pub type Spanned<Tok, Loc, Error> = Result<(Loc, Tok, Loc), Error>;
+
+/// An error from the lexer or a grammar action, reported with the line and
+/// column of `offset` once parsing stops
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatedError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl LocatedError {
+    pub fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self { offset, message: message.into() }
+    }
+}
+
+impl From<lox_ast::LexError> for LocatedError {
+    fn from(error: lox_ast::LexError) -> Self {
+        Self::new(error.span.start, error.kind.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok<'input> {
+    // Keywords
+    And,
+    Class,
+    Else,
+    False,
+    For,
+    Fun,
+    If,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    True,
+    Var,
+    While,
+    // Keywords of the `break`/`continue` extension
+    Break,
+    Continue,
+    // Keyword of the imports extension
+    Import,
+    // Operators and punctuation
+    EqualEqual,
+    BangEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Bang,
+    Equal,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Dot,
+    Semicolon,
+    // Punctuation of the conditional operator and type annotation extensions
+    Question,
+    Colon,
+    // Literals
+    Number(f64),
+    String(String),
+    Identifier(&'input str),
+}
+
+pub struct Lexer<'input> {
+    input: &'input str,
+    pos: usize,
+    options: LanguageOptions,
+}
+
+impl<'input> Lexer<'input> {
+    /// Tokenize `input`, producing the extra tokens of any extensions in `options`
+    pub fn new(input: &'input str, options: LanguageOptions) -> Self {
+        Lexer { input, pos: 0, options }
+    }
+
+    /// End of the run of characters matching `predicate` that begins at `start`
+    fn scan_while(&self, start: usize, predicate: impl Fn(char) -> bool) -> usize {
+        let rest = &self.input[start..];
+        start + rest.find(|c| !predicate(c)).unwrap_or(rest.len())
+    }
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Spanned<Tok<'input>, usize, LocatedError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Doc comments are looked up by offset in the grammar, so only their length matters here
+        match lexical::skip_trivia(&self.input[self.pos..], &LEX_OPTIONS) {
+            Ok(trivia) => self.pos += trivia.len,
+            Err(e) => {
+                let error = e.offset(self.pos);
+                self.pos = self.input.len();
+                return Some(Err(error.into()));
+            }
+        }
+
+        let start = self.pos;
+        let rest = &self.input[start..];
+        let c = rest.chars().next()?;
+
+        let two_char = match rest.get(..2) {
+            Some("==") => Some(Tok::EqualEqual),
+            Some("!=") => Some(Tok::BangEqual),
+            Some("<=") => Some(Tok::LessEqual),
+            Some(">=") => Some(Tok::GreaterEqual),
+            _ => None,
+        };
+        if let Some(tok) = two_char {
+            self.pos += 2;
+            return Some(Ok((start, tok, self.pos)));
+        }
+
+        let (tok, end) = match c {
+            '<' => (Tok::Less, start + 1),
+            '>' => (Tok::Greater, start + 1),
+            '+' => (Tok::Plus, start + 1),
+            '-' => (Tok::Minus, start + 1),
+            '*' => (Tok::Star, start + 1),
+            '/' => (Tok::Slash, start + 1),
+            '!' => (Tok::Bang, start + 1),
+            '=' => (Tok::Equal, start + 1),
+            '(' => (Tok::LeftParen, start + 1),
+            ')' => (Tok::RightParen, start + 1),
+            '{' => (Tok::LeftBrace, start + 1),
+            '}' => (Tok::RightBrace, start + 1),
+            ',' => (Tok::Comma, start + 1),
+            ';' => (Tok::Semicolon, start + 1),
+            '?' if self.options.conditional => (Tok::Question, start + 1),
+            ':' if self.options.conditional || self.options.type_annotations => (Tok::Colon, start + 1),
+            '"' => match lexical::scan_string(rest, &LEX_OPTIONS) {
+                Ok((value, len)) => (Tok::String(value.into_owned()), start + len),
+                Err(e) => {
+                    // Nothing after a malformed literal can be tokenized reliably
+                    self.pos = self.input.len();
+                    return Some(Err(e.offset(start).into()));
+                }
+            },
+            _ if lexical::starts_number(rest) => match lexical::scan_number(rest, &LEX_OPTIONS) {
+                Ok((value, len)) => (Tok::Number(value), start + len),
+                Err(e) => {
+                    self.pos = self.input.len();
+                    return Some(Err(e.offset(start).into()));
+                }
+            },
+            '.' => (Tok::Dot, start + 1),
+            c if is_identifier_start(c) => {
+                let end = self.scan_while(start, is_identifier_continue);
+                let tok = match &self.input[start..end] {
+                    "and" => Tok::And,
+                    "class" => Tok::Class,
+                    "else" => Tok::Else,
+                    "false" => Tok::False,
+                    "for" => Tok::For,
+                    "fun" => Tok::Fun,
+                    "if" => Tok::If,
+                    "nil" => Tok::Nil,
+                    "or" => Tok::Or,
+                    "print" => Tok::Print,
+                    "return" => Tok::Return,
+                    "super" => Tok::Super,
+                    "this" => Tok::This,
+                    "true" => Tok::True,
+                    "var" => Tok::Var,
+                    "while" => Tok::While,
+                    "break" if self.options.break_continue => Tok::Break,
+                    "continue" if self.options.break_continue => Tok::Continue,
+                    "import" if self.options.imports => Tok::Import,
+                    word => Tok::Identifier(word),
+                };
+                (tok, end)
+            }
+            _ => {
+                self.pos = self.input.len();
+                return Some(Err(LocatedError::new(start, "Invalid token")));
+            }
+        };
+
+        self.pos = end;
+        Some(Ok((start, tok, end)))
+    }
+}
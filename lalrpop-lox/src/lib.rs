@@ -14,18 +14,72 @@
 //! assert!(result.is_ok());
 //! ```
 
+mod lexer;
 mod parser;
 
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_ast::Diagnostic;
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "lalrpop";
-pub const PARSER_VERSION: &str = "0.20";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "LR(1) parser generator with excellent performance";
 
 /// Parse a Lox program from input string
 pub fn parse_program(input: &str) -> Result<Program, String> {
-    parser::parse_program(input)
+    parse_program_with(input, &LanguageOptions::default())
+}
+
+/// Parse a Lox program, accepting the language extensions in `options`
+pub fn parse_program_with(input: &str, options: &LanguageOptions) -> Result<Program, String> {
+    parser::parse_program(input, options).map_err(|error| error.describe(input))
+}
+
+/// Parse a Lox program, accepting the language extensions in `options`, and
+/// report a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str, options: &LanguageOptions) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, options, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+///
+/// The LR parser keeps its stack on the heap, so the limit only bounds the
+/// trees handed back, not the parse itself.
+pub fn parse_program_with_max_depth(
+    input: &str,
+    options: &LanguageOptions,
+    max_depth: usize,
+) -> Result<Program, Box<Diagnostic>> {
+    depth::check_depth(input, max_depth, &lexer::LEX_OPTIONS)?;
+    parser::parse_program(input, options)
+}
+
+/// This parser behind the common `LoxParser` interface
+pub struct LalrpopParser;
+
+impl LoxParser for LalrpopParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: true,
+            spans: false,
+            error_recovery: false,
+            streaming: false,
+            extensions: LanguageOptions::EXTENDED,
+            lexical: lexer::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parse_program_with_max_depth(source, &options.language, options.max_depth).map_err(|error| ParseError::syntax(*error))
+    }
 }
 
 #[cfg(test)]
@@ -70,7 +124,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -88,7 +142,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(initializer.is_none());
             }
@@ -253,7 +307,7 @@ mod tests {
 
         // Check first statement: var a = 10;
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "a");
                 assert!(matches!(initializer, Some(Expr::Literal(Value::Number(10.0)))));
             }
@@ -268,4 +322,113 @@ mod tests {
             _ => panic!("Expected print statement"),
         }
     }
+
+    #[test]
+    fn test_dangling_else() {
+        let input = "if (a) if (b) print 1; else print 2;";
+        let result = parse_program(input).unwrap();
+
+        // The else belongs to the inner if
+        match &result.statements[0] {
+            Stmt::If { then_branch, else_branch: None, .. } => {
+                assert!(matches!(**then_branch, Stmt::If { else_branch: Some(_), .. }));
+            }
+            _ => panic!("Expected if statement without else"),
+        }
+    }
+
+    #[test]
+    fn test_functions_and_classes() {
+        let input = r#"
+            fun add(a, b) { return a + b; }
+            class Point < Base {
+                init(x) { this.x = x; }
+                sum() { return super.sum() + add(this.x, 1); }
+            }
+            for (var i = 0; i < 3; i = i + 1) while (false) {}
+        "#;
+        let result = parse_program(input).unwrap();
+
+        assert_eq!(result.statements.len(), 3);
+        match &result.statements[0] {
+            Stmt::Function { name, params, .. } => {
+                assert_eq!(name, "add");
+                assert_eq!(params, &["a", "b"]);
+            }
+            _ => panic!("Expected function declaration"),
+        }
+        match &result.statements[1] {
+            Stmt::Class { superclass, methods, .. } => {
+                assert_eq!(superclass.as_deref(), Some("Base"));
+                assert_eq!(methods.len(), 2);
+            }
+            _ => panic!("Expected class declaration"),
+        }
+        assert!(matches!(result.statements[2], Stmt::For { .. }));
+    }
+
+    #[test]
+    fn test_language_extensions() {
+        let input = r#"
+            var f = fun (a, b) { return a > b ? a : b; };
+            while (true) { if (x) break; else continue; }
+            x = 1, 2;
+        "#;
+        let result = parse_program_with(input, &LanguageOptions::EXTENDED).unwrap();
+
+        assert_eq!(result.statements.len(), 3);
+        match &result.statements[0] {
+            Stmt::VarDeclaration { initializer: Some(Expr::Lambda { params, body }), .. } => {
+                assert_eq!(params, &["a", "b"]);
+                assert!(matches!(body[0], Stmt::Return { value: Some(Expr::Conditional { .. }) }));
+            }
+            _ => panic!("Expected lambda"),
+        }
+        match &result.statements[1] {
+            Stmt::While { body, .. } => match &**body {
+                Stmt::Block(stmts) => assert!(matches!(
+                    &stmts[0],
+                    Stmt::If { then_branch, else_branch: Some(else_branch), .. }
+                        if **then_branch == Stmt::Break && **else_branch == Stmt::Continue
+                )),
+                _ => panic!("Expected block"),
+            },
+            _ => panic!("Expected while loop"),
+        }
+        // The comma operator binds looser than assignment, but not inside call arguments
+        assert!(matches!(
+            &result.statements[2],
+            Stmt::Expression(Expr::Binary { left, operator: BinaryOp::Comma, .. })
+                if matches!(**left, Expr::Assignment { .. })
+        ));
+        let call = parse_program_with("f(1, 2);", &LanguageOptions::EXTENDED).unwrap();
+        assert!(matches!(
+            &call.statements[0],
+            Stmt::Expression(Expr::Call { arguments, .. }) if arguments.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_extensions_are_off_by_default() {
+        // `break` is an ordinary identifier in book Lox
+        assert!(parse_program("var break = 1;").is_ok());
+        assert!(parse_program("print a ? b : c;").is_err());
+        assert!(parse_program("var f = fun (a) { return a; };").is_err());
+        assert!(parse_program("print 1, 2;").is_err());
+        assert!(parse_program_with("var break = 1;", &LanguageOptions::EXTENDED).is_err());
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let options = LanguageOptions::EXTENDED;
+        let error = parse_program_with("print 1;\n  break;", &options).unwrap_err();
+        assert_eq!(error, "'break' outside of a loop at line 2, column 3");
+
+        // A function body inside a loop does not inherit it
+        let error = parse_program_with("while (true) { fun f() { continue; } }", &options).unwrap_err();
+        assert_eq!(error, "'continue' outside of a loop at line 1, column 26");
+
+        assert!(parse_program_with("for (;;) { { break; } }", &options).is_ok());
+        assert!(parse_program_with("while (a) { fun f() {} break; }", &options).is_ok());
+    }
 }
@@ -1,42 +1,55 @@
 //! Parser module for LALRPOP-generated Lox parser
 
-use lalrpop_util::{lalrpop_mod, ParseError, lexer::Token};
-use lox_ast::Program;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use lalrpop_util::{lalrpop_mod, ParseError};
+use lox_ast::{Diagnostic, LanguageOptions, Program, Span, hints, lexical};
+use crate::lexer::{LEX_OPTIONS, Lexer, LocatedError, Tok};
 
-// Include the generated parser
-lalrpop_mod!(pub lox);
+// Include the generated parser; its action signatures spell out the `for` header tuple in full
+lalrpop_mod!(#[allow(clippy::type_complexity)] pub lox);
 
-/// Parse a Lox program from input string
-pub fn parse_program(input: &str) -> Result<Program, String> {
+/// Parse a Lox program from input string, accepting the language extensions in `options`
+pub fn parse_program(input: &str, options: &LanguageOptions) -> Result<Program, Box<Diagnostic>> {
     let parser = lox::ProgramParser::new();
+    // A lexical error here is reported by the lexer when it reaches it
+    let docs = if LEX_OPTIONS.doc_comments {
+        lexical::doc_comments(input, &LEX_OPTIONS).unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
 
-    match parser.parse(input) {
+    // Raised while inside a loop, and reset inside function bodies, so `break` can be checked
+    let loop_depth = Cell::new(0);
+
+    match parser.parse(&docs, options, &loop_depth, Lexer::new(input, *options)) {
         Ok(program) => Ok(program),
-        Err(e) => Err(format_parse_error(e, input)),
+        Err(e) => Err(Box::new(hints::explain(input, parse_error_diagnostic(e, input), &LEX_OPTIONS, options))),
     }
 }
 
-/// Format LALRPOP parse errors into user-friendly messages
-fn format_parse_error(error: ParseError<usize, Token<'_>, &str>, input: &str) -> String {
+/// Turn a LALRPOP parse error into a diagnostic pointing into `input`
+fn parse_error_diagnostic(error: ParseError<usize, Tok<'_>, LocatedError>, input: &str) -> Diagnostic {
+    let expected_one_of = |expected: Vec<String>| format!("Expected one of: {}", expected.join(", "));
     match error {
         ParseError::InvalidToken { location } => {
-            format!("Invalid token at position {}", location)
+            Diagnostic::error("Invalid token", lexical::token_span(input, location, &LEX_OPTIONS))
         }
         ParseError::UnrecognizedEof { location, expected } => {
-            format!("Unexpected end of input at position {}. Expected one of: {}",
-                location, expected.join(", "))
+            Diagnostic::error("Unexpected end of input", Span::new(location, location))
+                .with_note(expected_one_of(expected))
+                .with_unclosed_delimiter(input, &LEX_OPTIONS)
         }
         ParseError::UnrecognizedToken { token: (start, _tok, end), expected } => {
-            let token_text = &input[start..end];
-            format!("Unexpected token '{}' at position {}. Expected one of: {}",
-                token_text, start, expected.join(", "))
+            Diagnostic::error(format!("Unexpected token '{}'", &input[start..end]), Span::new(start, end))
+                .with_note(expected_one_of(expected))
+                .with_unclosed_delimiter(input, &LEX_OPTIONS)
         }
         ParseError::ExtraToken { token: (start, _tok, end) } => {
-            let token_text = &input[start..end];
-            format!("Extra token '{}' at position {}", token_text, start)
+            Diagnostic::error(format!("Extra token '{}'", &input[start..end]), Span::new(start, end))
         }
         ParseError::User { error } => {
-            format!("Parse error: {}", error)
+            Diagnostic::error(error.message, lexical::token_span(input, error.offset, &LEX_OPTIONS))
         }
     }
 }
@@ -15,14 +15,40 @@
 
 mod parser;
 
-pub use parser::parse_program;
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use parser::{parse_program, parse_program_diagnostic, parse_program_with_max_depth};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "lelwel";
-pub const PARSER_VERSION: &str = "0.8";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "Resilient LL(1) parser generator with error recovery";
 
+/// This parser behind the common `LoxParser` interface
+pub struct LelwelParser;
+
+impl LoxParser for LelwelParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: false,
+            spans: false,
+            error_recovery: true,
+            streaming: false,
+            extensions: LanguageOptions::STRICT,
+            lexical: parser::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parser::parse_program_diagnostics(source, options.max_depth, usize::MAX).map_err(ParseError::Syntax)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,7 +91,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -110,4 +136,26 @@ mod tests {
             _ => panic!("Expected binary expression"),
         }
     }
+
+    #[test]
+    fn test_every_recovered_error_is_reported() {
+        let source = "var = 1;\nprint (2;\nretrun 3;";
+        let Err(ParseError::Syntax(diagnostics)) = LelwelParser.parse(source, &ParseOptions::default()) else {
+            panic!("Expected syntax errors");
+        };
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[1].secondary[0].message, "unclosed `(` opened here");
+        assert!(diagnostics[2].describe(source).ends_with("did you mean 'return'?"));
+
+        // The single-error form reports, and explains, just the first
+        assert_eq!(*parse_program_diagnostic(source).unwrap_err(), diagnostics[0]);
+    }
+
+    #[test]
+    fn test_invalid_assignment_target_is_an_error() {
+        let source = "print 1;\n1 + 2 = 3;";
+        let diagnostic = parse_program_diagnostic(source).unwrap_err();
+        assert_eq!(diagnostic.message, "Invalid assignment target");
+        assert_eq!(diagnostic.help.as_deref(), Some("only a variable or a field such as 'point.x' can be assigned"));
+    }
 }
@@ -1,6 +1,21 @@
-use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions, LexOptions, hints, is_keyword};
+use lox_ast::lexical;
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+
+/// Lexical extensions enabled through this crate's cargo features
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 64 * 1024;
 
 // Define the Token enum that Lelwel expects
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token {
     // Literals and keywords
@@ -16,7 +31,7 @@ pub enum Token {
     Error, EOF,
 }
 
-pub type Diagnostic = String;
+pub type Diagnostic = lox_ast::Diagnostic;
 // Span is defined in the generated code
 
 #[derive(Default)]
@@ -25,31 +40,34 @@ pub struct Context<'a> {
 }
 
 // Simple lexer
-fn lex(source: &str, _diags: &mut Vec<Diagnostic>) -> (Vec<Token>, Vec<Span>) {
+fn lex(source: &str, diags: &mut Vec<Diagnostic>) -> (Vec<Token>, Vec<Span>) {
     let mut tokens = Vec::new();
     let mut spans = Vec::new();
     let mut chars = source.char_indices().peekable();
 
     while let Some((start, ch)) = chars.next() {
+        // Whitespace and comments; doc comments are looked up by offset during conversion
+        match lexical::skip_trivia(&source[start..], &LEX_OPTIONS) {
+            Ok(trivia) if trivia.len > 0 => {
+                while chars.next_if(|&(pos, _)| pos < start + trivia.len).is_some() {}
+                continue;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                diags.push(e.offset(start).into());
+                tokens.push(Token::Error); spans.push(start..source.len());
+                break;
+            }
+        }
+
         match ch {
-            ' ' | '\t' | '\r' | '\n' => continue,
             '(' => { tokens.push(Token::LEFT_PAREN); spans.push(start..start+1); }
             ')' => { tokens.push(Token::RIGHT_PAREN); spans.push(start..start+1); }
             ';' => { tokens.push(Token::SEMICOLON); spans.push(start..start+1); }
             '+' => { tokens.push(Token::PLUS); spans.push(start..start+1); }
             '-' => { tokens.push(Token::MINUS); spans.push(start..start+1); }
             '*' => { tokens.push(Token::STAR); spans.push(start..start+1); }
-            '/' => {
-                if chars.peek() == Some(&(start + 1, '/')) {
-                    // Skip comment
-                    while let Some((_, ch)) = chars.next() {
-                        if ch == '\n' { break; }
-                    }
-                    continue;
-                } else {
-                    tokens.push(Token::SLASH); spans.push(start..start+1);
-                }
-            }
+            '/' => { tokens.push(Token::SLASH); spans.push(start..start+1); }
             '!' => {
                 if chars.peek() == Some(&(start + 1, '=')) {
                     chars.next();
@@ -82,23 +100,30 @@ fn lex(source: &str, _diags: &mut Vec<Diagnostic>) -> (Vec<Token>, Vec<Span>) {
                     tokens.push(Token::LESS); spans.push(start..start+1);
                 }
             }
-            '"' => {
-                let mut end = start + 1;
-                while let Some((pos, ch)) = chars.next() {
-                    end = pos + ch.len_utf8();
-                    if ch == '"' { break; }
+            '"' => match lexical::scan_string(&source[start..], &LEX_OPTIONS) {
+                Ok((_, len)) => {
+                    while chars.next_if(|&(pos, _)| pos < start + len).is_some() {}
+                    tokens.push(Token::STRING); spans.push(start..start+len);
                 }
-                tokens.push(Token::STRING); spans.push(start..end);
-            }
-            c if c.is_ascii_digit() => {
-                let mut end = start + c.len_utf8();
-                while let Some(&(pos, ch)) = chars.peek() {
-                    if ch.is_ascii_digit() || ch == '.' {
-                        end = pos + ch.len_utf8();
-                        chars.next();
-                    } else { break; }
+                Err(e) => {
+                    // The rest of the input cannot be tokenized reliably after a malformed literal
+                    diags.push(e.offset(start).into());
+                    tokens.push(Token::Error); spans.push(start..source.len());
+                    break;
+                }
+            },
+            _ if lexical::starts_number(&source[start..]) => {
+                match lexical::scan_number(&source[start..], &LEX_OPTIONS) {
+                    Ok((_, len)) => {
+                        while chars.next_if(|&(pos, _)| pos < start + len).is_some() {}
+                        tokens.push(Token::NUMBER); spans.push(start..start+len);
+                    }
+                    Err(e) => {
+                        diags.push(e.offset(start).into());
+                        tokens.push(Token::Error); spans.push(start..source.len());
+                        break;
+                    }
                 }
-                tokens.push(Token::NUMBER); spans.push(start..end);
             }
             c if c.is_ascii_alphabetic() || c == '_' => {
                 let mut end = start + c.len_utf8();
@@ -112,6 +137,14 @@ fn lex(source: &str, _diags: &mut Vec<Diagnostic>) -> (Vec<Token>, Vec<Span>) {
                 let token = match text {
                     "true" => Token::TRUE, "false" => Token::FALSE, "nil" => Token::NIL,
                     "print" => Token::PRINT, "var" => Token::VAR, "and" => Token::AND, "or" => Token::OR,
+                    // Reserved words this grammar does not use must not become identifiers
+                    _ if is_keyword(text) => {
+                        diags.push(Diagnostic::error(
+                            format!("reserved word '{}' cannot be used as an identifier", text),
+                            lox_ast::Span::new(start, end),
+                        ));
+                        Token::Error
+                    }
                     _ => Token::IDENTIFIER,
                 };
                 tokens.push(token); spans.push(start..end);
@@ -133,18 +166,37 @@ impl<'a> ParserCallbacks for Parser<'a> {
     fn create_tokens(source: &str, diags: &mut Vec<Diagnostic>) -> (Vec<Token>, Vec<Span>) {
         lex(source, diags)
     }
-    fn create_diagnostic(&self, _span: Span, message: String) -> Diagnostic { message }
+    fn create_diagnostic(&self, span: Span, message: String) -> Diagnostic {
+        // lelwel's messages read "invalid syntax, expected one of: ..."
+        let (message, expected) = message.split_once(", ").unwrap_or((&message, ""));
+        let diagnostic = Diagnostic::error(capitalize(message), span.into());
+        if expected.is_empty() { diagnostic } else { diagnostic.with_note(capitalize(expected)) }
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
 }
 
 // Simple AST conversion - focusing on basic cases first
-fn convert_cst(cst: &Cst, node: NodeRef, source: &str) -> Result<Program, String> {
+//
+// A statement the grammar accepts can still be invalid, such as an assignment
+// to `1 + 2`; the error points at that statement.
+fn convert_cst(cst: &Cst, node: NodeRef, source: &str) -> Result<Program, Box<Diagnostic>> {
+    let docs = if LEX_OPTIONS.doc_comments {
+        lexical::doc_comments(source, &LEX_OPTIONS).map_err(|e| Box::new(e.into()))?
+    } else {
+        Default::default()
+    };
     let mut statements = Vec::new();
 
     for child in cst.children(node) {
         if cst.match_rule(child, Rule::Statement) {
-            if let Ok(stmt) = convert_statement(cst, child, source) {
-                statements.push(stmt);
-            }
+            let span = cst.span(child);
+            let stmt = convert_statement(cst, child, source)
+                .map_err(|message| Box::new(Diagnostic::error(message, span.clone().into())))?;
+            statements.push(stmt.with_doc(docs.get(&span.start).cloned()));
         }
     }
 
@@ -188,7 +240,9 @@ fn convert_var_declaration(cst: &Cst, node: NodeRef, source: &str) -> Result<Stm
 
     Ok(Stmt::VarDeclaration {
         name: name.ok_or("Missing variable name")?,
-        initializer
+        type_annotation: None,
+        initializer,
+        doc: None,
     })
 }
 
@@ -202,6 +256,18 @@ fn convert_expression(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, St
 }
 
 fn convert_assignment(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
+    let children: Vec<_> = cst.children(node).collect();
+
+    // Check for assignment: logical_or [= assignment]
+    if children.len() == 3 {
+        let mut target = convert_logical_or(cst, children[0], source)?;
+        let value = convert_assignment(cst, children[2], source)?;
+        return match &mut target {
+            Expr::Variable(name) => Ok(Expr::Assignment { name: std::mem::take(name), value: Box::new(value) }),
+            _ => Err("Invalid assignment target".to_string()),
+        };
+    }
+
     for child in cst.children(node) {
         if cst.match_rule(child, Rule::LogicalOr) {
             return convert_logical_or(cst, child, source);
@@ -211,87 +277,73 @@ fn convert_assignment(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, St
 }
 
 fn convert_logical_or(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    for child in cst.children(node) {
-        if cst.match_rule(child, Rule::LogicalAnd) {
-            return convert_logical_and(cst, child, source);
-        }
-    }
-    Err("Invalid logical or".to_string())
+    convert_binary(cst, node, source, Rule::LogicalAnd, convert_logical_and)
 }
 
 fn convert_logical_and(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    for child in cst.children(node) {
-        if cst.match_rule(child, Rule::Equality) {
-            return convert_equality(cst, child, source);
-        }
-    }
-    Err("Invalid logical and".to_string())
+    convert_binary(cst, node, source, Rule::Equality, convert_equality)
 }
 
 fn convert_equality(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    for child in cst.children(node) {
-        if cst.match_rule(child, Rule::Comparison) {
-            return convert_comparison(cst, child, source);
-        }
-    }
-    Err("Invalid equality".to_string())
+    convert_binary(cst, node, source, Rule::Comparison, convert_comparison)
 }
 
 fn convert_comparison(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    for child in cst.children(node) {
-        if cst.match_rule(child, Rule::Term) {
-            return convert_term(cst, child, source);
-        }
-    }
-    Err("Invalid comparison".to_string())
+    convert_binary(cst, node, source, Rule::Term, convert_term)
 }
 
 fn convert_term(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    let children: Vec<_> = cst.children(node).collect();
-
-    // Check for binary operation: factor [+ term]
-    if children.len() == 3 {
-        let left = convert_factor(cst, children[0], source)?;
-        let right = convert_term(cst, children[2], source)?;
-        let op = if cst.match_token(children[1], Token::PLUS).is_some() {
-            BinaryOp::Add
-        } else {
-            BinaryOp::Subtract
-        };
-        return Ok(Expr::Binary { left: Box::new(left), operator: op, right: Box::new(right) });
-    }
-
-    // Single factor
-    for child in cst.children(node) {
-        if cst.match_rule(child, Rule::Factor) {
-            return convert_factor(cst, child, source);
-        }
-    }
-    Err("Invalid term".to_string())
+    convert_binary(cst, node, source, Rule::Factor, convert_factor)
 }
 
 fn convert_factor(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
-    let children: Vec<_> = cst.children(node).collect();
-
-    // Check for binary operation: unary [* factor]
-    if children.len() == 3 {
-        let left = convert_unary(cst, children[0], source)?;
-        let right = convert_factor(cst, children[2], source)?;
-        let op = if cst.match_token(children[1], Token::STAR).is_some() {
-            BinaryOp::Multiply
-        } else {
-            BinaryOp::Divide
-        };
-        return Ok(Expr::Binary { left: Box::new(left), operator: op, right: Box::new(right) });
-    }
+    convert_binary(cst, node, source, Rule::Unary, convert_unary)
+}
 
-    // Single unary
+/// Convert a binary level of the grammar, `operand (operator operand)*`,
+/// folding from the left so the operators are left-associative
+fn convert_binary(
+    cst: &Cst,
+    node: NodeRef,
+    source: &str,
+    operand_rule: Rule,
+    convert_operand: fn(&Cst, NodeRef, &str) -> Result<Expr, String>,
+) -> Result<Expr, String> {
+    let mut expr = None;
+    let mut operator = None;
     for child in cst.children(node) {
-        if cst.match_rule(child, Rule::Unary) {
-            return convert_unary(cst, child, source);
+        if cst.match_rule(child, operand_rule) {
+            let right = convert_operand(cst, child, source)?;
+            expr = Some(match (expr, operator.take()) {
+                (None, None) => right,
+                (Some(left), Some(operator)) => Expr::Binary { left: Box::new(left), operator, right: Box::new(right) },
+                _ => return Err("Expected a binary operator".to_string()),
+            });
+        } else if let Some(found) = binary_operator(cst, child) {
+            operator = Some(found);
         }
     }
-    Err("Invalid factor".to_string())
+    expr.ok_or_else(|| format!("Expected {:?}", operand_rule))
+}
+
+fn binary_operator(cst: &Cst, node: NodeRef) -> Option<BinaryOp> {
+    [
+        (Token::OR, BinaryOp::Or),
+        (Token::AND, BinaryOp::And),
+        (Token::BANG_EQUAL, BinaryOp::NotEqual),
+        (Token::EQUAL_EQUAL, BinaryOp::Equal),
+        (Token::GREATER, BinaryOp::Greater),
+        (Token::GREATER_EQUAL, BinaryOp::GreaterEqual),
+        (Token::LESS, BinaryOp::Less),
+        (Token::LESS_EQUAL, BinaryOp::LessEqual),
+        (Token::PLUS, BinaryOp::Add),
+        (Token::MINUS, BinaryOp::Subtract),
+        (Token::STAR, BinaryOp::Multiply),
+        (Token::SLASH, BinaryOp::Divide),
+    ]
+    .into_iter()
+    .find(|&(token, _)| cst.match_token(node, token).is_some())
+    .map(|(_, operator)| operator)
 }
 
 fn convert_unary(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, String> {
@@ -326,15 +378,20 @@ fn convert_primary(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, Strin
         } else if cst.match_token(child, Token::NIL).is_some() {
             return Ok(Expr::Literal(Value::Nil));
         } else if let Some((text, _)) = cst.match_token(child, Token::NUMBER) {
-            let num: f64 = text.parse().map_err(|_| "Invalid number")?;
+            let num = lexical::parse_number(text, &LEX_OPTIONS).map_err(|e| e.to_string())?;
             return Ok(Expr::Literal(Value::Number(num)));
         } else if let Some((text, _)) = cst.match_token(child, Token::STRING) {
             let content = &text[1..text.len()-1];
-            return Ok(Expr::Literal(Value::String(content.to_string())));
+            let value = if LEX_OPTIONS.escapes {
+                lexical::unescape(content).map_err(|e| e.to_string())?
+            } else {
+                content.to_string()
+            };
+            return Ok(Expr::Literal(Value::String(value)));
         } else if let Some((text, _)) = cst.match_token(child, Token::IDENTIFIER) {
             return Ok(Expr::Variable(text.to_string()));
         } else if cst.match_rule(child, Rule::Expression) {
-            return convert_expression(cst, child, source);
+            return Ok(Expr::Grouping(Box::new(convert_expression(cst, child, source)?)));
         }
     }
     Err("Invalid primary".to_string())
@@ -342,12 +399,49 @@ fn convert_primary(cst: &Cst, node: NodeRef, source: &str) -> Result<Expr, Strin
 
 /// Parse a complete Lox program from a string
 pub fn parse_program(input: &str) -> Result<Program, String> {
-    let mut diags = Vec::new();
-    let cst = Parser::parse(input, &mut diags);
+    parse_program_diagnostic(input).map_err(|error| error.describe(input))
+}
 
-    if !diags.is_empty() {
-        return Err(format!("Parse errors: {:?}", diags));
-    }
+/// Parse a complete Lox program, reporting a failure as a `Diagnostic` pointing into `input`
+///
+/// The parser recovers from errors and carries on, but only the first is
+/// reported, since the others are often caused by it; `LelwelParser` reports
+/// them all.
+pub fn parse_program_diagnostic(input: &str) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<Program, Box<Diagnostic>> {
+    parse_program_diagnostics(input, max_depth, 1)
+        .map_err(|diags| Box::new(diags.into_iter().next().expect("a failed parse has a diagnostic")))
+}
+
+/// Parse a complete Lox program, reporting the first `max_errors` of the errors
+/// the parser recovered from
+///
+/// Only the errors reported are explained, so asking for one costs no more
+/// than a backend that stops at the first.
+pub(crate) fn parse_program_diagnostics(
+    input: &str,
+    max_depth: usize,
+    max_errors: usize,
+) -> Result<Program, Vec<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS).map_err(|diag| vec![*diag])?;
+    // Both the generated parser and `convert_cst` recurse once per level
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        let mut diags = Vec::new();
+        let cst = Parser::parse(input, &mut diags);
+
+        if !diags.is_empty() {
+            diags.truncate(max_errors);
+            // Point every diagnostic at its unclosed delimiter from one scan of the input
+            let diags = Diagnostic::with_unclosed_delimiters(diags, input, &LEX_OPTIONS);
+            return Err(hints::explain_all(input, diags, &LEX_OPTIONS, &LanguageOptions::STRICT));
+        }
 
-    convert_cst(&cst, NodeRef(0), input)
+        convert_cst(&cst, NodeRef(0), input)
+            .map_err(|diag| vec![hints::explain(input, *diag, &LEX_OPTIONS, &LanguageOptions::STRICT)])
+    })
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+//! Compare the boxed `Program` with `ArenaProgram`
+//!
+//! Run with `cargo bench -p lox-ast --bench arena`. Before the timings, the
+//! number of heap allocations each representation needs for the same program is
+//! printed, counted by a wrapper around the system allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lox_ast::arena::{ArenaProgram, ExprNode, Literal};
+use lox_ast::{BinaryOp, Expr, Program, Stmt, Value};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Number of allocations made while running `f`
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+/// A program of `functions` functions, each a loop over arithmetic on a few shared names
+fn generate_program(functions: usize) -> Program {
+    let var = |name: &str| Box::new(Expr::Variable(name.to_string()));
+    let number = |n: f64| Box::new(Expr::Literal(Value::Number(n)));
+    let binary = |left, operator, right| Box::new(Expr::Binary { left, operator, right });
+
+    let statements = (0..functions)
+        .map(|i| {
+            let sum = binary(
+                binary(var("total"), BinaryOp::Add, binary(var("i"), BinaryOp::Multiply, number(i as f64))),
+                BinaryOp::Subtract,
+                Box::new(Expr::Call { callee: var("weight"), arguments: vec![*var("i"), *number(2.0)] }),
+            );
+            Stmt::Function {
+                name: format!("f{}", i),
+                params: vec!["total".to_string(), "n".to_string()],
+                signature: None,
+                body: vec![
+                    Stmt::For {
+                        initializer: Some(Box::new(Stmt::VarDeclaration {
+                            name: "i".to_string(),
+                            type_annotation: None,
+                            initializer: Some(*number(0.0)),
+                            doc: None,
+                        })),
+                        condition: Some(*binary(var("i"), BinaryOp::Less, var("n"))),
+                        increment: Some(Expr::Assignment {
+                            name: "i".to_string(),
+                            value: binary(var("i"), BinaryOp::Add, number(1.0)),
+                        }),
+                        body: Box::new(Stmt::Expression(Expr::Assignment { name: "total".to_string(), value: sum })),
+                    },
+                    Stmt::Print(Expr::Literal(Value::String("done".to_string()))),
+                    Stmt::Return { value: Some(*var("total")) },
+                ],
+                doc: None,
+            }
+        })
+        .collect();
+    Program::new(statements)
+}
+
+/// Sum the number literals by walking the boxed tree
+fn sum_boxed(program: &Program) -> f64 {
+    fn expr(e: &Expr) -> f64 {
+        match e {
+            Expr::Literal(Value::Number(n)) => *n,
+            Expr::Binary { left, right, .. } => expr(left) + expr(right),
+            Expr::Assignment { value, .. } => expr(value),
+            Expr::Call { callee, arguments } => expr(callee) + arguments.iter().map(expr).sum::<f64>(),
+            _ => 0.0,
+        }
+    }
+    fn stmt(s: &Stmt) -> f64 {
+        match s {
+            Stmt::Expression(e) | Stmt::Print(e) => expr(e),
+            Stmt::Return { value } => value.as_ref().map_or(0.0, expr),
+            Stmt::VarDeclaration { initializer, .. } => initializer.as_ref().map_or(0.0, expr),
+            Stmt::Function { body, .. } => body.iter().map(stmt).sum(),
+            Stmt::For { initializer, condition, increment, body } => {
+                initializer.as_deref().map_or(0.0, stmt)
+                    + condition.as_ref().map_or(0.0, expr)
+                    + increment.as_ref().map_or(0.0, expr)
+                    + stmt(body)
+            }
+            _ => 0.0,
+        }
+    }
+    program.statements.iter().map(stmt).sum()
+}
+
+/// Sum the number literals by scanning the arena's expressions in order
+fn sum_arena(program: &ArenaProgram) -> f64 {
+    program.arena.exprs().iter()
+        .map(|expr| match expr {
+            ExprNode::Literal(Literal::Number(n)) => *n,
+            _ => 0.0,
+        })
+        .sum()
+}
+
+fn bench_arena(c: &mut Criterion) {
+    let program = generate_program(2_000);
+    let arena = ArenaProgram::from_program(&program);
+    assert_eq!(arena.to_program(), program);
+    assert_eq!(sum_boxed(&program), sum_arena(&arena));
+
+    let (_, boxed_allocations) = count_allocations(|| program.clone());
+    let (_, arena_allocations) = count_allocations(|| arena.clone());
+    println!(
+        "allocations to copy a {}-node program: boxed {}, arena {}",
+        arena.arena.expr_count() + arena.arena.stmt_count(),
+        boxed_allocations,
+        arena_allocations,
+    );
+
+    let mut group = c.benchmark_group("clone");
+    group.bench_function("boxed", |b| b.iter(|| black_box(&program).clone()));
+    group.bench_function("arena", |b| b.iter(|| black_box(&arena).clone()));
+    group.finish();
+
+    let mut group = c.benchmark_group("sum_literals");
+    group.bench_function("boxed", |b| b.iter(|| sum_boxed(black_box(&program))));
+    group.bench_function("arena", |b| b.iter(|| sum_arena(black_box(&arena))));
+    group.finish();
+
+    let mut group = c.benchmark_group("convert");
+    group.bench_function("to_arena", |b| b.iter(|| ArenaProgram::from_program(black_box(&program))));
+    group.bench_function("to_program", |b| b.iter(|| black_box(&arena).to_program()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_arena);
+criterion_main!(benches);
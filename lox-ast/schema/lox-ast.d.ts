@@ -0,0 +1,284 @@
+// TypeScript definitions of a Lox program serialized by lox-ast with schema version 4
+// Generated by lox_ast::schema::typescript_definitions
+
+/** A complete Lox program */
+export interface Program {
+  schema_version: 4;
+  statements: Stmt[];
+}
+
+/** Lox statements */
+export type Stmt =
+  | ExpressionStmt
+  | PrintStmt
+  | VarDeclarationStmt
+  | BlockStmt
+  | IfStmt
+  | WhileStmt
+  | ForStmt
+  | FunctionStmt
+  | ReturnStmt
+  | BreakStmt
+  | ContinueStmt
+  | ClassStmt
+  | ImportStmt;
+
+/** Expression statement */
+export interface ExpressionStmt {
+  kind: "Expression";
+  expression: Expr;
+}
+
+/** Print statement */
+export interface PrintStmt {
+  kind: "Print";
+  expression: Expr;
+}
+
+/** Variable declaration */
+export interface VarDeclarationStmt {
+  kind: "VarDeclaration";
+  name: string;
+  type_annotation?: TypeAnnotation;
+  initializer: Expr | null;
+  doc?: string;
+}
+
+/** Block statement */
+export interface BlockStmt {
+  kind: "Block";
+  statements: Stmt[];
+}
+
+/** If statement */
+export interface IfStmt {
+  kind: "If";
+  condition: Expr;
+  then_branch: Stmt;
+  else_branch: Stmt | null;
+}
+
+/** While loop */
+export interface WhileStmt {
+  kind: "While";
+  condition: Expr;
+  body: Stmt;
+}
+
+/** For loop */
+export interface ForStmt {
+  kind: "For";
+  initializer: Stmt | null;
+  condition: Expr | null;
+  increment: Expr | null;
+  body: Stmt;
+}
+
+/** Function declaration */
+export interface FunctionStmt {
+  kind: "Function";
+  name: string;
+  params: string[];
+  signature?: FunctionSignature;
+  body: Stmt[];
+  doc?: string;
+}
+
+/** Return statement */
+export interface ReturnStmt {
+  kind: "Return";
+  value: Expr | null;
+}
+
+/** Break out of the innermost loop (extension) */
+export interface BreakStmt {
+  kind: "Break";
+}
+
+/** Skip to the next iteration of the innermost loop (extension) */
+export interface ContinueStmt {
+  kind: "Continue";
+}
+
+/** Class declaration */
+export interface ClassStmt {
+  kind: "Class";
+  name: string;
+  superclass: string | null;
+  fields: Stmt[];
+  methods: Stmt[];
+  doc?: string;
+}
+
+/** `import "path";`, run the named file first (extension) */
+export interface ImportStmt {
+  kind: "Import";
+  path: string;
+}
+
+/** Lox expressions */
+export type Expr =
+  | LiteralExpr
+  | VariableExpr
+  | BinaryExpr
+  | UnaryExpr
+  | GroupingExpr
+  | AssignmentExpr
+  | CallExpr
+  | GetExpr
+  | SetExpr
+  | ThisExpr
+  | SuperExpr
+  | ConditionalExpr
+  | LambdaExpr;
+
+/** Literal values */
+export interface LiteralExpr {
+  kind: "Literal";
+  value: Value;
+}
+
+/** Variable reference */
+export interface VariableExpr {
+  kind: "Variable";
+  name: string;
+}
+
+/** Binary operations */
+export interface BinaryExpr {
+  kind: "Binary";
+  left: Expr;
+  operator: BinaryOp;
+  right: Expr;
+}
+
+/** Unary operations */
+export interface UnaryExpr {
+  kind: "Unary";
+  operator: UnaryOp;
+  operand: Expr;
+}
+
+/** Grouping (parentheses) */
+export interface GroupingExpr {
+  kind: "Grouping";
+  expression: Expr;
+}
+
+/** Assignment */
+export interface AssignmentExpr {
+  kind: "Assignment";
+  name: string;
+  value: Expr;
+}
+
+/** Function call */
+export interface CallExpr {
+  kind: "Call";
+  callee: Expr;
+  arguments: Expr[];
+}
+
+/** Property access */
+export interface GetExpr {
+  kind: "Get";
+  object: Expr;
+  name: string;
+}
+
+/** Property assignment */
+export interface SetExpr {
+  kind: "Set";
+  object: Expr;
+  name: string;
+  value: Expr;
+}
+
+/** This expression */
+export interface ThisExpr {
+  kind: "This";
+}
+
+/** Super expression */
+export interface SuperExpr {
+  kind: "Super";
+  method: string;
+}
+
+/** Conditional expression `condition ? then_branch : else_branch` (extension) */
+export interface ConditionalExpr {
+  kind: "Conditional";
+  condition: Expr;
+  then_branch: Expr;
+  else_branch: Expr;
+}
+
+/** Anonymous function `fun (params) { body }` (extension) */
+export interface LambdaExpr {
+  kind: "Lambda";
+  params: string[];
+  body: Stmt[];
+}
+
+/** A Lox value: nil, a boolean, a number or a string */
+export type Value = null | boolean | number | string;
+
+/** Binary operators in Lox */
+export type BinaryOp = "Add" | "Subtract" | "Multiply" | "Divide" | "Greater" | "GreaterEqual" | "Less" | "LessEqual" | "Equal" | "NotEqual" | "And" | "Or" | "Comma";
+
+/** Unary operators in Lox */
+export type UnaryOp = "Minus" | "Not";
+
+/** A type written in the source, in the typed dialect */
+export type TypeAnnotation =
+  | AnyTypeAnnotation
+  | NilTypeAnnotation
+  | BoolTypeAnnotation
+  | NumberTypeAnnotation
+  | StringTypeAnnotation
+  | ClassTypeAnnotation
+  | FunctionTypeAnnotation;
+
+/** `Any`, which opts out of checking */
+export interface AnyTypeAnnotation {
+  kind: "Any";
+}
+
+/** `Nil` */
+export interface NilTypeAnnotation {
+  kind: "Nil";
+}
+
+/** `Bool` */
+export interface BoolTypeAnnotation {
+  kind: "Bool";
+}
+
+/** `Number` */
+export interface NumberTypeAnnotation {
+  kind: "Number";
+}
+
+/** `String` */
+export interface StringTypeAnnotation {
+  kind: "String";
+}
+
+/** An instance of the named class */
+export interface ClassTypeAnnotation {
+  kind: "Class";
+  name: string;
+}
+
+/** `fun(Number, String): Bool` */
+export interface FunctionTypeAnnotation {
+  kind: "Function";
+  params: TypeAnnotation[];
+  return_type: TypeAnnotation;
+}
+
+/** The annotations on a function's parameters and return value, `null` where unannotated */
+export interface FunctionSignature {
+  params: (TypeAnnotation | null)[];
+  return_type: TypeAnnotation | null;
+}
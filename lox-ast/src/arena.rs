@@ -0,0 +1,728 @@
+//! Arena representation of the AST with interned names
+//!
+//! The boxed `Program` allocates once per node and once per identifier or
+//! string. `AstArena` instead keeps every expression and statement in one
+//! vector each, refers to them by `ExprId` and `StmtId`, and stores names and
+//! string literals once in an `Interner`. Child lists (call arguments, block
+//! bodies, parameters) are slices of shared vectors, so a whole program lives in
+//! a handful of allocations.
+//!
+//! A parser can build into an arena directly with the `alloc_*` and `intern`
+//! methods, or any parser's `Program` can be converted with
+//! `ArenaProgram::from_program` and back with `ArenaProgram::to_program`.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{BinaryOp, Expr, FunctionSignature, Program, Stmt, TypeAnnotation, UnaryOp, Value};
+
+/// An interned name or string literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Index of an expression in an `AstArena`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+/// Index of a statement in an `AstArena`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StmtId(u32);
+
+/// A contiguous run of items in one of the arena's list vectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ListRange {
+    start: u32,
+    len: u32,
+}
+
+impl ListRange {
+    /// Number of items in the list
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Check if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn range(&self) -> std::ops::Range<usize> {
+        self.start as usize..(self.start + self.len) as usize
+    }
+}
+
+/// Interns strings so that each distinct one is stored once
+///
+/// The lookup table and the list of strings share one allocation per string.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    lookup: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the symbol for `text`, storing it if it has not been seen before
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.lookup.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        let text: Rc<str> = text.into();
+        self.strings.push(Rc::clone(&text));
+        self.lookup.insert(text, symbol);
+        symbol
+    }
+
+    /// Get the symbol for `text` without storing it
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        self.lookup.get(text).copied()
+    }
+
+    /// Get the text of `symbol`
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    /// Number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Check if no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// A literal value whose string, if any, is interned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Symbol),
+}
+
+/// An expression stored in an `AstArena`; mirrors `Expr`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprNode {
+    Literal(Literal),
+    Variable(Symbol),
+    Binary {
+        left: ExprId,
+        operator: BinaryOp,
+        right: ExprId,
+    },
+    Unary {
+        operator: UnaryOp,
+        operand: ExprId,
+    },
+    Grouping(ExprId),
+    Assignment {
+        name: Symbol,
+        value: ExprId,
+    },
+    /// `arguments` is a list of expressions
+    Call {
+        callee: ExprId,
+        arguments: ListRange,
+    },
+    Get {
+        object: ExprId,
+        name: Symbol,
+    },
+    Set {
+        object: ExprId,
+        name: Symbol,
+        value: ExprId,
+    },
+    This,
+    Super {
+        method: Symbol,
+    },
+    Conditional {
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: ExprId,
+    },
+    /// `params` is a list of symbols and `body` a list of statements
+    Lambda {
+        params: ListRange,
+        body: ListRange,
+    },
+}
+
+/// A statement stored in an `AstArena`; mirrors `Stmt`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtNode {
+    Expression(ExprId),
+    Print(ExprId),
+    VarDeclaration {
+        name: Symbol,
+        type_annotation: Option<TypeAnnotation>,
+        initializer: Option<ExprId>,
+        doc: Option<Symbol>,
+    },
+    /// A list of statements
+    Block(ListRange),
+    If {
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    },
+    While {
+        condition: ExprId,
+        body: StmtId,
+    },
+    For {
+        initializer: Option<StmtId>,
+        condition: Option<ExprId>,
+        increment: Option<ExprId>,
+        body: StmtId,
+    },
+    /// `params` is a list of symbols and `body` a list of statements
+    Function {
+        name: Symbol,
+        params: ListRange,
+        signature: Option<FunctionSignature>,
+        body: ListRange,
+        doc: Option<Symbol>,
+    },
+    Return {
+        value: Option<ExprId>,
+    },
+    Break,
+    Continue,
+    Import {
+        path: Symbol,
+    },
+    /// `fields` is a list of `VarDeclaration` statements and `methods` a list
+    /// of `Function` statements
+    Class {
+        name: Symbol,
+        superclass: Option<Symbol>,
+        fields: ListRange,
+        methods: ListRange,
+        doc: Option<Symbol>,
+    },
+}
+
+/// Storage for the nodes of one or more syntax trees
+#[derive(Debug, Clone, Default)]
+pub struct AstArena {
+    exprs: Vec<ExprNode>,
+    stmts: Vec<StmtNode>,
+    expr_lists: Vec<ExprId>,
+    stmt_lists: Vec<StmtId>,
+    symbol_lists: Vec<Symbol>,
+    interner: Interner,
+}
+
+impl AstArena {
+    /// Create an empty arena
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store an expression
+    pub fn alloc_expr(&mut self, expr: ExprNode) -> ExprId {
+        self.exprs.push(expr);
+        ExprId(self.exprs.len() as u32 - 1)
+    }
+
+    /// Store a statement
+    pub fn alloc_stmt(&mut self, stmt: StmtNode) -> StmtId {
+        self.stmts.push(stmt);
+        StmtId(self.stmts.len() as u32 - 1)
+    }
+
+    /// Store a list of expressions, such as call arguments
+    pub fn alloc_expr_list(&mut self, ids: impl IntoIterator<Item = ExprId>) -> ListRange {
+        let start = self.expr_lists.len();
+        self.expr_lists.extend(ids);
+        list_range(start, self.expr_lists.len())
+    }
+
+    /// Store a list of statements, such as a block body
+    pub fn alloc_stmt_list(&mut self, ids: impl IntoIterator<Item = StmtId>) -> ListRange {
+        let start = self.stmt_lists.len();
+        self.stmt_lists.extend(ids);
+        list_range(start, self.stmt_lists.len())
+    }
+
+    /// Store a list of symbols, such as function parameters
+    pub fn alloc_symbol_list(&mut self, symbols: impl IntoIterator<Item = Symbol>) -> ListRange {
+        let start = self.symbol_lists.len();
+        self.symbol_lists.extend(symbols);
+        list_range(start, self.symbol_lists.len())
+    }
+
+    /// Intern a name or string literal
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        self.interner.intern(text)
+    }
+
+    /// Get the text of an interned name or string literal
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        self.interner.resolve(symbol)
+    }
+
+    /// Get the interner holding this arena's names and strings
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    /// Get an expression
+    pub fn expr(&self, id: ExprId) -> &ExprNode {
+        &self.exprs[id.0 as usize]
+    }
+
+    /// Get a statement
+    pub fn stmt(&self, id: StmtId) -> &StmtNode {
+        &self.stmts[id.0 as usize]
+    }
+
+    /// Get the expressions of a list made by `alloc_expr_list`
+    pub fn expr_list(&self, list: ListRange) -> &[ExprId] {
+        &self.expr_lists[list.range()]
+    }
+
+    /// Get the statements of a list made by `alloc_stmt_list`
+    pub fn stmt_list(&self, list: ListRange) -> &[StmtId] {
+        &self.stmt_lists[list.range()]
+    }
+
+    /// Get the symbols of a list made by `alloc_symbol_list`
+    pub fn symbol_list(&self, list: ListRange) -> &[Symbol] {
+        &self.symbol_lists[list.range()]
+    }
+
+    /// Number of expressions stored
+    pub fn expr_count(&self) -> usize {
+        self.exprs.len()
+    }
+
+    /// Number of statements stored
+    pub fn stmt_count(&self) -> usize {
+        self.stmts.len()
+    }
+
+    /// All stored expressions, in allocation order
+    ///
+    /// Passes that do not care about tree shape, such as counting literals,
+    /// can scan this slice instead of walking the tree.
+    pub fn exprs(&self) -> &[ExprNode] {
+        &self.exprs
+    }
+
+    /// All stored statements, in allocation order
+    pub fn stmts(&self) -> &[StmtNode] {
+        &self.stmts
+    }
+
+    /// Copy a boxed statement and everything under it into the arena
+    pub fn add_stmt(&mut self, stmt: &Stmt) -> StmtId {
+        let node = match stmt {
+            Stmt::Expression(expr) => StmtNode::Expression(self.add_expr(expr)),
+            Stmt::Print(expr) => StmtNode::Print(self.add_expr(expr)),
+            Stmt::VarDeclaration { name, type_annotation, initializer, doc } => StmtNode::VarDeclaration {
+                name: self.intern(name),
+                type_annotation: type_annotation.clone(),
+                initializer: initializer.as_ref().map(|expr| self.add_expr(expr)),
+                doc: doc.as_deref().map(|doc| self.intern(doc)),
+            },
+            Stmt::Block(stmts) => StmtNode::Block(self.add_stmts(stmts)),
+            Stmt::If { condition, then_branch, else_branch } => StmtNode::If {
+                condition: self.add_expr(condition),
+                then_branch: self.add_stmt(then_branch),
+                else_branch: else_branch.as_deref().map(|stmt| self.add_stmt(stmt)),
+            },
+            Stmt::While { condition, body } => StmtNode::While {
+                condition: self.add_expr(condition),
+                body: self.add_stmt(body),
+            },
+            Stmt::For { initializer, condition, increment, body } => StmtNode::For {
+                initializer: initializer.as_deref().map(|stmt| self.add_stmt(stmt)),
+                condition: condition.as_ref().map(|expr| self.add_expr(expr)),
+                increment: increment.as_ref().map(|expr| self.add_expr(expr)),
+                body: self.add_stmt(body),
+            },
+            Stmt::Function { name, params, signature, body, doc } => StmtNode::Function {
+                name: self.intern(name),
+                params: self.add_symbols(params),
+                signature: signature.clone(),
+                body: self.add_stmts(body),
+                doc: doc.as_deref().map(|doc| self.intern(doc)),
+            },
+            Stmt::Return { value } => StmtNode::Return {
+                value: value.as_ref().map(|expr| self.add_expr(expr)),
+            },
+            Stmt::Break => StmtNode::Break,
+            Stmt::Continue => StmtNode::Continue,
+            Stmt::Import { path } => StmtNode::Import { path: self.intern(path) },
+            Stmt::Class { name, superclass, fields, methods, doc } => StmtNode::Class {
+                name: self.intern(name),
+                superclass: superclass.as_deref().map(|name| self.intern(name)),
+                fields: self.add_stmts(fields),
+                methods: self.add_stmts(methods),
+                doc: doc.as_deref().map(|doc| self.intern(doc)),
+            },
+        };
+        self.alloc_stmt(node)
+    }
+
+    /// Copy a boxed expression and everything under it into the arena
+    pub fn add_expr(&mut self, expr: &Expr) -> ExprId {
+        let node = match expr {
+            Expr::Literal(value) => ExprNode::Literal(match value {
+                Value::Nil => Literal::Nil,
+                Value::Bool(b) => Literal::Bool(*b),
+                Value::Number(n) => Literal::Number(*n),
+                Value::String(s) => Literal::String(self.intern(s)),
+            }),
+            Expr::Variable(name) => ExprNode::Variable(self.intern(name)),
+            Expr::Binary { left, operator, right } => ExprNode::Binary {
+                left: self.add_expr(left),
+                operator: operator.clone(),
+                right: self.add_expr(right),
+            },
+            Expr::Unary { operator, operand } => ExprNode::Unary {
+                operator: operator.clone(),
+                operand: self.add_expr(operand),
+            },
+            Expr::Grouping(inner) => ExprNode::Grouping(self.add_expr(inner)),
+            Expr::Assignment { name, value } => ExprNode::Assignment {
+                name: self.intern(name),
+                value: self.add_expr(value),
+            },
+            Expr::Call { callee, arguments } => {
+                let callee = self.add_expr(callee);
+                let ids: Vec<_> = arguments.iter().map(|arg| self.add_expr(arg)).collect();
+                ExprNode::Call { callee, arguments: self.alloc_expr_list(ids) }
+            }
+            Expr::Get { object, name } => ExprNode::Get {
+                object: self.add_expr(object),
+                name: self.intern(name),
+            },
+            Expr::Set { object, name, value } => ExprNode::Set {
+                object: self.add_expr(object),
+                name: self.intern(name),
+                value: self.add_expr(value),
+            },
+            Expr::This => ExprNode::This,
+            Expr::Super { method } => ExprNode::Super { method: self.intern(method) },
+            Expr::Conditional { condition, then_branch, else_branch } => ExprNode::Conditional {
+                condition: self.add_expr(condition),
+                then_branch: self.add_expr(then_branch),
+                else_branch: self.add_expr(else_branch),
+            },
+            Expr::Lambda { params, body } => ExprNode::Lambda {
+                params: self.add_symbols(params),
+                body: self.add_stmts(body),
+            },
+        };
+        self.alloc_expr(node)
+    }
+
+    /// Copy a list of statements; the children go in first so the list stays contiguous
+    fn add_stmts(&mut self, stmts: &[Stmt]) -> ListRange {
+        let ids: Vec<_> = stmts.iter().map(|stmt| self.add_stmt(stmt)).collect();
+        self.alloc_stmt_list(ids)
+    }
+
+    fn add_symbols(&mut self, names: &[String]) -> ListRange {
+        let symbols: Vec<_> = names.iter().map(|name| self.intern(name)).collect();
+        self.alloc_symbol_list(symbols)
+    }
+
+    /// Rebuild the boxed form of a statement
+    pub fn to_stmt(&self, id: StmtId) -> Stmt {
+        match self.stmt(id) {
+            StmtNode::Expression(expr) => Stmt::Expression(self.to_expr(*expr)),
+            StmtNode::Print(expr) => Stmt::Print(self.to_expr(*expr)),
+            StmtNode::VarDeclaration { name, type_annotation, initializer, doc } => Stmt::VarDeclaration {
+                name: self.resolve(*name).to_string(),
+                type_annotation: type_annotation.clone(),
+                initializer: initializer.map(|expr| self.to_expr(expr)),
+                doc: doc.map(|doc| self.resolve(doc).to_string()),
+            },
+            StmtNode::Block(stmts) => Stmt::Block(self.to_stmts(*stmts)),
+            StmtNode::If { condition, then_branch, else_branch } => Stmt::If {
+                condition: self.to_expr(*condition),
+                then_branch: Box::new(self.to_stmt(*then_branch)),
+                else_branch: else_branch.map(|stmt| Box::new(self.to_stmt(stmt))),
+            },
+            StmtNode::While { condition, body } => Stmt::While {
+                condition: self.to_expr(*condition),
+                body: Box::new(self.to_stmt(*body)),
+            },
+            StmtNode::For { initializer, condition, increment, body } => Stmt::For {
+                initializer: initializer.map(|stmt| Box::new(self.to_stmt(stmt))),
+                condition: condition.map(|expr| self.to_expr(expr)),
+                increment: increment.map(|expr| self.to_expr(expr)),
+                body: Box::new(self.to_stmt(*body)),
+            },
+            StmtNode::Function { name, params, signature, body, doc } => Stmt::Function {
+                name: self.resolve(*name).to_string(),
+                params: self.to_names(*params),
+                signature: signature.clone(),
+                body: self.to_stmts(*body),
+                doc: doc.map(|doc| self.resolve(doc).to_string()),
+            },
+            StmtNode::Return { value } => Stmt::Return {
+                value: value.map(|expr| self.to_expr(expr)),
+            },
+            StmtNode::Break => Stmt::Break,
+            StmtNode::Continue => Stmt::Continue,
+            StmtNode::Import { path } => Stmt::Import { path: self.resolve(*path).to_string() },
+            StmtNode::Class { name, superclass, fields, methods, doc } => Stmt::Class {
+                name: self.resolve(*name).to_string(),
+                superclass: superclass.map(|name| self.resolve(name).to_string()),
+                fields: self.to_stmts(*fields),
+                methods: self.to_stmts(*methods),
+                doc: doc.map(|doc| self.resolve(doc).to_string()),
+            },
+        }
+    }
+
+    /// Rebuild the boxed form of an expression
+    pub fn to_expr(&self, id: ExprId) -> Expr {
+        match self.expr(id) {
+            ExprNode::Literal(literal) => Expr::Literal(match literal {
+                Literal::Nil => Value::Nil,
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::Number(n) => Value::Number(*n),
+                Literal::String(s) => Value::String(self.resolve(*s).to_string()),
+            }),
+            ExprNode::Variable(name) => Expr::Variable(self.resolve(*name).to_string()),
+            ExprNode::Binary { left, operator, right } => Expr::Binary {
+                left: Box::new(self.to_expr(*left)),
+                operator: operator.clone(),
+                right: Box::new(self.to_expr(*right)),
+            },
+            ExprNode::Unary { operator, operand } => Expr::Unary {
+                operator: operator.clone(),
+                operand: Box::new(self.to_expr(*operand)),
+            },
+            ExprNode::Grouping(inner) => Expr::Grouping(Box::new(self.to_expr(*inner))),
+            ExprNode::Assignment { name, value } => Expr::Assignment {
+                name: self.resolve(*name).to_string(),
+                value: Box::new(self.to_expr(*value)),
+            },
+            ExprNode::Call { callee, arguments } => Expr::Call {
+                callee: Box::new(self.to_expr(*callee)),
+                arguments: self.expr_list(*arguments).iter().map(|&arg| self.to_expr(arg)).collect(),
+            },
+            ExprNode::Get { object, name } => Expr::Get {
+                object: Box::new(self.to_expr(*object)),
+                name: self.resolve(*name).to_string(),
+            },
+            ExprNode::Set { object, name, value } => Expr::Set {
+                object: Box::new(self.to_expr(*object)),
+                name: self.resolve(*name).to_string(),
+                value: Box::new(self.to_expr(*value)),
+            },
+            ExprNode::This => Expr::This,
+            ExprNode::Super { method } => Expr::Super { method: self.resolve(*method).to_string() },
+            ExprNode::Conditional { condition, then_branch, else_branch } => Expr::Conditional {
+                condition: Box::new(self.to_expr(*condition)),
+                then_branch: Box::new(self.to_expr(*then_branch)),
+                else_branch: Box::new(self.to_expr(*else_branch)),
+            },
+            ExprNode::Lambda { params, body } => Expr::Lambda {
+                params: self.to_names(*params),
+                body: self.to_stmts(*body),
+            },
+        }
+    }
+
+    fn to_stmts(&self, list: ListRange) -> Vec<Stmt> {
+        self.stmt_list(list).iter().map(|&id| self.to_stmt(id)).collect()
+    }
+
+    fn to_names(&self, list: ListRange) -> Vec<String> {
+        self.symbol_list(list).iter().map(|&symbol| self.resolve(symbol).to_string()).collect()
+    }
+}
+
+fn list_range(start: usize, end: usize) -> ListRange {
+    ListRange { start: start as u32, len: (end - start) as u32 }
+}
+
+/// A program stored in an arena
+#[derive(Debug, Clone, Default)]
+pub struct ArenaProgram {
+    pub arena: AstArena,
+    /// The top-level statements, a list in `arena`
+    pub statements: ListRange,
+}
+
+impl ArenaProgram {
+    /// Copy a boxed program into a new arena
+    pub fn from_program(program: &Program) -> Self {
+        let mut arena = AstArena::new();
+        let statements = arena.add_stmts(&program.statements);
+        Self { arena, statements }
+    }
+
+    /// Rebuild the boxed form of the program
+    pub fn to_program(&self) -> Program {
+        Program::new(self.arena.to_stmts(self.statements))
+    }
+
+    /// Get the top-level statements
+    pub fn statements(&self) -> &[StmtId] {
+        self.arena.stmt_list(self.statements)
+    }
+}
+
+impl From<&Program> for ArenaProgram {
+    fn from(program: &Program) -> Self {
+        Self::from_program(program)
+    }
+}
+
+impl From<&ArenaProgram> for Program {
+    fn from(program: &ArenaProgram) -> Self {
+        program.to_program()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(n: f64) -> Box<Expr> {
+        Box::new(Expr::Literal(Value::Number(n)))
+    }
+
+    /// A program using every kind of node
+    fn sample_program() -> Program {
+        let method = Stmt::Function {
+            name: "area".to_string(),
+            params: vec![],
+            signature: None,
+            body: vec![Stmt::Return {
+                value: Some(Expr::Binary {
+                    left: Box::new(Expr::Get { object: Box::new(Expr::This), name: "w".to_string() }),
+                    operator: BinaryOp::Multiply,
+                    right: Box::new(Expr::Super { method: "h".to_string() }),
+                }),
+            }],
+            doc: Some("Width times height".to_string()),
+        };
+        Program::new(vec![
+            Stmt::VarDeclaration {
+                name: "x".to_string(),
+                type_annotation: None,
+                initializer: Some(Expr::Literal(Value::String("hi".to_string()))),
+                doc: Some("A greeting".to_string()),
+            },
+            Stmt::Class {
+                name: "Rect".to_string(),
+                superclass: Some("Shape".to_string()),
+                fields: vec![],
+                methods: vec![method],
+                doc: None,
+            },
+            Stmt::For {
+                initializer: Some(Box::new(Stmt::VarDeclaration {
+                    name: "i".to_string(),
+                    type_annotation: None,
+                    initializer: Some(*number(0.0)),
+                    doc: None,
+                })),
+                condition: Some(Expr::Binary {
+                    left: Box::new(Expr::Variable("i".to_string())),
+                    operator: BinaryOp::Less,
+                    right: number(3.0),
+                }),
+                increment: None,
+                body: Box::new(Stmt::Block(vec![
+                    Stmt::If {
+                        condition: Expr::Unary { operator: UnaryOp::Not, operand: Box::new(Expr::Literal(Value::Nil)) },
+                        then_branch: Box::new(Stmt::Break),
+                        else_branch: Some(Box::new(Stmt::Continue)),
+                    },
+                    Stmt::While { condition: Expr::Literal(Value::Bool(false)), body: Box::new(Stmt::Block(vec![])) },
+                ])),
+            },
+            Stmt::Expression(Expr::Assignment {
+                name: "f".to_string(),
+                value: Box::new(Expr::Lambda {
+                    params: vec!["a".to_string(), "b".to_string()],
+                    body: vec![Stmt::Return { value: None }],
+                }),
+            }),
+            Stmt::Print(Expr::Call {
+                callee: Box::new(Expr::Variable("f".to_string())),
+                arguments: vec![
+                    Expr::Grouping(number(1.0)),
+                    Expr::Conditional { condition: number(1.0), then_branch: number(2.0), else_branch: number(3.0) },
+                ],
+            }),
+            Stmt::Expression(Expr::Set {
+                object: Box::new(Expr::Variable("x".to_string())),
+                name: "y".to_string(),
+                value: number(4.0),
+            }),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = sample_program();
+        let arena = ArenaProgram::from_program(&program);
+        assert_eq!(arena.statements().len(), program.statements.len());
+        assert_eq!(arena.to_program(), program);
+        assert_eq!(Program::from(&ArenaProgram::from(&program)), program);
+    }
+
+    #[test]
+    fn test_names_are_interned_once() {
+        let mut interner = Interner::new();
+        let a = interner.intern("count");
+        let b = interner.intern("total");
+        assert_eq!(interner.intern("count"), a);
+        assert_ne!(a, b);
+        assert_eq!(interner.resolve(b), "total");
+        assert_eq!(interner.get("missing"), None);
+        assert_eq!(interner.len(), 2);
+        // The table and the list hold the same copy of each string
+        assert!(interner.lookup.keys().all(|key| Rc::ptr_eq(key, &interner.strings[interner.lookup[key].0 as usize])));
+
+        // `x` is declared and assigned; `i` is declared and read
+        let arena = ArenaProgram::from_program(&sample_program()).arena;
+        let x = arena.interner().get("x").unwrap();
+        let i = arena.interner().get("i").unwrap();
+        let declarations = arena.stmts().iter()
+            .filter(|stmt| matches!(stmt, StmtNode::VarDeclaration { name, .. } if *name == i));
+        assert_eq!(declarations.count(), 1);
+        let reads = arena.exprs().iter()
+            .filter(|expr| matches!(expr, ExprNode::Variable(name) if *name == x));
+        assert_eq!(reads.count(), 1);
+    }
+
+    #[test]
+    fn test_building_directly() {
+        let mut arena = AstArena::new();
+        let one = arena.alloc_expr(ExprNode::Literal(Literal::Number(1.0)));
+        let two = arena.alloc_expr(ExprNode::Literal(Literal::Number(2.0)));
+        let callee = arena.intern("max");
+        let callee = arena.alloc_expr(ExprNode::Variable(callee));
+        let arguments = arena.alloc_expr_list([one, two]);
+        let call = arena.alloc_expr(ExprNode::Call { callee, arguments });
+        let print = arena.alloc_stmt(StmtNode::Print(call));
+
+        assert_eq!(arena.expr_list(arguments), &[one, two]);
+        assert_eq!(arena.to_stmt(print), Stmt::Print(Expr::Call {
+            callee: Box::new(Expr::Variable("max".to_string())),
+            arguments: vec![*number(1.0), *number(2.0)],
+        }));
+    }
+}
@@ -0,0 +1,322 @@
+//! Borrowed flavour of the AST, tied to the source buffer
+//!
+//! Names are slices of the source, and string literals and doc comments are
+//! `Cow`s that only own their text when it differs from the source (decoded
+//! escape sequences, doc comments joined across lines). A parser that builds
+//! these types allocates nothing for names or plain strings; `into_owned`
+//! converts the result to the owned types in the crate root.
+
+use std::borrow::Cow;
+
+use crate::{BinaryOp, FunctionSignature, TypeAnnotation, UnaryOp};
+
+/// A Lox value borrowing from the source; mirrors `crate::Value`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'src> {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Cow<'src, str>),
+}
+
+impl Value<'_> {
+    /// Convert to the owned value
+    pub fn into_owned(self) -> crate::Value {
+        match self {
+            Value::Nil => crate::Value::Nil,
+            Value::Bool(b) => crate::Value::Bool(b),
+            Value::Number(n) => crate::Value::Number(n),
+            Value::String(s) => crate::Value::String(s.into_owned()),
+        }
+    }
+}
+
+/// A Lox expression borrowing from the source; mirrors `crate::Expr`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'src> {
+    Literal(Value<'src>),
+    Variable(&'src str),
+    Binary {
+        left: Box<Expr<'src>>,
+        operator: BinaryOp,
+        right: Box<Expr<'src>>,
+    },
+    Unary {
+        operator: UnaryOp,
+        operand: Box<Expr<'src>>,
+    },
+    Grouping(Box<Expr<'src>>),
+    Assignment {
+        name: &'src str,
+        value: Box<Expr<'src>>,
+    },
+    Call {
+        callee: Box<Expr<'src>>,
+        arguments: Vec<Expr<'src>>,
+    },
+    Get {
+        object: Box<Expr<'src>>,
+        name: &'src str,
+    },
+    Set {
+        object: Box<Expr<'src>>,
+        name: &'src str,
+        value: Box<Expr<'src>>,
+    },
+    This,
+    Super {
+        method: &'src str,
+    },
+    Conditional {
+        condition: Box<Expr<'src>>,
+        then_branch: Box<Expr<'src>>,
+        else_branch: Box<Expr<'src>>,
+    },
+    Lambda {
+        params: Vec<&'src str>,
+        body: Vec<Stmt<'src>>,
+    },
+}
+
+impl Expr<'_> {
+    /// Convert to the owned expression, copying every name and string
+    ///
+    /// Stack-safe however deep the expression is.
+    pub fn into_owned(self) -> crate::Expr {
+        let boxed = |expr: Box<Expr<'_>>| Box::new(expr.into_owned());
+        crate::depth::grow(|| match self {
+            Expr::Literal(value) => crate::Expr::Literal(value.into_owned()),
+            Expr::Variable(name) => crate::Expr::Variable(name.to_string()),
+            Expr::Binary { left, operator, right } => crate::Expr::Binary {
+                left: boxed(left),
+                operator,
+                right: boxed(right),
+            },
+            Expr::Unary { operator, operand } => crate::Expr::Unary {
+                operator,
+                operand: boxed(operand),
+            },
+            Expr::Grouping(inner) => crate::Expr::Grouping(boxed(inner)),
+            Expr::Assignment { name, value } => crate::Expr::Assignment {
+                name: name.to_string(),
+                value: boxed(value),
+            },
+            Expr::Call { callee, arguments } => crate::Expr::Call {
+                callee: boxed(callee),
+                arguments: arguments.into_iter().map(Expr::into_owned).collect(),
+            },
+            Expr::Get { object, name } => crate::Expr::Get {
+                object: boxed(object),
+                name: name.to_string(),
+            },
+            Expr::Set { object, name, value } => crate::Expr::Set {
+                object: boxed(object),
+                name: name.to_string(),
+                value: boxed(value),
+            },
+            Expr::This => crate::Expr::This,
+            Expr::Super { method } => crate::Expr::Super { method: method.to_string() },
+            Expr::Conditional { condition, then_branch, else_branch } => crate::Expr::Conditional {
+                condition: boxed(condition),
+                then_branch: boxed(then_branch),
+                else_branch: boxed(else_branch),
+            },
+            Expr::Lambda { params, body } => crate::Expr::Lambda {
+                params: owned_names(params),
+                body: owned_stmts(body),
+            },
+        })
+    }
+}
+
+/// A Lox statement borrowing from the source; mirrors `crate::Stmt`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt<'src> {
+    Expression(Expr<'src>),
+    Print(Expr<'src>),
+    VarDeclaration {
+        name: &'src str,
+        type_annotation: Option<TypeAnnotation>,
+        initializer: Option<Expr<'src>>,
+        doc: Option<Cow<'src, str>>,
+    },
+    Block(Vec<Stmt<'src>>),
+    If {
+        condition: Expr<'src>,
+        then_branch: Box<Stmt<'src>>,
+        else_branch: Option<Box<Stmt<'src>>>,
+    },
+    While {
+        condition: Expr<'src>,
+        body: Box<Stmt<'src>>,
+    },
+    For {
+        initializer: Option<Box<Stmt<'src>>>,
+        condition: Option<Expr<'src>>,
+        increment: Option<Expr<'src>>,
+        body: Box<Stmt<'src>>,
+    },
+    Function {
+        name: &'src str,
+        params: Vec<&'src str>,
+        signature: Option<FunctionSignature>,
+        body: Vec<Stmt<'src>>,
+        doc: Option<Cow<'src, str>>,
+    },
+    Return {
+        value: Option<Expr<'src>>,
+    },
+    Break,
+    Continue,
+    Class {
+        name: &'src str,
+        superclass: Option<&'src str>,
+        fields: Vec<Stmt<'src>>,
+        methods: Vec<Stmt<'src>>,
+        doc: Option<Cow<'src, str>>,
+    },
+    Import {
+        path: Cow<'src, str>,
+    },
+}
+
+impl<'src> Stmt<'src> {
+    /// Attach a doc comment to a variable, function or class declaration
+    ///
+    /// Doc comments before any other statement are dropped.
+    pub fn with_doc(mut self, text: Option<impl Into<Cow<'src, str>>>) -> Self {
+        if let Stmt::VarDeclaration { doc, .. }
+        | Stmt::Function { doc, .. }
+        | Stmt::Class { doc, .. } = &mut self
+        {
+            *doc = text.map(Into::into);
+        }
+        self
+    }
+
+    /// Convert to the owned statement, copying every name and string
+    pub fn into_owned(self) -> crate::Stmt {
+        let boxed = |stmt: Box<Stmt<'_>>| Box::new(stmt.into_owned());
+        let owned_doc = |doc: Option<Cow<'_, str>>| doc.map(Cow::into_owned);
+        match self {
+            Stmt::Expression(expr) => crate::Stmt::Expression(expr.into_owned()),
+            Stmt::Print(expr) => crate::Stmt::Print(expr.into_owned()),
+            Stmt::VarDeclaration { name, type_annotation, initializer, doc } => crate::Stmt::VarDeclaration {
+                name: name.to_string(),
+                type_annotation,
+                initializer: initializer.map(Expr::into_owned),
+                doc: owned_doc(doc),
+            },
+            Stmt::Block(stmts) => crate::Stmt::Block(owned_stmts(stmts)),
+            Stmt::If { condition, then_branch, else_branch } => crate::Stmt::If {
+                condition: condition.into_owned(),
+                then_branch: boxed(then_branch),
+                else_branch: else_branch.map(boxed),
+            },
+            Stmt::While { condition, body } => crate::Stmt::While {
+                condition: condition.into_owned(),
+                body: boxed(body),
+            },
+            Stmt::For { initializer, condition, increment, body } => crate::Stmt::For {
+                initializer: initializer.map(boxed),
+                condition: condition.map(Expr::into_owned),
+                increment: increment.map(Expr::into_owned),
+                body: boxed(body),
+            },
+            Stmt::Function { name, params, signature, body, doc } => crate::Stmt::Function {
+                name: name.to_string(),
+                params: owned_names(params),
+                signature,
+                body: owned_stmts(body),
+                doc: owned_doc(doc),
+            },
+            Stmt::Return { value } => crate::Stmt::Return {
+                value: value.map(Expr::into_owned),
+            },
+            Stmt::Break => crate::Stmt::Break,
+            Stmt::Continue => crate::Stmt::Continue,
+            Stmt::Class { name, superclass, fields, methods, doc } => crate::Stmt::Class {
+                name: name.to_string(),
+                superclass: superclass.map(str::to_string),
+                fields: owned_stmts(fields),
+                methods: owned_stmts(methods),
+                doc: owned_doc(doc),
+            },
+            Stmt::Import { path } => crate::Stmt::Import { path: path.into_owned() },
+        }
+    }
+}
+
+fn owned_stmts(stmts: Vec<Stmt<'_>>) -> Vec<crate::Stmt> {
+    stmts.into_iter().map(Stmt::into_owned).collect()
+}
+
+fn owned_names(names: Vec<&str>) -> Vec<String> {
+    names.into_iter().map(str::to_string).collect()
+}
+
+/// A complete Lox program borrowing from its source; mirrors `crate::Program`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<'src> {
+    pub statements: Vec<Stmt<'src>>,
+}
+
+impl<'src> Program<'src> {
+    /// Create a new program with the given statements
+    pub fn new(statements: Vec<Stmt<'src>>) -> Self {
+        Self { statements }
+    }
+
+    /// Convert to the owned program, which no longer borrows from the source
+    pub fn into_owned(self) -> crate::Program {
+        crate::Program::new(owned_stmts(self.statements))
+    }
+}
+
+impl<'src> From<Program<'src>> for crate::Program {
+    fn from(program: Program<'src>) -> Self {
+        program.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_owned() {
+        let source = String::from(r#"var greeting = "hi";"#);
+        let program = Program::new(vec![
+            Stmt::VarDeclaration {
+                name: &source[4..12],
+                type_annotation: None,
+                initializer: Some(Expr::Literal(Value::String(Cow::Borrowed(&source[16..18])))),
+                doc: None,
+            }
+            .with_doc(Some("Say hello")),
+            Stmt::Print(Expr::Call {
+                callee: Box::new(Expr::Get { object: Box::new(Expr::This), name: "greet" }),
+                arguments: vec![Expr::Variable("greeting")],
+            }),
+        ]);
+
+        let owned = program.into_owned();
+        // The owned program outlives the source it was parsed from
+        drop(source);
+        assert_eq!(owned, crate::Program::new(vec![
+            crate::Stmt::VarDeclaration {
+                name: "greeting".to_string(),
+                type_annotation: None,
+                initializer: Some(crate::Expr::Literal(crate::Value::String("hi".to_string()))),
+                doc: Some("Say hello".to_string()),
+            },
+            crate::Stmt::Print(crate::Expr::Call {
+                callee: Box::new(crate::Expr::Get {
+                    object: Box::new(crate::Expr::This),
+                    name: "greet".to_string(),
+                }),
+                arguments: vec![crate::Expr::Variable("greeting".to_string())],
+            }),
+        ]));
+    }
+}
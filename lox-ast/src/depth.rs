@@ -0,0 +1,218 @@
+//! Limits on how deeply a program may nest
+//!
+//! Every backend parses by recursion, going one or more calls deeper for each
+//! `(`, `{`, prefix operator, right-associative `=` or `?` and `if`, `while`,
+//! `for` or `else` body still open. Deeply nested input, such as ten thousand
+//! `(`, would overflow the stack and abort the process. So before parsing, a
+//! backend measures the nesting with [`check_depth`], which fails with a
+//! "nesting too deep" diagnostic past the limit, and then parses on a stack
+//! with room for the depth found, using [`with_stack`].
+//!
+//! A tree can be deep without any nesting in the source: `1 + 1 + ... + 1` is
+//! a chain of `Binary` nodes as long as the sum. Dropping, cloning, comparing
+//! and debug-printing an [`Expr`](crate::Expr) therefore never run out of
+//! stack however deep the tree is.
+//!
+//! ```
+//! use lox_ast::LexOptions;
+//! use lox_ast::depth::check_depth;
+//!
+//! assert_eq!(check_depth("print -(1 + 2);", 8, &LexOptions::default()), Ok(2));
+//!
+//! let source = format!("print {}1{};", "(".repeat(300), ")".repeat(300));
+//! let error = check_depth(&source, 256, &LexOptions::default()).unwrap_err();
+//! assert!(error.describe(&source).starts_with("Nesting too deep at line 1, column 263;"));
+//! ```
+
+use std::ops::ControlFlow;
+
+use crate::lexical::{self, LexOptions, is_identifier_start, is_keyword};
+use crate::{Diagnostic, Span};
+
+/// How deeply a program may nest unless the caller asks for more
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Stack kept free for what a parse needs besides the nesting itself
+const BASE_STACK: usize = 256 * 1024;
+
+/// Below this much free stack, [`grow`] moves to a new stack
+const RED_ZONE: usize = 64 * 1024;
+
+/// The size of each new stack [`grow`] moves to
+const STACK_SEGMENT: usize = 1024 * 1024;
+
+/// The expression nesting of one `(` or `{` level
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    /// The depth of the level itself
+    depth: usize,
+    /// Right-associative operators and statement bodies open on the level
+    chain: usize,
+    /// Prefix operators waiting for their operand
+    prefix: usize,
+}
+
+impl Level {
+    fn current(&self) -> usize {
+        self.depth + self.chain + self.prefix
+    }
+}
+
+/// Measure how deeply `source` nests, failing at the first token that goes
+/// deeper than `max_depth`
+///
+/// The depth counts the open `(` and `{`, the prefix `!` and `-` waiting for
+/// their operand, the `=` and `?` whose right-hand side is being read, and
+/// the `if`, `while`, `for` and `else` whose body is. A source that does not
+/// lex is measured up to the lexical error, which the parser then reports.
+pub fn check_depth(source: &str, max_depth: usize, options: &LexOptions) -> Result<usize, Box<Diagnostic>> {
+    let mut levels = vec![Level::default()];
+    let mut deepest = 0;
+    let mut previous: Option<Span> = None;
+    let mut statement_ended = false;
+    let mut too_deep = None;
+
+    let _ = lexical::scan_tokens(source, options, |span, _| {
+        let token = &source[span.start..span.end];
+        let follows = |text: &str| previous.is_some_and(|previous| previous.end == span.start && &source[previous.start..previous.end] == text);
+        let after_operand = previous.is_some_and(|previous| ends_operand(&source[previous.start..previous.end]));
+        let level = levels.last_mut().expect("the outermost level is never closed");
+        if statement_ended && token != "else" {
+            level.chain = 0;
+        }
+        statement_ended = false;
+
+        match token {
+            "(" | "{" => {
+                let depth = level.current() + 1;
+                levels.push(Level { depth, ..Level::default() });
+            }
+            ")" | "}" => {
+                if levels.len() > 1 {
+                    levels.pop();
+                }
+                statement_ended = token == "}";
+            }
+            "!" if !source[span.end..].starts_with('=') => level.prefix += 1,
+            "-" if !after_operand => level.prefix += 1,
+            "=" if !source[span.end..].starts_with('=') && !["=", "!", "<", ">"].into_iter().any(follows) => {
+                level.prefix = 0;
+                level.chain += 1;
+            }
+            "?" => {
+                level.prefix = 0;
+                level.chain += 1;
+            }
+            "if" | "while" | "for" | "else" => level.chain += 1,
+            ";" => {
+                level.prefix = 0;
+                statement_ended = true;
+            }
+            "," => {
+                level.prefix = 0;
+                level.chain = 0;
+            }
+            _ if !ends_operand(token) => level.prefix = 0,
+            _ => {}
+        }
+
+        let depth = levels.last().map_or(0, Level::current);
+        deepest = deepest.max(depth);
+        previous = Some(span);
+        if depth > max_depth {
+            too_deep = Some(span);
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+
+    match too_deep {
+        Some(span) => Err(Box::new(
+            Diagnostic::error("Nesting too deep", span)
+                .with_label(format!("nested deeper than the limit of {}", max_depth))
+                .with_help("split the expression or statement into smaller ones, or raise the parser's maximum depth"),
+        )),
+        None => Ok(deepest),
+    }
+}
+
+/// Whether `token` can end an operand, so that a `-` after it is binary
+fn ends_operand(token: &str) -> bool {
+    match token {
+        ")" | "this" | "super" | "nil" | "true" | "false" => true,
+        _ if token.starts_with('"') || token.starts_with(|c: char| c.is_ascii_digit() || c == '.') => true,
+        _ => token.starts_with(is_identifier_start) && !is_keyword(token),
+    }
+}
+
+/// Run `f`, which recurses at most `depth` levels deep using up to
+/// `bytes_per_level` of stack on each, on a stack with room for it
+///
+/// When the current stack is too small, `f` runs on a new one allocated for
+/// the purpose.
+pub fn with_stack<R>(depth: usize, bytes_per_level: usize, f: impl FnOnce() -> R) -> R {
+    let needed = (depth + 1).saturating_mul(bytes_per_level).saturating_add(BASE_STACK);
+    match stacker::remaining_stack() {
+        Some(remaining) if remaining >= needed => f(),
+        _ => stacker::grow(needed, f),
+    }
+}
+
+/// Run one level of a recursive walk over a tree, moving to a new stack first
+/// when the current one is nearly used up
+///
+/// For walks whose depth the nesting limit does not bound, such as over the
+/// `Binary` chain of a long sum.
+pub fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(source: &str) -> usize {
+        check_depth(source, usize::MAX, &LexOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(depth(""), 0);
+        assert_eq!(depth("print 1 + 2 * 3 - 4;"), 0);
+        assert_eq!(depth("print (1 + (2 * 3));"), 2);
+        assert_eq!(depth("print !!-x;"), 3);
+        assert_eq!(depth("print -x - -y - -z;"), 1);
+        assert_eq!(depth("a = b = c = 1;"), 3);
+        assert_eq!(depth("print a == b != c <= d >= e;"), 0);
+        assert_eq!(depth("{ { print 1; } }"), 2);
+        assert_eq!(depth("while (a) if (b) print -(c);"), 4);
+        assert_eq!(depth("if (a) print 1; else if (b) print 2; else print 3;"), 4);
+        assert_eq!(depth("f(a = 1, b = 2);"), 2);
+        assert_eq!(depth("print \"((((\"; // ((((\nprint 1;"), 0);
+    }
+
+    #[test]
+    fn test_nesting_too_deep() {
+        let options = LexOptions::default();
+        let source = "print ((1));";
+        assert_eq!(check_depth(source, 2, &options), Ok(2));
+
+        let error = check_depth(source, 1, &options).unwrap_err();
+        assert_eq!(error.message, "Nesting too deep");
+        assert_eq!(error.primary.span, Span::new(7, 8));
+        assert_eq!(error.primary.message, "nested deeper than the limit of 1");
+
+        let unary = format!("print {}x;", "!".repeat(DEFAULT_MAX_DEPTH + 1));
+        let error = check_depth(&unary, DEFAULT_MAX_DEPTH, &options).unwrap_err();
+        assert_eq!(error.primary.span, Span::new(6 + DEFAULT_MAX_DEPTH, 7 + DEFAULT_MAX_DEPTH));
+    }
+
+    #[test]
+    fn test_with_stack() {
+        fn nest(depth: usize) -> usize {
+            let padding = std::hint::black_box([0u8; 1024]);
+            if depth == 0 { padding[0] as usize } else { nest(depth - 1) + 1 }
+        }
+        assert_eq!(with_stack(10_000, 8192, || nest(10_000)), 10_000);
+    }
+}
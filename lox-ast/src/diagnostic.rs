@@ -0,0 +1,235 @@
+//! Structured errors that point into the source
+//!
+//! Every parser reports a failure as a [`Diagnostic`]: a message, the span it
+//! is about, and optionally secondary labels, notes and help text. The
+//! `parse_program` functions turn it into a single line with
+//! [`Diagnostic::describe`]; the `lox-diagnostics` crate renders it as a
+//! report with the source snippet underlined.
+//!
+//! ```
+//! use lox_ast::Span;
+//! use lox_ast::diagnostic::Diagnostic;
+//!
+//! let source = "print (1;";
+//! let diagnostic = Diagnostic::error("Expected ')'", Span::new(8, 9))
+//!     .with_secondary(Span::new(6, 7), "unclosed `(` opened here");
+//! assert_eq!(diagnostic.describe(source), "Expected ')' at line 1, column 9");
+//! ```
+
+use std::fmt;
+
+use crate::lexical::{self, LexError, LexOptions};
+use crate::source::LineIndex;
+use crate::span::Span;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A span of source text and what to say about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    /// Shown next to the underline; may be empty
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self { span, message: message.into() }
+    }
+}
+
+/// A problem found in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Where the problem is
+    pub primary: Label,
+    /// Related places, such as where an unclosed delimiter was opened
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    /// A suggestion for fixing the problem
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// An error about `span`
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    /// A warning about `span`
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Warning, message, span)
+    }
+
+    /// An error about the token at `offset` in `source`, or about the input
+    /// ending there, for a parser that can only tell where it got stuck
+    pub fn unexpected(source: &str, offset: usize, options: &LexOptions) -> Self {
+        let token = lexical::token_span(source, offset, options);
+        if token.is_empty() {
+            Self::error("Unexpected end of input", token)
+        } else {
+            Self::error(format!("Unexpected '{}'", &source[token.start..token.end]), token)
+        }
+    }
+
+    fn new(severity: Severity, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            primary: Label::new(span, ""),
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    /// Set the text shown under the primary span
+    pub fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.primary.message = message.into();
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Point at the innermost `(` or `{` still open where the error is, if any
+    pub fn with_unclosed_delimiter(self, source: &str, options: &LexOptions) -> Self {
+        let open = lexical::unclosed_delimiter(source, self.primary.span.start, options);
+        self.with_opened_at(source, open)
+    }
+
+    /// `with_unclosed_delimiter` for each of `diagnostics`, scanning `source` once
+    pub fn with_unclosed_delimiters(diagnostics: Vec<Self>, source: &str, options: &LexOptions) -> Vec<Self> {
+        let offsets: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.primary.span.start).collect();
+        let open = lexical::unclosed_delimiters(source, &offsets, options);
+        diagnostics.into_iter().zip(open).map(|(diagnostic, open)| diagnostic.with_opened_at(source, open)).collect()
+    }
+
+    fn with_opened_at(self, source: &str, open: Option<Span>) -> Self {
+        match open {
+            Some(open) => {
+                let message = format!("unclosed `{}` opened here", &source[open.start..open.end]);
+                self.with_secondary(open, message)
+            }
+            None => self,
+        }
+    }
+
+    /// The one-line form the `parse_program` functions return: the message,
+    /// the line and column of the primary span, any notes and the help
+    pub fn describe(&self, source: &str) -> String {
+        let location = LineIndex::new(source).location(source, self.primary.span.start);
+        let mut described = format!("{} at {}", self.message, location);
+        for note in &self.notes {
+            described.push_str(". ");
+            described.push_str(note);
+        }
+        if let Some(help) = &self.help {
+            described.push_str("; ");
+            described.push_str(help);
+        }
+        described
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        Diagnostic::error(error.kind.to_string(), error.span)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexical::LexErrorKind;
+
+    const STRICT: LexOptions = LexOptions {
+        escapes: false,
+        number_extensions: false,
+        block_comments: false,
+        doc_comments: false,
+    };
+
+    #[test]
+    fn test_lexical_errors_describe_the_same() {
+        let source = "print 1;\nprint \"oops;";
+        let error = lexical::validate(source, &STRICT).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(Diagnostic::from(error.clone()).describe(source), error.describe(source));
+    }
+
+    #[test]
+    fn test_unclosed_delimiter() {
+        let source = "fun f() {\n  print (1 + \")\";\n}";
+        let at = source.find(';').unwrap();
+        let diagnostic =
+            Diagnostic::error("Expected ')'", Span::new(at, at + 1)).with_unclosed_delimiter(source, &STRICT);
+        let paren = source.find("(1").unwrap();
+        assert_eq!(diagnostic.secondary, vec![Label::new(Span::new(paren, paren + 1), "unclosed `(` opened here")]);
+
+        // Closed delimiters, and ones after the error, are not reported
+        let source = "print (1); print (2";
+        let diagnostic =
+            Diagnostic::error("Expected ';'", Span::new(9, 10)).with_unclosed_delimiter(source, &STRICT);
+        assert!(diagnostic.secondary.is_empty());
+        let diagnostic = Diagnostic::error("Unexpected end of input", Span::new(19, 19))
+            .with_unclosed_delimiter(source, &STRICT);
+        assert_eq!(diagnostic.secondary[0].span, Span::new(17, 18));
+    }
+
+    #[test]
+    fn test_unclosed_delimiters() {
+        // One scan answers for every diagnostic, whatever order they come in
+        let source = "print (1;\n{ print 2 }\nprint (3";
+        let at = |text: &str| source.find(text).unwrap();
+        let diagnostics = vec![
+            Diagnostic::error("Unexpected end of input", Span::new(source.len(), source.len())),
+            Diagnostic::error("Expected ')'", Span::new(at(";"), at(";") + 1)),
+            Diagnostic::error("Expected ';'", Span::new(at("}"), at("}") + 1)),
+        ];
+        let expected: Vec<_> = diagnostics.iter().map(|d| d.clone().with_unclosed_delimiter(source, &STRICT)).collect();
+        let enriched = Diagnostic::with_unclosed_delimiters(diagnostics, source, &STRICT);
+        assert_eq!(enriched, expected);
+        assert_eq!(enriched[2].secondary[0].span, Span::new(at("{"), at("{") + 1));
+    }
+
+    #[test]
+    fn test_token_span() {
+        let source = "print \"two words\" + abc1;";
+        assert_eq!(lexical::token_span(source, 0, &STRICT), Span::new(0, 5));
+        assert_eq!(lexical::token_span(source, 6, &STRICT), Span::new(6, 17));
+        assert_eq!(lexical::token_span(source, 18, &STRICT), Span::new(18, 19));
+        assert_eq!(lexical::token_span(source, source.len(), &STRICT), Span::new(25, 25));
+        assert_eq!(lexical::token_span("\"open", 0, &STRICT), Span::new(0, 1));
+
+        assert_eq!(Diagnostic::unexpected(source, 18, &STRICT).describe(source), "Unexpected '+' at line 1, column 19");
+        assert_eq!(Diagnostic::unexpected(source, 25, &STRICT).message, "Unexpected end of input");
+    }
+}
@@ -0,0 +1,80 @@
+//! Language extensions beyond the Lox described in the book
+//!
+//! Unlike the lexical extensions, which are fixed when a backend is built, these
+//! are chosen per parse by passing `LanguageOptions` to a backend's
+//! `parse_program_with`. The default is strict book Lox.
+
+use crate::lexical::is_keyword;
+
+/// Words that become reserved when `break` and `continue` are enabled
+pub const LOOP_CONTROL_KEYWORDS: &[&str] = &["break", "continue"];
+
+/// The word that becomes reserved when imports are enabled
+pub const IMPORT_KEYWORD: &str = "import";
+
+/// Language extensions to accept while parsing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LanguageOptions {
+    /// `break;` and `continue;` inside loops
+    pub break_continue: bool,
+    /// The conditional operator `condition ? a : b`
+    pub conditional: bool,
+    /// Anonymous functions `fun (params) { body }` in expressions
+    pub lambdas: bool,
+    /// The comma operator `a, b`, which evaluates both and yields `b`
+    pub comma_operator: bool,
+    /// Type annotations on variables, parameters, return values and class fields
+    pub type_annotations: bool,
+    /// `import "path";` statements, which a loader resolves to other files
+    pub imports: bool,
+}
+
+impl LanguageOptions {
+    /// Strict book Lox, with every extension turned off
+    pub const STRICT: LanguageOptions = LanguageOptions {
+        break_continue: false,
+        conditional: false,
+        lambdas: false,
+        comma_operator: false,
+        type_annotations: false,
+        imports: false,
+    };
+
+    /// Every extension turned on
+    pub const EXTENDED: LanguageOptions = LanguageOptions {
+        break_continue: true,
+        conditional: true,
+        lambdas: true,
+        comma_operator: true,
+        type_annotations: true,
+        imports: true,
+    };
+
+    /// Check whether `word` is reserved under these options
+    pub fn is_reserved(&self, word: &str) -> bool {
+        is_keyword(word)
+            || (self.break_continue && LOOP_CONTROL_KEYWORDS.contains(&word))
+            || (self.imports && word == IMPORT_KEYWORD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_strict() {
+        assert_eq!(LanguageOptions::default(), LanguageOptions::STRICT);
+    }
+
+    #[test]
+    fn test_loop_control_keywords_reserved_only_when_enabled() {
+        assert!(!LanguageOptions::STRICT.is_reserved("break"));
+        assert!(LanguageOptions::EXTENDED.is_reserved("break"));
+        assert!(LanguageOptions::EXTENDED.is_reserved("continue"));
+        assert!(LanguageOptions::STRICT.is_reserved("while"));
+        assert!(!LanguageOptions::EXTENDED.is_reserved("breaks"));
+        assert!(!LanguageOptions::STRICT.is_reserved("import"));
+        assert!(LanguageOptions::EXTENDED.is_reserved("import"));
+    }
+}
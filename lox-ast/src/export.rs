@@ -0,0 +1,425 @@
+//! Graphviz and Mermaid renderings of programs
+//!
+//! `to_dot` and `to_mermaid` draw a program as a tree: each node is labelled
+//! with what it holds (an operator, a literal, a name) and each edge with the
+//! field it comes from (`left`, `condition`, `then_branch`, `arguments[0]`).
+//!
+//! `render_side_by_side` draws two trees for the same input, such as the
+//! output of two parsers, next to each other. Subtrees present in only one of
+//! them, or whose labels differ, are highlighted in both:
+//!
+//! ```
+//! use lox_ast::export::{GraphFormat, render_side_by_side};
+//! use lox_ast::{Expr, Program, Stmt, Value};
+//!
+//! let one = Program::new(vec![Stmt::Print(Expr::Literal(Value::Number(1.0)))]);
+//! let two = Program::new(vec![Stmt::Print(Expr::Literal(Value::Number(2.0)))]);
+//! let dot = render_side_by_side(("before", &one), ("after", &two), GraphFormat::Dot);
+//! assert!(dot.contains("l2 [label=\"1\", class=\"changed\""));
+//! ```
+//!
+//! The DOT output is rendered with `dot -Tsvg`, and the Mermaid output can be
+//! pasted into a ```` ```mermaid ```` block.
+//!
+//! `to_outline` writes the same tree as indented text, one node per line,
+//! which reads well in snapshot files and line diffs.
+
+use crate::{Expr, Program, Stmt};
+
+/// The graph languages programs can be rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+}
+
+/// Render `program` as a Graphviz DOT digraph
+pub fn to_dot(program: &Program) -> String {
+    render(program, GraphFormat::Dot)
+}
+
+/// Render `program` as a Mermaid flowchart
+pub fn to_mermaid(program: &Program) -> String {
+    render(program, GraphFormat::Mermaid)
+}
+
+/// Render `program` in `format`
+pub fn render(program: &Program, format: GraphFormat) -> String {
+    let tree = Node::program(program);
+    let mut graph = Graph::new(format);
+    graph.tree(&tree, "n");
+    graph.finish()
+}
+
+/// The tree of `program` as indented text: each node on its own line,
+/// after the edge leading to it and indented two spaces under its parent
+///
+/// ```text
+/// Program
+///   statements[0]: Print
+///     expression: +
+/// ```
+pub fn to_outline(program: &Program) -> String {
+    let mut outline = String::new();
+    Node::program(program).outline(None, 0, &mut outline);
+    outline
+}
+
+/// Render two programs next to each other, each under its label, with the
+/// subtrees where they differ highlighted
+pub fn render_side_by_side(left: (&str, &Program), right: (&str, &Program), format: GraphFormat) -> String {
+    let (mut left_tree, mut right_tree) = (Node::program(left.1), Node::program(right.1));
+    compare(&mut left_tree, &mut right_tree);
+
+    let mut graph = Graph::new(format);
+    graph.cluster("left", left.0, &left_tree, "l");
+    graph.cluster("right", right.0, &right_tree, "r");
+    graph.finish()
+}
+
+/// A node of the rendered tree
+struct Node {
+    label: String,
+    /// Children with the label of the edge leading to them
+    children: Vec<(String, Node)>,
+    /// Part of a subtree that differs from the other side of a comparison
+    changed: bool,
+}
+
+impl Node {
+    fn new(label: impl Into<String>) -> Self {
+        Node { label: label.into(), children: Vec::new(), changed: false }
+    }
+
+    fn child(mut self, edge: &str, node: Node) -> Self {
+        self.children.push((edge.to_string(), node));
+        self
+    }
+
+    fn optional(self, edge: &str, node: Option<Node>) -> Self {
+        match node {
+            Some(node) => self.child(edge, node),
+            None => self,
+        }
+    }
+
+    fn list(mut self, edge: &str, nodes: impl IntoIterator<Item = Node>) -> Self {
+        for (index, node) in nodes.into_iter().enumerate() {
+            self.children.push((format!("{}[{}]", edge, index), node));
+        }
+        self
+    }
+
+    fn program(program: &Program) -> Self {
+        Node::new("Program").list("statements", program.statements.iter().map(Node::stmt))
+    }
+
+    fn stmt(stmt: &Stmt) -> Self {
+        match stmt {
+            Stmt::Expression(expr) => Node::new("Expression").child("expression", Node::expr(expr)),
+            Stmt::Print(expr) => Node::new("Print").child("expression", Node::expr(expr)),
+            Stmt::VarDeclaration { name, type_annotation, initializer, .. } => {
+                let label = match type_annotation {
+                    Some(annotation) => format!("var {}: {}", name, annotation),
+                    None => format!("var {}", name),
+                };
+                Node::new(label).optional("initializer", initializer.as_ref().map(Node::expr))
+            }
+            Stmt::Block(statements) => Node::new("Block").list("statements", statements.iter().map(Node::stmt)),
+            Stmt::If { condition, then_branch, else_branch } => Node::new("If")
+                .child("condition", Node::expr(condition))
+                .child("then_branch", Node::stmt(then_branch))
+                .optional("else_branch", else_branch.as_deref().map(Node::stmt)),
+            Stmt::While { condition, body } => {
+                Node::new("While").child("condition", Node::expr(condition)).child("body", Node::stmt(body))
+            }
+            Stmt::For { initializer, condition, increment, body } => Node::new("For")
+                .optional("initializer", initializer.as_deref().map(Node::stmt))
+                .optional("condition", condition.as_ref().map(Node::expr))
+                .optional("increment", increment.as_ref().map(Node::expr))
+                .child("body", Node::stmt(body)),
+            Stmt::Function { name, params, body, .. } => {
+                Node::new(format!("fun {}({})", name, params.join(", "))).list("body", body.iter().map(Node::stmt))
+            }
+            Stmt::Return { value } => Node::new("Return").optional("value", value.as_ref().map(Node::expr)),
+            Stmt::Break => Node::new("Break"),
+            Stmt::Continue => Node::new("Continue"),
+            Stmt::Import { path } => Node::new(format!("import \"{}\"", path)),
+            Stmt::Class { name, superclass, fields, methods, .. } => {
+                let label = match superclass {
+                    Some(superclass) => format!("class {} < {}", name, superclass),
+                    None => format!("class {}", name),
+                };
+                Node::new(label)
+                    .list("fields", fields.iter().map(Node::stmt))
+                    .list("methods", methods.iter().map(Node::stmt))
+            }
+        }
+    }
+
+    fn expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::Literal(value) => Node::new(value.to_string()),
+            Expr::Variable(name) => Node::new(name.as_str()),
+            Expr::Binary { left, operator, right } => {
+                Node::new(operator.to_string()).child("left", Node::expr(left)).child("right", Node::expr(right))
+            }
+            Expr::Unary { operator, operand } => Node::new(operator.to_string()).child("operand", Node::expr(operand)),
+            Expr::Grouping(inner) => Node::new("( )").child("expression", Node::expr(inner)),
+            Expr::Assignment { name, value } => Node::new(format!("{} =", name)).child("value", Node::expr(value)),
+            Expr::Call { callee, arguments } => {
+                Node::new("call").child("callee", Node::expr(callee)).list("arguments", arguments.iter().map(Node::expr))
+            }
+            Expr::Get { object, name } => Node::new(format!(".{}", name)).child("object", Node::expr(object)),
+            Expr::Set { object, name, value } => Node::new(format!(".{} =", name))
+                .child("object", Node::expr(object))
+                .child("value", Node::expr(value)),
+            Expr::This => Node::new("this"),
+            Expr::Super { method } => Node::new(format!("super.{}", method)),
+            Expr::Conditional { condition, then_branch, else_branch } => Node::new("?:")
+                .child("condition", Node::expr(condition))
+                .child("then_branch", Node::expr(then_branch))
+                .child("else_branch", Node::expr(else_branch)),
+            Expr::Lambda { params, body } => {
+                Node::new(format!("fun ({})", params.join(", "))).list("body", body.iter().map(Node::stmt))
+            }
+        }
+    }
+
+    fn outline(&self, edge: Option<&str>, depth: usize, outline: &mut String) {
+        outline.push_str(&"  ".repeat(depth));
+        if let Some(edge) = edge {
+            outline.push_str(edge);
+            outline.push_str(": ");
+        }
+        // Keep one node per line whatever the strings in it hold
+        for c in self.label.chars() {
+            if c.is_control() { outline.extend(c.escape_default()) } else { outline.push(c) }
+        }
+        outline.push('\n');
+        for (edge, child) in &self.children {
+            child.outline(Some(edge), depth + 1, outline);
+        }
+    }
+
+    /// Flag this node and everything below it as changed
+    fn mark(&mut self) {
+        self.changed = true;
+        for (_, child) in &mut self.children {
+            child.mark();
+        }
+    }
+}
+
+/// Flag the subtrees where two trees differ
+///
+/// Children are paired by their edge label, so a statement added to a block
+/// only flags that statement.
+fn compare(left: &mut Node, right: &mut Node) {
+    if left.label != right.label {
+        left.mark();
+        right.mark();
+        return;
+    }
+    let mut matched = vec![false; right.children.len()];
+    for (edge, left_child) in &mut left.children {
+        match right.children.iter().position(|(right_edge, _)| right_edge == edge) {
+            Some(index) => {
+                matched[index] = true;
+                compare(left_child, &mut right.children[index].1);
+            }
+            None => left_child.mark(),
+        }
+    }
+    for ((_, right_child), matched) in right.children.iter_mut().zip(matched) {
+        if !matched {
+            right_child.mark();
+        }
+    }
+}
+
+/// Text of a graph being written
+struct Graph {
+    format: GraphFormat,
+    out: String,
+    highlighted: bool,
+}
+
+impl Graph {
+    fn new(format: GraphFormat) -> Self {
+        let out = match format {
+            GraphFormat::Dot => "digraph lox {\n  node [shape=box, fontname=\"monospace\"];\n  edge [fontname=\"monospace\", fontsize=10];\n".to_string(),
+            GraphFormat::Mermaid => "flowchart TD\n".to_string(),
+        };
+        Graph { format, out, highlighted: false }
+    }
+
+    /// Write the nodes of `tree`, numbered in depth-first order with ids
+    /// starting with `prefix`
+    fn tree(&mut self, tree: &Node, prefix: &str) {
+        let mut next_id = 0;
+        self.node(tree, prefix, &mut next_id);
+    }
+
+    fn node(&mut self, node: &Node, prefix: &str, next_id: &mut usize) -> String {
+        let id = format!("{}{}", prefix, *next_id);
+        let highlight = node.changed;
+        *next_id += 1;
+        self.highlighted |= highlight;
+        match self.format {
+            GraphFormat::Dot => {
+                let style = if highlight { ", class=\"changed\", style=filled, fillcolor=\"#f8d7da\", color=\"#dc3545\"" } else { "" };
+                self.out.push_str(&format!("  {} [label=\"{}\"{}];\n", id, escape_dot(&node.label), style));
+            }
+            GraphFormat::Mermaid => {
+                let class = if highlight { ":::changed" } else { "" };
+                self.out.push_str(&format!("  {}[\"{}\"]{}\n", id, escape_mermaid(&node.label), class));
+            }
+        }
+        for (edge, child) in &node.children {
+            let child_id = self.node(child, prefix, next_id);
+            match self.format {
+                GraphFormat::Dot => {
+                    self.out.push_str(&format!("  {} -> {} [label=\"{}\"];\n", id, child_id, escape_dot(edge)));
+                }
+                GraphFormat::Mermaid => {
+                    self.out.push_str(&format!("  {} -->|\"{}\"| {}\n", id, escape_mermaid(edge), child_id));
+                }
+            }
+        }
+        id
+    }
+
+    /// Write `tree` inside a titled box
+    fn cluster(&mut self, name: &str, title: &str, tree: &Node, prefix: &str) {
+        match self.format {
+            GraphFormat::Dot => {
+                self.out.push_str(&format!("  subgraph cluster_{} {{\n  label=\"{}\";\n", name, escape_dot(title)));
+                self.tree(tree, prefix);
+                self.out.push_str("  }\n");
+            }
+            GraphFormat::Mermaid => {
+                self.out.push_str(&format!("  subgraph {}[\"{}\"]\n", name, escape_mermaid(title)));
+                self.tree(tree, prefix);
+                self.out.push_str("  end\n");
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        match self.format {
+            GraphFormat::Dot => self.out.push_str("}\n"),
+            GraphFormat::Mermaid if self.highlighted => {
+                self.out.push_str("  classDef changed fill:#f8d7da,stroke:#dc3545\n");
+            }
+            GraphFormat::Mermaid => {}
+        }
+        self.out
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;").replace('"', "#quot;").replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinaryOp, Value};
+
+    fn number(value: f64) -> Box<Expr> {
+        Box::new(Expr::Literal(Value::Number(value)))
+    }
+
+    /// `if (x) print 1 + 2;`
+    fn sample() -> Program {
+        Program::new(vec![Stmt::If {
+            condition: Expr::Variable("x".to_string()),
+            then_branch: Box::new(Stmt::Print(Expr::Binary { left: number(1.0), operator: BinaryOp::Add, right: number(2.0) })),
+            else_branch: None,
+        }])
+    }
+
+    #[test]
+    fn test_outline() {
+        assert_eq!(
+            to_outline(&sample()),
+            "Program\n  statements[0]: If\n    condition: x\n    then_branch: Print\n      expression: +\n        left: 1\n        right: 2\n"
+        );
+        let multiline = Program::new(vec![Stmt::Print(Expr::Literal(Value::String("a\nb".to_string())))]);
+        assert_eq!(to_outline(&multiline), "Program\n  statements[0]: Print\n    expression: \"a\\nb\"\n");
+    }
+
+    #[test]
+    fn test_dot() {
+        let expected = "digraph lox {
+  node [shape=box, fontname=\"monospace\"];
+  edge [fontname=\"monospace\", fontsize=10];
+  n0 [label=\"Program\"];
+  n1 [label=\"If\"];
+  n2 [label=\"x\"];
+  n1 -> n2 [label=\"condition\"];
+  n3 [label=\"Print\"];
+  n4 [label=\"+\"];
+  n5 [label=\"1\"];
+  n4 -> n5 [label=\"left\"];
+  n6 [label=\"2\"];
+  n4 -> n6 [label=\"right\"];
+  n3 -> n4 [label=\"expression\"];
+  n1 -> n3 [label=\"then_branch\"];
+  n0 -> n1 [label=\"statements[0]\"];
+}
+";
+        assert_eq!(to_dot(&sample()), expected);
+    }
+
+    #[test]
+    fn test_mermaid() {
+        let mermaid = to_mermaid(&sample());
+        assert!(mermaid.starts_with("flowchart TD\n  n0[\"Program\"]\n  n1[\"If\"]\n  n2[\"x\"]\n  n1 -->|\"condition\"| n2\n"));
+        assert!(!mermaid.contains("classDef"));
+    }
+
+    #[test]
+    fn test_labels_are_escaped() {
+        let program = Program::new(vec![Stmt::Print(Expr::Literal(Value::String("say \"#1\"".to_string())))]);
+        assert!(to_dot(&program).contains("n2 [label=\"\\\"say \\\"#1\\\"\\\"\"];"));
+        assert!(to_mermaid(&program).contains("n2[\"#quot;say #quot;#35;1#quot;#quot;\"]"));
+    }
+
+    #[test]
+    fn test_side_by_side_highlights_differences() {
+        let mut other = sample();
+        // Parse `1 + 2` as a grouping, and add a statement
+        if let Stmt::If { then_branch, .. } = &mut other.statements[0]
+            && let Stmt::Print(expr) = then_branch.as_mut()
+        {
+            *expr = Expr::Grouping(Box::new(expr.clone()));
+        }
+        other.statements.push(Stmt::Break);
+
+        let dot = render_side_by_side(("nom", &sample()), ("pest", &other), GraphFormat::Dot);
+        let changed: Vec<&str> =
+            dot.lines().filter(|line| line.contains("class=\"changed\"")).map(|line| line.trim().split(' ').next().unwrap()).collect();
+        // The binary node and its operands on the left; the grouping, the
+        // binary node, its operands and the added statement on the right
+        assert_eq!(changed, vec!["l4", "l5", "l6", "r4", "r5", "r6", "r7", "r8"]);
+        assert!(dot.contains("subgraph cluster_left {\n  label=\"nom\";\n"));
+        assert!(dot.contains("subgraph cluster_right {\n  label=\"pest\";\n"));
+
+        let mermaid = render_side_by_side(("nom", &sample()), ("pest", &other), GraphFormat::Mermaid);
+        assert!(mermaid.contains("  r4[\"( )\"]:::changed\n"));
+        assert!(mermaid.contains("  r8[\"Break\"]:::changed\n"));
+        assert!(mermaid.contains("  l3[\"Print\"]\n"));
+        assert!(mermaid.ends_with("classDef changed fill:#f8d7da,stroke:#dc3545\n"));
+
+        let same = render_side_by_side(("nom", &sample()), ("pest", &sample()), GraphFormat::Dot);
+        assert!(!same.contains("changed"));
+    }
+}
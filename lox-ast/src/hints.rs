@@ -0,0 +1,584 @@
+//! Plain explanations of common syntax errors
+//!
+//! Each parser reports a failure in its own terms, often as the list of tokens
+//! it would have accepted, and not always at the same place: some point at the
+//! token they got stuck on, others at the start of the statement that failed.
+//! [`explain`] looks at the tokens of the statement around the error and, when
+//! it recognizes one of the usual mistakes, replaces the diagnostic with one
+//! that says what is wrong:
+//!
+//! - an unbalanced `(`, `)`, `{` or `}`
+//! - `var` used inside an expression
+//! - a misspelled keyword such as `retrun`, or one from another language such as `function`
+//! - `=` in an `if` or `while` condition where `==` was meant
+//! - assignment to something that is not a variable or field, such as `1 + 2 = 3`
+//! - a missing `;` at the end of a statement
+//!
+//! Anything else, including every lexical error, is returned unchanged. Every
+//! backend's `parse_program_diagnostic` applies it, so they all explain these
+//! mistakes the same way.
+//!
+//! ```
+//! use lox_ast::{Diagnostic, LanguageOptions, LexOptions, Span};
+//! use lox_ast::hints::explain;
+//!
+//! let source = "retrun 1;";
+//! let diagnostic = Diagnostic::error("Unexpected token '1'", Span::new(7, 8));
+//! let explained = explain(source, diagnostic, &LexOptions::default(), &LanguageOptions::STRICT);
+//! assert_eq!(explained.describe(source), "Unexpected identifier 'retrun' at line 1, column 1; did you mean 'return'?");
+//! ```
+
+use std::ops::{ControlFlow, Range};
+
+use crate::diagnostic::Diagnostic;
+use crate::dialect::{IMPORT_KEYWORD, LOOP_CONTROL_KEYWORDS, LanguageOptions};
+use crate::lexical::{self, KEYWORDS, LexOptions};
+use crate::span::Span;
+
+/// Words other languages use, and the Lox keyword that does the same job
+const FOREIGN_KEYWORDS: &[(&str, &str)] = &[
+    ("const", "var"),
+    ("def", "fun"),
+    ("elif", "else"),
+    ("elsif", "else"),
+    ("fn", "fun"),
+    ("func", "fun"),
+    ("function", "fun"),
+    ("let", "var"),
+    ("null", "nil"),
+    ("self", "this"),
+];
+
+/// Keywords that begin a statement, so cannot continue the expression before them
+const STATEMENT_KEYWORDS: &[&str] = &["class", "for", "fun", "if", "print", "return", "var", "while"];
+
+/// Keywords that are expressions on their own
+const LITERAL_KEYWORDS: &[&str] = &["false", "nil", "super", "this", "true"];
+
+/// Replace `diagnostic` with a plainer one if the error around it is a common mistake
+///
+/// `lex` and `language` must be the options `source` was parsed with.
+pub fn explain(source: &str, diagnostic: Diagnostic, lex: &LexOptions, language: &LanguageOptions) -> Diagnostic {
+    match Hints::new(source, lex, language) {
+        Some(hints) => hints.explain(diagnostic),
+        None => diagnostic,
+    }
+}
+
+/// `explain` for each of `diagnostics`, tokenizing `source` once
+///
+/// For a backend that recovers from errors and reports them all.
+pub fn explain_all(
+    source: &str,
+    diagnostics: Vec<Diagnostic>,
+    lex: &LexOptions,
+    language: &LanguageOptions,
+) -> Vec<Diagnostic> {
+    match Hints::new(source, lex, language) {
+        Some(hints) => diagnostics.into_iter().map(|diagnostic| hints.explain(diagnostic)).collect(),
+        None => diagnostics,
+    }
+}
+
+/// The keyword `word` is probably a misspelling of, if any
+///
+/// Allows one edit in words of up to four letters and two in longer ones,
+/// counting a swap of neighbouring letters as one edit.
+pub fn suggest_keyword(word: &str, language: &LanguageOptions) -> Option<&'static str> {
+    if word.len() < 2 || language.is_reserved(word) {
+        return None;
+    }
+    let limit = if word.len() <= 4 { 1 } else { 2 };
+    let extensions = LOOP_CONTROL_KEYWORDS
+        .iter()
+        .filter(|_| language.break_continue)
+        .chain(std::iter::once(&IMPORT_KEYWORD).filter(|_| language.imports));
+    let keywords = KEYWORDS.iter().chain(extensions).map(|&keyword| (keyword, keyword));
+    let foreign = FOREIGN_KEYWORDS.iter().copied();
+    keywords
+        .chain(foreign)
+        .map(|(spelling, keyword)| (edit_distance(word, spelling), keyword))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, keyword)| keyword)
+}
+
+/// The number of single-character insertions, deletions, substitutions and
+/// swaps of neighbouring characters that turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i characters of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in distances[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    span: Span,
+    text: &'a str,
+}
+
+/// The tokens of `source`, with `==`, `!=`, `<=` and `>=` as one token each,
+/// or `None` if it does not lex
+fn tokens<'a>(source: &'a str, options: &LexOptions) -> Option<Vec<Token<'a>>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let scanned = lexical::scan_tokens(source, options, |span, _| {
+        let text = &source[span.start..span.end];
+        match tokens.last_mut() {
+            Some(last) if last.span.end == span.start && text == "=" && matches!(last.text, "=" | "!" | "<" | ">") => {
+                last.span.end = span.end;
+                last.text = &source[last.span.start..span.end];
+            }
+            _ => tokens.push(Token { span, text }),
+        }
+        ControlFlow::Continue(())
+    });
+    scanned.ok().map(|()| tokens)
+}
+
+/// A closing delimiter and the opening one it matches
+fn opener(closer: &str) -> Option<&'static str> {
+    match closer {
+        ")" => Some("("),
+        "}" => Some("{"),
+        _ => None,
+    }
+}
+
+fn closer(opener: &str) -> &'static str {
+    if opener == "(" { ")" } else { "}" }
+}
+
+struct Hints<'a> {
+    source: &'a str,
+    tokens: Vec<Token<'a>>,
+    language: &'a LanguageOptions,
+}
+
+impl<'a> Hints<'a> {
+    /// The tokens of `source`, or `None` when there are none to look at
+    fn new(source: &'a str, lex: &LexOptions, language: &'a LanguageOptions) -> Option<Self> {
+        let tokens = tokens(source, lex).filter(|tokens| !tokens.is_empty())?;
+        Some(Hints { source, tokens, language })
+    }
+
+    fn explain(&self, diagnostic: Diagnostic) -> Diagnostic {
+        let at = self.tokens.partition_point(|token| token.span.end <= diagnostic.primary.span.start);
+        let statement = self.statement(at);
+        if let Some(hint) = self.explain_statement(&statement, at) {
+            return hint;
+        }
+        // A backend that points at the start of a declaration with a body may
+        // have failed anywhere inside it, so try the statements of the body in turn
+        if at <= statement.end && self.tokens.get(statement.end).is_some_and(|token| token.text == "{") {
+            let body_end = self.closing(statement.end).unwrap_or(self.tokens.len());
+            let mut next = statement.end + 1;
+            while next < body_end {
+                let inner = self.statement(next);
+                if let Some(hint) = self.explain_statement(&inner, next) {
+                    return hint;
+                }
+                next = inner.end + 1;
+            }
+        }
+        diagnostic
+    }
+
+    /// The tokens of the statement containing token `at`; `end` is the index of
+    /// the `;`, `{` or `}` that ends it, or the number of tokens
+    ///
+    /// The `;`s in the header of a `for` loop do not end it.
+    fn statement(&self, at: usize) -> Range<usize> {
+        let is_boundary = |token: &Token| matches!(token.text, ";" | "{" | "}");
+        let start = self.tokens[..at].iter().rposition(is_boundary).map_or(0, |i| i + 1);
+        let in_for = self.tokens.get(start).is_some_and(|token| token.text == "for");
+        let mut depth = 0usize;
+        let mut end = start;
+        while let Some(token) = self.tokens.get(end) {
+            match token.text {
+                "(" => depth += 1,
+                ")" => depth = depth.saturating_sub(1),
+                ";" if in_for && depth > 0 => {}
+                ";" | "{" | "}" => break,
+                _ => {}
+            }
+            end += 1;
+        }
+        start..end
+    }
+
+    fn is_name(&self, token: &Token) -> bool {
+        token.text.starts_with(lexical::is_identifier_start) && !self.language.is_reserved(token.text)
+    }
+
+    /// Whether `token` can start an operand: a name, literal, `this` or `super`
+    fn starts_operand(&self, token: &Token) -> bool {
+        self.is_name(token)
+            || lexical::starts_number(token.text)
+            || token.text.starts_with('"')
+            || LITERAL_KEYWORDS.contains(&token.text)
+    }
+
+    /// Whether `token` can end an expression
+    fn ends_operand(&self, token: &Token) -> bool {
+        token.text == ")" || (token.text != "super" && self.starts_operand(token))
+    }
+
+    /// The first hint that applies to the statement around token `at`
+    fn explain_statement(&self, statement: &Range<usize>, at: usize) -> Option<Diagnostic> {
+        self.unbalanced(statement, at)
+            .or_else(|| self.var_in_expression(statement))
+            .or_else(|| self.misspelled_keyword(statement))
+            .or_else(|| self.invalid_assignment(statement))
+            .or_else(|| self.missing_semicolon(statement, at))
+    }
+
+    /// The index of the `)` or `}` matching the `(` or `{` at `open`
+    fn closing(&self, open: usize) -> Option<usize> {
+        let opener = self.tokens[open].text;
+        let mut depth = 0;
+        for (i, token) in self.tokens.iter().enumerate().skip(open) {
+            match token.text {
+                text if text == opener => depth += 1,
+                text if text == closer(opener) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn expected_closer(&self, open: usize, span: Span, place: &str) -> Diagnostic {
+        let open = self.tokens[open];
+        Diagnostic::error(format!("Expected '{}' {}", closer(open.text), place), span)
+            .with_secondary(open.span, format!("unclosed `{}` opened here", open.text))
+    }
+
+    /// A `)` or `}` with nothing to close, or a `(` or `{` never closed
+    fn unbalanced(&self, statement: &Range<usize>, at: usize) -> Option<Diagnostic> {
+        let in_statement = |i: usize| statement.start <= i && i <= statement.end;
+        let mut open: Vec<usize> = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            let terminates = i == statement.end && (token.text == ";" || (token.text == "{" && !self.language.lambdas));
+            if terminates
+                && let Some(&paren) = open.last()
+                && self.tokens[paren].text == "("
+                && paren >= statement.start
+            {
+                let place = format!("before '{}'", token.text);
+                return Some(self.expected_closer(paren, token.span, &place).with_label("expected ')' before this"));
+            }
+            if matches!(token.text, "(" | "{") {
+                open.push(i);
+            } else if let Some(opener) = opener(token.text) {
+                match open.last() {
+                    Some(&top) if self.tokens[top].text == opener => {
+                        open.pop();
+                    }
+                    Some(&top) if in_statement(i) => {
+                        let place = format!("before '{}'", token.text);
+                        return Some(self.expected_closer(top, token.span, &place));
+                    }
+                    None if in_statement(i) => {
+                        return Some(
+                            Diagnostic::error(format!("Unmatched '{}'", token.text), token.span)
+                                .with_label(format!("no `{}` is open here", opener)),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let unclosed = if at == self.tokens.len() {
+            open.last()
+        } else {
+            open.iter().find(|&&i| in_statement(i))
+        };
+        unclosed.map(|&i| {
+            let end = self.tokens[self.tokens.len() - 1].span.end;
+            self.expected_closer(i, Span::new(end, end), "at end of input")
+        })
+    }
+
+    /// `var` after the start of a statement
+    fn var_in_expression(&self, statement: &Range<usize>) -> Option<Diagnostic> {
+        let var = (statement.start.max(1)..statement.end).find(|&i| {
+            let before = self.tokens[i - 1].text;
+            let for_initializer = before == "(" && i >= 2 && self.tokens[i - 2].text == "for";
+            self.tokens[i].text == "var" && !matches!(before, ";" | "{" | "}" | ")" | "else") && !for_initializer
+        })?;
+        Some(
+            Diagnostic::error("'var' cannot be used inside an expression", self.tokens[var].span)
+                .with_label("declarations are statements")
+                .with_help("declare the variable in a statement of its own before this one"),
+        )
+    }
+
+    /// A name close to a keyword, where a name cannot be: before another
+    /// operand, before `{`, or before a parenthesized list and `{`
+    fn misspelled_keyword(&self, statement: &Range<usize>) -> Option<Diagnostic> {
+        statement.clone().find_map(|i| {
+            let token = &self.tokens[i];
+            let next = self.tokens.get(i + 1)?;
+            let misplaced = self.starts_operand(next)
+                || next.text == "{"
+                || (next.text == "("
+                    && self.closing(i + 1).and_then(|close| self.tokens.get(close + 1)).is_some_and(|after| after.text == "{"));
+            if !self.is_name(token) || !misplaced {
+                return None;
+            }
+            let keyword = suggest_keyword(token.text, self.language)?;
+            Some(
+                Diagnostic::error(format!("Unexpected identifier '{}'", token.text), token.span)
+                    .with_label("not a keyword")
+                    .with_help(format!("did you mean '{}'?", keyword)),
+            )
+        })
+    }
+
+    /// `=` after something that cannot be assigned to
+    fn invalid_assignment(&self, statement: &Range<usize>) -> Option<Diagnostic> {
+        statement.clone().filter(|&i| self.tokens[i].text == "=").find_map(|equals| {
+            let (start, stop) = self.assignment_target(statement.start, equals);
+            let stop = stop.map(|i| self.tokens[i].text);
+            if start == equals || stop == Some("var") || self.is_assignable(start..equals) {
+                return None;
+            }
+            let in_condition = stop == Some("(") && start >= 2 && matches!(self.tokens[start - 2].text, "if" | "while");
+            if in_condition {
+                return Some(
+                    Diagnostic::error("Unexpected '=' in condition", self.tokens[equals].span)
+                        .with_label("'=' assigns a value")
+                        .with_help("use '==' to compare values"),
+                );
+            }
+            let span = Span::new(self.tokens[start].span.start, self.tokens[equals - 1].span.end);
+            Some(
+                Diagnostic::error("Invalid assignment target", span)
+                    .with_label("cannot assign to this")
+                    .with_help("only a variable or a field such as 'point.x' can be assigned"),
+            )
+        })
+    }
+
+    /// The first token of the expression on the left of the `=` at `equals`,
+    /// and the token before it that stopped the search, if any
+    fn assignment_target(&self, from: usize, equals: usize) -> (usize, Option<usize>) {
+        let mut depth = 0;
+        for i in (from..equals).rev() {
+            match self.tokens[i].text {
+                ")" => depth += 1,
+                "(" if depth > 0 => depth -= 1,
+                "(" | "," | "=" | ";" | "?" | ":" | "print" | "return" | "var" if depth == 0 => {
+                    return (i + 1, Some(i));
+                }
+                _ => {}
+            }
+        }
+        (from, None)
+    }
+
+    /// Whether the tokens in `range` are a variable or a field access
+    fn is_assignable(&self, range: Range<usize>) -> bool {
+        let first = &self.tokens[range.start];
+        if !self.is_name(first) && first.text != "this" {
+            return false;
+        }
+        let mut ends_with_name = self.is_name(first);
+        let mut i = range.start + 1;
+        while i < range.end {
+            match self.tokens[i].text {
+                "." if i + 1 < range.end && self.is_name(&self.tokens[i + 1]) => {
+                    ends_with_name = true;
+                    i += 2;
+                }
+                "(" => match self.closing(i) {
+                    Some(close) if close < range.end => {
+                        ends_with_name = false;
+                        i = close + 1;
+                    }
+                    _ => return false,
+                },
+                _ => return false,
+            }
+        }
+        ends_with_name
+    }
+
+    /// An expression followed by something that cannot continue it: an operand
+    /// or statement keyword on a later line, a `}`, or the end of the input
+    fn missing_semicolon(&self, statement: &Range<usize>, at: usize) -> Option<Diagnostic> {
+        let last = statement.clone().find(|&i| {
+            let token = &self.tokens[i];
+            if !self.ends_operand(token) {
+                return false;
+            }
+            match self.tokens.get(i + 1) {
+                Some(next) if i + 1 < statement.end => {
+                    self.source[token.span.end..next.span.start].contains('\n')
+                        && (self.starts_operand(next) || STATEMENT_KEYWORDS.contains(&next.text))
+                }
+                Some(next) => next.text == "}",
+                None => at == self.tokens.len(),
+            }
+        })?;
+        let end = self.tokens[last].span.end;
+        Some(Diagnostic::error("Missing ';' at end of statement", Span::new(end, end)).with_label("expected ';' here"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Explain an error at the first occurrence of `at` in `source`, the way
+    /// a backend that points at the token it got stuck on would report it
+    fn explain_at(source: &str, at: &str) -> Diagnostic {
+        let offset = source.find(at).unwrap();
+        let diagnostic = Diagnostic::unexpected(source, offset, &LexOptions::default());
+        explain(source, diagnostic, &LexOptions::default(), &LanguageOptions::STRICT)
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("return", "return"), 0);
+        assert_eq!(edit_distance("retrun", "return"), 1);
+        assert_eq!(edit_distance("funciton", "function"), 1);
+        assert_eq!(edit_distance("pint", "print"), 1);
+        assert_eq!(edit_distance("", "var"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest_keyword() {
+        let strict = LanguageOptions::STRICT;
+        assert_eq!(suggest_keyword("retrun", &strict), Some("return"));
+        assert_eq!(suggest_keyword("whiel", &strict), Some("while"));
+        assert_eq!(suggest_keyword("funciton", &strict), Some("fun"));
+        assert_eq!(suggest_keyword("let", &strict), Some("var"));
+        assert_eq!(suggest_keyword("Print", &strict), Some("print"));
+        assert_eq!(suggest_keyword("total", &strict), None);
+        assert_eq!(suggest_keyword("x", &strict), None);
+        assert_eq!(suggest_keyword("brek", &strict), None);
+        assert_eq!(suggest_keyword("brek", &LanguageOptions::EXTENDED), Some("break"));
+    }
+
+    #[test]
+    fn test_missing_semicolon() {
+        let source = "var x = 1\nprint x;";
+        // Backends point either at the next statement or at the start of this one
+        for at in ["print", "var"] {
+            let diagnostic = explain_at(source, at);
+            assert_eq!(diagnostic.describe(source), "Missing ';' at end of statement at line 1, column 10");
+        }
+        let source = "fun f() { return 1 }";
+        assert_eq!(explain_at(source, "}").primary.span, Span::new(18, 18));
+        let source = "print 1\n";
+        let end = Diagnostic::unexpected(source, source.len(), &LexOptions::default());
+        let diagnostic = explain(source, end, &LexOptions::default(), &LanguageOptions::STRICT);
+        assert_eq!(diagnostic.describe(source), "Missing ';' at end of statement at line 1, column 8");
+    }
+
+    #[test]
+    fn test_unbalanced_delimiters() {
+        let source = "print (a + 1;\nprint a;";
+        let diagnostic = explain_at(source, ";");
+        assert_eq!(diagnostic.message, "Expected ')' before ';'");
+        assert_eq!(diagnostic.secondary[0].span, Span::new(6, 7));
+        assert_eq!(explain_at(source, "print"), diagnostic);
+
+        let source = "fun f() {\n  print 1;\n";
+        let diagnostic = explain_at(source, "fun");
+        assert_eq!(diagnostic.describe(source), "Expected '}' at end of input at line 2, column 11");
+        assert_eq!(diagnostic.secondary[0].message, "unclosed `{` opened here");
+
+        assert_eq!(explain_at("print 1);", ")").message, "Unmatched ')'");
+        assert_eq!(explain_at("if (a { print a; }", "{").message, "Expected ')' before '{'");
+    }
+
+    #[test]
+    fn test_var_in_expression() {
+        let source = "print var x = 1;";
+        assert_eq!(explain_at(source, "var").message, "'var' cannot be used inside an expression");
+        assert_eq!(explain_at(source, "print").primary.span, Span::new(6, 9));
+        assert_eq!(explain_at("for (var i = 0; i < 1; i = i + 1) print var;", "print").primary.span, Span::new(40, 43));
+    }
+
+    #[test]
+    fn test_misspelled_keywords() {
+        assert_eq!(explain_at("retrun x;", "x").help.as_deref(), Some("did you mean 'return'?"));
+        assert_eq!(explain_at("funciton greet() {}", "greet").help.as_deref(), Some("did you mean 'fun'?"));
+        assert_eq!(explain_at("whiel (true) { }", "{").help.as_deref(), Some("did you mean 'while'?"));
+        // Backends that point at the start of the enclosing declaration get the same hint
+        let source = "fun f(n) {\n  if (n) { retrun n; }\n}";
+        assert_eq!(explain_at(source, "fun").describe(source), explain_at(source, "n;").describe(source));
+        // A call of a function with a keyword-like name is fine, so nothing is suggested
+        assert_eq!(explain_at("prnt(1) + ;", ";").message, "Unexpected ';'");
+    }
+
+    #[test]
+    fn test_explain_all() {
+        let source = "retrun 1;\nprint 2;\n1 + 2 = 3;";
+        let diagnostics: Vec<_> = ["1;", "print", "="]
+            .iter()
+            .map(|at| Diagnostic::unexpected(source, source.find(at).unwrap(), &LexOptions::default()))
+            .collect();
+        let one_by_one: Vec<_> = diagnostics
+            .iter()
+            .map(|d| explain(source, d.clone(), &LexOptions::default(), &LanguageOptions::STRICT))
+            .collect();
+        let explained = explain_all(source, diagnostics, &LexOptions::default(), &LanguageOptions::STRICT);
+        assert_eq!(explained, one_by_one);
+        assert_eq!(explained[2].message, "Invalid assignment target");
+    }
+
+    #[test]
+    fn test_assignments() {
+        let source = "var a = 1;\na + 2 = 3;";
+        let diagnostic = explain_at(source, "a +");
+        assert_eq!(diagnostic.message, "Invalid assignment target");
+        assert_eq!(&source[diagnostic.primary.span.start..diagnostic.primary.span.end], "a + 2");
+
+        let source = "if (n % 2 = 0) print n;";
+        let diagnostic = explain_at(source, "=");
+        assert_eq!(diagnostic.message, "Unexpected '=' in condition");
+        assert_eq!(diagnostic.help.as_deref(), Some("use '==' to compare values"));
+
+        // Variables, fields and fields of call results can be assigned
+        for source in ["a.b = 1 +;", "f(x).y = 1 +;", "this.x = y = 1 +;"] {
+            assert_eq!(explain_at(source, ";").message, "Unexpected ';'", "{}", source);
+        }
+        assert_eq!(explain_at("f(x) = 1;", "=").message, "Invalid assignment target");
+    }
+
+    #[test]
+    fn test_other_errors_are_unchanged() {
+        let source = "print 1 +;";
+        let diagnostic = explain_at(source, ";");
+        assert_eq!(diagnostic, Diagnostic::unexpected(source, 9, &LexOptions::default()));
+        let source = "print \"open;\nprint (1;";
+        let diagnostic = Diagnostic::error("Unterminated string", Span::new(6, 7));
+        assert_eq!(explain(source, diagnostic.clone(), &LexOptions::default(), &LanguageOptions::STRICT), diagnostic);
+    }
+}
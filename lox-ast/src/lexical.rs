@@ -0,0 +1,861 @@
+//! Lexical rules shared by every Lox parser implementation
+//!
+//! Keeping the reserved word list, identifier character classes and literal
+//! scanners in one place means every backend agrees on where a token ends and
+//! what value it carries.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::ControlFlow;
+use crate::source::LineIndex;
+use crate::span::Span;
+
+/// Reserved words of the Lox language, in alphabetical order
+pub const KEYWORDS: &[&str] = &[
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+/// Check whether `word` is a reserved word
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.binary_search(&word).is_ok()
+}
+
+/// Check whether `c` may start an identifier
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// Check whether `c` may appear after the first character of an identifier
+pub fn is_identifier_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Check whether `word` is a valid identifier, i.e. well-formed and not reserved
+pub fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => {
+            is_identifier_start(first) && chars.all(is_identifier_continue) && !is_keyword(word)
+        }
+        None => false,
+    }
+}
+
+/// Lexical extensions a backend has been built with
+///
+/// Backends fill this in from their own cargo features, so that strict book Lox
+/// stays the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexOptions {
+    /// Process `\n`, `\t`, `\"`, `\\` and `\u{..}` escape sequences in strings
+    pub escapes: bool,
+    /// Accept `0x`/`0b` number literals and `_` digit separators
+    pub number_extensions: bool,
+    /// Skip `/* ... */` block comments, which may nest
+    pub block_comments: bool,
+    /// Keep `///` comments as documentation for the declaration that follows
+    pub doc_comments: bool,
+}
+
+/// Kinds of lexical error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A string literal with no closing quote
+    UnterminatedString,
+    /// An unknown or malformed escape sequence, e.g. `\q` or `\u{110000}`
+    InvalidEscape(String),
+    /// A number with no digits before its decimal point, e.g. `.5`
+    LeadingDot,
+    /// A number with no digits after its decimal point, e.g. `1.`
+    TrailingDot,
+    /// A number with a second decimal point, e.g. `1.2.3`
+    MultipleDecimalPoints,
+    /// A number in exponent notation, e.g. `1e10`
+    UnsupportedExponent,
+    /// A number with a leading sign, e.g. `+1`; negation is the unary `-` operator
+    SignedNumber(char),
+    /// A character that cannot continue a number literal, e.g. the `x` in `12x`
+    InvalidNumberCharacter(char),
+    /// A `_` separator that is not between two digits, e.g. `1__000` or `1_`
+    MisplacedSeparator,
+    /// A radix prefix with no digits after it, e.g. `0x`
+    MissingDigits(String),
+    /// Text that is not a number literal at all, e.g. `inf` or `NaN`
+    NotANumber(String),
+    /// A block comment with no closing `*/`
+    UnterminatedComment,
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedString => write!(f, "Unterminated string"),
+            LexErrorKind::InvalidEscape(sequence) => {
+                write!(f, "Invalid escape sequence '{}'", sequence)
+            }
+            LexErrorKind::LeadingDot => write!(f, "Number literal must start with a digit"),
+            LexErrorKind::TrailingDot => write!(f, "Number literal cannot end with '.'"),
+            LexErrorKind::MultipleDecimalPoints => {
+                write!(f, "Number literal has more than one decimal point")
+            }
+            LexErrorKind::UnsupportedExponent => {
+                write!(f, "Exponent notation is not supported in number literals")
+            }
+            LexErrorKind::SignedNumber(sign) => {
+                write!(f, "Number literal cannot start with '{}'", sign)
+            }
+            LexErrorKind::InvalidNumberCharacter(c) => {
+                write!(f, "Invalid character '{}' in number literal", c)
+            }
+            LexErrorKind::MisplacedSeparator => {
+                write!(f, "Digit separator '_' must be between digits")
+            }
+            LexErrorKind::MissingDigits(prefix) => write!(f, "Expected digits after '{}'", prefix),
+            LexErrorKind::NotANumber(text) => write!(f, "'{}' is not a number literal", text),
+            LexErrorKind::UnterminatedComment => write!(f, "Unterminated block comment"),
+        }
+    }
+}
+
+/// A lexical error and the span of source it points at
+///
+/// For an unterminated string or block comment the span covers the opening delimiter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexError {
+    /// Create a new lexical error
+    pub fn new(kind: LexErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Shift the error's span by `by` bytes
+    pub fn offset(self, by: usize) -> Self {
+        Self::new(self.kind, self.span.offset(by))
+    }
+
+    /// Render the error with the line and column it points at in `source`
+    pub fn describe(&self, source: &str) -> String {
+        format!("{} at {}", self.kind, LineIndex::new(source).location(source, self.span.start))
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Scan a string literal at the start of `input`, which must begin with `"`
+///
+/// A string runs to the next unescaped `"` and may span several lines; `""` is
+/// the empty string. Returns the literal's value and its length in bytes,
+/// including both quotes. The value borrows from `input` unless escape
+/// sequences had to be decoded. Error spans are relative to the start of `input`.
+pub fn scan_string<'a>(input: &'a str, options: &LexOptions) -> Result<(Cow<'a, str>, usize), LexError> {
+    debug_assert!(input.starts_with('"'));
+
+    let mut pos = 1;
+    let mut escaped = false;
+
+    while let Some(c) = input[pos..].chars().next() {
+        match c {
+            '"' => {
+                let raw = &input[1..pos];
+                let value = if escaped {
+                    Cow::Owned(unescape(raw).map_err(|e| e.offset(1))?)
+                } else {
+                    Cow::Borrowed(raw)
+                };
+                return Ok((value, pos + 1));
+            }
+            '\\' if options.escapes => {
+                // A backslash ending the input leaves the string unterminated, reported at its quote
+                if pos + 1 == input.len() {
+                    break;
+                }
+                // Check each escape as it is reached, so a bad one is reported before a missing quote
+                let len = escape_len(&input[pos..]);
+                decode_escape(&input[pos..pos + len]).map_err(|e| e.offset(pos))?;
+                escaped = true;
+                pos += len;
+            }
+            _ => pos += c.len_utf8(),
+        }
+    }
+
+    Err(LexError::new(LexErrorKind::UnterminatedString, Span::new(0, 1)))
+}
+
+/// Process the escape sequences in the body of a string literal (without its quotes)
+pub fn unescape(raw: &str) -> Result<String, LexError> {
+    let mut value = String::with_capacity(raw.len());
+    let mut rest = raw;
+
+    while let Some(pos) = rest.find('\\') {
+        value.push_str(&rest[..pos]);
+        let len = escape_len(&rest[pos..]);
+        let offset = raw.len() - rest.len() + pos;
+        value.push(decode_escape(&rest[pos..pos + len]).map_err(|e| e.offset(offset))?);
+        rest = &rest[pos + len..];
+    }
+
+    value.push_str(rest);
+    Ok(value)
+}
+
+/// Length in bytes of the escape sequence at the start of `input`, which begins with `\`
+fn escape_len(input: &str) -> usize {
+    let mut chars = input.char_indices().skip(1);
+    match chars.next() {
+        Some((_, 'u')) if input[2..].starts_with('{') => {
+            // Run to the closing brace, but never past the end of the string
+            input[3..]
+                .find(['}', '"'])
+                .filter(|&i| input.as_bytes()[3 + i] == b'}')
+                .map_or(2, |i| 3 + i + 1)
+        }
+        Some((pos, c)) => pos + c.len_utf8(),
+        None => 1,
+    }
+}
+
+/// Decode a single escape sequence such as `\n` or `\u{1F600}`
+fn decode_escape(sequence: &str) -> Result<char, LexError> {
+    let invalid = || {
+        LexError::new(
+            LexErrorKind::InvalidEscape(sequence.to_string()),
+            Span::new(0, sequence.len()),
+        )
+    };
+
+    match &sequence[1..] {
+        "n" => Ok('\n'),
+        "t" => Ok('\t'),
+        "\"" => Ok('"'),
+        "\\" => Ok('\\'),
+        "" => Err(LexError::new(LexErrorKind::UnterminatedString, Span::new(0, 1))),
+        unicode if unicode.starts_with("u{") && unicode.ends_with('}') => {
+            let digits = &unicode[2..unicode.len() - 1];
+            if digits.is_empty() || digits.len() > 6 {
+                return Err(invalid());
+            }
+            u32::from_str_radix(digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(invalid)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Check whether a number literal starts at the beginning of `input`
+///
+/// This includes malformed literals such as `.5`, so that scanning them with
+/// [`scan_number`] produces a targeted error.
+pub fn starts_number(input: &str) -> bool {
+    let mut chars = input.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('.') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+/// Scan a number literal at the start of `input`
+///
+/// A number is one or more digits, optionally followed by `.` and one or more
+/// digits. With `options.number_extensions`, `0x`/`0b` literals and `_`
+/// separators between digits are also accepted. A literal must not run on into
+/// a letter, digit or further `.`, so `1.`, `1.2.3` and `1e10` are errors rather
+/// than a number followed by something else. Returns the literal's value and its
+/// length in bytes; error spans are relative to the start of `input`.
+pub fn scan_number(input: &str, options: &LexOptions) -> Result<(f64, usize), LexError> {
+    let error = |kind, start: usize, len: usize| Err(LexError::new(kind, Span::new(start, start + len)));
+
+    if input.starts_with('.') {
+        return error(LexErrorKind::LeadingDot, 0, 1);
+    }
+    if !input.starts_with(|c: char| c.is_ascii_digit()) {
+        // Report the whole word, or the one character, that is not a number
+        let len = match input.find(|c: char| !is_identifier_continue(c)).unwrap_or(input.len()) {
+            0 => input.chars().next().map_or(0, char::len_utf8),
+            len => len,
+        };
+        return error(LexErrorKind::NotANumber(input[..len].to_string()), 0, len);
+    }
+
+    if options.number_extensions {
+        for (prefix, radix) in [("0x", 16), ("0X", 16), ("0b", 2), ("0B", 2)] {
+            if input.starts_with(prefix) {
+                return scan_radix_number(input, prefix, radix);
+            }
+        }
+    }
+
+    let mut end = scan_digits(input, 0, 10, options)?;
+    if input[end..].starts_with('.') {
+        if !starts_number(&input[end..]) {
+            return error(LexErrorKind::TrailingDot, end, 1);
+        }
+        end = scan_digits(input, end + 1, 10, options)?;
+        if input[end..].starts_with('.') {
+            let kind = if starts_number(&input[end..]) {
+                LexErrorKind::MultipleDecimalPoints
+            } else {
+                LexErrorKind::TrailingDot
+            };
+            return error(kind, end, 1);
+        }
+    }
+    check_number_end(input, end)?;
+
+    let digits: String = input[..end].chars().filter(|&c| c != '_').collect();
+    let value = digits
+        .parse()
+        .map_err(|_| LexError::new(LexErrorKind::NotANumber(input[..end].to_string()), Span::new(0, end)))?;
+    Ok((value, end))
+}
+
+/// Parse `text` as a complete number literal
+///
+/// Unlike `str::parse::<f64>`, this only accepts what the Lox lexer accepts, so
+/// `inf`, `NaN`, `+1`, `.5` and `1e10` are all rejected.
+pub fn parse_number(text: &str, options: &LexOptions) -> Result<f64, LexError> {
+    match text.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            Err(LexError::new(LexErrorKind::SignedNumber(sign), Span::new(0, 1)))
+        }
+        Some(c) if c.is_ascii_digit() || c == '.' => {
+            let (value, len) = scan_number(text, options)?;
+            match text[len..].chars().next() {
+                None => Ok(value),
+                Some(c) => Err(LexError::new(
+                    LexErrorKind::InvalidNumberCharacter(c),
+                    Span::new(len, len + c.len_utf8()),
+                )),
+            }
+        }
+        _ => Err(LexError::new(
+            LexErrorKind::NotANumber(text.to_string()),
+            Span::new(0, text.len()),
+        )),
+    }
+}
+
+/// Scan a `0x` or `0b` literal at the start of `input`
+fn scan_radix_number(input: &str, prefix: &str, radix: u32) -> Result<(f64, usize), LexError> {
+    let options = LexOptions { number_extensions: true, ..LexOptions::default() };
+    let start = prefix.len();
+
+    if !input[start..].starts_with(|c: char| c.is_digit(radix)) {
+        return Err(LexError::new(
+            LexErrorKind::MissingDigits(prefix.to_string()),
+            Span::new(0, start),
+        ));
+    }
+
+    let end = scan_digits(input, start, radix, &options)?;
+    if let Some(c) = input[end..].chars().next().filter(|&c| c == '.') {
+        return Err(LexError::new(LexErrorKind::InvalidNumberCharacter(c), Span::new(end, end + 1)));
+    }
+    check_number_end(input, end)?;
+
+    let value = input[start..end]
+        .chars()
+        .filter_map(|c| c.to_digit(radix))
+        .fold(0.0, |value, digit| value * f64::from(radix) + f64::from(digit));
+    Ok((value, end))
+}
+
+/// Scan a run of digits in `radix` starting at `start`, which must be a digit
+///
+/// `_` separators are accepted between digits when number extensions are on.
+fn scan_digits(input: &str, start: usize, radix: u32, options: &LexOptions) -> Result<usize, LexError> {
+    let mut end = start;
+
+    for (i, c) in input[start..].char_indices() {
+        let pos = start + i;
+        if c.is_digit(radix) {
+            end = pos + 1;
+        } else if c == '_' && options.number_extensions {
+            let next_is_digit = input[pos + 1..].starts_with(|c: char| c.is_digit(radix));
+            if end != pos || !next_is_digit {
+                return Err(LexError::new(LexErrorKind::MisplacedSeparator, Span::new(pos, pos + 1)));
+            }
+        } else {
+            break;
+        }
+    }
+
+    Ok(end)
+}
+
+/// Check that the number literal ending at `end` is not run on into an identifier character
+fn check_number_end(input: &str, end: usize) -> Result<(), LexError> {
+    let rest = &input[end..];
+    let Some(c) = rest.chars().next().filter(|&c| is_identifier_continue(c)) else {
+        return Ok(());
+    };
+
+    let kind = if matches!(c, 'e' | 'E') && starts_exponent(&rest[1..]) {
+        LexErrorKind::UnsupportedExponent
+    } else {
+        LexErrorKind::InvalidNumberCharacter(c)
+    };
+    Err(LexError::new(kind, Span::new(end, end + c.len_utf8())))
+}
+
+/// Check whether `input` looks like the digits of an exponent, e.g. `10` or `-3`
+fn starts_exponent(input: &str) -> bool {
+    let digits = input.strip_prefix(['+', '-']).unwrap_or(input);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Scan a block comment at the start of `input`, which must begin with `/*`
+///
+/// Block comments nest, so `/* a /* b */ c */` is a single comment. Returns the
+/// comment's length in bytes; an unterminated comment is reported at its
+/// outermost `/*`.
+pub fn scan_block_comment(input: &str) -> Result<usize, LexError> {
+    debug_assert!(input.starts_with("/*"));
+
+    let mut depth = 0;
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let rest = &input[pos..];
+        if rest.starts_with("/*") {
+            depth += 1;
+            pos += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            pos += 2;
+            if depth == 0 {
+                return Ok(pos);
+            }
+        } else {
+            pos += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    Err(LexError::new(LexErrorKind::UnterminatedComment, Span::new(0, 2)))
+}
+
+/// Whitespace and comments between two tokens
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trivia {
+    /// Length of the trivia in bytes
+    pub len: usize,
+    /// Text of the `///` doc comments in the trivia, one line per comment
+    pub doc: Option<String>,
+}
+
+/// Skip the whitespace and comments at the start of `input`
+///
+/// Line comments run to the end of the line. With `options.block_comments`,
+/// `/* ... */` comments are skipped too, and with `options.doc_comments` the
+/// text of every `///` comment (but not `////`) is collected, minus one leading
+/// space, so that it can be attached to the declaration that follows.
+pub fn skip_trivia(input: &str, options: &LexOptions) -> Result<Trivia, LexError> {
+    let mut pos = 0;
+    let mut doc: Option<String> = None;
+
+    loop {
+        let rest = &input[pos..];
+        let trimmed = rest.trim_start_matches([' ', '\t', '\r', '\n']);
+        pos += rest.len() - trimmed.len();
+
+        if trimmed.starts_with("//") {
+            let len = trimmed.find('\n').unwrap_or(trimmed.len());
+            let comment = &trimmed[..len];
+            if options.doc_comments && comment.starts_with("///") && !comment.starts_with("////") {
+                let line = comment[3..].strip_prefix(' ').unwrap_or(&comment[3..]).trim_end();
+                match &mut doc {
+                    Some(text) => {
+                        text.push('\n');
+                        text.push_str(line);
+                    }
+                    None => doc = Some(line.to_string()),
+                }
+            }
+            pos += len;
+        } else if options.block_comments && trimmed.starts_with("/*") {
+            pos += scan_block_comment(trimmed).map_err(|e| e.offset(pos))?;
+        } else {
+            return Ok(Trivia { len: pos, doc });
+        }
+    }
+}
+
+/// Find the doc comments in `source`, keyed by the offset of the token each one precedes
+///
+/// Backends that cannot collect doc comments while skipping trivia look up a
+/// declaration's doc comment here by the offset of its first token.
+pub fn doc_comments(source: &str, options: &LexOptions) -> Result<BTreeMap<usize, String>, LexError> {
+    let mut docs = BTreeMap::new();
+    scan_tokens(source, options, |token, doc| {
+        if let Some(doc) = doc {
+            docs.insert(token.start, doc);
+        }
+        ControlFlow::Continue(())
+    })?;
+    Ok(docs)
+}
+
+/// Check `source` for lexical errors without building any tokens
+///
+/// Backends whose combinators cannot report *why* a token failed to match use
+/// this to turn a generic parse failure into a targeted message.
+pub fn validate(source: &str, options: &LexOptions) -> Result<(), LexError> {
+    scan_tokens(source, options, |_, _| ControlFlow::Continue(()))
+}
+
+/// The span of the token starting at `offset`, or an empty span at the end of the input
+///
+/// Used to underline the token a parse error points at. A token that does not
+/// lex, such as an unterminated string, is taken to be its first character.
+pub fn token_span(source: &str, offset: usize, options: &LexOptions) -> Span {
+    let rest = &source[offset.min(source.len())..];
+    let len = token_len(rest, options).unwrap_or_else(|_| rest.chars().next().map_or(0, char::len_utf8));
+    Span::new(offset, offset + len)
+}
+
+/// The innermost `(` or `{` opened before `offset` and not closed by then
+///
+/// A parser that fails at `offset` is often missing the closing delimiter of
+/// this one, so error messages point at where it was opened.
+pub fn unclosed_delimiter(source: &str, offset: usize, options: &LexOptions) -> Option<Span> {
+    unclosed_delimiters(source, &[offset], options)[0]
+}
+
+/// `unclosed_delimiter` for each of `offsets`, in any order, from a single
+/// scan of `source`
+///
+/// A backend that recovers from errors can report one per statement, and
+/// scanning again for each would take time quadratic in the input.
+pub fn unclosed_delimiters(source: &str, offsets: &[usize], options: &LexOptions) -> Vec<Option<Span>> {
+    let mut order: Vec<usize> = (0..offsets.len()).collect();
+    order.sort_by_key(|&i| offsets[i]);
+    let mut pending = order.into_iter().peekable();
+    let mut found = vec![None; offsets.len()];
+    let mut open: Vec<Span> = Vec::new();
+
+    let scanned = scan_tokens(source, options, |token, _| {
+        while let Some(i) = pending.next_if(|&i| token.start >= offsets[i]) {
+            found[i] = open.last().copied();
+        }
+        if pending.peek().is_none() {
+            return ControlFlow::Break(());
+        }
+        match &source[token.start..token.end] {
+            "(" | "{" => open.push(token),
+            close @ (")" | "}") => {
+                let opener = if close == ")" { "(" } else { "{" };
+                if open.last().is_some_and(|last| &source[last.start..last.end] == opener) {
+                    open.pop();
+                }
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    });
+    // Offsets past the last token see every delimiter still open, unless a
+    // lexical error stopped the scan before them
+    if scanned.is_ok() {
+        for i in pending {
+            found[i] = open.last().copied();
+        }
+    }
+    found
+}
+
+/// Walk over the tokens of `source`, calling `visit` with the span of each and
+/// the doc comment before it until `visit` breaks
+///
+/// Only literals and identifiers are scanned as whole tokens; any other
+/// character is stepped over on its own.
+pub(crate) fn scan_tokens(
+    source: &str,
+    options: &LexOptions,
+    mut visit: impl FnMut(Span, Option<String>) -> ControlFlow<()>,
+) -> Result<(), LexError> {
+    let mut pos = 0;
+
+    while pos < source.len() {
+        let trivia = skip_trivia(&source[pos..], options).map_err(|e| e.offset(pos))?;
+        pos += trivia.len;
+        if pos == source.len() {
+            break;
+        }
+        let len = token_len(&source[pos..], options).map_err(|e| e.offset(pos))?;
+        if visit(Span::new(pos, pos + len), trivia.doc).is_break() {
+            break;
+        }
+        pos += len;
+    }
+
+    Ok(())
+}
+
+/// The length of the token at the start of `rest`, which must not be empty
+fn token_len(rest: &str, options: &LexOptions) -> Result<usize, LexError> {
+    if rest.starts_with('"') {
+        scan_string(rest, options).map(|(_, len)| len)
+    } else if rest.starts_with(is_identifier_start) {
+        // Skip whole identifiers so digits inside them are not taken for numbers
+        Ok(rest.find(|c| !is_identifier_continue(c)).unwrap_or(rest.len()))
+    } else if starts_number(rest) {
+        scan_number(rest, options).map(|(_, len)| len)
+    } else {
+        Ok(rest.chars().next().map_or(0, char::len_utf8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords_sorted() {
+        let mut sorted = KEYWORDS.to_vec();
+        sorted.sort_unstable();
+        assert_eq!(sorted, KEYWORDS);
+    }
+
+    #[test]
+    fn test_is_keyword() {
+        for keyword in KEYWORDS {
+            assert!(is_keyword(keyword));
+        }
+        assert!(!is_keyword("nilly"));
+        assert!(!is_keyword("orchid"));
+        assert!(!is_keyword("printer"));
+        assert!(!is_keyword("Print"));
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("nilly"));
+        assert!(is_identifier("trueValue"));
+        assert!(is_identifier("_private"));
+        assert!(is_identifier("x1"));
+        assert!(!is_identifier("class"));
+        assert!(!is_identifier("1x"));
+        assert!(!is_identifier(""));
+    }
+
+    const STRICT: LexOptions = LexOptions {
+        escapes: false,
+        number_extensions: false,
+        block_comments: false,
+        doc_comments: false,
+    };
+    const ESCAPES: LexOptions = LexOptions { escapes: true, ..STRICT };
+    const NUMBERS: LexOptions = LexOptions { number_extensions: true, ..STRICT };
+    const COMMENTS: LexOptions = LexOptions { block_comments: true, doc_comments: true, ..STRICT };
+
+    #[test]
+    fn test_scan_string() {
+        assert_eq!(scan_string(r#""hello" + 1"#, &STRICT), Ok(("hello".into(), 7)));
+        assert_eq!(scan_string(r#""""#, &STRICT), Ok(("".into(), 2)));
+        assert_eq!(scan_string("\"one\ntwo\"", &STRICT), Ok(("one\ntwo".into(), 9)));
+    }
+
+    #[test]
+    fn test_scan_string_strict_keeps_backslashes() {
+        assert_eq!(scan_string(r#""C:\dir\""#, &STRICT), Ok((r"C:\dir\".into(), 9)));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let error = scan_string("\"never closed\n", &STRICT).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(error.span, Span::new(0, 1));
+    }
+
+    #[test]
+    fn test_scan_string_escapes() {
+        let (value, len) = scan_string(r#""a\tb\n\"q\" \\ \u{1F600}!" rest"#, &ESCAPES).unwrap();
+        assert_eq!(value, "a\tb\n\"q\" \\ \u{1F600}!");
+        assert_eq!(len, 27);
+        assert!(matches!(value, Cow::Owned(_)));
+
+        // Without any escapes the value borrows from the input
+        let (value, _) = scan_string(r#""plain""#, &ESCAPES).unwrap();
+        assert!(matches!(value, Cow::Borrowed("plain")));
+
+        let error = scan_string(r#""bad \q""#, &ESCAPES).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::InvalidEscape(r"\q".to_string()));
+        assert_eq!(error.span, Span::new(5, 7));
+
+        let error = scan_string(r#""\u{110000}""#, &ESCAPES).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::InvalidEscape(r"\u{110000}".to_string()));
+
+        let error = scan_string(r#""escaped quote \""#, &ESCAPES).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(error.span, Span::new(0, 1));
+
+        let error = scan_string(r#""trailing backslash \"#, &ESCAPES).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(error.span, Span::new(0, 1));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"tab\there"), Ok("tab\there".to_string()));
+        assert_eq!(unescape(r"\u{41}\u{42}"), Ok("AB".to_string()));
+        assert_eq!(unescape("plain"), Ok("plain".to_string()));
+        assert_eq!(unescape(r"x\z").unwrap_err().span, Span::new(1, 3));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("print \"ok\"; // \"not a string", &STRICT), Ok(()));
+
+        let source = "var a = \"one\ntwo\";\nprint \"oops;";
+        let error = validate(source, &STRICT).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(error.describe(source), "Unterminated string at line 3, column 7");
+    }
+
+    #[test]
+    fn test_scan_number() {
+        assert_eq!(scan_number("42;", &STRICT), Ok((42.0, 2)));
+        assert_eq!(scan_number("3.25 + 1", &STRICT), Ok((3.25, 4)));
+        assert_eq!(scan_number("0.5)", &STRICT), Ok((0.5, 3)));
+    }
+
+    #[test]
+    fn test_scan_number_without_digits() {
+        let error = scan_number("abc + 1", &STRICT).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::NotANumber("abc".to_string()));
+        assert_eq!(error.span, Span::new(0, 3));
+
+        let error = scan_number("+1", &STRICT).unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::NotANumber("+".to_string()));
+        assert_eq!(error.span, Span::new(0, 1));
+
+        assert_eq!(scan_number("", &STRICT).unwrap_err().kind, LexErrorKind::NotANumber(String::new()));
+        let separated = LexOptions { number_extensions: true, ..STRICT };
+        assert_eq!(scan_number("_1", &separated).unwrap_err().kind, LexErrorKind::NotANumber("_1".to_string()));
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let kind = |input| scan_number(input, &STRICT).unwrap_err().kind;
+        assert_eq!(kind("1."), LexErrorKind::TrailingDot);
+        assert_eq!(kind("1.;"), LexErrorKind::TrailingDot);
+        assert_eq!(kind("1.2.3"), LexErrorKind::MultipleDecimalPoints);
+        assert_eq!(kind("1.2."), LexErrorKind::TrailingDot);
+        assert_eq!(kind(".5"), LexErrorKind::LeadingDot);
+        assert_eq!(kind("1e10"), LexErrorKind::UnsupportedExponent);
+        assert_eq!(kind("2.5E-3"), LexErrorKind::UnsupportedExponent);
+        assert_eq!(kind("12abc"), LexErrorKind::InvalidNumberCharacter('a'));
+        assert_eq!(kind("0x1F"), LexErrorKind::InvalidNumberCharacter('x'));
+        assert_eq!(kind("1_000"), LexErrorKind::InvalidNumberCharacter('_'));
+
+        assert_eq!(scan_number("1.2.3", &STRICT).unwrap_err().span, Span::new(3, 4));
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("10", &STRICT), Ok(10.0));
+        assert_eq!(parse_number("10.75", &STRICT), Ok(10.75));
+
+        let kind = |text| parse_number(text, &STRICT).unwrap_err().kind;
+        assert_eq!(kind("inf"), LexErrorKind::NotANumber("inf".to_string()));
+        assert_eq!(kind("NaN"), LexErrorKind::NotANumber("NaN".to_string()));
+        assert_eq!(kind("+1"), LexErrorKind::SignedNumber('+'));
+        assert_eq!(kind("-1"), LexErrorKind::SignedNumber('-'));
+        assert_eq!(kind(".5"), LexErrorKind::LeadingDot);
+        assert_eq!(kind("5."), LexErrorKind::TrailingDot);
+        assert_eq!(kind("1e10"), LexErrorKind::UnsupportedExponent);
+        assert_eq!(kind("1 "), LexErrorKind::InvalidNumberCharacter(' '));
+        assert_eq!(kind(""), LexErrorKind::NotANumber(String::new()));
+    }
+
+    #[test]
+    fn test_number_extensions() {
+        assert_eq!(parse_number("0xFF", &NUMBERS), Ok(255.0));
+        assert_eq!(parse_number("0b1010", &NUMBERS), Ok(10.0));
+        assert_eq!(parse_number("1_000_000", &NUMBERS), Ok(1_000_000.0));
+        assert_eq!(parse_number("2.718_5", &NUMBERS), Ok(2.7185));
+        assert_eq!(parse_number("0xdead_beef", &NUMBERS), Ok(3_735_928_559.0));
+
+        let kind = |text| parse_number(text, &NUMBERS).unwrap_err().kind;
+        assert_eq!(kind("1__0"), LexErrorKind::MisplacedSeparator);
+        assert_eq!(kind("1_"), LexErrorKind::MisplacedSeparator);
+        assert_eq!(kind("1_.5"), LexErrorKind::MisplacedSeparator);
+        assert_eq!(kind("0x"), LexErrorKind::MissingDigits("0x".to_string()));
+        assert_eq!(kind("0x_ff"), LexErrorKind::MissingDigits("0x".to_string()));
+        assert_eq!(kind("0b102"), LexErrorKind::InvalidNumberCharacter('2'));
+        assert_eq!(kind("0x1.5"), LexErrorKind::InvalidNumberCharacter('.'));
+    }
+
+    #[test]
+    fn test_validate_numbers() {
+        assert_eq!(validate("var x1 = 2.5; print x1;", &STRICT), Ok(()));
+
+        let source = "var a = 1;\nprint 1.2.3;";
+        let error = validate(source, &STRICT).unwrap_err();
+        assert_eq!(error.describe(source), "Number literal has more than one decimal point at line 2, column 10");
+    }
+
+    #[test]
+    fn test_scan_block_comment() {
+        assert_eq!(scan_block_comment("/* a */ b"), Ok(7));
+        assert_eq!(scan_block_comment("/* a /* b */ c */ d"), Ok(17));
+        assert_eq!(scan_block_comment("/**/"), Ok(4));
+
+        let error = scan_block_comment("/* a /* b */ c").unwrap_err();
+        assert_eq!(error.kind, LexErrorKind::UnterminatedComment);
+        assert_eq!(error.span, Span::new(0, 2));
+    }
+
+    #[test]
+    fn test_skip_trivia() {
+        let trivia = skip_trivia("  // note\n\t/* block */ print", &COMMENTS).unwrap();
+        assert_eq!(trivia, Trivia { len: 23, doc: None });
+
+        // Without the extension `/*` is the start of a token, not a comment
+        assert_eq!(skip_trivia(" /* x */", &STRICT).unwrap().len, 1);
+
+        let error = skip_trivia("\n  /* open", &COMMENTS).unwrap_err();
+        assert_eq!(error.span, Span::new(3, 5));
+    }
+
+    #[test]
+    fn test_doc_comment_trivia() {
+        let trivia = skip_trivia("/// Adds two numbers.\n///\n///   Indented.\n//// Not a doc\nfun", &COMMENTS).unwrap();
+        assert_eq!(trivia.doc.as_deref(), Some("Adds two numbers.\n\n  Indented."));
+
+        let ignored = LexOptions { doc_comments: false, ..COMMENTS };
+        assert_eq!(skip_trivia("/// Not kept\nvar", &ignored).unwrap().doc, None);
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = "/// The answer\nvar a = 42;\n// plain\nvar b = \"/// not a comment\";\n/// Second\nvar c;";
+        let docs = doc_comments(source, &COMMENTS).unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[&source.find("var a").unwrap()], "The answer");
+        assert_eq!(docs[&source.find("var c").unwrap()], "Second");
+    }
+
+    #[test]
+    fn test_validate_comments() {
+        assert_eq!(validate("/* \" */ print 1;", &COMMENTS), Ok(()));
+
+        let source = "print 1;\n  /* never /* closed */";
+        let error = validate(source, &COMMENTS).unwrap_err();
+        assert_eq!(error.describe(source), "Unterminated block comment at line 2, column 3");
+    }
+}
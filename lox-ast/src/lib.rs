@@ -6,8 +6,36 @@
 use std::fmt;
 use serde::{Deserialize, Serialize};
 
+pub mod arena;
+pub mod borrowed;
+pub mod depth;
+pub mod diagnostic;
+pub mod dialect;
+pub mod export;
+pub mod hints;
+pub mod lexical;
+pub mod optimize;
+pub mod schema;
+pub mod serialization;
+pub mod source;
+pub mod span;
+pub mod types;
+
+pub use arena::{ArenaProgram, AstArena, ExprId, StmtId, Symbol};
+pub use diagnostic::{Diagnostic, Label, Severity};
+pub use dialect::LanguageOptions;
+pub use lexical::{KEYWORDS, LexError, LexErrorKind, LexOptions, Trivia, is_keyword};
+pub use optimize::optimize;
+pub use serialization::SCHEMA_VERSION;
+pub use source::{FileId, Location, SourceFile, SourceMap};
+pub use span::{Span, TextEdit};
+pub use types::{FunctionSignature, TypeAnnotation};
+
 /// Represents a Lox value
+///
+/// Serialized as the plain value: `null`, a boolean, a number or a string.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
 pub enum Value {
     Nil,
     Bool(bool),
@@ -46,6 +74,9 @@ pub enum BinaryOp {
     // Logical
     And,
     Or,
+
+    // Sequencing (the comma operator extension)
+    Comma,
 }
 
 impl fmt::Display for BinaryOp {
@@ -63,6 +94,7 @@ impl fmt::Display for BinaryOp {
             BinaryOp::NotEqual => "!=",
             BinaryOp::And => "and",
             BinaryOp::Or => "or",
+            BinaryOp::Comma => ",",
         };
         write!(f, "{}", op_str)
     }
@@ -86,13 +118,23 @@ impl fmt::Display for UnaryOp {
 }
 
 /// Lox expressions
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Serialized as an object whose `"kind"` is the variant name (see `serialization`).
+///
+/// Dropping, cloning, comparing and debug-printing are stack-safe however
+/// deeply the expression nests (see `depth`).
+///
+/// Since 0.2.0 this type implements `Drop`, so a pattern cannot move a
+/// subexpression out of it (E0509); use `std::mem::take` instead, which
+/// leaves `nil` behind. Code that did so against 0.1 no longer compiles.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Expr {
     /// Literal values
-    Literal(Value),
+    Literal(#[serde(with = "serialization::value_field")] Value),
 
     /// Variable reference
-    Variable(String),
+    Variable(#[serde(with = "serialization::name_field")] String),
 
     /// Binary operations
     Binary {
@@ -108,7 +150,7 @@ pub enum Expr {
     },
 
     /// Grouping (parentheses)
-    Grouping(Box<Expr>),
+    Grouping(#[serde(with = "serialization::expression_field")] Box<Expr>),
 
     /// Assignment
     Assignment {
@@ -142,25 +184,208 @@ pub enum Expr {
     Super {
         method: String,
     },
+
+    /// Conditional expression `condition ? then_branch : else_branch` (extension)
+    Conditional {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+
+    /// Anonymous function `fun (params) { body }` (extension)
+    Lambda {
+        params: Vec<String>,
+        body: Vec<Stmt>,
+    },
+}
+
+impl Expr {
+    /// Move the subexpressions that have subexpressions of their own into
+    /// `pending`, leaving `This` in their place
+    fn take_children(&mut self, pending: &mut Vec<Expr>) {
+        let mut take = |expr: &mut Expr| {
+            if !matches!(expr, Expr::Literal(_) | Expr::Variable(_) | Expr::This | Expr::Super { .. }) {
+                pending.push(std::mem::replace(expr, Expr::This));
+            }
+        };
+        match self {
+            Expr::Binary { left, right, .. } => {
+                take(left);
+                take(right);
+            }
+            Expr::Unary { operand: expr, .. }
+            | Expr::Grouping(expr)
+            | Expr::Assignment { value: expr, .. }
+            | Expr::Get { object: expr, .. } => take(expr),
+            Expr::Call { callee, arguments } => {
+                take(callee);
+                arguments.iter_mut().for_each(take);
+            }
+            Expr::Set { object, value, .. } => {
+                take(object);
+                take(value);
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                take(condition);
+                take(then_branch);
+                take(else_branch);
+            }
+            Expr::Literal(_) | Expr::Variable(_) | Expr::This | Expr::Super { .. } | Expr::Lambda { .. } => {}
+        }
+    }
+}
+
+impl Default for Expr {
+    /// `nil`, so that `std::mem::take` can move a subexpression out of an
+    /// expression, which the `Drop` impl rules out for patterns
+    fn default() -> Self {
+        Expr::Literal(Value::Nil)
+    }
+}
+
+impl Drop for Expr {
+    /// Drop the subexpressions from a list rather than by recursion
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_children(&mut pending);
+        while let Some(mut expr) = pending.pop() {
+            expr.take_children(&mut pending);
+        }
+    }
+}
+
+impl Clone for Expr {
+    fn clone(&self) -> Self {
+        depth::grow(|| match self {
+            Expr::Literal(value) => Expr::Literal(value.clone()),
+            Expr::Variable(name) => Expr::Variable(name.clone()),
+            Expr::Binary { left, operator, right } => {
+                Expr::Binary { left: left.clone(), operator: operator.clone(), right: right.clone() }
+            }
+            Expr::Unary { operator, operand } => Expr::Unary { operator: operator.clone(), operand: operand.clone() },
+            Expr::Grouping(expr) => Expr::Grouping(expr.clone()),
+            Expr::Assignment { name, value } => Expr::Assignment { name: name.clone(), value: value.clone() },
+            Expr::Call { callee, arguments } => Expr::Call { callee: callee.clone(), arguments: arguments.clone() },
+            Expr::Get { object, name } => Expr::Get { object: object.clone(), name: name.clone() },
+            Expr::Set { object, name, value } => {
+                Expr::Set { object: object.clone(), name: name.clone(), value: value.clone() }
+            }
+            Expr::This => Expr::This,
+            Expr::Super { method } => Expr::Super { method: method.clone() },
+            Expr::Conditional { condition, then_branch, else_branch } => Expr::Conditional {
+                condition: condition.clone(),
+                then_branch: then_branch.clone(),
+                else_branch: else_branch.clone(),
+            },
+            Expr::Lambda { params, body } => Expr::Lambda { params: params.clone(), body: body.clone() },
+        })
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        depth::grow(|| match (self, other) {
+            (Expr::Literal(a), Expr::Literal(b)) => a == b,
+            (Expr::Variable(a), Expr::Variable(b)) => a == b,
+            (
+                Expr::Binary { left, operator, right },
+                Expr::Binary { left: other_left, operator: other_operator, right: other_right },
+            ) => operator == other_operator && left == other_left && right == other_right,
+            (Expr::Unary { operator, operand }, Expr::Unary { operator: other_operator, operand: other_operand }) => {
+                operator == other_operator && operand == other_operand
+            }
+            (Expr::Grouping(a), Expr::Grouping(b)) => a == b,
+            (Expr::Assignment { name, value }, Expr::Assignment { name: other_name, value: other_value }) => {
+                name == other_name && value == other_value
+            }
+            (Expr::Call { callee, arguments }, Expr::Call { callee: other_callee, arguments: other_arguments }) => {
+                callee == other_callee && arguments == other_arguments
+            }
+            (Expr::Get { object, name }, Expr::Get { object: other_object, name: other_name }) => {
+                name == other_name && object == other_object
+            }
+            (
+                Expr::Set { object, name, value },
+                Expr::Set { object: other_object, name: other_name, value: other_value },
+            ) => name == other_name && object == other_object && value == other_value,
+            (Expr::This, Expr::This) => true,
+            (Expr::Super { method }, Expr::Super { method: other_method }) => method == other_method,
+            (
+                Expr::Conditional { condition, then_branch, else_branch },
+                Expr::Conditional { condition: other_condition, then_branch: other_then, else_branch: other_else },
+            ) => condition == other_condition && then_branch == other_then && else_branch == other_else,
+            (Expr::Lambda { params, body }, Expr::Lambda { params: other_params, body: other_body }) => {
+                params == other_params && body == other_body
+            }
+            _ => false,
+        })
+    }
+}
+
+impl fmt::Debug for Expr {
+    /// The same output as `#[derive(Debug)]`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        depth::grow(|| match self {
+            Expr::Literal(value) => f.debug_tuple("Literal").field(value).finish(),
+            Expr::Variable(name) => f.debug_tuple("Variable").field(name).finish(),
+            Expr::Binary { left, operator, right } => {
+                f.debug_struct("Binary").field("left", left).field("operator", operator).field("right", right).finish()
+            }
+            Expr::Unary { operator, operand } => {
+                f.debug_struct("Unary").field("operator", operator).field("operand", operand).finish()
+            }
+            Expr::Grouping(expr) => f.debug_tuple("Grouping").field(expr).finish(),
+            Expr::Assignment { name, value } => {
+                f.debug_struct("Assignment").field("name", name).field("value", value).finish()
+            }
+            Expr::Call { callee, arguments } => {
+                f.debug_struct("Call").field("callee", callee).field("arguments", arguments).finish()
+            }
+            Expr::Get { object, name } => f.debug_struct("Get").field("object", object).field("name", name).finish(),
+            Expr::Set { object, name, value } => {
+                f.debug_struct("Set").field("object", object).field("name", name).field("value", value).finish()
+            }
+            Expr::This => f.write_str("This"),
+            Expr::Super { method } => f.debug_struct("Super").field("method", method).finish(),
+            Expr::Conditional { condition, then_branch, else_branch } => f
+                .debug_struct("Conditional")
+                .field("condition", condition)
+                .field("then_branch", then_branch)
+                .field("else_branch", else_branch)
+                .finish(),
+            Expr::Lambda { params, body } => {
+                f.debug_struct("Lambda").field("params", params).field("body", body).finish()
+            }
+        })
+    }
 }
 
 /// Lox statements
+///
+/// Serialized as an object whose `"kind"` is the variant name (see `serialization`).
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Stmt {
     /// Expression statement
-    Expression(Expr),
+    Expression(#[serde(with = "serialization::expression_field")] Expr),
 
     /// Print statement
-    Print(Expr),
+    Print(#[serde(with = "serialization::expression_field")] Expr),
 
     /// Variable declaration
     VarDeclaration {
         name: String,
+        /// Declared type, in the typed dialect
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        type_annotation: Option<TypeAnnotation>,
         initializer: Option<Expr>,
+        /// Text of the `///` doc comments before the declaration
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
     },
 
     /// Block statement
-    Block(Vec<Stmt>),
+    Block(#[serde(with = "serialization::statements_field")] Vec<Stmt>),
 
     /// If statement
     If {
@@ -187,7 +412,13 @@ pub enum Stmt {
     Function {
         name: String,
         params: Vec<String>,
+        /// Parameter and return types, in the typed dialect
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<FunctionSignature>,
         body: Vec<Stmt>,
+        /// Text of the `///` doc comments before the declaration
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
     },
 
     /// Return statement
@@ -195,16 +426,62 @@ pub enum Stmt {
         value: Option<Expr>,
     },
 
+    /// Break out of the innermost loop (extension)
+    Break,
+
+    /// Skip to the next iteration of the innermost loop (extension)
+    Continue,
+
     /// Class declaration
     Class {
         name: String,
         superclass: Option<String>,
-        methods: Vec<Stmt>, // Should be Function statements
+        /// Type-only field declarations of the typed dialect, each an
+        /// uninitialized `VarDeclaration` with a type annotation
+        fields: Vec<Stmt>,
+        /// Function statements
+        methods: Vec<Stmt>,
+        /// Text of the `///` doc comments before the declaration
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
+    },
+
+    /// `import "path";`, run the named file first (extension)
+    Import {
+        path: String,
     },
 }
 
+impl Stmt {
+    /// Get the doc comment of a variable, function or class declaration
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Stmt::VarDeclaration { doc, .. }
+            | Stmt::Function { doc, .. }
+            | Stmt::Class { doc, .. } => doc.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Attach a doc comment to a variable, function or class declaration
+    ///
+    /// Doc comments before any other statement are dropped.
+    pub fn with_doc(mut self, text: Option<String>) -> Self {
+        if let Stmt::VarDeclaration { doc, .. }
+        | Stmt::Function { doc, .. }
+        | Stmt::Class { doc, .. } = &mut self
+        {
+            *doc = text;
+        }
+        self
+    }
+}
+
 /// A complete Lox program
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Serialized with a `schema_version` field next to `statements`; see
+/// `serialization` for reading documents written by older versions.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub statements: Vec<Stmt>,
 }
@@ -263,6 +540,7 @@ mod tests {
         assert_eq!(BinaryOp::Add.to_string(), "+");
         assert_eq!(BinaryOp::Equal.to_string(), "==");
         assert_eq!(BinaryOp::And.to_string(), "and");
+        assert_eq!(BinaryOp::Comma.to_string(), ",");
     }
 
     #[test]
@@ -306,4 +584,37 @@ mod tests {
 
         assert_eq!(expr1, expr2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_deep_expr_is_stack_safe() {
+        let deep = || {
+            (0..100_000).fold(Expr::Literal(Value::Number(1.0)), |operand, _| Expr::Unary {
+                operator: UnaryOp::Minus,
+                operand: Box::new(operand),
+            })
+        };
+        let expr = deep();
+        let copy = expr.clone();
+        assert_eq!(copy, deep());
+        assert!(format!("{:?}", copy).starts_with("Unary { operator: Minus, operand: Unary {"));
+        drop(expr);
+    }
+
+    #[test]
+    fn test_stmt_doc() {
+        let stmt = Stmt::VarDeclaration { name: "x".to_string(), type_annotation: None, initializer: None, doc: None }
+            .with_doc(Some("The x coordinate".to_string()));
+        assert_eq!(stmt.doc(), Some("The x coordinate"));
+
+        let print = Stmt::Print(Expr::Literal(Value::Nil)).with_doc(Some("Dropped".to_string()));
+        assert_eq!(print.doc(), None);
+    }
+
+    #[test]
+    fn test_doc_is_omitted_from_json_when_absent() {
+        let stmt = Stmt::VarDeclaration { name: "x".to_string(), type_annotation: None, initializer: None, doc: None };
+        let json = serde_json::to_string(&stmt).unwrap();
+        assert!(!json.contains("doc"));
+        assert_eq!(serde_json::from_str::<Stmt>(&json).unwrap(), stmt);
+    }
+}
@@ -0,0 +1,430 @@
+//! Constant folding and algebraic simplification
+//!
+//! `optimize` rewrites a `Program` into an equivalent one that does less work at
+//! runtime. Only rewrites that cannot change what the program prints or which
+//! runtime errors it raises are made:
+//!
+//! - Arithmetic and comparisons on number literals, concatenation of string
+//!   literals, equality of any two literals, and `!`/`-` on literals are folded.
+//!   Operands of the wrong type, such as `"a" + 1` or `-"a"`, are left for the
+//!   runtime to reject, and so are results that are not finite, since a literal
+//!   cannot spell them.
+//! - `Grouping` nodes are removed; the tree already records the precedence.
+//! - `and`, `or`, `?:` and `,` with a literal on the left are short-circuited.
+//! - `-(-e)` becomes `e` when `e` always yields a number, and `!!e` becomes `e`
+//!   when it always yields a boolean. For a plain variable the double negation
+//!   stays, because it raises a runtime error when the variable holds a string.
+//! - `if` on a literal keeps only the branch taken, and `while`/`for` loops whose
+//!   condition is a falsey literal are removed (a `for` keeps its initializer).
+
+use std::mem;
+
+use crate::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+
+/// Fold constants and remove dead branches and loops throughout a program
+pub fn optimize(program: Program) -> Program {
+    Program::new(optimize_stmts(program.statements))
+}
+
+/// Fold constants in a single expression
+pub fn optimize_expr(mut expr: Expr) -> Expr {
+    match &mut expr {
+        Expr::Grouping(inner) => optimize_expr(take(inner)),
+        Expr::Unary { operator, operand } => fold_unary(operator.clone(), optimize_expr(take(operand))),
+        Expr::Binary { left, operator, right } => {
+            fold_binary(optimize_expr(take(left)), operator.clone(), optimize_expr(take(right)))
+        }
+        Expr::Conditional { condition, then_branch, else_branch } => match optimize_expr(take(condition)) {
+            Expr::Literal(ref value) if is_truthy(value) => optimize_expr(take(then_branch)),
+            Expr::Literal(_) => optimize_expr(take(else_branch)),
+            condition => Expr::Conditional {
+                condition: Box::new(condition),
+                then_branch: boxed(take(then_branch)),
+                else_branch: boxed(take(else_branch)),
+            },
+        },
+        Expr::Assignment { name, value } => Expr::Assignment { name: mem::take(name), value: boxed(take(value)) },
+        Expr::Call { callee, arguments } => Expr::Call {
+            callee: boxed(take(callee)),
+            arguments: mem::take(arguments).into_iter().map(optimize_expr).collect(),
+        },
+        Expr::Get { object, name } => Expr::Get { object: boxed(take(object)), name: mem::take(name) },
+        Expr::Set { object, name, value } => Expr::Set {
+            object: boxed(take(object)),
+            name: mem::take(name),
+            value: boxed(take(value)),
+        },
+        Expr::Lambda { params, body } => Expr::Lambda { params: mem::take(params), body: optimize_stmts(mem::take(body)) },
+        Expr::Literal(_) | Expr::Variable(_) | Expr::This | Expr::Super { .. } => expr,
+    }
+}
+
+/// Optimize a statement, returning `None` when it has no effect at all
+pub fn optimize_stmt(stmt: Stmt) -> Option<Stmt> {
+    let optimized = match stmt {
+        Stmt::Expression(expr) => Stmt::Expression(optimize_expr(expr)),
+        Stmt::Print(expr) => Stmt::Print(optimize_expr(expr)),
+        Stmt::VarDeclaration { name, type_annotation, initializer, doc } => Stmt::VarDeclaration {
+            name,
+            type_annotation,
+            initializer: initializer.map(optimize_expr),
+            doc,
+        },
+        Stmt::Block(stmts) => Stmt::Block(optimize_stmts(stmts)),
+        Stmt::If { condition, then_branch, else_branch } => match optimize_expr(condition) {
+            Expr::Literal(ref value) if is_truthy(value) => return optimize_stmt(*then_branch),
+            Expr::Literal(_) => return else_branch.and_then(|stmt| optimize_stmt(*stmt)),
+            condition => Stmt::If {
+                condition,
+                then_branch: Box::new(optimize_body(*then_branch)),
+                else_branch: else_branch.and_then(|stmt| optimize_stmt(*stmt)).map(Box::new),
+            },
+        },
+        Stmt::While { condition, body } => match optimize_expr(condition) {
+            Expr::Literal(ref value) if !is_truthy(value) => return None,
+            condition => Stmt::While { condition, body: Box::new(optimize_body(*body)) },
+        },
+        Stmt::For { initializer, condition, increment, body } => {
+            let initializer = initializer.and_then(|stmt| optimize_stmt(*stmt));
+            match condition.map(optimize_expr) {
+                Some(Expr::Literal(ref value)) if !is_truthy(value) => {
+                    // Only the initializer runs, still in a scope of its own
+                    return initializer.map(|stmt| match stmt {
+                        Stmt::VarDeclaration { .. } => Stmt::Block(vec![stmt]),
+                        stmt => stmt,
+                    });
+                }
+                condition => Stmt::For {
+                    initializer: initializer.map(Box::new),
+                    condition,
+                    increment: increment.map(optimize_expr),
+                    body: Box::new(optimize_body(*body)),
+                },
+            }
+        }
+        Stmt::Function { name, params, signature, body, doc } => Stmt::Function {
+            name,
+            params,
+            signature,
+            body: optimize_stmts(body),
+            doc,
+        },
+        Stmt::Return { value } => Stmt::Return { value: value.map(optimize_expr) },
+        Stmt::Class { name, superclass, fields, methods, doc } => Stmt::Class {
+            name,
+            superclass,
+            fields,
+            methods: optimize_stmts(methods),
+            doc,
+        },
+        Stmt::Break | Stmt::Continue | Stmt::Import { .. } => stmt,
+    };
+    Some(optimized)
+}
+
+fn optimize_stmts(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts.into_iter().filter_map(optimize_stmt).collect()
+}
+
+/// Optimize a statement that must stay in place, such as a loop body
+fn optimize_body(stmt: Stmt) -> Stmt {
+    optimize_stmt(stmt).unwrap_or(Stmt::Block(Vec::new()))
+}
+
+fn boxed(expr: Expr) -> Box<Expr> {
+    Box::new(optimize_expr(expr))
+}
+
+/// Move a subexpression out, which `Expr`'s `Drop` does not allow by pattern
+fn take(expr: &mut Box<Expr>) -> Expr {
+    mem::take(&mut **expr)
+}
+
+/// Lox truthiness: `nil` and `false` are falsey, everything else is truthy
+fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Bool(false))
+}
+
+/// Lox equality: values of different types are never equal
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Check whether evaluating `expr` yields a number whenever it does not raise an error
+fn always_number(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Value::Number(_)) => true,
+        Expr::Unary { operator: UnaryOp::Minus, .. } => true,
+        Expr::Binary { operator, .. } => {
+            matches!(operator, BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide)
+        }
+        _ => false,
+    }
+}
+
+/// Check whether evaluating `expr` yields a boolean whenever it does not raise an error
+fn always_bool(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(Value::Bool(_)) => true,
+        Expr::Unary { operator: UnaryOp::Not, .. } => true,
+        Expr::Binary { operator, .. } => matches!(
+            operator,
+            BinaryOp::Greater
+                | BinaryOp::GreaterEqual
+                | BinaryOp::Less
+                | BinaryOp::LessEqual
+                | BinaryOp::Equal
+                | BinaryOp::NotEqual
+        ),
+        _ => false,
+    }
+}
+
+fn number(n: f64) -> Option<Expr> {
+    n.is_finite().then_some(Expr::Literal(Value::Number(n)))
+}
+
+fn boolean(b: bool) -> Option<Expr> {
+    Some(Expr::Literal(Value::Bool(b)))
+}
+
+fn fold_unary(operator: UnaryOp, mut operand: Expr) -> Expr {
+    match (&operator, &mut operand) {
+        (UnaryOp::Not, Expr::Literal(value)) => Expr::Literal(Value::Bool(!is_truthy(value))),
+        (UnaryOp::Minus, Expr::Literal(Value::Number(n))) => Expr::Literal(Value::Number(-*n)),
+        (UnaryOp::Minus, Expr::Unary { operator: UnaryOp::Minus, operand: inner }) if always_number(inner) => {
+            take(inner)
+        }
+        (UnaryOp::Not, Expr::Unary { operator: UnaryOp::Not, operand: inner }) if always_bool(inner) => take(inner),
+        _ => Expr::Unary { operator, operand: Box::new(operand) },
+    }
+}
+
+fn fold_binary(left: Expr, operator: BinaryOp, right: Expr) -> Expr {
+    // The logical operators and the comma only need the left operand to be known
+    if let Expr::Literal(value) = &left {
+        match operator {
+            BinaryOp::And if !is_truthy(value) => return left,
+            BinaryOp::Or if is_truthy(value) => return left,
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Comma => return right,
+            _ => {}
+        }
+    }
+
+    let folded = match (&left, &right) {
+        (Expr::Literal(Value::Number(a)), Expr::Literal(Value::Number(b))) => match operator {
+            BinaryOp::Add => number(a + b),
+            BinaryOp::Subtract => number(a - b),
+            BinaryOp::Multiply => number(a * b),
+            BinaryOp::Divide => number(a / b),
+            BinaryOp::Greater => boolean(a > b),
+            BinaryOp::GreaterEqual => boolean(a >= b),
+            BinaryOp::Less => boolean(a < b),
+            BinaryOp::LessEqual => boolean(a <= b),
+            _ => None,
+        },
+        (Expr::Literal(Value::String(a)), Expr::Literal(Value::String(b))) if operator == BinaryOp::Add => {
+            Some(Expr::Literal(Value::String(format!("{}{}", a, b))))
+        }
+        _ => None,
+    };
+    let folded = folded.or_else(|| match (&left, &operator, &right) {
+        (Expr::Literal(a), BinaryOp::Equal, Expr::Literal(b)) => boolean(values_equal(a, b)),
+        (Expr::Literal(a), BinaryOp::NotEqual, Expr::Literal(b)) => boolean(!values_equal(a, b)),
+        _ => None,
+    });
+
+    folded.unwrap_or_else(|| Expr::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(n: f64) -> Expr {
+        Expr::Literal(Value::Number(n))
+    }
+
+    fn string(s: &str) -> Expr {
+        Expr::Literal(Value::String(s.to_string()))
+    }
+
+    fn var(name: &str) -> Expr {
+        Expr::Variable(name.to_string())
+    }
+
+    fn binary(left: Expr, operator: BinaryOp, right: Expr) -> Expr {
+        Expr::Binary { left: Box::new(left), operator, right: Box::new(right) }
+    }
+
+    fn unary(operator: UnaryOp, operand: Expr) -> Expr {
+        Expr::Unary { operator, operand: Box::new(operand) }
+    }
+
+    fn group(expr: Expr) -> Expr {
+        Expr::Grouping(Box::new(expr))
+    }
+
+    fn print(expr: Expr) -> Stmt {
+        Stmt::Print(expr)
+    }
+
+    #[test]
+    fn test_folds_arithmetic() {
+        // 1 + 2 * 3
+        let expr = binary(num(1.0), BinaryOp::Add, binary(num(2.0), BinaryOp::Multiply, num(3.0)));
+        assert_eq!(optimize_expr(expr), num(7.0));
+
+        // (1 + 2) * -(4 - 1) < 0
+        let expr = binary(
+            binary(
+                group(binary(num(1.0), BinaryOp::Add, num(2.0))),
+                BinaryOp::Multiply,
+                unary(UnaryOp::Minus, group(binary(num(4.0), BinaryOp::Subtract, num(1.0)))),
+            ),
+            BinaryOp::Less,
+            num(0.0),
+        );
+        assert_eq!(optimize_expr(expr), Expr::Literal(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_folds_strings_equality_and_not() {
+        assert_eq!(optimize_expr(binary(string("a"), BinaryOp::Add, string("b"))), string("ab"));
+        assert_eq!(optimize_expr(unary(UnaryOp::Not, Expr::Literal(Value::Bool(true)))), Expr::Literal(Value::Bool(false)));
+        assert_eq!(optimize_expr(unary(UnaryOp::Not, Expr::Literal(Value::Nil))), Expr::Literal(Value::Bool(true)));
+        assert_eq!(optimize_expr(binary(string("1"), BinaryOp::Equal, num(1.0))), Expr::Literal(Value::Bool(false)));
+        assert_eq!(optimize_expr(binary(Expr::Literal(Value::Nil), BinaryOp::NotEqual, Expr::Literal(Value::Nil))), Expr::Literal(Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_keeps_runtime_errors() {
+        let unchanged = [
+            binary(string("a"), BinaryOp::Add, num(1.0)),
+            binary(string("a"), BinaryOp::Less, string("b")),
+            unary(UnaryOp::Minus, string("a")),
+            // -(-x) raises when x is a string, so it cannot become x
+            unary(UnaryOp::Minus, unary(UnaryOp::Minus, var("x"))),
+            // Division by zero gives infinity, which no literal can spell
+            binary(num(1.0), BinaryOp::Divide, num(0.0)),
+        ];
+        for expr in unchanged {
+            assert_eq!(optimize_expr(expr.clone()), expr);
+        }
+    }
+
+    #[test]
+    fn test_removes_double_negation_of_known_types() {
+        let difference = binary(var("a"), BinaryOp::Subtract, var("b"));
+        let expr = unary(UnaryOp::Minus, group(unary(UnaryOp::Minus, group(difference.clone()))));
+        assert_eq!(optimize_expr(expr), difference);
+
+        let comparison = binary(var("a"), BinaryOp::Less, var("b"));
+        let expr = unary(UnaryOp::Not, unary(UnaryOp::Not, comparison.clone()));
+        assert_eq!(optimize_expr(expr), comparison);
+
+        let expr = unary(UnaryOp::Not, unary(UnaryOp::Not, var("a")));
+        assert_eq!(optimize_expr(expr.clone()), expr);
+    }
+
+    #[test]
+    fn test_short_circuits_literal_operands() {
+        let call = Expr::Call { callee: Box::new(var("f")), arguments: vec![] };
+        let falsey = Expr::Literal(Value::Bool(false));
+        assert_eq!(optimize_expr(binary(falsey.clone(), BinaryOp::And, call.clone())), falsey);
+        assert_eq!(optimize_expr(binary(num(1.0), BinaryOp::And, call.clone())), call);
+        assert_eq!(optimize_expr(binary(num(1.0), BinaryOp::Or, call.clone())), num(1.0));
+        assert_eq!(optimize_expr(binary(Expr::Literal(Value::Nil), BinaryOp::Or, call.clone())), call);
+        assert_eq!(optimize_expr(binary(num(1.0), BinaryOp::Comma, call.clone())), call);
+
+        // The right operand is not a literal, so `f() and false` must still call f
+        let expr = binary(call.clone(), BinaryOp::And, falsey.clone());
+        assert_eq!(optimize_expr(expr.clone()), expr);
+
+        let expr = Expr::Conditional {
+            condition: Box::new(group(binary(num(1.0), BinaryOp::Greater, num(2.0)))),
+            then_branch: Box::new(string("yes")),
+            else_branch: Box::new(string("no")),
+        };
+        assert_eq!(optimize_expr(expr), string("no"));
+    }
+
+    #[test]
+    fn test_removes_dead_branches_and_loops() {
+        let program = Program::new(vec![
+            Stmt::If {
+                condition: Expr::Literal(Value::Bool(false)),
+                then_branch: Box::new(print(string("never"))),
+                else_branch: None,
+            },
+            Stmt::If {
+                condition: binary(num(1.0), BinaryOp::Less, num(2.0)),
+                then_branch: Box::new(print(string("then"))),
+                else_branch: Some(Box::new(print(string("else")))),
+            },
+            Stmt::While {
+                condition: Expr::Literal(Value::Nil),
+                body: Box::new(print(string("never"))),
+            },
+            Stmt::For {
+                initializer: Some(Box::new(Stmt::VarDeclaration {
+                    name: "i".to_string(),
+                    type_annotation: None,
+                    initializer: Some(num(0.0)),
+                    doc: None,
+                })),
+                condition: Some(Expr::Literal(Value::Bool(false))),
+                increment: None,
+                body: Box::new(print(var("i"))),
+            },
+            Stmt::While {
+                condition: var("running"),
+                body: Box::new(Stmt::If {
+                    condition: Expr::Literal(Value::Bool(false)),
+                    then_branch: Box::new(Stmt::Break),
+                    else_branch: None,
+                }),
+            },
+        ]);
+
+        assert_eq!(optimize(program), Program::new(vec![
+            print(string("then")),
+            Stmt::Block(vec![Stmt::VarDeclaration {
+                name: "i".to_string(),
+                type_annotation: None,
+                initializer: Some(num(0.0)),
+                doc: None,
+            }]),
+            Stmt::While { condition: var("running"), body: Box::new(Stmt::Block(vec![])) },
+        ]));
+    }
+
+    #[test]
+    fn test_optimizes_nested_bodies() {
+        let program = Program::new(vec![Stmt::Function {
+            name: "f".to_string(),
+            params: vec![],
+            signature: None,
+            body: vec![Stmt::Return { value: Some(binary(string("a"), BinaryOp::Add, string("b"))) }],
+            doc: Some("Docs are kept".to_string()),
+        }]);
+
+        assert_eq!(optimize(program), Program::new(vec![Stmt::Function {
+            name: "f".to_string(),
+            params: vec![],
+            signature: None,
+            body: vec![Stmt::Return { value: Some(string("ab")) }],
+            doc: Some("Docs are kept".to_string()),
+        }]));
+    }
+}
@@ -0,0 +1,409 @@
+//! JSON Schema and TypeScript definitions for the serialized AST
+//!
+//! Both are generated from one description of the format written by
+//! `serialization` (schema version `SCHEMA_VERSION`), so tools in other
+//! languages can check and type the documents the parsers produce:
+//!
+//! - `json_schema` returns a JSON Schema (draft 2020-12) whose root is a
+//!   `Program`, with a definition per type and per `Stmt`, `Expr` and
+//!   `TypeAnnotation` variant (`PrintStmt`, `LiteralExpr`, ...)
+//! - `typescript_definitions` returns the matching `.d.ts` declarations,
+//!   where the variants form unions discriminated by `kind`
+//!
+//! Copies of both are checked in under `lox-ast/schema/`; the tests fail when
+//! they are out of date, and rewrite them when run with `UPDATE_SCHEMA=1`.
+//! `parser_tests::validation` checks the parsers' output against the schema.
+
+use serde_json::{Map, Value as Json, json};
+
+use crate::SCHEMA_VERSION;
+
+/// The JSON Schema dialect of `json_schema`
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The shape of a field's value
+enum Shape {
+    /// A string
+    Str,
+    /// The current `SCHEMA_VERSION`
+    Version,
+    /// One of the definitions below
+    Named(&'static str),
+    /// A list
+    Array(&'static Shape),
+    /// A value or `null`
+    Nullable(&'static Shape),
+}
+
+struct Field {
+    name: &'static str,
+    shape: Shape,
+    /// Left out of the document when absent rather than written as `null`
+    optional: bool,
+}
+
+/// A `Stmt`, `Expr` or `TypeAnnotation` variant, tagged with its `kind`
+struct Variant {
+    name: &'static str,
+    description: &'static str,
+    fields: &'static [Field],
+}
+
+enum Definition {
+    Struct { fields: &'static [Field] },
+    Tagged { variants: &'static [Variant] },
+    Strings { values: &'static [&'static str] },
+    /// `Value`, which is the plain JSON value
+    Value,
+}
+
+struct TypeDef {
+    name: &'static str,
+    description: &'static str,
+    definition: Definition,
+}
+
+const fn field(name: &'static str, shape: Shape) -> Field {
+    Field { name, shape, optional: false }
+}
+
+const fn optional(name: &'static str, shape: Shape) -> Field {
+    Field { name, shape, optional: true }
+}
+
+const fn variant(name: &'static str, description: &'static str, fields: &'static [Field]) -> Variant {
+    Variant { name, description, fields }
+}
+
+const STMT: Shape = Shape::Named("Stmt");
+const EXPR: Shape = Shape::Named("Expr");
+const STRINGS: Shape = Shape::Array(&Shape::Str);
+const STMTS: Shape = Shape::Array(&STMT);
+
+/// Every type in a serialized program, in the order they are written out
+const TYPES: &[TypeDef] = &[
+    TypeDef {
+        name: "Program",
+        description: "A complete Lox program",
+        definition: Definition::Struct {
+            fields: &[field("schema_version", Shape::Version), field("statements", STMTS)],
+        },
+    },
+    TypeDef {
+        name: "Stmt",
+        description: "Lox statements",
+        definition: Definition::Tagged {
+            variants: &[
+                variant("Expression", "Expression statement", &[field("expression", EXPR)]),
+                variant("Print", "Print statement", &[field("expression", EXPR)]),
+                variant("VarDeclaration", "Variable declaration", &[
+                    field("name", Shape::Str),
+                    optional("type_annotation", Shape::Named("TypeAnnotation")),
+                    field("initializer", Shape::Nullable(&EXPR)),
+                    optional("doc", Shape::Str),
+                ]),
+                variant("Block", "Block statement", &[field("statements", STMTS)]),
+                variant("If", "If statement", &[
+                    field("condition", EXPR),
+                    field("then_branch", STMT),
+                    field("else_branch", Shape::Nullable(&STMT)),
+                ]),
+                variant("While", "While loop", &[field("condition", EXPR), field("body", STMT)]),
+                variant("For", "For loop", &[
+                    field("initializer", Shape::Nullable(&STMT)),
+                    field("condition", Shape::Nullable(&EXPR)),
+                    field("increment", Shape::Nullable(&EXPR)),
+                    field("body", STMT),
+                ]),
+                variant("Function", "Function declaration", &[
+                    field("name", Shape::Str),
+                    field("params", STRINGS),
+                    optional("signature", Shape::Named("FunctionSignature")),
+                    field("body", STMTS),
+                    optional("doc", Shape::Str),
+                ]),
+                variant("Return", "Return statement", &[field("value", Shape::Nullable(&EXPR))]),
+                variant("Break", "Break out of the innermost loop (extension)", &[]),
+                variant("Continue", "Skip to the next iteration of the innermost loop (extension)", &[]),
+                variant("Class", "Class declaration", &[
+                    field("name", Shape::Str),
+                    field("superclass", Shape::Nullable(&Shape::Str)),
+                    field("fields", STMTS),
+                    field("methods", STMTS),
+                    optional("doc", Shape::Str),
+                ]),
+                variant("Import", "`import \"path\";`, run the named file first (extension)", &[field("path", Shape::Str)]),
+            ],
+        },
+    },
+    TypeDef {
+        name: "Expr",
+        description: "Lox expressions",
+        definition: Definition::Tagged {
+            variants: &[
+                variant("Literal", "Literal values", &[field("value", Shape::Named("Value"))]),
+                variant("Variable", "Variable reference", &[field("name", Shape::Str)]),
+                variant("Binary", "Binary operations", &[
+                    field("left", EXPR),
+                    field("operator", Shape::Named("BinaryOp")),
+                    field("right", EXPR),
+                ]),
+                variant("Unary", "Unary operations", &[
+                    field("operator", Shape::Named("UnaryOp")),
+                    field("operand", EXPR),
+                ]),
+                variant("Grouping", "Grouping (parentheses)", &[field("expression", EXPR)]),
+                variant("Assignment", "Assignment", &[field("name", Shape::Str), field("value", EXPR)]),
+                variant("Call", "Function call", &[field("callee", EXPR), field("arguments", Shape::Array(&EXPR))]),
+                variant("Get", "Property access", &[field("object", EXPR), field("name", Shape::Str)]),
+                variant("Set", "Property assignment", &[
+                    field("object", EXPR),
+                    field("name", Shape::Str),
+                    field("value", EXPR),
+                ]),
+                variant("This", "This expression", &[]),
+                variant("Super", "Super expression", &[field("method", Shape::Str)]),
+                variant("Conditional", "Conditional expression `condition ? then_branch : else_branch` (extension)", &[
+                    field("condition", EXPR),
+                    field("then_branch", EXPR),
+                    field("else_branch", EXPR),
+                ]),
+                variant("Lambda", "Anonymous function `fun (params) { body }` (extension)", &[
+                    field("params", STRINGS),
+                    field("body", STMTS),
+                ]),
+            ],
+        },
+    },
+    TypeDef {
+        name: "Value",
+        description: "A Lox value: nil, a boolean, a number or a string",
+        definition: Definition::Value,
+    },
+    TypeDef {
+        name: "BinaryOp",
+        description: "Binary operators in Lox",
+        definition: Definition::Strings {
+            values: &[
+                "Add", "Subtract", "Multiply", "Divide", "Greater", "GreaterEqual", "Less", "LessEqual", "Equal",
+                "NotEqual", "And", "Or", "Comma",
+            ],
+        },
+    },
+    TypeDef {
+        name: "UnaryOp",
+        description: "Unary operators in Lox",
+        definition: Definition::Strings { values: &["Minus", "Not"] },
+    },
+    TypeDef {
+        name: "TypeAnnotation",
+        description: "A type written in the source, in the typed dialect",
+        definition: Definition::Tagged {
+            variants: &[
+                variant("Any", "`Any`, which opts out of checking", &[]),
+                variant("Nil", "`Nil`", &[]),
+                variant("Bool", "`Bool`", &[]),
+                variant("Number", "`Number`", &[]),
+                variant("String", "`String`", &[]),
+                variant("Class", "An instance of the named class", &[field("name", Shape::Str)]),
+                variant("Function", "`fun(Number, String): Bool`", &[
+                    field("params", Shape::Array(&Shape::Named("TypeAnnotation"))),
+                    field("return_type", Shape::Named("TypeAnnotation")),
+                ]),
+            ],
+        },
+    },
+    TypeDef {
+        name: "FunctionSignature",
+        description: "The annotations on a function's parameters and return value, `null` where unannotated",
+        definition: Definition::Struct {
+            fields: &[
+                field("params", Shape::Array(&Shape::Nullable(&Shape::Named("TypeAnnotation")))),
+                field("return_type", Shape::Nullable(&Shape::Named("TypeAnnotation"))),
+            ],
+        },
+    },
+];
+
+/// The definition name of a variant, e.g. `PrintStmt`
+fn variant_name(owner: &str, variant: &Variant) -> String {
+    format!("{}{}", variant.name, owner)
+}
+
+/// The JSON Schema of a serialized `Program`
+pub fn json_schema() -> Json {
+    let mut definitions = Map::new();
+    for type_def in TYPES {
+        let schema = match &type_def.definition {
+            Definition::Struct { fields } => object_schema(None, fields),
+            Definition::Tagged { variants } => {
+                for variant in *variants {
+                    let mut schema = object_schema(Some(variant.name), variant.fields);
+                    schema["description"] = json!(variant.description);
+                    definitions.insert(variant_name(type_def.name, variant), schema);
+                }
+                let alternatives: Vec<Json> =
+                    variants.iter().map(|variant| reference(&variant_name(type_def.name, variant))).collect();
+                json!({ "oneOf": alternatives })
+            }
+            Definition::Strings { values } => json!({ "type": "string", "enum": values }),
+            Definition::Value => json!({ "type": ["null", "boolean", "number", "string"] }),
+        };
+        let mut schema = schema;
+        schema["description"] = json!(type_def.description);
+        definitions.insert(type_def.name.to_string(), schema);
+    }
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": "Lox program",
+        "description": format!("A Lox program serialized by lox-ast with schema version {}", SCHEMA_VERSION),
+        "$ref": "#/$defs/Program",
+        "$defs": definitions,
+    })
+}
+
+fn reference(name: &str) -> Json {
+    json!({ "$ref": format!("#/$defs/{}", name) })
+}
+
+fn object_schema(kind: Option<&str>, fields: &[Field]) -> Json {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    if let Some(kind) = kind {
+        properties.insert("kind".to_string(), json!({ "const": kind }));
+        required.push("kind");
+    }
+    for field in fields {
+        properties.insert(field.name.to_string(), shape_schema(&field.shape));
+        if !field.optional {
+            required.push(field.name);
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn shape_schema(shape: &Shape) -> Json {
+    match shape {
+        Shape::Str => json!({ "type": "string" }),
+        Shape::Version => json!({ "const": SCHEMA_VERSION }),
+        Shape::Named(name) => reference(name),
+        Shape::Array(items) => json!({ "type": "array", "items": shape_schema(items) }),
+        Shape::Nullable(shape) => json!({ "anyOf": [shape_schema(shape), { "type": "null" }] }),
+    }
+}
+
+/// TypeScript declarations of a serialized `Program`
+pub fn typescript_definitions() -> String {
+    let mut out = format!(
+        "// TypeScript definitions of a Lox program serialized by lox-ast with schema version {}\n\
+         // Generated by lox_ast::schema::typescript_definitions\n",
+        SCHEMA_VERSION
+    );
+    for type_def in TYPES {
+        out.push('\n');
+        doc_comment(&mut out, type_def.description);
+        match &type_def.definition {
+            Definition::Struct { fields } => interface(&mut out, type_def.name, None, fields),
+            Definition::Tagged { variants } => {
+                out.push_str(&format!("export type {} =\n", type_def.name));
+                for variant in *variants {
+                    out.push_str(&format!("  | {}\n", variant_name(type_def.name, variant)));
+                }
+                out.pop();
+                out.push_str(";\n");
+                for variant in *variants {
+                    out.push('\n');
+                    doc_comment(&mut out, variant.description);
+                    interface(&mut out, &variant_name(type_def.name, variant), Some(variant.name), variant.fields);
+                }
+            }
+            Definition::Strings { values } => {
+                let values: Vec<String> = values.iter().map(|value| format!("\"{}\"", value)).collect();
+                out.push_str(&format!("export type {} = {};\n", type_def.name, values.join(" | ")));
+            }
+            Definition::Value => out.push_str(&format!("export type {} = null | boolean | number | string;\n", type_def.name)),
+        }
+    }
+    out
+}
+
+fn doc_comment(out: &mut String, text: &str) {
+    out.push_str(&format!("/** {} */\n", text));
+}
+
+fn interface(out: &mut String, name: &str, kind: Option<&str>, fields: &[Field]) {
+    out.push_str(&format!("export interface {} {{\n", name));
+    if let Some(kind) = kind {
+        out.push_str(&format!("  kind: \"{}\";\n", kind));
+    }
+    for field in fields {
+        let marker = if field.optional { "?" } else { "" };
+        out.push_str(&format!("  {}{}: {};\n", field.name, marker, shape_type(&field.shape)));
+    }
+    out.push_str("}\n");
+}
+
+fn shape_type(shape: &Shape) -> String {
+    match shape {
+        Shape::Str => "string".to_string(),
+        Shape::Version => SCHEMA_VERSION.to_string(),
+        Shape::Named(name) => name.to_string(),
+        Shape::Array(items @ Shape::Nullable(_)) => format!("({})[]", shape_type(items)),
+        Shape::Array(items) => format!("{}[]", shape_type(items)),
+        Shape::Nullable(shape) => format!("{} | null", shape_type(shape)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::tests::sample_program;
+
+    /// Compare a generated file with its checked-in copy, or rewrite the copy
+    /// when `UPDATE_SCHEMA` is set
+    fn check_file(name: &str, generated: &str) {
+        let path = format!("{}/schema/{}", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(&path, generated).unwrap();
+            return;
+        }
+        let checked_in = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(checked_in == generated, "schema/{} is out of date; rerun the tests with UPDATE_SCHEMA=1", name);
+    }
+
+    #[test]
+    fn test_checked_in_files_are_current() {
+        check_file("lox-ast.schema.json", &format!("{}\n", serde_json::to_string_pretty(&json_schema()).unwrap()));
+        check_file("lox-ast.d.ts", &typescript_definitions());
+    }
+
+    #[test]
+    fn test_every_variant_is_described() {
+        let schema = json_schema();
+        let definitions = schema["$defs"].as_object().unwrap();
+        let document = serde_json::to_string(&sample_program()).unwrap();
+        for type_def in TYPES {
+            if let Definition::Tagged { variants } = &type_def.definition {
+                for variant in *variants {
+                    assert!(definitions.contains_key(&variant_name(type_def.name, variant)));
+                    // The sample program, which exercises every variant, uses this kind
+                    assert!(document.contains(&format!("\"kind\":\"{}\"", variant.name)), "{}", variant.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_typescript_unions() {
+        let definitions = typescript_definitions();
+        assert!(definitions.contains("export type Expr =\n  | LiteralExpr\n  | VariableExpr\n"));
+        assert!(definitions.contains("export interface VarDeclarationStmt {\n  kind: \"VarDeclaration\";\n  name: string;\n  type_annotation?: TypeAnnotation;\n  initializer: Expr | null;\n  doc?: string;\n}\n"));
+        assert!(definitions.contains("  params: (TypeAnnotation | null)[];\n"));
+        assert!(definitions.contains("export type UnaryOp = \"Minus\" | \"Not\";\n"));
+    }
+}
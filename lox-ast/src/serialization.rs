@@ -0,0 +1,684 @@
+//! Versioned on-disk formats for programs
+//!
+//! Programs are saved as JSON or as CBOR, a compact binary encoding of the
+//! same data. Both are produced by the types' `serde` implementations:
+//!
+//! - `Program` is an object with a `schema_version` and its `statements`
+//! - `Stmt`, `Expr` and `TypeAnnotation` are objects whose `"kind"` is the
+//!   variant name, next to the variant's fields. Variants that wrap a single
+//!   value name it `expression`, `statements`, `value` or `name`.
+//! - `Value` is the plain JSON value: `null`, a boolean, a number or a string
+//! - `BinaryOp` and `UnaryOp` are their variant names, e.g. `"Add"`
+//! - optional fields hold `null`, except `doc`, `type_annotation` and
+//!   `signature`, which are left out when absent
+//!
+//! ```json
+//! {
+//!   "schema_version": 4,
+//!   "statements": [
+//!     {"kind": "Print", "expression": {
+//!       "kind": "Binary",
+//!       "left": {"kind": "Literal", "value": 1.0},
+//!       "operator": "Add",
+//!       "right": {"kind": "Variable", "name": "x"}
+//!     }}
+//!   ]
+//! }
+//! ```
+//!
+//! `SCHEMA_VERSION` changes whenever the format does, and `from_json` and
+//! `from_cbor` migrate documents written with an older version. The versions
+//! so far:
+//!
+//! 1. serde's default encoding, with enums as `{"Variant": content}` and no
+//!    `schema_version`
+//! 2. `"kind"` tags and the `schema_version` header
+//! 3. adds the `Import` statement
+//! 4. the format above, which lists a class's `fields` apart from its `methods`
+//!
+//! JSON has no representation for infinite or NaN numbers, which only the
+//! binary format keeps.
+
+use std::fmt;
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value as Json, json};
+
+use crate::{Program, Stmt};
+
+/// The version of the format written by this crate
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// Why a document could not be read
+#[derive(Debug)]
+pub enum SerializationError {
+    /// Malformed JSON, or JSON that does not describe a program
+    Json(serde_json::Error),
+    /// Malformed CBOR, or CBOR that does not describe a program
+    Cbor(String),
+    /// A document written by a newer version of this crate
+    UnsupportedVersion(u32),
+    /// An older document that does not have the shape its version describes
+    Migration { from_version: u32, message: String },
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Json(error) => write!(f, "Invalid JSON program: {}", error),
+            SerializationError::Cbor(message) => write!(f, "Invalid CBOR program: {}", message),
+            SerializationError::UnsupportedVersion(version) => write!(
+                f,
+                "Schema version {} is newer than the supported version {}",
+                version, SCHEMA_VERSION
+            ),
+            SerializationError::Migration { from_version, message } => {
+                write!(f, "Cannot migrate from schema version {}: {}", from_version, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl From<serde_json::Error> for SerializationError {
+    fn from(error: serde_json::Error) -> Self {
+        SerializationError::Json(error)
+    }
+}
+
+/// Encode `program` as JSON
+pub fn to_json(program: &Program) -> String {
+    serde_json::to_string(program).expect("programs always serialize")
+}
+
+/// Encode `program` as indented JSON
+pub fn to_json_pretty(program: &Program) -> String {
+    serde_json::to_string_pretty(program).expect("programs always serialize")
+}
+
+/// Decode a JSON program written with any schema version
+pub fn from_json(json: &str) -> Result<Program, SerializationError> {
+    let header: Header = serde_json::from_str(json)?;
+    if header.version() == SCHEMA_VERSION {
+        return Ok(serde_json::from_str(json)?);
+    }
+    let document = migrate(serde_json::from_str(json)?)?;
+    Ok(serde_json::from_value(document)?)
+}
+
+/// Encode `program` as CBOR
+pub fn to_cbor(program: &Program) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(program, &mut bytes).expect("writing to a Vec cannot fail");
+    bytes
+}
+
+/// Decode a CBOR program written with any schema version
+pub fn from_cbor(bytes: &[u8]) -> Result<Program, SerializationError> {
+    let cbor_error = |error: ciborium::de::Error<std::io::Error>| SerializationError::Cbor(error.to_string());
+    let header: Header = ciborium::from_reader(bytes).map_err(cbor_error)?;
+    if header.version() == SCHEMA_VERSION {
+        return ciborium::from_reader(bytes).map_err(cbor_error);
+    }
+    let document = migrate(ciborium::from_reader(bytes).map_err(cbor_error)?)?;
+    Ok(serde_json::from_value(document)?)
+}
+
+/// Upgrade a document of any schema version to `SCHEMA_VERSION`
+pub fn migrate(document: Json) -> Result<Json, SerializationError> {
+    let mut version = match document.get("schema_version") {
+        None => 1,
+        Some(version) => version.as_u64().and_then(|version| u32::try_from(version).ok()).ok_or_else(|| {
+            SerializationError::Migration { from_version: 0, message: format!("invalid schema_version {}", version) }
+        })?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(SerializationError::UnsupportedVersion(version));
+    }
+    let mut document = document;
+    while version < SCHEMA_VERSION {
+        let migration = match version {
+            1 => v1::migrate,
+            2 => v2::migrate,
+            3 => v3::migrate,
+            _ => unreachable!("every version below SCHEMA_VERSION has a migration"),
+        };
+        document = migration(document)
+            .map_err(|message| SerializationError::Migration { from_version: version, message })?;
+        version += 1;
+    }
+    Ok(document)
+}
+
+/// The part of a document read to decide whether it needs migrating
+#[derive(Deserialize)]
+struct Header {
+    schema_version: Option<u32>,
+}
+
+impl Header {
+    fn version(&self) -> u32 {
+        self.schema_version.unwrap_or(1)
+    }
+}
+
+impl Serialize for Program {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Program", 2)?;
+        state.serialize_field("schema_version", &SCHEMA_VERSION)?;
+        state.serialize_field("statements", &self.statements)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Program {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Program", &["schema_version", "statements"], ProgramVisitor)
+    }
+}
+
+struct ProgramVisitor;
+
+impl<'de> Visitor<'de> for ProgramVisitor {
+    type Value = Program;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a Lox program")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Program, A::Error> {
+        let mut version = None;
+        let mut statements: Option<Vec<Stmt>> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "schema_version" => version = Some(map.next_value::<u32>()?),
+                // Checked before the statements, which an old document would
+                // fail to match with a less helpful error
+                "statements" => {
+                    check_version(version.unwrap_or(1))?;
+                    statements = Some(map.next_value()?);
+                }
+                _ => {
+                    map.next_value::<de::IgnoredAny>()?;
+                }
+            }
+        }
+        check_version(version.unwrap_or(1))?;
+        let statements = statements.ok_or_else(|| de::Error::missing_field("statements"))?;
+        Ok(Program { statements })
+    }
+}
+
+fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+    if version == SCHEMA_VERSION {
+        Ok(())
+    } else {
+        Err(E::custom(format!(
+            "schema version {} is not {}; read the document with lox_ast::serialization, which migrates it",
+            version, SCHEMA_VERSION
+        )))
+    }
+}
+
+/// Generate a `serde(with)` module that stores a newtype variant's content
+/// under a field name, since internally tagged variants must be objects
+macro_rules! single_field {
+    ($module:ident, $field:literal) => {
+        pub(crate) mod $module {
+            use serde::ser::SerializeStruct;
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<T: Serialize, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut state = serializer.serialize_struct("Variant", 1)?;
+                state.serialize_field($field, value)?;
+                state.end()
+            }
+
+            #[derive(Deserialize)]
+            struct Variant<T> {
+                #[serde(rename = $field)]
+                content: T,
+            }
+
+            pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+                Variant::deserialize(deserializer).map(|variant| variant.content)
+            }
+        }
+    };
+}
+
+single_field!(expression_field, "expression");
+single_field!(statements_field, "statements");
+single_field!(value_field, "value");
+single_field!(name_field, "name");
+
+/// Reading schema version 3, which listed a class's fields among its methods
+mod v3 {
+    use super::*;
+
+    pub(super) fn migrate(mut document: Json) -> Result<Json, String> {
+        split_fields(&mut document);
+        document["schema_version"] = json!(4);
+        Ok(document)
+    }
+
+    /// Move the `VarDeclaration`s out of the methods of every class within `value`
+    fn split_fields(value: &mut Json) {
+        match value {
+            Json::Array(items) => items.iter_mut().for_each(split_fields),
+            Json::Object(object) => {
+                if object.get("kind").is_some_and(|kind| kind == "Class")
+                    && let Some(Json::Array(members)) = object.get_mut("methods")
+                {
+                    let (fields, methods) =
+                        members.drain(..).partition(|member: &Json| member["kind"] == "VarDeclaration");
+                    *members = methods;
+                    object.insert("fields".to_string(), Json::Array(fields));
+                }
+                object.values_mut().for_each(split_fields);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reading schema version 2, which version 3 only added a statement to
+mod v2 {
+    use super::*;
+
+    pub(super) fn migrate(mut document: Json) -> Result<Json, String> {
+        document["schema_version"] = json!(3);
+        Ok(document)
+    }
+}
+
+/// Reading schema version 1, serde's default externally tagged encoding
+mod v1 {
+    use super::*;
+
+    pub(super) fn migrate(mut document: Json) -> Result<Json, String> {
+        let statements = document.get_mut("statements").map(Json::take).ok_or("missing statements")?;
+        Ok(json!({ "schema_version": 2, "statements": list(statements, stmt)? }))
+    }
+
+    /// The content a field holds, which decides how it is migrated
+    #[derive(Clone, Copy)]
+    enum Field {
+        Expr,
+        Exprs,
+        Stmt,
+        Stmts,
+        Type,
+        Types,
+        Signature,
+    }
+
+    /// Split `{"Variant": content}`, or a bare `"Variant"` for unit variants
+    fn variant(value: Json) -> Result<(String, Json), String> {
+        match value {
+            Json::String(name) => Ok((name, Json::Null)),
+            Json::Object(object) if object.len() == 1 => Ok(object.into_iter().next().expect("one entry")),
+            other => Err(format!("expected an enum variant, found {}", other)),
+        }
+    }
+
+    fn list(value: Json, item: fn(Json) -> Result<Json, String>) -> Result<Json, String> {
+        match value {
+            Json::Array(items) => items.into_iter().map(item).collect::<Result<_, _>>().map(Json::Array),
+            other => Err(format!("expected a list, found {}", other)),
+        }
+    }
+
+    fn tagged(kind: &str, fields: Map<String, Json>) -> Json {
+        let mut object = Map::new();
+        object.insert("kind".to_string(), Json::String(kind.to_string()));
+        object.extend(fields);
+        Json::Object(object)
+    }
+
+    /// Migrate the fields of a struct variant, converting those listed in
+    /// `nested` and keeping the rest
+    fn struct_variant(kind: &str, content: Json, nested: &[(&str, Field)]) -> Result<Json, String> {
+        let Json::Object(mut fields) = content else {
+            return Err(format!("expected the fields of {}, found {}", kind, content));
+        };
+        for (name, field) in nested {
+            if let Some(value) = fields.get_mut(*name) {
+                let old = value.take();
+                *value = if old.is_null() {
+                    Json::Null
+                } else {
+                    match field {
+                        Field::Expr => expr(old)?,
+                        Field::Exprs => list(old, expr)?,
+                        Field::Stmt => stmt(old)?,
+                        Field::Stmts => list(old, stmt)?,
+                        Field::Type => type_annotation(old)?,
+                        Field::Types => list(old, type_annotation)?,
+                        Field::Signature => signature(old)?,
+                    }
+                };
+            }
+        }
+        Ok(tagged(kind, fields))
+    }
+
+    fn single(kind: &str, field: &str, value: Json) -> Json {
+        tagged(kind, Map::from_iter([(field.to_string(), value)]))
+    }
+
+    fn stmt(value: Json) -> Result<Json, String> {
+        let (kind, content) = variant(value)?;
+        match kind.as_str() {
+            "Expression" | "Print" => Ok(single(&kind, "expression", expr(content)?)),
+            "Block" => Ok(single(&kind, "statements", list(content, stmt)?)),
+            "Break" | "Continue" => Ok(tagged(&kind, Map::new())),
+            "VarDeclaration" => {
+                struct_variant(&kind, content, &[("type_annotation", Field::Type), ("initializer", Field::Expr)])
+            }
+            "If" => struct_variant(
+                &kind,
+                content,
+                &[("condition", Field::Expr), ("then_branch", Field::Stmt), ("else_branch", Field::Stmt)],
+            ),
+            "While" => struct_variant(&kind, content, &[("condition", Field::Expr), ("body", Field::Stmt)]),
+            "For" => struct_variant(
+                &kind,
+                content,
+                &[
+                    ("initializer", Field::Stmt),
+                    ("condition", Field::Expr),
+                    ("increment", Field::Expr),
+                    ("body", Field::Stmt),
+                ],
+            ),
+            "Function" => struct_variant(&kind, content, &[("signature", Field::Signature), ("body", Field::Stmts)]),
+            "Return" => struct_variant(&kind, content, &[("value", Field::Expr)]),
+            "Class" => struct_variant(&kind, content, &[("methods", Field::Stmts)]),
+            _ => Err(format!("unknown statement {}", kind)),
+        }
+    }
+
+    fn expr(value: Json) -> Result<Json, String> {
+        let (kind, content) = variant(value)?;
+        match kind.as_str() {
+            "Literal" => Ok(single(&kind, "value", literal(content)?)),
+            "Variable" => Ok(single(&kind, "name", content)),
+            "Grouping" => Ok(single(&kind, "expression", expr(content)?)),
+            "This" => Ok(tagged(&kind, Map::new())),
+            "Binary" => struct_variant(&kind, content, &[("left", Field::Expr), ("right", Field::Expr)]),
+            "Unary" => struct_variant(&kind, content, &[("operand", Field::Expr)]),
+            "Assignment" => struct_variant(&kind, content, &[("value", Field::Expr)]),
+            "Call" => struct_variant(&kind, content, &[("callee", Field::Expr), ("arguments", Field::Exprs)]),
+            "Get" => struct_variant(&kind, content, &[("object", Field::Expr)]),
+            "Set" => struct_variant(&kind, content, &[("object", Field::Expr), ("value", Field::Expr)]),
+            "Super" => struct_variant(&kind, content, &[]),
+            "Conditional" => struct_variant(
+                &kind,
+                content,
+                &[("condition", Field::Expr), ("then_branch", Field::Expr), ("else_branch", Field::Expr)],
+            ),
+            "Lambda" => struct_variant(&kind, content, &[("body", Field::Stmts)]),
+            _ => Err(format!("unknown expression {}", kind)),
+        }
+    }
+
+    fn literal(value: Json) -> Result<Json, String> {
+        let (kind, content) = variant(value)?;
+        match kind.as_str() {
+            "Nil" => Ok(Json::Null),
+            "Bool" | "Number" | "String" => Ok(content),
+            _ => Err(format!("unknown value {}", kind)),
+        }
+    }
+
+    fn type_annotation(value: Json) -> Result<Json, String> {
+        let (kind, content) = variant(value)?;
+        match kind.as_str() {
+            "Any" | "Nil" | "Bool" | "Number" | "String" => Ok(tagged(&kind, Map::new())),
+            "Class" => Ok(single(&kind, "name", content)),
+            "Function" => struct_variant(&kind, content, &[("params", Field::Types), ("return_type", Field::Type)]),
+            _ => Err(format!("unknown type {}", kind)),
+        }
+    }
+
+    fn signature(value: Json) -> Result<Json, String> {
+        let Json::Object(mut fields) = value else {
+            return Err(format!("expected a function signature, found {}", value));
+        };
+        if let Some(params) = fields.get_mut("params") {
+            *params = list(params.take(), |param| if param.is_null() { Ok(param) } else { type_annotation(param) })?;
+        }
+        if let Some(return_type) = fields.get_mut("return_type").filter(|return_type| !return_type.is_null()) {
+            *return_type = type_annotation(return_type.take())?;
+        }
+        Ok(Json::Object(fields))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{BinaryOp, Expr, FunctionSignature, TypeAnnotation, UnaryOp, Value};
+
+    fn variable(name: &str) -> Expr {
+        Expr::Variable(name.to_string())
+    }
+
+    fn number(value: f64) -> Expr {
+        Expr::Literal(Value::Number(value))
+    }
+
+    /// A program with every kind of statement, expression, value and type
+    pub(crate) fn sample_program() -> Program {
+        let method = Stmt::Function {
+            name: "area".to_string(),
+            params: vec!["scale".to_string()],
+            signature: FunctionSignature::from_parts(vec![Some(TypeAnnotation::Number)], Some(TypeAnnotation::Any)),
+            body: vec![Stmt::Return {
+                value: Some(Expr::Binary {
+                    left: Box::new(Expr::Get { object: Box::new(Expr::This), name: "side".to_string() }),
+                    operator: BinaryOp::Multiply,
+                    right: Box::new(Expr::Call {
+                        callee: Box::new(Expr::Super { method: "scale".to_string() }),
+                        arguments: vec![variable("scale")],
+                    }),
+                }),
+            }],
+            doc: Some("The area".to_string()),
+        };
+        let field = Stmt::VarDeclaration {
+            name: "side".to_string(),
+            type_annotation: Some(TypeAnnotation::Function {
+                params: vec![TypeAnnotation::Class("Point".to_string()), TypeAnnotation::String],
+                return_type: Box::new(TypeAnnotation::Bool),
+            }),
+            initializer: None,
+            doc: None,
+        };
+        Program::new(vec![
+            Stmt::VarDeclaration {
+                name: "x".to_string(),
+                type_annotation: Some(TypeAnnotation::Nil),
+                initializer: Some(Expr::Literal(Value::Nil)),
+                doc: None,
+            },
+            Stmt::Print(Expr::Grouping(Box::new(Expr::Unary {
+                operator: UnaryOp::Not,
+                operand: Box::new(Expr::Literal(Value::Bool(true))),
+            }))),
+            Stmt::Expression(Expr::Assignment {
+                name: "x".to_string(),
+                value: Box::new(Expr::Conditional {
+                    condition: Box::new(variable("x")),
+                    then_branch: Box::new(Expr::Literal(Value::String("yes".to_string()))),
+                    else_branch: Box::new(Expr::Lambda { params: vec![], body: vec![Stmt::Break, Stmt::Continue] }),
+                }),
+            }),
+            Stmt::Block(vec![Stmt::If {
+                condition: variable("x"),
+                then_branch: Box::new(Stmt::While { condition: number(1.0), body: Box::new(Stmt::Block(vec![])) }),
+                else_branch: Some(Box::new(Stmt::For {
+                    initializer: Some(Box::new(Stmt::Expression(number(0.5)))),
+                    condition: None,
+                    increment: Some(Expr::Set {
+                        object: Box::new(variable("p")),
+                        name: "y".to_string(),
+                        value: Box::new(number(2.0)),
+                    }),
+                    body: Box::new(Stmt::Return { value: None }),
+                })),
+            }]),
+            Stmt::Import { path: "shapes.lox".to_string() },
+            Stmt::Class {
+                name: "Square".to_string(),
+                superclass: Some("Shape".to_string()),
+                fields: vec![field],
+                methods: vec![method],
+                doc: None,
+            },
+        ])
+    }
+
+    /// `sample_program` as serde encoded it before schema versions existed
+    const VERSION_1: &str = r#"{"statements":[
+        {"VarDeclaration":{"name":"x","type_annotation":"Nil","initializer":{"Literal":"Nil"}}},
+        {"Print":{"Grouping":{"Unary":{"operator":"Not","operand":{"Literal":{"Bool":true}}}}}},
+        {"Expression":{"Assignment":{"name":"x","value":{"Conditional":{
+            "condition":{"Variable":"x"},
+            "then_branch":{"Literal":{"String":"yes"}},
+            "else_branch":{"Lambda":{"params":[],"body":["Break","Continue"]}}}}}}},
+        {"Block":[{"If":{
+            "condition":{"Variable":"x"},
+            "then_branch":{"While":{"condition":{"Literal":{"Number":1.0}},"body":{"Block":[]}}},
+            "else_branch":{"For":{
+                "initializer":{"Expression":{"Literal":{"Number":0.5}}},
+                "condition":null,
+                "increment":{"Set":{"object":{"Variable":"p"},"name":"y","value":{"Literal":{"Number":2.0}}}},
+                "body":{"Return":{"value":null}}}}}}]},
+        {"Class":{"name":"Square","superclass":"Shape","methods":[
+            {"VarDeclaration":{"name":"side","type_annotation":{"Function":{
+                "params":[{"Class":"Point"},"String"],"return_type":"Bool"}},"initializer":null}},
+            {"Function":{"name":"area","params":["scale"],
+                "signature":{"params":["Number"],"return_type":"Any"},
+                "body":[{"Return":{"value":{"Binary":{
+                    "left":{"Get":{"object":"This","name":"side"}},
+                    "operator":"Multiply",
+                    "right":{"Call":{"callee":{"Super":{"method":"scale"}},"arguments":[{"Variable":"scale"}]}}}}}}],
+                "doc":"The area"}}]}}
+    ]}"#;
+
+    #[test]
+    fn test_json_round_trip() {
+        let program = sample_program();
+        assert_eq!(from_json(&to_json(&program)).unwrap(), program);
+        assert_eq!(from_json(&to_json_pretty(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let program = sample_program();
+        let bytes = to_cbor(&program);
+        assert!(bytes.len() < to_json(&program).len());
+        assert_eq!(from_cbor(&bytes).unwrap(), program);
+
+        let infinite = Program::new(vec![Stmt::Print(number(f64::INFINITY))]);
+        assert_eq!(from_cbor(&to_cbor(&infinite)).unwrap(), infinite);
+    }
+
+    #[test]
+    fn test_json_uses_kind_tags() {
+        let program = Program::new(vec![Stmt::Print(Expr::Binary {
+            left: Box::new(number(1.0)),
+            operator: BinaryOp::Add,
+            right: Box::new(variable("x")),
+        })]);
+        let expected = json!({
+            "schema_version": 4,
+            "statements": [
+                {"kind": "Print", "expression": {
+                    "kind": "Binary",
+                    "left": {"kind": "Literal", "value": 1.0},
+                    "operator": "Add",
+                    "right": {"kind": "Variable", "name": "x"}
+                }}
+            ]
+        });
+        assert_eq!(serde_json::to_value(&program).unwrap(), expected);
+    }
+
+    /// `sample_program` without the statements added after version 1
+    fn version_1_program() -> Program {
+        let mut program = sample_program();
+        program.statements.retain(|stmt| !matches!(stmt, Stmt::Import { .. }));
+        program
+    }
+
+    #[test]
+    fn test_migrates_version_1() {
+        assert_eq!(from_json(VERSION_1).unwrap(), version_1_program());
+
+        let document: Json = serde_json::from_str(VERSION_1).unwrap();
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&document, &mut bytes).unwrap();
+        assert_eq!(from_cbor(&bytes).unwrap(), version_1_program());
+
+        let migrated = migrate(document).unwrap();
+        assert_eq!(migrated["schema_version"], SCHEMA_VERSION);
+        assert_eq!(migrated, serde_json::to_value(version_1_program()).unwrap());
+    }
+
+    /// `program` in the format of schema version 3, which listed a class's
+    /// fields ahead of its methods
+    fn version_3_document(program: &Program) -> Json {
+        fn merge_fields(value: &mut Json) {
+            match value {
+                Json::Array(items) => items.iter_mut().for_each(merge_fields),
+                Json::Object(object) => {
+                    if let Some(Json::Array(mut members)) = object.remove("fields")
+                        && let Some(Json::Array(methods)) = object.get_mut("methods")
+                    {
+                        members.append(methods);
+                        *methods = members;
+                    }
+                    object.values_mut().for_each(merge_fields);
+                }
+                _ => {}
+            }
+        }
+        let mut document = serde_json::to_value(program).unwrap();
+        merge_fields(&mut document);
+        document["schema_version"] = json!(3);
+        document
+    }
+
+    #[test]
+    fn test_migrates_version_2() {
+        let mut document = version_3_document(&version_1_program());
+        document["schema_version"] = json!(2);
+        assert_eq!(from_json(&document.to_string()).unwrap(), version_1_program());
+    }
+
+    #[test]
+    fn test_migrates_version_3() {
+        let document = version_3_document(&sample_program());
+        assert_eq!(document["statements"][5]["methods"][0]["kind"], "VarDeclaration");
+        assert_eq!(from_json(&document.to_string()).unwrap(), sample_program());
+    }
+
+    #[test]
+    fn test_version_errors() {
+        let newer = from_json(r#"{"schema_version": 5, "statements": []}"#).unwrap_err();
+        assert!(matches!(newer, SerializationError::UnsupportedVersion(5)));
+
+        let malformed = from_json(r#"{"statements": [{"Print": {"Unknown": 1}}]}"#).unwrap_err();
+        assert_eq!(malformed.to_string(), "Cannot migrate from schema version 1: unknown expression Unknown");
+
+        // Plain serde reads only the current version, and says where to go instead
+        let error = serde_json::from_str::<Program>(VERSION_1).unwrap_err();
+        assert!(error.to_string().contains("lox_ast::serialization"), "{error}");
+    }
+}
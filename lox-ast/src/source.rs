@@ -0,0 +1,325 @@
+//! Source files and the conversion of byte offsets to lines and columns
+//!
+//! Spans are byte offsets, which is what the parsers work with but not what
+//! people or editors want to see. A [`SourceMap`] holds the text of every file
+//! of a program under a [`FileId`], along with a [`LineIndex`] of where each
+//! line starts, so that looking up a location is a binary search instead of a
+//! scan from the start of the file.
+//!
+//! Lines are separated by `\n`, so a `\r` before it stays part of the line.
+//! Two kinds of column are available: [`Location`] counts characters from 1,
+//! the way error messages report them, and [`Utf16Position`] counts UTF-16
+//! code units from 0 on a 0-based line, which is what the Language Server
+//! Protocol uses.
+//!
+//! ```
+//! use lox_ast::source::{Location, SourceMap, Utf16Position};
+//! use lox_ast::Span;
+//!
+//! let mut sources = SourceMap::new();
+//! let id = sources.add("main.lox", "var π = 3.14;\nprint π;");
+//! let file = sources.file(id);
+//! assert_eq!(file.location(21), Location { line: 2, column: 7 });
+//! assert_eq!(file.utf16_position(21), Utf16Position { line: 1, character: 6 });
+//! assert_eq!(file.slice(Span::new(21, 23)), Some("π"));
+//! ```
+
+use std::fmt;
+
+use crate::span::Span;
+
+/// Identifies a file of a `SourceMap`; ids are handed out in the order files are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// The position of the file in its `SourceMap`
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A 1-based line and column, with the column counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A 0-based line and a column in UTF-16 code units, as the Language Server
+/// Protocol counts them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Utf16Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+/// The byte offset at which each line of a text starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    /// Index the lines of `source`
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { line_starts, len: source.len() }
+    }
+
+    /// The number of lines; an empty text, or one ending in `\n`, has an empty last line
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The 0-based line containing `offset`; offsets past the end are on the last line
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// The span of a 0-based line, without its `\n`, or `None` past the last line
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_starts.get(line + 1).map_or(self.len, |next| next - 1);
+        Some(Span::new(start, end))
+    }
+
+    /// The location of `offset` in `source`, which must be the indexed text
+    pub fn location(&self, source: &str, offset: usize) -> Location {
+        let offset = floor_char_boundary(source, offset);
+        let line = self.line(offset);
+        let column = source[self.line_starts[line]..offset].chars().count() + 1;
+        Location { line: line + 1, column }
+    }
+
+    /// The LSP position of `offset` in `source`, which must be the indexed text
+    pub fn utf16_position(&self, source: &str, offset: usize) -> Utf16Position {
+        let offset = floor_char_boundary(source, offset);
+        let line = self.line(offset);
+        let character = source[self.line_starts[line]..offset].encode_utf16().count();
+        Utf16Position { line, character }
+    }
+
+    /// The byte offset of an LSP position in `source`, which must be the indexed text
+    ///
+    /// Like an LSP server, this clamps a character past the end of its line to
+    /// the end of the line, and a line past the end to the end of the text. A
+    /// position inside a surrogate pair is moved to the start of the character.
+    pub fn offset_of_utf16(&self, source: &str, position: Utf16Position) -> usize {
+        let Some(line) = self.line_span(position.line) else {
+            return self.len;
+        };
+        let mut units = 0;
+        for (i, c) in source[line.start..line.end].char_indices() {
+            units += c.len_utf16();
+            if units > position.character {
+                return line.start + i;
+            }
+        }
+        line.end
+    }
+}
+
+/// A named source text and its line index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    lines: LineIndex,
+}
+
+impl SourceFile {
+    /// Index `source`, to be reported as coming from `name`
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let lines = LineIndex::new(&source);
+        SourceFile { name: name.into(), source, lines }
+    }
+
+    /// The name the file was added under, usually its path
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// The 1-based line and character column of a byte offset
+    pub fn location(&self, offset: usize) -> Location {
+        self.lines.location(&self.source, offset)
+    }
+
+    /// The LSP position of a byte offset
+    pub fn utf16_position(&self, offset: usize) -> Utf16Position {
+        self.lines.utf16_position(&self.source, offset)
+    }
+
+    /// The byte offset of an LSP position, clamped to the text
+    pub fn offset_of_utf16(&self, position: Utf16Position) -> usize {
+        self.lines.offset_of_utf16(&self.source, position)
+    }
+
+    /// The text covered by `span`, or `None` if it is out of bounds or splits a character
+    pub fn slice(&self, span: Span) -> Option<&str> {
+        self.source.get(span.start..span.end)
+    }
+
+    /// The text of a 1-based line, without its line ending
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let span = self.lines.line_span(line.checked_sub(1)?)?;
+        let text = &self.source[span.start..span.end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+}
+
+/// The source files of a program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file and return its id
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(u32::try_from(self.files.len()).expect("too many source files"));
+        self.files.push(SourceFile::new(name, source));
+        id
+    }
+
+    /// Look up a file; panics if `id` came from another `SourceMap`
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.index()]
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    /// Find a file by the name it was added under
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.iter().find(|(_, file)| file.name == name).map(|(id, _)| id)
+    }
+
+    /// Every file with its id, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files.iter().enumerate().map(|(i, file)| (FileId(i as u32), file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The text of `span` in file `id`
+    pub fn slice(&self, id: FileId, span: Span) -> Option<&str> {
+        self.get(id)?.slice(span)
+    }
+
+    /// Describe where `offset` is in file `id`, e.g. `main.lox:2:7`
+    pub fn describe(&self, id: FileId, offset: usize) -> String {
+        let file = self.file(id);
+        let location = file.location(offset);
+        format!("{}:{}:{}", file.name, location.line, location.column)
+    }
+}
+
+/// The closest character boundary at or before `offset`, clamped to the text
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        let source = "var a = \"one\ntwo\";\r\nprint a;\n";
+        let lines = LineIndex::new(source);
+        assert_eq!(lines.line_count(), 4);
+        assert_eq!(lines.line(0), 0);
+        assert_eq!(lines.line(12), 0);
+        assert_eq!(lines.line(13), 1);
+        assert_eq!(lines.line(source.len()), 3);
+        assert_eq!(lines.line(source.len() + 10), 3);
+        assert_eq!(lines.line_span(1), Some(Span::new(13, 19)));
+        assert_eq!(lines.line_span(3), Some(Span::new(29, 29)));
+        assert_eq!(lines.line_span(4), None);
+        assert_eq!(LineIndex::new("").line_count(), 1);
+    }
+
+    #[test]
+    fn test_locations_match_a_scan() {
+        let source = "var π = \"ü\";\n\n  print π + \"𝄞\"; // done\nx";
+        let file = SourceFile::new("test.lox", source);
+        for offset in 0..=source.len() + 1 {
+            let before = &source[..floor_char_boundary(source, offset)];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            let scanned = Location {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+            };
+            assert_eq!(file.location(offset), scanned, "offset {}", offset);
+        }
+        assert_eq!(file.location(source.find("print").unwrap()), Location { line: 3, column: 3 });
+        assert_eq!(file.location(source.len()).to_string(), "line 4, column 2");
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let source = "print \"𝄞π\";\nx";
+        let file = SourceFile::new("test.lox", source);
+        let pi = source.find('π').unwrap();
+        // The clef is one character, four bytes and two UTF-16 code units
+        assert_eq!(file.location(pi), Location { line: 1, column: 9 });
+        assert_eq!(file.utf16_position(pi), Utf16Position { line: 0, character: 9 });
+        assert_eq!(file.offset_of_utf16(Utf16Position { line: 0, character: 9 }), pi);
+
+        for (offset, _) in source.char_indices() {
+            assert_eq!(file.offset_of_utf16(file.utf16_position(offset)), offset);
+        }
+        // Inside the surrogate pair, past the end of a line and past the last line
+        assert_eq!(file.offset_of_utf16(Utf16Position { line: 0, character: 8 }), pi - 4);
+        assert_eq!(file.offset_of_utf16(Utf16Position { line: 0, character: 99 }), source.find('\n').unwrap());
+        assert_eq!(file.offset_of_utf16(Utf16Position { line: 5, character: 0 }), source.len());
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut sources = SourceMap::new();
+        let main = sources.add("main.lox", "import \"lib.lox\";\r\nprint greet();");
+        let lib = sources.add("lib.lox", "fun greet() { return \"hi\"; }");
+        assert_eq!((main.index(), lib.index()), (0, 1));
+        assert_eq!(sources.find("lib.lox"), Some(lib));
+        assert_eq!(sources.find("other.lox"), None);
+        assert_eq!(sources.slice(lib, Span::new(4, 9)), Some("greet"));
+        assert_eq!(sources.slice(lib, Span::new(4, 99)), None);
+        assert_eq!(sources.describe(main, 25), "main.lox:2:7");
+        assert_eq!(sources.file(main).line_text(1), Some("import \"lib.lox\";"));
+        assert_eq!(sources.file(main).line_text(3), None);
+        assert_eq!(sources.iter().map(|(_, file)| file.name()).collect::<Vec<_>>(), ["main.lox", "lib.lox"]);
+    }
+}
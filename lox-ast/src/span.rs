@@ -0,0 +1,103 @@
+//! Source locations
+
+use std::fmt;
+use std::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// A half-open byte range `start..end` into the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new span covering `start..end`
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Length of the span in bytes
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Check if the span covers no bytes
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Shift the span by `by` bytes, e.g. to make a token-relative span absolute
+    pub fn offset(self, by: usize) -> Self {
+        Self::new(self.start + by, self.end + by)
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self::new(range.start, range.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A change to source text: the bytes in `range` are replaced by `replacement`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Span,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// Create an edit replacing `range` with `replacement`
+    pub fn new(range: impl Into<Span>, replacement: impl Into<String>) -> Self {
+        Self { range: range.into(), replacement: replacement.into() }
+    }
+
+    /// Apply the edit to `source`
+    ///
+    /// Panics if the range is out of bounds or splits a character.
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.replacement.len());
+        edited.push_str(&source[..self.range.start]);
+        edited.push_str(&self.replacement);
+        edited.push_str(&source[self.range.end..]);
+        edited
+    }
+
+    /// Move an offset after the edited range to where its text is after the edit
+    pub fn shift(&self, offset: usize) -> usize {
+        offset - self.range.len() + self.replacement.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_offset() {
+        let span = Span::new(0, 1).offset(10);
+        assert_eq!(span, Span::new(10, 11));
+        assert_eq!(span.len(), 1);
+        assert!(!span.is_empty());
+    }
+
+    #[test]
+    fn test_text_edit() {
+        let edit = TextEdit::new(6..7, "x + 1");
+        assert_eq!(edit.apply("print a;"), "print x + 1;");
+        assert_eq!(edit.shift(7), 11);
+        assert_eq!(TextEdit::new(0..6, "").apply("print a;"), "a;");
+    }
+}
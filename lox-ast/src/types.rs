@@ -0,0 +1,115 @@
+//! Type annotations for the gradually typed dialect
+//!
+//! With `LanguageOptions::type_annotations`, variables, parameters, return
+//! values and class fields may be annotated:
+//!
+//! ```lox
+//! var x: Number = 1;
+//! fun add(a: Number, b: Number): Number { return a + b; }
+//! class Point { x: Number; y: Number; }
+//! ```
+//!
+//! Annotations are only read by static tools such as a type checker; they do
+//! not change how a program runs.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// A type written in the source
+///
+/// Serialized as an object whose `"kind"` is the variant name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum TypeAnnotation {
+    /// `Any`, which opts out of checking
+    Any,
+    /// `Nil`
+    Nil,
+    /// `Bool`
+    Bool,
+    /// `Number`
+    Number,
+    /// `String`
+    String,
+    /// An instance of the named class
+    Class(#[serde(with = "crate::serialization::name_field")] String),
+    /// `fun(Number, String): Bool`
+    Function {
+        params: Vec<TypeAnnotation>,
+        return_type: Box<TypeAnnotation>,
+    },
+}
+
+impl TypeAnnotation {
+    /// The type a bare name stands for: a built-in type, or else a class
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Any" => TypeAnnotation::Any,
+            "Nil" => TypeAnnotation::Nil,
+            "Bool" => TypeAnnotation::Bool,
+            "Number" => TypeAnnotation::Number,
+            "String" => TypeAnnotation::String,
+            _ => TypeAnnotation::Class(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeAnnotation::Any => write!(f, "Any"),
+            TypeAnnotation::Nil => write!(f, "Nil"),
+            TypeAnnotation::Bool => write!(f, "Bool"),
+            TypeAnnotation::Number => write!(f, "Number"),
+            TypeAnnotation::String => write!(f, "String"),
+            TypeAnnotation::Class(name) => write!(f, "{}", name),
+            TypeAnnotation::Function { params, return_type } => {
+                write!(f, "fun(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, "): {}", return_type)
+            }
+        }
+    }
+}
+
+/// The annotations on a function's parameters and return value
+///
+/// `params` has one entry per parameter, `None` where a parameter is unannotated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionSignature {
+    pub params: Vec<Option<TypeAnnotation>>,
+    pub return_type: Option<TypeAnnotation>,
+}
+
+impl FunctionSignature {
+    /// Build a signature, or `None` when nothing is annotated
+    pub fn from_parts(params: Vec<Option<TypeAnnotation>>, return_type: Option<TypeAnnotation>) -> Option<Self> {
+        let annotated = return_type.is_some() || params.iter().any(Option::is_some);
+        annotated.then_some(FunctionSignature { params, return_type })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let annotation = TypeAnnotation::Function {
+            params: vec![TypeAnnotation::Number, TypeAnnotation::from_name("Point")],
+            return_type: Box::new(TypeAnnotation::from_name("Bool")),
+        };
+        assert_eq!(annotation.to_string(), "fun(Number, Point): Bool");
+    }
+
+    #[test]
+    fn test_signature_only_when_annotated() {
+        assert_eq!(FunctionSignature::from_parts(vec![None, None], None), None);
+        assert!(FunctionSignature::from_parts(vec![None, Some(TypeAnnotation::Number)], None).is_some());
+    }
+}
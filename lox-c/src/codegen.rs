@@ -0,0 +1,660 @@
+//! Lowering of statements and expressions to C
+//!
+//! Every Lox function becomes a C function with an array of slots that holds
+//! its local variables and the intermediate results of expressions, so the
+//! collector sees every live value. Each expression is written as a sequence
+//! of C statements that leave its value in a given slot. Local variables are
+//! cells (`LoxCell`) so closures can share them: a closure keeps pointers to
+//! the cells of the variables it uses from enclosing functions, its upvalues.
+//! Variables declared at the top level of the program are globals, looked up
+//! by index, and may be used before they are declared as in Lox.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+
+/// Where a variable lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Place {
+    /// A cell in a slot of the current function
+    Local(usize),
+    /// A cell captured by the current closure
+    Upvalue(usize),
+    Global(usize),
+}
+
+/// Where a closure's upvalue comes from in the function that creates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    Local(usize),
+    Upvalue(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// A function being compiled
+struct FunctionState {
+    kind: FunctionKind,
+    body: String,
+    indent: usize,
+    /// Local variables of each enclosing block, innermost last, with their slots
+    scopes: Vec<Vec<(String, usize)>>,
+    next_slot: usize,
+    slot_count: usize,
+    upvalues: Vec<(String, Capture)>,
+    /// For each enclosing loop, the label that `continue` jumps to, if it
+    /// cannot use C's `continue`
+    loops: Vec<Option<String>>,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind) -> Self {
+        FunctionState {
+            kind,
+            body: String::new(),
+            indent: 1,
+            scopes: Vec::new(),
+            next_slot: 0,
+            slot_count: 1,
+            upvalues: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().flat_map(|scope| scope.iter().rev()).find(|(local, _)| local == name).map(|(_, slot)| *slot)
+    }
+
+    fn upvalue(&mut self, name: &str, capture: Capture) -> usize {
+        if let Some(index) = self.upvalues.iter().position(|(_, existing)| *existing == capture) {
+            return index;
+        }
+        self.upvalues.push((name.to_string(), capture));
+        self.upvalues.len() - 1
+    }
+}
+
+pub(crate) struct Compiler {
+    /// Finished C functions, each before any function that creates it
+    functions: Vec<String>,
+    function_count: usize,
+    label_count: usize,
+    globals: Vec<String>,
+    global_indices: HashMap<String, usize>,
+    stack: Vec<FunctionState>,
+}
+
+impl Compiler {
+    pub(crate) fn new() -> Self {
+        let mut compiler = Compiler {
+            functions: Vec::new(),
+            function_count: 0,
+            label_count: 0,
+            globals: Vec::new(),
+            global_indices: HashMap::new(),
+            stack: Vec::new(),
+        };
+        compiler.global("clock");
+        compiler
+    }
+
+    /// Compile `program`, returning the C code that follows the runtime
+    pub(crate) fn program(mut self, program: &Program) -> String {
+        self.stack.push(FunctionState::new(FunctionKind::Script));
+        for statement in &program.statements {
+            self.stmt(statement);
+        }
+        let script = self.stack.pop().expect("the script is on the stack");
+
+        let mut out = String::new();
+        for function in &self.functions {
+            out.push_str(function);
+            out.push('\n');
+        }
+        let names: Vec<String> = self.globals.iter().map(|name| c_string(name)).collect();
+        out.push_str("int main(void) {\n");
+        let _ = writeln!(out, "    static Value globals[{}];", self.globals.len());
+        let _ = writeln!(out, "    static bool defined[{}];", self.globals.len());
+        let _ = writeln!(out, "    static const char *names[] = {{{}}};", names.join(", "));
+        let _ = writeln!(out, "    lox_init_globals(globals, defined, names, {});", self.globals.len());
+        out.push_str("    lox_define_global(0, lox_native(lox_clock, 0));\n");
+        let _ = writeln!(out, "    Value slots[{}];", script.slot_count);
+        out.push_str("    LoxFrame frame;\n");
+        let _ = writeln!(out, "    lox_enter(&frame, slots, {});", script.slot_count);
+        out.push_str(&script.body);
+        out.push_str("    lox_leave(&frame);\n");
+        out.push_str("    return 0;\n");
+        out.push_str("}\n");
+        out
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.stack.last_mut().expect("code is written inside a function")
+    }
+
+    fn line(&mut self, text: &str) {
+        let function = self.current();
+        for _ in 0..function.indent {
+            function.body.push_str("    ");
+        }
+        function.body.push_str(text);
+        function.body.push('\n');
+    }
+
+    fn open(&mut self, text: &str) {
+        self.line(text);
+        self.current().indent += 1;
+    }
+
+    fn close(&mut self, text: &str) {
+        self.current().indent -= 1;
+        self.line(text);
+    }
+
+    /// Reserve a slot for a temporary value or a local variable
+    fn slot(&mut self) -> usize {
+        let function = self.current();
+        let slot = function.next_slot;
+        function.next_slot += 1;
+        function.slot_count = function.slot_count.max(function.next_slot);
+        slot
+    }
+
+    /// Run `f` with the slots it reserves released afterwards
+    fn with_temps<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let mark = self.current().next_slot;
+        let result = f(self);
+        self.current().next_slot = mark;
+        result
+    }
+
+    fn label(&mut self, name: &str) -> String {
+        self.label_count += 1;
+        format!("{name}_{}", self.label_count)
+    }
+
+    fn global(&mut self, name: &str) -> usize {
+        if let Some(index) = self.global_indices.get(name) {
+            return *index;
+        }
+        self.globals.push(name.to_string());
+        self.global_indices.insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self) {
+        let function = self.current();
+        let scope = function.scopes.pop().expect("scopes are balanced");
+        if let Some((_, first)) = scope.first() {
+            function.next_slot = *first;
+        }
+    }
+
+    /// Check whether a declaration here makes a global variable
+    fn at_top_level(&self) -> bool {
+        let function = self.stack.last().expect("code is written inside a function");
+        function.kind == FunctionKind::Script && function.scopes.is_empty()
+    }
+
+    /// Declare a local variable in a new slot, initialized to `value`
+    fn declare_local(&mut self, name: &str, value: &str) -> usize {
+        let slot = self.slot();
+        self.line(&format!("slots[{slot}] = lox_cell({value});"));
+        self.current().scopes.last_mut().expect("locals are declared in a scope").push((name.to_string(), slot));
+        slot
+    }
+
+    fn resolve(&mut self, name: &str) -> Place {
+        let depth = self.stack.len() - 1;
+        match self.resolve_in(depth, name) {
+            Some(Capture::Local(slot)) => Place::Local(slot),
+            Some(Capture::Upvalue(index)) => Place::Upvalue(index),
+            None => Place::Global(self.global(name)),
+        }
+    }
+
+    /// Find `name` in the function at `depth`, capturing it from enclosing
+    /// functions as needed
+    fn resolve_in(&mut self, depth: usize, name: &str) -> Option<Capture> {
+        if let Some(slot) = self.stack[depth].local(name) {
+            return Some(Capture::Local(slot));
+        }
+        if depth == 0 {
+            return None;
+        }
+        let capture = self.resolve_in(depth - 1, name)?;
+        Some(Capture::Upvalue(self.stack[depth].upvalue(name, capture)))
+    }
+
+    fn read(&mut self, name: &str) -> String {
+        match self.resolve(name) {
+            Place::Local(slot) => format!("LOCAL(slots[{slot}])"),
+            Place::Upvalue(index) => format!("closure->upvalues[{index}]->value"),
+            Place::Global(index) => format!("lox_get_global({index})"),
+        }
+    }
+
+    fn write(&mut self, name: &str, value: &str) {
+        let line = match self.resolve(name) {
+            Place::Local(slot) => format!("LOCAL(slots[{slot}]) = {value};"),
+            Place::Upvalue(index) => format!("closure->upvalues[{index}]->value = {value};"),
+            Place::Global(index) => format!("lox_set_global({index}, {value});"),
+        };
+        self.line(&line);
+    }
+
+    /// Declare a variable, function or class, giving it the value in `slot`
+    /// once `define` has written it
+    fn declaration(&mut self, name: &str, define: impl FnOnce(&mut Self, usize)) {
+        if self.at_top_level() {
+            let index = self.global(name);
+            self.with_temps(|compiler| {
+                let slot = compiler.slot();
+                define(compiler, slot);
+                compiler.line(&format!("lox_define_global({index}, slots[{slot}]);"));
+            });
+        } else {
+            // Declared before the value is made, so functions and classes
+            // can refer to themselves
+            let local = self.declare_local(name, "NIL_VAL");
+            self.with_temps(|compiler| {
+                let slot = compiler.slot();
+                define(compiler, slot);
+                compiler.line(&format!("LOCAL(slots[{local}]) = slots[{slot}];"));
+            });
+        }
+    }
+
+    fn stmt(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression(expr) => self.with_temps(|compiler| {
+                let slot = compiler.slot();
+                compiler.expr(expr, slot);
+            }),
+            Stmt::Print(expr) => self.with_temps(|compiler| {
+                let slot = compiler.slot();
+                compiler.expr(expr, slot);
+                compiler.line(&format!("lox_print(slots[{slot}]);"));
+            }),
+            Stmt::VarDeclaration { name, initializer, .. } => {
+                if self.at_top_level() {
+                    self.declaration(name, |compiler, slot| compiler.initializer(initializer.as_ref(), slot));
+                } else {
+                    // Unlike a function, the variable is not in scope in its
+                    // own initializer
+                    let value = self.with_temps(|compiler| {
+                        let slot = compiler.slot();
+                        compiler.initializer(initializer.as_ref(), slot);
+                        slot
+                    });
+                    self.declare_local(name, &format!("slots[{value}]"));
+                }
+            }
+            Stmt::Block(statements) => {
+                self.open("{");
+                self.begin_scope();
+                for statement in statements {
+                    self.stmt(statement);
+                }
+                self.end_scope();
+                self.close("}");
+            }
+            Stmt::If { condition, then_branch, else_branch } => self.with_temps(|compiler| {
+                let slot = compiler.slot();
+                compiler.expr(condition, slot);
+                compiler.open(&format!("if (lox_truthy(slots[{slot}])) {{"));
+                compiler.scoped(then_branch);
+                if let Some(else_branch) = else_branch {
+                    compiler.close("} else {");
+                    compiler.current().indent += 1;
+                    compiler.scoped(else_branch);
+                }
+                compiler.close("}");
+            }),
+            Stmt::While { condition, body } => {
+                self.open("for (;;) {");
+                self.exit_unless(condition);
+                self.current().loops.push(None);
+                self.scoped(body);
+                self.current().loops.pop();
+                self.close("}");
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                self.open("{");
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer);
+                }
+                self.open("for (;;) {");
+                if let Some(condition) = condition {
+                    self.exit_unless(condition);
+                }
+                let label = self.label("continue");
+                self.current().loops.push(Some(label.clone()));
+                self.scoped(body);
+                self.current().loops.pop();
+                self.line(&format!("{label}:;"));
+                if let Some(increment) = increment {
+                    self.stmt(&Stmt::Expression(increment.clone()));
+                }
+                self.close("}");
+                self.end_scope();
+                self.close("}");
+            }
+            Stmt::Function { name, params, body, .. } => {
+                self.declaration(name, |compiler, slot| compiler.closure(name, params, body, FunctionKind::Function, slot));
+            }
+            Stmt::Return { value } => self.with_temps(|compiler| {
+                let result = match (value, compiler.current().kind) {
+                    (Some(value), _) => {
+                        let slot = compiler.slot();
+                        compiler.expr(value, slot);
+                        format!("slots[{slot}]")
+                    }
+                    (None, FunctionKind::Initializer) => compiler.read("this"),
+                    (None, _) => "NIL_VAL".to_string(),
+                };
+                compiler.line("lox_leave(&frame);");
+                compiler.line(&format!("return {result};"));
+            }),
+            Stmt::Break => self.line("break;"),
+            Stmt::Continue => {
+                let line = match self.current().loops.last() {
+                    Some(Some(label)) => format!("goto {label};"),
+                    _ => "continue;".to_string(),
+                };
+                self.line(&line);
+            }
+            // Imported files are spliced in by linking the program before it is compiled
+            Stmt::Import { .. } => {}
+            Stmt::Class { name, superclass, methods, .. } => self.class(name, superclass.as_deref(), methods),
+        }
+    }
+
+    fn initializer(&mut self, initializer: Option<&Expr>, slot: usize) {
+        match initializer {
+            Some(initializer) => self.expr(initializer, slot),
+            None => self.line(&format!("slots[{slot}] = NIL_VAL;")),
+        }
+    }
+
+    /// Write a statement in a block of its own, as the body of a branch or loop
+    fn scoped(&mut self, statement: &Stmt) {
+        self.begin_scope();
+        match statement {
+            Stmt::Block(statements) => {
+                for statement in statements {
+                    self.stmt(statement);
+                }
+            }
+            statement => self.stmt(statement),
+        }
+        self.end_scope();
+    }
+
+    /// Leave the enclosing C loop when `condition` is falsey
+    fn exit_unless(&mut self, condition: &Expr) {
+        self.with_temps(|compiler| {
+            let slot = compiler.slot();
+            compiler.expr(condition, slot);
+            compiler.line(&format!("if (!lox_truthy(slots[{slot}])) break;"));
+        });
+    }
+
+    fn class(&mut self, name: &str, superclass: Option<&str>, methods: &[Stmt]) {
+        self.declaration(name, |compiler, slot| {
+            match superclass {
+                Some(superclass) => {
+                    let value = compiler.read(superclass);
+                    compiler.line(&format!("slots[{slot}] = lox_class({}, {value}, true);", c_string(name)));
+                }
+                None => compiler.line(&format!("slots[{slot}] = lox_class({}, NIL_VAL, false);", c_string(name))),
+            }
+            // `super` is a variable in a scope around the methods, captured
+            // by the methods that use it
+            compiler.open("{");
+            compiler.begin_scope();
+            if superclass.is_some() {
+                compiler.declare_local("super", &format!("OBJ_VAL(AS_CLASS(slots[{slot}])->superclass)"));
+            }
+            // Fields only give types in the typed dialect and have no
+            // runtime effect
+            for method in methods {
+                if let Stmt::Function { name: method_name, params, body, .. } = method {
+                    let kind = if method_name == "init" { FunctionKind::Initializer } else { FunctionKind::Method };
+                    compiler.with_temps(|compiler| {
+                        let closure = compiler.slot();
+                        compiler.closure(method_name, params, body, kind, closure);
+                        compiler.line(&format!("lox_add_method(slots[{slot}], {}, slots[{closure}]);", c_string(method_name)));
+                    });
+                }
+            }
+            compiler.end_scope();
+            compiler.close("}");
+        });
+    }
+
+    /// Compile a function and write the creation of its closure into `slot`
+    fn closure(&mut self, name: &str, params: &[String], body: &[Stmt], kind: FunctionKind, slot: usize) {
+        self.function_count += 1;
+        let c_name = format!("lox_fn_{}_{name}", self.function_count);
+
+        self.stack.push(FunctionState::new(kind));
+        self.begin_scope();
+        if kind != FunctionKind::Function {
+            self.declare_local("this", "receiver");
+        }
+        for (index, param) in params.iter().enumerate() {
+            self.declare_local(param, &format!("args[{index}]"));
+        }
+        for statement in body {
+            self.stmt(statement);
+        }
+        if !matches!(body.last(), Some(Stmt::Return { .. })) {
+            self.stmt(&Stmt::Return { value: None });
+        }
+        self.end_scope();
+        let function = self.stack.pop().expect("the function was pushed above");
+
+        let mut text = String::new();
+        let _ = writeln!(text, "static Value {c_name}(LoxClosure *closure, Value receiver, Value *args) {{");
+        text.push_str("    (void)closure;\n    (void)receiver;\n    (void)args;\n");
+        let _ = writeln!(text, "    Value slots[{}];", function.slot_count);
+        text.push_str("    LoxFrame frame;\n");
+        let _ = writeln!(text, "    lox_enter(&frame, slots, {});", function.slot_count);
+        text.push_str(&function.body);
+        text.push_str("}\n");
+        self.functions.push(text);
+
+        self.line(&format!(
+            "slots[{slot}] = lox_closure({c_name}, {}, {}, {});",
+            c_string(name),
+            params.len(),
+            function.upvalues.len()
+        ));
+        for (index, (_, capture)) in function.upvalues.iter().enumerate() {
+            let cell = match capture {
+                Capture::Local(local) => format!("AS_CELL(slots[{local}])"),
+                Capture::Upvalue(upvalue) => format!("closure->upvalues[{upvalue}]"),
+            };
+            self.line(&format!("AS_CLOSURE(slots[{slot}])->upvalues[{index}] = {cell};"));
+        }
+    }
+
+    /// Write the evaluation of `expr` into `slot`
+    fn expr(&mut self, expr: &Expr, slot: usize) {
+        match expr {
+            Expr::Literal(value) => {
+                let value = match value {
+                    Value::Nil => "NIL_VAL".to_string(),
+                    Value::Bool(value) => format!("BOOL_VAL({value})"),
+                    Value::Number(value) => format!("NUMBER_VAL({})", c_number(*value)),
+                    Value::String(value) => format!("lox_string({}, {})", c_string(value), value.len()),
+                };
+                self.line(&format!("slots[{slot}] = {value};"));
+            }
+            Expr::Variable(name) => {
+                let value = self.read(name);
+                self.line(&format!("slots[{slot}] = {value};"));
+            }
+            Expr::Grouping(expr) => self.expr(expr, slot),
+            Expr::Unary { operator, operand } => {
+                self.expr(operand, slot);
+                let function = match operator {
+                    UnaryOp::Minus => "lox_negate",
+                    UnaryOp::Not => "lox_not",
+                };
+                self.line(&format!("slots[{slot}] = {function}(slots[{slot}]);"));
+            }
+            Expr::Binary { left, operator, right } => self.binary(left, operator, right, slot),
+            Expr::Assignment { name, value } => {
+                self.expr(value, slot);
+                self.write(name, &format!("slots[{slot}]"));
+            }
+            Expr::Call { callee, arguments } => {
+                self.expr(callee, slot);
+                self.with_temps(|compiler| {
+                    let first = compiler.current().next_slot;
+                    for argument in arguments {
+                        let argument_slot = compiler.slot();
+                        compiler.expr(argument, argument_slot);
+                    }
+                    let args = if arguments.is_empty() { "NULL".to_string() } else { format!("&slots[{first}]") };
+                    compiler.line(&format!("slots[{slot}] = lox_call(slots[{slot}], {}, {args});", arguments.len()));
+                });
+            }
+            Expr::Get { object, name } => {
+                self.expr(object, slot);
+                self.line(&format!("slots[{slot}] = lox_get(slots[{slot}], {});", c_string(name)));
+            }
+            Expr::Set { object, name, value } => {
+                self.expr(object, slot);
+                self.with_temps(|compiler| {
+                    let value_slot = compiler.slot();
+                    compiler.expr(value, value_slot);
+                    compiler.line(&format!(
+                        "slots[{slot}] = lox_set(slots[{slot}], {}, slots[{value_slot}]);",
+                        c_string(name)
+                    ));
+                });
+            }
+            Expr::This => {
+                let value = self.read("this");
+                self.line(&format!("slots[{slot}] = {value};"));
+            }
+            Expr::Super { method } => {
+                let superclass = self.read("super");
+                let this = self.read("this");
+                self.line(&format!("slots[{slot}] = lox_get_super({superclass}, {this}, {});", c_string(method)));
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                self.expr(condition, slot);
+                self.open(&format!("if (lox_truthy(slots[{slot}])) {{"));
+                self.expr(then_branch, slot);
+                self.close("} else {");
+                self.current().indent += 1;
+                self.expr(else_branch, slot);
+                self.close("}");
+            }
+            Expr::Lambda { params, body } => self.closure("lambda", params, body, FunctionKind::Function, slot),
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, operator: &BinaryOp, right: &Expr, slot: usize) {
+        let function = match operator {
+            BinaryOp::Add => "lox_add",
+            BinaryOp::Subtract => "lox_subtract",
+            BinaryOp::Multiply => "lox_multiply",
+            BinaryOp::Divide => "lox_divide",
+            BinaryOp::Greater => "lox_greater",
+            BinaryOp::GreaterEqual => "lox_greater_equal",
+            BinaryOp::Less => "lox_less",
+            BinaryOp::LessEqual => "lox_less_equal",
+            BinaryOp::Equal => "lox_equal",
+            BinaryOp::NotEqual => "lox_not_equal",
+            BinaryOp::And | BinaryOp::Or => {
+                self.expr(left, slot);
+                let test = if *operator == BinaryOp::And { "" } else { "!" };
+                self.open(&format!("if ({test}lox_truthy(slots[{slot}])) {{"));
+                self.expr(right, slot);
+                self.close("}");
+                return;
+            }
+            BinaryOp::Comma => {
+                self.expr(left, slot);
+                self.expr(right, slot);
+                return;
+            }
+        };
+        self.expr(left, slot);
+        self.with_temps(|compiler| {
+            let right_slot = compiler.slot();
+            compiler.expr(right, right_slot);
+            compiler.line(&format!("slots[{slot}] = {function}(slots[{slot}], slots[{right_slot}]);"));
+        });
+    }
+}
+
+/// A C string literal with the same bytes as `value`
+fn c_string(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\t' => literal.push_str("\\t"),
+            // Octal escapes take at most three digits, so a digit after one
+            // is never read as part of it; `?` is escaped to avoid trigraphs
+            0x20..=0x7e if byte != b'?' => literal.push(byte as char),
+            _ => {
+                let _ = write!(literal, "\\{byte:03o}");
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn c_number(value: f64) -> String {
+    if value.is_nan() {
+        "NAN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "INFINITY" } else { "-INFINITY" }.to_string()
+    } else {
+        // Debug formatting always includes a `.` or an exponent, so C reads a double
+        format!("{value:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_string() {
+        assert_eq!(c_string("plain"), "\"plain\"");
+        assert_eq!(c_string("quote \" and \\ slash\n"), "\"quote \\\" and \\\\ slash\\n\"");
+        assert_eq!(c_string("é1"), "\"\\303\\2511\"");
+        assert_eq!(c_string("??="), "\"\\077\\077=\"");
+    }
+
+    #[test]
+    fn test_c_number() {
+        assert_eq!(c_number(1.0), "1.0");
+        assert_eq!(c_number(0.5), "0.5");
+        assert_eq!(c_number(1e300), "1e300");
+        assert_eq!(c_number(f64::INFINITY), "INFINITY");
+    }
+}
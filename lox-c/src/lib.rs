@@ -0,0 +1,111 @@
+//! Ahead-of-time compilation of Lox programs to native executables
+//!
+//! `to_c` lowers a parsed program to a single portable C99 file: a small
+//! runtime (`RUNTIME`) with tagged values, strings, closures, classes and a
+//! mark-and-sweep garbage collector, followed by one C function per Lox
+//! function and a `main` for the top-level code. `compile` hands that file to
+//! the system C compiler to build an executable.
+//!
+//! The executable prints what the program prints. A runtime error writes the
+//! message a Lox interpreter would give to standard error and exits with
+//! status 70.
+//!
+//! `import` statements are skipped: a program split across files should be
+//! linked into one with `lox-loader` before it is compiled.
+//!
+//! # Example
+//!
+//! ```
+//! let program = nom_lox::parse_program("print \"hello\";").unwrap();
+//! let c = lox_c::to_c(&program);
+//! assert!(c.contains("lox_print(slots[0]);"));
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use lox_ast::Program;
+
+mod codegen;
+
+/// The runtime every compiled program starts with
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+/// How to invoke the C compiler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildOptions {
+    /// The compiler command; `$CC` if set, otherwise `cc`
+    pub compiler: String,
+    /// Flags passed before the source, e.g. `-O2` or `-DLOX_STRESS_GC` to
+    /// collect garbage before every allocation
+    pub flags: Vec<String>,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            compiler: std::env::var("CC").unwrap_or_else(|_| "cc".to_string()),
+            flags: vec!["-O2".to_string()],
+        }
+    }
+}
+
+/// Why an executable could not be built
+#[derive(Debug)]
+pub enum CompileError {
+    /// The compiler could not be run
+    Io(io::Error),
+    /// The compiler rejected the generated code
+    Compiler { status: ExitStatus, stderr: String },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Io(error) => write!(f, "Failed to run the C compiler: {}", error),
+            CompileError::Compiler { status, stderr } => write!(f, "The C compiler failed ({}):\n{}", status, stderr),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<io::Error> for CompileError {
+    fn from(error: io::Error) -> Self {
+        CompileError::Io(error)
+    }
+}
+
+/// Lower `program` to C, runtime included
+pub fn to_c(program: &Program) -> String {
+    format!("{RUNTIME}\n{}", codegen::Compiler::new().program(program))
+}
+
+/// Build an executable for `program` at `output` with the default options
+pub fn compile(program: &Program, output: &Path) -> Result<(), CompileError> {
+    compile_with(program, output, &BuildOptions::default())
+}
+
+/// Build an executable for `program` at `output`, passing the C code to the
+/// compiler on standard input
+pub fn compile_with(program: &Program, output: &Path, options: &BuildOptions) -> Result<(), CompileError> {
+    let mut child = Command::new(&options.compiler)
+        .args(&options.flags)
+        .args(["-std=c99", "-x", "c", "-", "-o"])
+        .arg(output)
+        .arg("-lm")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let source = to_c(program);
+    child.stdin.take().expect("stdin is piped").write_all(source.as_bytes())?;
+    let result = child.wait_with_output()?;
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(CompileError::Compiler { status: result.status, stderr: String::from_utf8_lossy(&result.stderr).into_owned() })
+    }
+}
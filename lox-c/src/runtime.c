@@ -0,0 +1,609 @@
+/* Runtime support for Lox programs compiled to C.
+ *
+ * Values are tagged unions; strings, variables captured by closures, closures,
+ * classes, instances and bound methods live on a heap reclaimed by a
+ * mark-and-sweep collector. Compiled functions keep every value they are
+ * working with in a frame of slots, and the frames are linked together so the
+ * collector can find them. Define LOX_STRESS_GC to collect before every
+ * allocation.
+ *
+ * The functions are `static inline` so that those a program does not use
+ * raise no warnings. */
+
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+typedef enum { VAL_NIL, VAL_BOOL, VAL_NUMBER, VAL_OBJ } ValueType;
+
+typedef struct Obj Obj;
+
+typedef struct {
+    ValueType type;
+    union {
+        bool boolean;
+        double number;
+        Obj *obj;
+    } as;
+} Value;
+
+typedef enum { OBJ_STRING, OBJ_CELL, OBJ_CLOSURE, OBJ_NATIVE, OBJ_CLASS, OBJ_INSTANCE, OBJ_BOUND_METHOD } ObjType;
+
+struct Obj {
+    ObjType type;
+    bool marked;
+    size_t size;
+    Obj *next;
+};
+
+typedef struct {
+    Obj obj;
+    size_t length;
+    char chars[];
+} LoxString;
+
+/* A variable declared outside the global scope, boxed so closures can share it */
+typedef struct {
+    Obj obj;
+    Value value;
+} LoxCell;
+
+typedef struct LoxClosure LoxClosure;
+typedef Value (*LoxFn)(LoxClosure *closure, Value receiver, Value *args);
+
+struct LoxClosure {
+    Obj obj;
+    LoxFn fn;
+    const char *name;
+    int arity;
+    int upvalue_count;
+    LoxCell *upvalues[];
+};
+
+typedef struct {
+    Obj obj;
+    Value (*fn)(Value *args);
+    int arity;
+} LoxNative;
+
+typedef struct {
+    const char *name;
+    LoxClosure *closure;
+} LoxMethod;
+
+typedef struct LoxClass {
+    Obj obj;
+    const char *name;
+    struct LoxClass *superclass;
+    LoxMethod *methods;
+    int method_count;
+} LoxClass;
+
+typedef struct {
+    const char *name;
+    Value value;
+} LoxField;
+
+typedef struct {
+    Obj obj;
+    LoxClass *class;
+    LoxField *fields;
+    int field_count;
+    int field_capacity;
+} LoxInstance;
+
+typedef struct {
+    Obj obj;
+    Value receiver;
+    LoxClosure *method;
+} LoxBoundMethod;
+
+/* The slots of a running function */
+typedef struct LoxFrame {
+    struct LoxFrame *parent;
+    Value *slots;
+    int count;
+} LoxFrame;
+
+#define NIL_VAL ((Value){VAL_NIL, {.number = 0}})
+#define BOOL_VAL(value) ((Value){VAL_BOOL, {.boolean = (value)}})
+#define NUMBER_VAL(value) ((Value){VAL_NUMBER, {.number = (value)}})
+#define OBJ_VAL(object) ((Value){VAL_OBJ, {.obj = (Obj *)(object)}})
+
+#define IS_OBJ_TYPE(value, kind) ((value).type == VAL_OBJ && (value).as.obj->type == (kind))
+#define AS_STRING(value) ((LoxString *)(value).as.obj)
+#define AS_CELL(value) ((LoxCell *)(value).as.obj)
+#define AS_CLOSURE(value) ((LoxClosure *)(value).as.obj)
+#define AS_CLASS(value) ((LoxClass *)(value).as.obj)
+#define AS_INSTANCE(value) ((LoxInstance *)(value).as.obj)
+
+/* Read and write a local variable, whose slot holds its cell */
+#define LOCAL(slot) (AS_CELL(slot)->value)
+
+#define MAX_CALL_DEPTH 10000
+
+static Obj *lox_objects = NULL;
+static size_t lox_bytes_allocated = 0;
+static size_t lox_next_gc = 1024 * 1024;
+static LoxFrame *lox_frames = NULL;
+static int lox_call_depth = 0;
+static Value *lox_globals = NULL;
+static bool *lox_globals_defined = NULL;
+static const char **lox_global_names = NULL;
+static int lox_global_count = 0;
+
+/* Values held by the runtime itself across an allocation or a call */
+static Value *lox_temp_roots = NULL;
+static int lox_temp_root_count = 0;
+static int lox_temp_root_capacity = 0;
+
+static Obj **lox_gray = NULL;
+static int lox_gray_count = 0;
+static int lox_gray_capacity = 0;
+
+static inline void lox_runtime_error(const char *format, ...) {
+    va_list args;
+    va_start(args, format);
+    vfprintf(stderr, format, args);
+    va_end(args);
+    fputc('\n', stderr);
+    fflush(stdout);
+    exit(70);
+}
+
+static inline void *lox_xrealloc(void *pointer, size_t size) {
+    void *result = realloc(pointer, size);
+    if (result == NULL && size != 0) {
+        fputs("Out of memory.\n", stderr);
+        exit(70);
+    }
+    return result;
+}
+
+static inline void lox_push_root(Value value) {
+    if (lox_temp_root_count == lox_temp_root_capacity) {
+        lox_temp_root_capacity = lox_temp_root_capacity < 16 ? 16 : lox_temp_root_capacity * 2;
+        lox_temp_roots = lox_xrealloc(lox_temp_roots, sizeof(Value) * lox_temp_root_capacity);
+    }
+    lox_temp_roots[lox_temp_root_count++] = value;
+}
+
+static inline void lox_pop_root(void) { lox_temp_root_count--; }
+
+static inline void lox_mark_object(Obj *object) {
+    if (object == NULL || object->marked) return;
+    object->marked = true;
+    if (lox_gray_count == lox_gray_capacity) {
+        lox_gray_capacity = lox_gray_capacity < 64 ? 64 : lox_gray_capacity * 2;
+        lox_gray = lox_xrealloc(lox_gray, sizeof(Obj *) * lox_gray_capacity);
+    }
+    lox_gray[lox_gray_count++] = object;
+}
+
+static inline void lox_mark_value(Value value) {
+    if (value.type == VAL_OBJ) lox_mark_object(value.as.obj);
+}
+
+static inline void lox_blacken(Obj *object) {
+    switch (object->type) {
+    case OBJ_STRING:
+    case OBJ_NATIVE:
+        break;
+    case OBJ_CELL:
+        lox_mark_value(((LoxCell *)object)->value);
+        break;
+    case OBJ_CLOSURE: {
+        LoxClosure *closure = (LoxClosure *)object;
+        for (int i = 0; i < closure->upvalue_count; i++) lox_mark_object((Obj *)closure->upvalues[i]);
+        break;
+    }
+    case OBJ_CLASS: {
+        LoxClass *class = (LoxClass *)object;
+        lox_mark_object((Obj *)class->superclass);
+        for (int i = 0; i < class->method_count; i++) lox_mark_object((Obj *)class->methods[i].closure);
+        break;
+    }
+    case OBJ_INSTANCE: {
+        LoxInstance *instance = (LoxInstance *)object;
+        lox_mark_object((Obj *)instance->class);
+        for (int i = 0; i < instance->field_count; i++) lox_mark_value(instance->fields[i].value);
+        break;
+    }
+    case OBJ_BOUND_METHOD: {
+        LoxBoundMethod *bound = (LoxBoundMethod *)object;
+        lox_mark_value(bound->receiver);
+        lox_mark_object((Obj *)bound->method);
+        break;
+    }
+    }
+}
+
+static inline void lox_free_object(Obj *object) {
+    lox_bytes_allocated -= object->size;
+    switch (object->type) {
+    case OBJ_CLASS:
+        free(((LoxClass *)object)->methods);
+        break;
+    case OBJ_INSTANCE:
+        free(((LoxInstance *)object)->fields);
+        break;
+    default:
+        break;
+    }
+    free(object);
+}
+
+static inline void lox_collect_garbage(void) {
+    for (LoxFrame *frame = lox_frames; frame != NULL; frame = frame->parent) {
+        for (int i = 0; i < frame->count; i++) lox_mark_value(frame->slots[i]);
+    }
+    for (int i = 0; i < lox_global_count; i++) lox_mark_value(lox_globals[i]);
+    for (int i = 0; i < lox_temp_root_count; i++) lox_mark_value(lox_temp_roots[i]);
+    while (lox_gray_count > 0) lox_blacken(lox_gray[--lox_gray_count]);
+
+    Obj **link = &lox_objects;
+    while (*link != NULL) {
+        Obj *object = *link;
+        if (object->marked) {
+            object->marked = false;
+            link = &object->next;
+        } else {
+            *link = object->next;
+            lox_free_object(object);
+        }
+    }
+    lox_next_gc = lox_bytes_allocated * 2 > 1024 * 1024 ? lox_bytes_allocated * 2 : 1024 * 1024;
+}
+
+static inline Obj *lox_allocate(size_t size, ObjType type) {
+#ifdef LOX_STRESS_GC
+    lox_collect_garbage();
+#else
+    if (lox_bytes_allocated > lox_next_gc) lox_collect_garbage();
+#endif
+    lox_bytes_allocated += size;
+    Obj *object = lox_xrealloc(NULL, size);
+    object->type = type;
+    object->marked = false;
+    object->size = size;
+    object->next = lox_objects;
+    lox_objects = object;
+    return object;
+}
+
+static inline void lox_enter(LoxFrame *frame, Value *slots, int count) {
+    if (++lox_call_depth > MAX_CALL_DEPTH) lox_runtime_error("Stack overflow.");
+    for (int i = 0; i < count; i++) slots[i] = NIL_VAL;
+    frame->parent = lox_frames;
+    frame->slots = slots;
+    frame->count = count;
+    lox_frames = frame;
+}
+
+static inline void lox_leave(LoxFrame *frame) {
+    lox_frames = frame->parent;
+    lox_call_depth--;
+}
+
+static inline void lox_init_globals(Value *globals, bool *defined, const char **names, int count) {
+    lox_globals = globals;
+    lox_globals_defined = defined;
+    lox_global_names = names;
+    lox_global_count = count;
+    for (int i = 0; i < count; i++) {
+        globals[i] = NIL_VAL;
+        defined[i] = false;
+    }
+}
+
+static inline Value lox_get_global(int index) {
+    if (!lox_globals_defined[index]) lox_runtime_error("Undefined variable '%s'.", lox_global_names[index]);
+    return lox_globals[index];
+}
+
+static inline void lox_define_global(int index, Value value) {
+    lox_globals[index] = value;
+    lox_globals_defined[index] = true;
+}
+
+static inline void lox_set_global(int index, Value value) {
+    if (!lox_globals_defined[index]) lox_runtime_error("Undefined variable '%s'.", lox_global_names[index]);
+    lox_globals[index] = value;
+}
+
+static inline Value lox_string(const char *chars, size_t length) {
+    LoxString *string = (LoxString *)lox_allocate(sizeof(LoxString) + length + 1, OBJ_STRING);
+    string->length = length;
+    memcpy(string->chars, chars, length);
+    string->chars[length] = '\0';
+    return OBJ_VAL(string);
+}
+
+static inline Value lox_cell(Value value) {
+    lox_push_root(value);
+    LoxCell *cell = (LoxCell *)lox_allocate(sizeof(LoxCell), OBJ_CELL);
+    lox_pop_root();
+    cell->value = value;
+    return OBJ_VAL(cell);
+}
+
+/* The upvalues are filled in by the caller before anything else is allocated */
+static inline Value lox_closure(LoxFn fn, const char *name, int arity, int upvalue_count) {
+    size_t size = sizeof(LoxClosure) + sizeof(LoxCell *) * upvalue_count;
+    LoxClosure *closure = (LoxClosure *)lox_allocate(size, OBJ_CLOSURE);
+    closure->fn = fn;
+    closure->name = name;
+    closure->arity = arity;
+    closure->upvalue_count = upvalue_count;
+    for (int i = 0; i < upvalue_count; i++) closure->upvalues[i] = NULL;
+    return OBJ_VAL(closure);
+}
+
+static inline Value lox_native(Value (*fn)(Value *args), int arity) {
+    LoxNative *native = (LoxNative *)lox_allocate(sizeof(LoxNative), OBJ_NATIVE);
+    native->fn = fn;
+    native->arity = arity;
+    return OBJ_VAL(native);
+}
+
+static inline Value lox_bound_method(Value receiver, LoxClosure *method) {
+    lox_push_root(receiver);
+    lox_push_root(OBJ_VAL(method));
+    LoxBoundMethod *bound = (LoxBoundMethod *)lox_allocate(sizeof(LoxBoundMethod), OBJ_BOUND_METHOD);
+    lox_pop_root();
+    lox_pop_root();
+    bound->receiver = receiver;
+    bound->method = method;
+    return OBJ_VAL(bound);
+}
+
+static inline bool lox_truthy(Value value) {
+    return !(value.type == VAL_NIL || (value.type == VAL_BOOL && !value.as.boolean));
+}
+
+static inline bool lox_values_equal(Value a, Value b) {
+    if (a.type != b.type) return false;
+    switch (a.type) {
+    case VAL_NIL:
+        return true;
+    case VAL_BOOL:
+        return a.as.boolean == b.as.boolean;
+    case VAL_NUMBER:
+        return a.as.number == b.as.number;
+    case VAL_OBJ:
+        if (IS_OBJ_TYPE(a, OBJ_STRING) && IS_OBJ_TYPE(b, OBJ_STRING)) {
+            LoxString *x = AS_STRING(a);
+            LoxString *y = AS_STRING(b);
+            return x->length == y->length && memcmp(x->chars, y->chars, x->length) == 0;
+        }
+        return a.as.obj == b.as.obj;
+    }
+    return false;
+}
+
+static inline Value lox_equal(Value a, Value b) { return BOOL_VAL(lox_values_equal(a, b)); }
+
+static inline Value lox_not_equal(Value a, Value b) { return BOOL_VAL(!lox_values_equal(a, b)); }
+
+static inline Value lox_not(Value a) { return BOOL_VAL(!lox_truthy(a)); }
+
+static inline Value lox_negate(Value a) {
+    if (a.type != VAL_NUMBER) lox_runtime_error("Operand must be a number.");
+    return NUMBER_VAL(-a.as.number);
+}
+
+static inline Value lox_add(Value a, Value b) {
+    if (a.type == VAL_NUMBER && b.type == VAL_NUMBER) return NUMBER_VAL(a.as.number + b.as.number);
+    if (IS_OBJ_TYPE(a, OBJ_STRING) && IS_OBJ_TYPE(b, OBJ_STRING)) {
+        LoxString *x = AS_STRING(a);
+        LoxString *y = AS_STRING(b);
+        size_t length = x->length + y->length;
+        lox_push_root(a);
+        lox_push_root(b);
+        LoxString *string = (LoxString *)lox_allocate(sizeof(LoxString) + length + 1, OBJ_STRING);
+        lox_pop_root();
+        lox_pop_root();
+        string->length = length;
+        memcpy(string->chars, x->chars, x->length);
+        memcpy(string->chars + x->length, y->chars, y->length);
+        string->chars[length] = '\0';
+        return OBJ_VAL(string);
+    }
+    lox_runtime_error("Operands must be two numbers or two strings.");
+    return NIL_VAL;
+}
+
+#define LOX_NUMBER_OP(name, result, op)                                                             \
+    static inline Value name(Value a, Value b) {                                                           \
+        if (a.type != VAL_NUMBER || b.type != VAL_NUMBER) lox_runtime_error("Operands must be numbers."); \
+        return result(a.as.number op b.as.number);                                                  \
+    }
+
+LOX_NUMBER_OP(lox_subtract, NUMBER_VAL, -)
+LOX_NUMBER_OP(lox_multiply, NUMBER_VAL, *)
+LOX_NUMBER_OP(lox_divide, NUMBER_VAL, /)
+LOX_NUMBER_OP(lox_greater, BOOL_VAL, >)
+LOX_NUMBER_OP(lox_greater_equal, BOOL_VAL, >=)
+LOX_NUMBER_OP(lox_less, BOOL_VAL, <)
+LOX_NUMBER_OP(lox_less_equal, BOOL_VAL, <=)
+
+/* Write a number the way the Lox interpreters do: integers without a
+ * fractional part, anything else with the fewest digits that read back the
+ * same */
+static inline void lox_print_number(double number) {
+    if (isnan(number)) {
+        printf("NaN");
+    } else if (isinf(number)) {
+        printf(number > 0 ? "Infinity" : "-Infinity");
+    } else if (number == 0 && signbit(number)) {
+        printf("-0");
+    } else if (number == floor(number) && fabs(number) < 1e21) {
+        printf("%.0f", number);
+    } else {
+        char buffer[32];
+        for (int precision = 1; precision <= 17; precision++) {
+            snprintf(buffer, sizeof buffer, "%.*g", precision, number);
+            if (strtod(buffer, NULL) == number) break;
+        }
+        printf("%s", buffer);
+    }
+}
+
+static inline void lox_print_value(Value value) {
+    switch (value.type) {
+    case VAL_NIL:
+        printf("nil");
+        return;
+    case VAL_BOOL:
+        printf(value.as.boolean ? "true" : "false");
+        return;
+    case VAL_NUMBER:
+        lox_print_number(value.as.number);
+        return;
+    case VAL_OBJ:
+        break;
+    }
+    switch (value.as.obj->type) {
+    case OBJ_STRING:
+        fwrite(AS_STRING(value)->chars, 1, AS_STRING(value)->length, stdout);
+        break;
+    case OBJ_CELL:
+        lox_print_value(AS_CELL(value)->value);
+        break;
+    case OBJ_CLOSURE:
+        printf("<fn %s>", AS_CLOSURE(value)->name);
+        break;
+    case OBJ_NATIVE:
+        printf("<native fn>");
+        break;
+    case OBJ_CLASS:
+        printf("%s", AS_CLASS(value)->name);
+        break;
+    case OBJ_INSTANCE:
+        printf("%s instance", AS_INSTANCE(value)->class->name);
+        break;
+    case OBJ_BOUND_METHOD:
+        printf("<fn %s>", ((LoxBoundMethod *)value.as.obj)->method->name);
+        break;
+    }
+}
+
+static inline void lox_print(Value value) {
+    lox_print_value(value);
+    putchar('\n');
+}
+
+static inline LoxClosure *lox_find_method(LoxClass *class, const char *name) {
+    for (; class != NULL; class = class->superclass) {
+        for (int i = 0; i < class->method_count; i++) {
+            if (strcmp(class->methods[i].name, name) == 0) return class->methods[i].closure;
+        }
+    }
+    return NULL;
+}
+
+static inline Value lox_class(const char *name, Value superclass, bool has_superclass) {
+    if (has_superclass && !IS_OBJ_TYPE(superclass, OBJ_CLASS)) lox_runtime_error("Superclass must be a class.");
+    LoxClass *class = (LoxClass *)lox_allocate(sizeof(LoxClass), OBJ_CLASS);
+    class->name = name;
+    class->superclass = has_superclass ? AS_CLASS(superclass) : NULL;
+    class->methods = NULL;
+    class->method_count = 0;
+    return OBJ_VAL(class);
+}
+
+static inline void lox_add_method(Value class_value, const char *name, Value closure) {
+    LoxClass *class = AS_CLASS(class_value);
+    class->methods = lox_xrealloc(class->methods, sizeof(LoxMethod) * (class->method_count + 1));
+    class->methods[class->method_count++] = (LoxMethod){name, AS_CLOSURE(closure)};
+}
+
+static inline Value lox_call_closure(LoxClosure *closure, Value receiver, int argc, Value *args) {
+    if (argc != closure->arity) lox_runtime_error("Expected %d arguments but got %d.", closure->arity, argc);
+    return closure->fn(closure, receiver, args);
+}
+
+static inline Value lox_call(Value callee, int argc, Value *args) {
+    if (callee.type == VAL_OBJ) {
+        switch (callee.as.obj->type) {
+        case OBJ_CLOSURE:
+            return lox_call_closure(AS_CLOSURE(callee), NIL_VAL, argc, args);
+        case OBJ_BOUND_METHOD: {
+            LoxBoundMethod *bound = (LoxBoundMethod *)callee.as.obj;
+            return lox_call_closure(bound->method, bound->receiver, argc, args);
+        }
+        case OBJ_NATIVE: {
+            LoxNative *native = (LoxNative *)callee.as.obj;
+            if (argc != native->arity) lox_runtime_error("Expected %d arguments but got %d.", native->arity, argc);
+            return native->fn(args);
+        }
+        case OBJ_CLASS: {
+            LoxClass *class = AS_CLASS(callee);
+            LoxInstance *instance = (LoxInstance *)lox_allocate(sizeof(LoxInstance), OBJ_INSTANCE);
+            instance->class = class;
+            instance->fields = NULL;
+            instance->field_count = 0;
+            instance->field_capacity = 0;
+            Value result = OBJ_VAL(instance);
+            LoxClosure *init = lox_find_method(class, "init");
+            if (init != NULL) {
+                lox_push_root(result);
+                lox_call_closure(init, result, argc, args);
+                lox_pop_root();
+            } else if (argc != 0) {
+                lox_runtime_error("Expected 0 arguments but got %d.", argc);
+            }
+            return result;
+        }
+        default:
+            break;
+        }
+    }
+    lox_runtime_error("Can only call functions and classes.");
+    return NIL_VAL;
+}
+
+static inline Value lox_get(Value object, const char *name) {
+    if (!IS_OBJ_TYPE(object, OBJ_INSTANCE)) lox_runtime_error("Only instances have properties.");
+    LoxInstance *instance = AS_INSTANCE(object);
+    for (int i = 0; i < instance->field_count; i++) {
+        if (strcmp(instance->fields[i].name, name) == 0) return instance->fields[i].value;
+    }
+    LoxClosure *method = lox_find_method(instance->class, name);
+    if (method == NULL) lox_runtime_error("Undefined property '%s'.", name);
+    return lox_bound_method(object, method);
+}
+
+static inline Value lox_set(Value object, const char *name, Value value) {
+    if (!IS_OBJ_TYPE(object, OBJ_INSTANCE)) lox_runtime_error("Only instances have fields.");
+    LoxInstance *instance = AS_INSTANCE(object);
+    for (int i = 0; i < instance->field_count; i++) {
+        if (strcmp(instance->fields[i].name, name) == 0) {
+            instance->fields[i].value = value;
+            return value;
+        }
+    }
+    if (instance->field_count == instance->field_capacity) {
+        instance->field_capacity = instance->field_capacity < 4 ? 4 : instance->field_capacity * 2;
+        instance->fields = lox_xrealloc(instance->fields, sizeof(LoxField) * instance->field_capacity);
+    }
+    instance->fields[instance->field_count++] = (LoxField){name, value};
+    return value;
+}
+
+/* `super.name` in a method of a subclass of `superclass`, bound to `receiver` */
+static inline Value lox_get_super(Value superclass, Value receiver, const char *name) {
+    LoxClosure *method = lox_find_method(AS_CLASS(superclass), name);
+    if (method == NULL) lox_runtime_error("Undefined property '%s'.", name);
+    return lox_bound_method(receiver, method);
+}
+
+static inline Value lox_clock(Value *args) {
+    (void)args;
+    return NUMBER_VAL((double)clock() / CLOCKS_PER_SEC);
+}
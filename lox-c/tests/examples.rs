@@ -0,0 +1,240 @@
+//! Compile Lox programs with the system C compiler and check what they print
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use lox_ast::LanguageOptions;
+use lox_c::BuildOptions;
+
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+/// A path for an executable that no other test uses
+fn executable_path() -> PathBuf {
+    let build = BUILDS.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("lox-c-test-{}-{}", std::process::id(), build))
+}
+
+fn compile_and_run(source: &str, options: &LanguageOptions, flags: &[&str]) -> Output {
+    let program = nom_lox::parse_program_with(source, options).unwrap();
+    let executable = executable_path();
+    let build = BuildOptions { flags: flags.iter().map(|flag| flag.to_string()).collect(), ..BuildOptions::default() };
+    lox_c::compile_with(&program, &executable, &build).unwrap();
+    let output = Command::new(&executable).output().unwrap();
+    let _ = fs::remove_file(&executable);
+    output
+}
+
+/// Run a program that should succeed, returning what it printed
+fn run(source: &str) -> String {
+    let output = compile_and_run(source, &LanguageOptions::default(), &["-O1"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run a program that should fail, returning its error message
+fn run_error(source: &str) -> String {
+    let output = compile_and_run(source, &LanguageOptions::default(), &["-O1"]);
+    assert_eq!(output.status.code(), Some(70));
+    String::from_utf8(output.stderr).unwrap().trim_end().to_string()
+}
+
+/// Describe the first line where `actual` differs from `expected`
+fn diff(expected: &str, actual: &str) -> Option<String> {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return None,
+            (expected, actual) if expected == actual => continue,
+            (expected, actual) => {
+                return Some(format!("line {line}: expected {expected:?}, got {actual:?}"));
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn examples() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    let mut examples: Vec<PathBuf> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    examples.sort();
+    examples
+}
+
+fn check_examples(flags: &[&str]) {
+    let examples = examples();
+    assert!(!examples.is_empty());
+    for example in examples {
+        let source = fs::read_to_string(&example).unwrap();
+        let expected = fs::read_to_string(example.with_extension("out")).unwrap();
+        let output = compile_and_run(&source, &LanguageOptions::default(), flags);
+        assert!(output.status.success(), "{}: {}", example.display(), String::from_utf8_lossy(&output.stderr));
+        let actual = String::from_utf8(output.stdout).unwrap();
+        if let Some(difference) = diff(&expected, &actual) {
+            panic!("{}: {difference}", example.display());
+        }
+    }
+}
+
+#[test]
+fn test_examples() {
+    check_examples(&["-O2", "-Wall", "-Wextra", "-Werror"]);
+}
+
+#[test]
+fn test_examples_collecting_garbage_on_every_allocation() {
+    check_examples(&["-O0", "-DLOX_STRESS_GC"]);
+}
+
+#[test]
+fn test_values_and_operators() {
+    let source = r#"
+        print 1 + 2;
+        print 10 / 4;
+        print 1 / 3;
+        print -0;
+        print 1 / 0;
+        print "con" + "cat";
+        print "a" == "a";
+        print nil == false;
+        print !nil;
+        print 0 and "zero";
+        print nil or "default";
+        print 3 > 2;
+    "#;
+    let expected = "3\n2.5\n0.3333333333333333\n-0\nInfinity\nconcat\ntrue\nfalse\ntrue\nzero\ndefault\ntrue\n";
+    assert_eq!(run(source), expected);
+}
+
+#[test]
+fn test_closures_share_variables() {
+    let source = r#"
+        var show;
+        var set;
+        {
+            var a = "initial";
+            fun showA() { print a; }
+            fun setA(value) { a = value; }
+            show = showA;
+            set = setA;
+        }
+        show();
+        set("changed");
+        show();
+
+        var a = "global";
+        {
+            fun showGlobal() { print a; }
+            showGlobal();
+            var a = "block";
+            showGlobal();
+        }
+
+        fun outer() {
+            var x = "outer";
+            fun middle() {
+                fun inner() { return x; }
+                return inner;
+            }
+            return middle()();
+        }
+        print outer();
+    "#;
+    assert_eq!(run(source), "initial\nchanged\nglobal\nglobal\nouter\n");
+}
+
+#[test]
+fn test_classes() {
+    let source = r#"
+        class Counter {
+            init() { this.count = 0; }
+            increment() {
+                this.count = this.count + 1;
+                return this;
+            }
+            adder() {
+                fun add(n) { this.count = this.count + n; }
+                return add;
+            }
+        }
+        var counter = Counter();
+        counter.increment().increment();
+        counter.adder()(10);
+        print counter.count;
+        print counter.init() == counter;
+        print counter.count;
+    "#;
+    assert_eq!(run(source), "12\ntrue\n0\n");
+}
+
+#[test]
+fn test_loops_and_extensions() {
+    let source = r#"
+        var total = 0;
+        for (var i = 0; i < 10; i = i + 1) {
+            if (i == 2) continue;
+            if (i == 6) break;
+            total = total + i;
+        }
+        print total;
+        var n = 0;
+        while (true) {
+            n = n + 1;
+            if (n < 3) continue;
+            break;
+        }
+        print n;
+        print n > 2 ? "big" : "small";
+        var square = fun (x) { return x * x; };
+        print square(4);
+    "#;
+    let output = compile_and_run(source, &LanguageOptions::EXTENDED, &["-O1"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "13\n3\nbig\n16\n");
+}
+
+#[test]
+fn test_runtime_errors() {
+    assert_eq!(run_error(r#"print "a" + 1;"#), "Operands must be two numbers or two strings.");
+    assert_eq!(run_error(r#"print 1 < "b";"#), "Operands must be numbers.");
+    assert_eq!(run_error("print undefined;"), "Undefined variable 'undefined'.");
+    assert_eq!(run_error("fun f(a) {} f();"), "Expected 1 arguments but got 0.");
+    assert_eq!(run_error("var x = 1; x();"), "Can only call functions and classes.");
+    assert_eq!(run_error("class A {} print A().b;"), "Undefined property 'b'.");
+    assert_eq!(run_error("var x = 1; class B < x {}"), "Superclass must be a class.");
+    assert_eq!(run_error("fun f() { f(); } f();"), "Stack overflow.");
+}
+
+#[test]
+fn test_output_before_an_error_is_kept() {
+    let output = compile_and_run("print 1; print -nil;", &LanguageOptions::default(), &["-O1"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Operand must be a number.\n");
+}
+
+#[test]
+fn test_garbage_is_collected() {
+    // Several hundred MB of strings and cells if nothing were freed
+    let source = r#"
+        var kept = "";
+        for (var i = 0; i < 1000000; i = i + 1) {
+            var garbage = "a long string that quickly becomes garbage, repeated " + "many times over";
+            if (i == 0) kept = garbage;
+        }
+        print kept;
+    "#;
+    let program = nom_lox::parse_program(source).unwrap();
+    let executable = executable_path();
+    lox_c::compile(&program, &executable).unwrap();
+    // Limit the executable to 64 MB of address space
+    let output = Command::new("sh").arg("-c").arg("ulimit -v 65536 && exec \"$0\"").arg(&executable).output().unwrap();
+    let _ = fs::remove_file(&executable);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a long string that quickly becomes garbage, repeated many times over\n");
+}
@@ -0,0 +1,258 @@
+//! Reports of parse errors for people to read
+//!
+//! Every parser can report a failure as a `lox_ast::Diagnostic`. A
+//! [`Renderer`] turns one into text in one of two styles:
+//!
+//! - [`Style::Rich`] shows the source lines involved with the primary span
+//!   underlined and secondary labels, notes and help beneath, using
+//!   `codespan-reporting`, optionally in color.
+//! - [`Style::Plain`] writes one `file:line:column: severity: message` line per
+//!   label, followed by the notes, for logs and tools that read compiler
+//!   output.
+//!
+//! [`Renderer::for_terminal`] picks rich colored output when writing to a
+//! terminal and plain text otherwise.
+//!
+//! ```
+//! use lox_ast::{LanguageOptions, SourceMap};
+//! use lox_diagnostics::Renderer;
+//!
+//! let source = "print (1;";
+//! let diagnostic = nom_lox::parse_program_diagnostic(source, &LanguageOptions::default()).unwrap_err();
+//! let mut sources = SourceMap::new();
+//! let file = sources.add("main.lox", source);
+//! let report = Renderer::rich().render(&sources, file, &diagnostic);
+//! assert!(report.contains("unclosed `(` opened here"));
+//! ```
+
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+
+use codespan_reporting::diagnostic as codespan;
+use codespan_reporting::files::{self, Files};
+use codespan_reporting::term::{self, termcolor};
+use lox_ast::{Diagnostic, FileId, Severity, SourceMap};
+
+/// How a diagnostic is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Source snippets with underlined spans
+    Rich,
+    /// One line per location, without the source
+    Plain,
+}
+
+/// Renders diagnostics as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Renderer {
+    pub style: Style,
+    /// Whether to color rich output with ANSI escape codes
+    pub color: bool,
+}
+
+impl Renderer {
+    /// Rich output without color
+    pub fn rich() -> Self {
+        Renderer { style: Style::Rich, color: false }
+    }
+
+    /// Plain output
+    pub fn plain() -> Self {
+        Renderer { style: Style::Plain, color: false }
+    }
+
+    /// Rich colored output for a terminal, or plain output when `stream` is
+    /// redirected; `NO_COLOR` turns the colors off
+    pub fn for_terminal(stream: &impl IsTerminal) -> Self {
+        if stream.is_terminal() {
+            let color = std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+            Renderer { style: Style::Rich, color }
+        } else {
+            Self::plain()
+        }
+    }
+
+    pub fn with_color(self, color: bool) -> Self {
+        Renderer { color, ..self }
+    }
+
+    /// Render a diagnostic about file `file` of `sources`
+    pub fn render(&self, sources: &SourceMap, file: FileId, diagnostic: &Diagnostic) -> String {
+        match self.style {
+            Style::Rich => self.render_rich(sources, file, diagnostic),
+            Style::Plain => render_plain(sources, file, diagnostic),
+        }
+    }
+
+    /// Render a diagnostic about a single source called `name`
+    pub fn render_source(&self, name: &str, source: &str, diagnostic: &Diagnostic) -> String {
+        let mut sources = SourceMap::new();
+        let file = sources.add(name, source);
+        self.render(&sources, file, diagnostic)
+    }
+
+    /// Write a diagnostic to standard error, styled for wherever it goes
+    pub fn emit(sources: &SourceMap, file: FileId, diagnostic: &Diagnostic) -> io::Result<()> {
+        let stderr = io::stderr();
+        let report = Self::for_terminal(&stderr).render(sources, file, diagnostic);
+        stderr.lock().write_all(report.as_bytes())
+    }
+
+    fn render_rich(&self, sources: &SourceMap, file: FileId, diagnostic: &Diagnostic) -> String {
+        let severity = match diagnostic.severity {
+            Severity::Error => codespan::Severity::Error,
+            Severity::Warning => codespan::Severity::Warning,
+        };
+        let mut labels = vec![
+            codespan::Label::primary(file, diagnostic.primary.span)
+                .with_message(diagnostic.primary.message.clone()),
+        ];
+        labels.extend(diagnostic.secondary.iter().map(|label| {
+            codespan::Label::secondary(file, label.span).with_message(label.message.clone())
+        }));
+        let mut notes = diagnostic.notes.clone();
+        notes.extend(diagnostic.help.iter().map(|help| format!("help: {}", help)));
+        let report = codespan::Diagnostic::new(severity)
+            .with_message(diagnostic.message.clone())
+            .with_labels(labels)
+            .with_notes(notes);
+
+        let mut buffer = if self.color { termcolor::Buffer::ansi() } else { termcolor::Buffer::no_color() };
+        term::emit(&mut buffer, &term::Config::default(), &Sources(sources), &report)
+            .expect("diagnostic spans are within their file");
+        String::from_utf8(buffer.into_inner()).expect("reports are UTF-8")
+    }
+}
+
+/// `file:line:column: severity: message` for the primary span, the same for
+/// each secondary label as a note, then the notes and help
+fn render_plain(sources: &SourceMap, file: FileId, diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let mut message = diagnostic.message.clone();
+    if !diagnostic.primary.message.is_empty() {
+        message = format!("{}: {}", message, diagnostic.primary.message);
+    }
+    let location = sources.describe(file, diagnostic.primary.span.start);
+    let mut report = format!("{}: {}: {}\n", location, severity, message);
+    for label in &diagnostic.secondary {
+        report += &format!("{}: note: {}\n", sources.describe(file, label.span.start), label.message);
+    }
+    for note in &diagnostic.notes {
+        report += &format!("  note: {}\n", note);
+    }
+    if let Some(help) = &diagnostic.help {
+        report += &format!("  help: {}\n", help);
+    }
+    report
+}
+
+/// A `SourceMap` as codespan's file database
+struct Sources<'a>(&'a SourceMap);
+
+impl Sources<'_> {
+    /// The offset at which a 0-based line starts, or the end of the file for
+    /// the line after the last
+    fn line_start(&self, id: FileId, line: usize) -> Result<usize, files::Error> {
+        let file = self.0.get(id).ok_or(files::Error::FileMissing)?;
+        let lines = file.line_index();
+        match lines.line_span(line) {
+            Some(span) => Ok(span.start),
+            None if line == lines.line_count() => Ok(file.source().len()),
+            None => Err(files::Error::LineTooLarge { given: line, max: lines.line_count() - 1 }),
+        }
+    }
+}
+
+impl<'a> Files<'a> for Sources<'a> {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, files::Error> {
+        self.0.get(id).map(|file| file.name()).ok_or(files::Error::FileMissing)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, files::Error> {
+        self.0.get(id).map(|file| file.source()).ok_or(files::Error::FileMissing)
+    }
+
+    fn line_index(&'a self, id: FileId, byte_index: usize) -> Result<usize, files::Error> {
+        let file = self.0.get(id).ok_or(files::Error::FileMissing)?;
+        Ok(file.line_index().line(byte_index))
+    }
+
+    fn line_range(&'a self, id: FileId, line_index: usize) -> Result<Range<usize>, files::Error> {
+        Ok(self.line_start(id, line_index)?..self.line_start(id, line_index + 1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox_ast::Span;
+
+    fn diagnostic() -> Diagnostic {
+        Diagnostic::error("Expected ')'", Span::new(19, 20))
+            .with_label("expected ')' here")
+            .with_secondary(Span::new(13, 14), "unclosed `(` opened here")
+            .with_note("Expected one of: \")\", \",\"")
+            .with_help("add ')' before the ';'")
+    }
+
+    const SOURCE: &str = "var a;\nprint (a + 1;\n";
+
+    #[test]
+    fn test_rich() {
+        let report = Renderer::rich().render_source("main.lox", SOURCE, &diagnostic());
+        let lines: Vec<&str> = report.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines.join("\n").trim_end(),
+            r#"error: Expected ')'
+  ┌─ main.lox:2:13
+  │
+2 │ print (a + 1;
+  │       -     ^ expected ')' here
+  │       │
+  │       unclosed `(` opened here
+  │
+  = Expected one of: ")", ","
+  = help: add ')' before the ';'
+"#
+            .trim_end()
+        );
+        let colored = Renderer::rich().with_color(true).render_source("main.lox", SOURCE, &diagnostic());
+        assert!(colored.contains("\u{1b}["));
+        assert_ne!(colored, report);
+    }
+
+    #[test]
+    fn test_plain() {
+        let report = Renderer::plain().render_source("main.lox", SOURCE, &diagnostic());
+        assert_eq!(
+            report,
+            "main.lox:2:13: error: Expected ')': expected ')' here\n\
+             main.lox:2:7: note: unclosed `(` opened here\n  \
+             note: Expected one of: \")\", \",\"\n  \
+             help: add ')' before the ';'\n"
+        );
+    }
+
+    #[test]
+    fn test_spans_at_the_end_of_input() {
+        let source = "print 1";
+        let diagnostic = Diagnostic::warning("Unexpected end of input", Span::new(7, 7));
+        let report = Renderer::rich().render_source("main.lox", source, &diagnostic);
+        assert!(report.starts_with("warning: Unexpected end of input\n  ┌─ main.lox:1:8\n"), "{}", report);
+        let report = Renderer::plain().render_source("main.lox", source, &diagnostic);
+        assert_eq!(report, "main.lox:1:8: warning: Unexpected end of input\n");
+    }
+
+    #[test]
+    fn test_for_terminal() {
+        // Test output is captured, so standard output is not a terminal here
+        assert_eq!(Renderer::for_terminal(&io::stdout()).style, Style::Plain);
+    }
+}
@@ -0,0 +1,65 @@
+//! Snapshots of every backend's reports for common mistakes
+//!
+//! Each case has a file under `tests/snapshots/` with the rich and plain
+//! report from every parser. The test fails when a report changes, and
+//! rewrites the files when run with `UPDATE_SNAPSHOTS=1`; review the diff
+//! before committing it.
+
+use lox_ast::{Diagnostic, LanguageOptions, Program};
+use lox_diagnostics::Renderer;
+
+type ParseDiagnostic = fn(&str) -> Result<Program, Box<Diagnostic>>;
+
+const BACKENDS: &[(&str, ParseDiagnostic)] = &[
+    ("nom", |source| nom_lox::parse_program_diagnostic(source, &LanguageOptions::default())),
+    ("lalrpop", |source| lalrpop_lox::parse_program_diagnostic(source, &LanguageOptions::default())),
+    ("pest", pest_lox::parse_program_diagnostic),
+    ("winnow", winnow_lox::parse_program_diagnostic),
+    ("pom", pom_lox::parse_program_diagnostic),
+    ("lelwel", lelwel_lox::parse_program_diagnostic),
+];
+
+const CASES: &[(&str, &str)] = &[
+    ("unclosed_paren", "var total = 1;\nprint (total + 1;\n"),
+    ("missing_semicolon", "var x = 1\nprint x;\n"),
+    ("unterminated_string", "var greeting = \"hello;\nprint greeting;\n"),
+    ("unclosed_block", "fun greet() {\n  print \"hi\";\n"),
+    ("invalid_assignment", "var a = 1;\na + 2 = 3;\n"),
+    ("misspelled_keyword", "fun double(n) {\n  retrun n * 2;\n}\n"),
+    ("assignment_in_condition", "var n = 4;\nif (n % 2 = 0) print \"even\";\n"),
+    ("var_in_expression", "print var x = 1;\n"),
+];
+
+/// Every backend's reports for `source`, with trailing whitespace removed
+/// from each line so the files survive editors
+fn reports(source: &str) -> String {
+    let mut reports = String::new();
+    for (backend, parse) in BACKENDS {
+        let report = match parse(source) {
+            Ok(_) => "accepted\n".to_string(),
+            Err(diagnostic) => format!(
+                "{}\n{}",
+                Renderer::rich().render_source("input.lox", source, &diagnostic),
+                Renderer::plain().render_source("input.lox", source, &diagnostic)
+            ),
+        };
+        reports += &format!("=== {} ===\n{}\n", backend, report.trim_end());
+    }
+    reports.lines().map(|line| line.trim_end().to_string() + "\n").collect()
+}
+
+#[test]
+fn test_reports_match_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut stale = Vec::new();
+    for (name, source) in CASES {
+        let path = format!("{}/tests/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        let generated = format!("--- source ---\n{}{}", source, reports(source));
+        if update {
+            std::fs::write(&path, &generated).unwrap();
+        } else if std::fs::read_to_string(&path).unwrap_or_default() != generated {
+            stale.push(*name);
+        }
+    }
+    assert!(stale.is_empty(), "snapshots {:?} are out of date; rerun the tests with UPDATE_SNAPSHOTS=1", stale);
+}
@@ -0,0 +1,482 @@
+//! Translation of statements and expressions
+
+use std::collections::{HashMap, HashSet};
+
+use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+
+use crate::resolve::{NATIVES, Resolution};
+
+/// Binding strength of a JavaScript expression, used to decide where
+/// parentheses are needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Comma,
+    Assignment,
+    Conditional,
+    Equality,
+    Unary,
+    Primary,
+}
+
+/// Names that cannot be used as JavaScript bindings in strict mode, or that
+/// would change meaning if shadowed
+const RESERVED: &[&str] = &[
+    "arguments", "await", "case", "catch", "const", "constructor", "debugger", "default", "delete",
+    "do", "enum", "eval", "export", "extends", "finally", "function", "implements", "import", "in",
+    "instanceof", "interface", "let", "new", "null", "package", "private", "protected", "public",
+    "static", "switch", "throw", "try", "typeof", "undefined", "void", "with", "yield", "Infinity",
+    "NaN",
+];
+
+/// The JavaScript name for a Lox variable. Lox names cannot contain `$`, so
+/// appending one never collides with another variable.
+fn binding(name: &str) -> String {
+    if RESERVED.contains(&name) { format!("{name}$") } else { name.to_string() }
+}
+
+/// The JavaScript name for a Lox method, which may be any word except
+/// `constructor`
+fn method_key(name: &str) -> String {
+    if name == "constructor" { "constructor$".to_string() } else { name.to_string() }
+}
+
+fn string_literal(value: &str) -> String {
+    serde_json::to_string(value).expect("strings always serialize")
+}
+
+fn number_literal(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        format!("{value}")
+    }
+}
+
+/// The kind of function whose body is being written, which decides what a
+/// bare `return` yields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Function,
+    Initializer,
+}
+
+pub(crate) struct Emitter {
+    out: String,
+    indent: usize,
+    /// The JavaScript names of the Lox variables declared in each enclosing
+    /// block, innermost last
+    scopes: Vec<HashMap<String, String>>,
+    /// Number of local variables renamed so far, for unique names
+    renamed: usize,
+    resolution: Resolution,
+    /// Globals declared up front whose own declaration has not been written yet
+    unset: HashSet<String>,
+    /// JavaScript names of the enclosing classes, innermost last
+    classes: Vec<String>,
+    function: Option<FunctionKind>,
+}
+
+impl Emitter {
+    pub(crate) fn new() -> Self {
+        Emitter {
+            out: String::new(),
+            indent: 0,
+            scopes: Vec::new(),
+            renamed: 0,
+            resolution: Resolution::default(),
+            unset: HashSet::new(),
+            classes: Vec::new(),
+            function: None,
+        }
+    }
+
+    pub(crate) fn program(mut self, program: &Program) -> String {
+        self.resolution = Resolution::new(program);
+        self.line("{");
+        self.indent += 1;
+        let mut globals: HashMap<String, String> = NATIVES.iter().map(|name| (name.to_string(), name.to_string())).collect();
+        for name in std::mem::take(&mut self.resolution.hoisted) {
+            let js_name = binding(&name);
+            self.line(&format!("let {js_name} = $unset;"));
+            globals.insert(name.clone(), js_name);
+            self.unset.insert(name);
+        }
+        self.scopes.push(globals);
+        for statement in &program.statements {
+            self.stmt(statement);
+        }
+        self.scopes.pop();
+        self.indent -= 1;
+        self.line("}");
+        self.out
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Write statements one level deeper, in a scope of their own
+    fn block_body(&mut self, statements: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        for statement in statements {
+            self.stmt(statement);
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+    }
+
+    /// Record a declaration in the current scope, returning its JavaScript
+    /// name and whether it was already declared there. Lox allows redeclaring
+    /// a variable, which becomes an assignment since `let` does not; so do
+    /// the declarations of globals that were declared up front.
+    ///
+    /// A local variable whose name is used earlier in its block gets a name
+    /// of its own (see `resolve`), so that code keeps seeing what the name
+    /// meant there.
+    fn declare(&mut self, name: &str, declaration: &Stmt) -> (String, bool) {
+        if self.scopes.len() == 1 {
+            self.unset.remove(name);
+        }
+        let scope = self.scopes.last_mut().expect("statements are written inside a scope");
+        if let Some(js_name) = scope.get(name) {
+            return (js_name.clone(), true);
+        }
+        let js_name = if self.resolution.is_renamed(declaration) {
+            self.renamed += 1;
+            format!("{name}${}", self.renamed)
+        } else {
+            binding(name)
+        };
+        scope.insert(name.to_string(), js_name.clone());
+        (js_name, false)
+    }
+
+    /// The JavaScript name a Lox variable refers to here, and whether it is a
+    /// global whose declaration has not run yet
+    fn lookup(&self, name: &str) -> Option<(String, bool)> {
+        let (depth, js_name) =
+            self.scopes.iter().enumerate().rev().find_map(|(depth, scope)| Some((depth, scope.get(name)?)))?;
+        Some((js_name.clone(), depth == 0 && self.unset.contains(name)))
+    }
+
+    /// Read a Lox variable, failing at runtime if it is not defined yet
+    fn read(&self, name: &str) -> String {
+        match self.lookup(name) {
+            Some((js_name, false)) => js_name,
+            Some((js_name, true)) => format!("$check({js_name}, {})", string_literal(name)),
+            None => format!("$undefined({})", string_literal(name)),
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: &Expr) -> (String, Precedence) {
+        let value = self.expr(value, Precedence::Assignment);
+        let quoted = string_literal(name);
+        match self.lookup(name) {
+            Some((js_name, false)) => (format!("{js_name} = {value}"), Precedence::Assignment),
+            Some((js_name, true)) => {
+                (format!("{js_name} = $assign({js_name}, {quoted}, {value})"), Precedence::Assignment)
+            }
+            None => (format!("$assign($unset, {quoted}, {value})"), Precedence::Primary),
+        }
+    }
+
+    /// Write a statement used as the body of `if`, `while` or `for`, which
+    /// is always braced
+    fn body(&mut self, header: &str, body: &Stmt) {
+        self.line(&format!("{header} {{"));
+        match body {
+            Stmt::Block(statements) => self.block_body(statements),
+            statement => self.block_body(std::slice::from_ref(statement)),
+        }
+    }
+
+    fn stmt(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression(expr) => {
+                let expr = self.expr(expr, Precedence::Comma);
+                self.line(&format!("{expr};"));
+            }
+            Stmt::Print(expr) => {
+                let expr = self.expr(expr, Precedence::Assignment);
+                self.line(&format!("$print({expr});"));
+            }
+            Stmt::VarDeclaration { name, initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => self.expr(initializer, Precedence::Assignment),
+                    None => "null".to_string(),
+                };
+                let (js_name, redeclared) = self.declare(name, statement);
+                let keyword = if redeclared { "" } else { "let " };
+                self.line(&format!("{keyword}{js_name} = {value};"));
+            }
+            Stmt::Block(statements) => {
+                self.line("{");
+                self.block_body(statements);
+                self.line("}");
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                let condition = self.condition(condition, Precedence::Assignment);
+                self.body(&format!("if ({condition})"), then_branch);
+                let mut else_branch = else_branch.as_deref();
+                while let Some(branch) = else_branch {
+                    match branch {
+                        Stmt::If { condition, then_branch, else_branch: next } => {
+                            let condition = self.condition(condition, Precedence::Assignment);
+                            self.body(&format!("}} else if ({condition})"), then_branch);
+                            else_branch = next.as_deref();
+                        }
+                        branch => {
+                            self.body("} else", branch);
+                            else_branch = None;
+                        }
+                    }
+                }
+                self.line("}");
+            }
+            Stmt::While { condition, body } => {
+                let condition = self.condition(condition, Precedence::Assignment);
+                self.body(&format!("while ({condition})"), body);
+                self.line("}");
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                self.for_loop(initializer.as_deref(), condition.as_ref(), increment.as_ref(), body)
+            }
+            Stmt::Function { name, params, body, .. } => {
+                let (js_name, redeclared) = self.declare(name, statement);
+                let keyword = if redeclared { "" } else { "let " };
+                let function = self.function(params, body);
+                let (first, rest) = function.split_once('\n').unwrap_or((&function, ""));
+                self.line(&format!("{keyword}{js_name} = {first}"));
+                self.out.push_str(rest);
+                self.out.pop();
+                self.out.push_str(";\n");
+            }
+            Stmt::Return { value } => {
+                let value = match (value, self.function) {
+                    (Some(value), _) => self.expr(value, Precedence::Comma),
+                    (None, Some(FunctionKind::Initializer)) => "this".to_string(),
+                    (None, _) => "null".to_string(),
+                };
+                self.line(&format!("return {value};"));
+            }
+            Stmt::Break => self.line("break;"),
+            Stmt::Continue => self.line("continue;"),
+            // Imported files are spliced in by linking the program before it is translated
+            Stmt::Import { .. } => {}
+            Stmt::Class { name, superclass, methods, .. } => {
+                self.class(statement, name, superclass.as_deref(), methods)
+            }
+        }
+    }
+
+    fn for_loop(&mut self, initializer: Option<&Stmt>, condition: Option<&Expr>, increment: Option<&Expr>, body: &Stmt) {
+        // A `let` in the loop header would give each iteration its own
+        // binding, but Lox closures all share the one variable, so it is
+        // declared in a block around the loop instead
+        let declares = matches!(initializer, Some(Stmt::VarDeclaration { .. }));
+        if declares {
+            self.line("{");
+            self.scopes.push(HashMap::new());
+            self.indent += 1;
+            self.stmt(initializer.expect("checked above"));
+        }
+        let initializer = match initializer {
+            Some(Stmt::Expression(expr)) => self.expr(expr, Precedence::Comma),
+            _ => String::new(),
+        };
+        let condition = match condition {
+            Some(condition) => format!(" {}", self.condition(condition, Precedence::Assignment)),
+            None => String::new(),
+        };
+        let increment = match increment {
+            Some(increment) => format!(" {}", self.expr(increment, Precedence::Comma)),
+            None => String::new(),
+        };
+        self.body(&format!("for ({initializer};{condition};{increment})"), body);
+        self.line("}");
+        if declares {
+            self.indent -= 1;
+            self.scopes.pop();
+            self.line("}");
+        }
+    }
+
+    fn class(&mut self, declaration: &Stmt, name: &str, superclass: Option<&str>, methods: &[Stmt]) {
+        let base = match superclass {
+            Some(superclass) => format!("$superclass({})", self.read(superclass)),
+            None => "$Instance".to_string(),
+        };
+        let (js_name, redeclared) = self.declare(name, declaration);
+        if redeclared {
+            self.line(&format!("{js_name} = class {js_name} extends {base} {{"));
+        } else {
+            self.line(&format!("class {js_name} extends {base} {{"));
+        }
+        self.classes.push(js_name);
+        self.indent += 1;
+        // Fields only give types in the typed dialect and have no runtime effect
+        for method in methods {
+            if let Stmt::Function { name, params, body, .. } = method {
+                let kind = if name == "init" { FunctionKind::Initializer } else { FunctionKind::Function };
+                let js_params: Vec<String> = params.iter().map(|param| binding(param)).collect();
+                self.line(&format!("{}({}) {{", method_key(name), js_params.join(", ")));
+                self.function_body(kind, params, body);
+                self.line("}");
+            }
+        }
+        self.indent -= 1;
+        self.classes.pop();
+        self.line(if redeclared { "};" } else { "}" });
+    }
+
+    /// Write an arrow function, returning its text; the first line is not
+    /// indented so it can follow other text
+    fn function(&mut self, params: &[String], body: &[Stmt]) -> String {
+        let js_params: Vec<String> = params.iter().map(|param| binding(param)).collect();
+        let start = self.out.len();
+        self.out.push_str(&format!("({}) => {{\n", js_params.join(", ")));
+        self.function_body(FunctionKind::Function, params, body);
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str("}\n");
+        self.out.split_off(start)
+    }
+
+    fn function_body(&mut self, kind: FunctionKind, params: &[String], body: &[Stmt]) {
+        let enclosing = self.function.replace(kind);
+        // Parameters share a scope with the body's top-level declarations
+        self.scopes.push(params.iter().map(|param| (param.clone(), binding(param))).collect());
+        self.indent += 1;
+        for statement in body {
+            self.stmt(statement);
+        }
+        if !matches!(body.last(), Some(Stmt::Return { .. })) {
+            self.line(if kind == FunctionKind::Initializer { "return this;" } else { "return null;" });
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+        self.function = enclosing;
+    }
+
+    /// Write an expression whose truthiness is tested. Comparisons already
+    /// yield a boolean, so only other expressions go through `$truthy`.
+    fn condition(&mut self, expr: &Expr, context: Precedence) -> String {
+        let boolean = match expr {
+            Expr::Literal(Value::Bool(_)) | Expr::Unary { operator: UnaryOp::Not, .. } => true,
+            Expr::Binary { operator, .. } => matches!(
+                operator,
+                BinaryOp::Greater
+                    | BinaryOp::GreaterEqual
+                    | BinaryOp::Less
+                    | BinaryOp::LessEqual
+                    | BinaryOp::Equal
+                    | BinaryOp::NotEqual
+            ),
+            Expr::Grouping(expr) => return self.condition(expr, context),
+            _ => false,
+        };
+        if boolean { self.expr(expr, context) } else { format!("$truthy({})", self.expr(expr, Precedence::Assignment)) }
+    }
+
+    fn expr(&mut self, expr: &Expr, context: Precedence) -> String {
+        let (text, precedence) = self.expr_with_precedence(expr);
+        if precedence < context { format!("({text})") } else { text }
+    }
+
+    fn expr_with_precedence(&mut self, expr: &Expr) -> (String, Precedence) {
+        match expr {
+            Expr::Literal(value) => match value {
+                Value::Nil => ("null".to_string(), Precedence::Primary),
+                Value::Bool(value) => (value.to_string(), Precedence::Primary),
+                Value::Number(value) if value.is_sign_negative() => (number_literal(*value), Precedence::Unary),
+                Value::Number(value) => (number_literal(*value), Precedence::Primary),
+                Value::String(value) => (string_literal(value), Precedence::Primary),
+            },
+            Expr::Variable(name) => (self.read(name), Precedence::Primary),
+            Expr::Grouping(expr) => self.expr_with_precedence(expr),
+            Expr::Binary { left, operator, right } => self.binary(left, operator, right),
+            Expr::Unary { operator: UnaryOp::Minus, operand } if matches!(**operand, Expr::Literal(Value::Number(_))) => {
+                (format!("-{}", self.expr(operand, Precedence::Primary)), Precedence::Unary)
+            }
+            Expr::Unary { operator: UnaryOp::Minus, operand } => {
+                (format!("$neg({})", self.expr(operand, Precedence::Assignment)), Precedence::Primary)
+            }
+            Expr::Unary { operator: UnaryOp::Not, operand } => {
+                (format!("!{}", self.condition(operand, Precedence::Unary)), Precedence::Unary)
+            }
+            Expr::Assignment { name, value } => self.assign(name, value),
+            Expr::Call { callee, arguments } => {
+                let mut args = vec![self.expr(callee, Precedence::Assignment)];
+                for argument in arguments {
+                    args.push(self.expr(argument, Precedence::Assignment));
+                }
+                (format!("$call({})", args.join(", ")), Precedence::Primary)
+            }
+            Expr::Get { object, name } => {
+                let object = self.expr(object, Precedence::Assignment);
+                (format!("$get({object}, {})", string_literal(name)), Precedence::Primary)
+            }
+            Expr::Set { object, name, value } => {
+                let object = self.expr(object, Precedence::Assignment);
+                let value = self.expr(value, Precedence::Assignment);
+                (format!("$set({object}, {}, {value})", string_literal(name)), Precedence::Primary)
+            }
+            Expr::This => ("this".to_string(), Precedence::Primary),
+            Expr::Super { method } => {
+                let class = self.classes.last().cloned().unwrap_or_else(|| "undefined".to_string());
+                (format!("$super({class}, this, {})", string_literal(method)), Precedence::Primary)
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                let condition = self.condition(condition, Precedence::Assignment);
+                let then_branch = self.expr(then_branch, Precedence::Assignment);
+                let else_branch = self.expr(else_branch, Precedence::Conditional);
+                (format!("{condition} ? {then_branch} : {else_branch}"), Precedence::Conditional)
+            }
+            Expr::Lambda { params, body } => {
+                let function = self.function(params, body);
+                (function.trim_end().to_string(), Precedence::Assignment)
+            }
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, operator: &BinaryOp, right: &Expr) -> (String, Precedence) {
+        let helper = match operator {
+            BinaryOp::Add => "$add",
+            BinaryOp::Subtract => "$sub",
+            BinaryOp::Multiply => "$mul",
+            BinaryOp::Divide => "$div",
+            BinaryOp::Greater => "$gt",
+            BinaryOp::GreaterEqual => "$ge",
+            BinaryOp::Less => "$lt",
+            BinaryOp::LessEqual => "$le",
+            BinaryOp::Equal | BinaryOp::NotEqual => {
+                let operator = if *operator == BinaryOp::Equal { "===" } else { "!==" };
+                let left = self.expr(left, Precedence::Equality);
+                let right = self.expr(right, Precedence::Unary);
+                return (format!("{left} {operator} {right}"), Precedence::Equality);
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                let helper = if *operator == BinaryOp::And { "$and" } else { "$or" };
+                let left = self.expr(left, Precedence::Assignment);
+                let right = self.expr(right, Precedence::Assignment);
+                return (format!("{helper}({left}, () => {right})"), Precedence::Primary);
+            }
+            BinaryOp::Comma => {
+                let left = self.expr(left, Precedence::Comma);
+                let right = self.expr(right, Precedence::Assignment);
+                return (format!("{left}, {right}"), Precedence::Comma);
+            }
+        };
+        let left = self.expr(left, Precedence::Assignment);
+        let right = self.expr(right, Precedence::Assignment);
+        (format!("{helper}({left}, {right})"), Precedence::Primary)
+    }
+}
@@ -0,0 +1,323 @@
+//! Translation of Lox programs to JavaScript
+//!
+//! `transpile` turns a parsed program into a standalone ES2020 script that
+//! prints the same output as the program would. The script starts with a
+//! small runtime (`RUNTIME`) of helpers whose names begin with `$`, which no
+//! Lox name can contain, followed by the program itself inside a block.
+//!
+//! The parts of Lox that JavaScript does differently go through the runtime:
+//! only `nil` and `false` are falsey, `+` accepts two numbers or two strings,
+//! arithmetic and comparisons need numbers, calls check the number of
+//! arguments, and `print` writes numbers without a trailing `.0` and
+//! functions, classes and instances the way Lox does. Mistakes are thrown as
+//! a `RuntimeError` with the message a Lox interpreter would give.
+//!
+//! Everything else maps onto the closest JavaScript construct: variables are
+//! `let` bindings, functions and lambdas are arrow functions (so closures
+//! capture `this` the way Lox methods expect), and classes are JavaScript
+//! classes whose constructor runs `init`. Scopes are resolved before the
+//! program is written, so a local gets a fresh name where its block uses the
+//! name earlier for something else, and using a global that is not defined
+//! (yet) fails with Lox's `Undefined variable` error.
+//!
+//! `import` statements are skipped: a program split across files should be
+//! linked into one with `lox-loader` before it is translated.
+//!
+//! # Example
+//!
+//! ```
+//! let program = nom_lox::parse_program("var greeting = \"hi\"; print greeting + \"!\";").unwrap();
+//! let script = lox_js::transpile(&program);
+//! assert!(script.ends_with("{\n  let greeting = \"hi\";\n  $print($add(greeting, \"!\"));\n}\n"));
+//! ```
+
+use lox_ast::Program;
+
+mod emit;
+mod resolve;
+
+/// The helpers every translated program starts with
+pub const RUNTIME: &str = include_str!("runtime.js");
+
+/// Translate `program` to a JavaScript script, runtime included
+pub fn transpile(program: &Program) -> String {
+    format!("{RUNTIME}\n{}", transpile_without_runtime(program))
+}
+
+/// Translate `program` without the runtime, for scripts that load `RUNTIME`
+/// separately
+pub fn transpile_without_runtime(program: &Program) -> String {
+    emit::Emitter::new().program(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox_ast::LanguageOptions;
+    use rquickjs::{Context, Runtime};
+
+    /// Run a translated program, returning what it printed and the message of
+    /// the error it threw, if any
+    fn run_with(source: &str, options: &LanguageOptions) -> (String, Option<String>) {
+        let program = nom_lox::parse_program_with(source, options).unwrap();
+        let script = transpile(&program);
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            ctx.eval::<(), _>("globalThis.output = []; globalThis.console = { log: (line) => output.push(line) };").unwrap();
+            let error = match ctx.eval::<(), _>(script.as_str()) {
+                Ok(()) => None,
+                Err(rquickjs::Error::Exception) => {
+                    let exception = ctx.catch();
+                    let message = exception.as_exception().and_then(|exception| exception.message());
+                    Some(message.unwrap_or_else(|| format!("{exception:?}")))
+                }
+                Err(error) => panic!("{error}\n{script}"),
+            };
+            (ctx.eval::<String, _>("output.join('\\n')").unwrap(), error)
+        })
+    }
+
+    fn run(source: &str) -> String {
+        let (output, error) = run_with(source, &LanguageOptions::default());
+        assert_eq!(error, None);
+        output
+    }
+
+    fn run_error(source: &str) -> String {
+        run_with(source, &LanguageOptions::default()).1.expect("the program should fail")
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let source = r#"
+            if (0) print "0 is truthy";
+            if ("") print "empty string is truthy";
+            if (nil) print "unreachable"; else print "nil is falsey";
+            print !false;
+            print !0;
+            print nil or "default";
+            print 0 and "zero";
+            print false and undefined;
+        "#;
+        assert_eq!(run(source), "0 is truthy\nempty string is truthy\nnil is falsey\ntrue\nfalse\ndefault\nzero\nfalse");
+    }
+
+    #[test]
+    fn test_operators_check_types() {
+        assert_eq!(run(r#"print 1 + 2; print "a" + "b"; print 1 == 1; print "1" == 1; print nil == nil;"#), "3\nab\ntrue\nfalse\ntrue");
+        assert_eq!(run_error(r#"print "a" + 1;"#), "Operands must be two numbers or two strings.");
+        assert_eq!(run_error(r#"print 1 + nil;"#), "Operands must be two numbers or two strings.");
+        assert_eq!(run_error(r#"print "a" < "b";"#), "Operands must be numbers.");
+        assert_eq!(run_error(r#"print -"a";"#), "Operand must be a number.");
+    }
+
+    #[test]
+    fn test_print_formatting() {
+        let source = r#"
+            print 1.0;
+            print 2.5;
+            print 10 / 4;
+            print -0;
+            print 1 / 0;
+            print nil;
+            print true;
+            print "text";
+            fun f() {}
+            print f;
+            print clock;
+            class C {}
+            print C;
+            print C();
+        "#;
+        assert_eq!(run(source), "1\n2.5\n2.5\n-0\nInfinity\nnil\ntrue\ntext\n<fn f>\n<native fn>\nC\nC instance");
+    }
+
+    #[test]
+    fn test_classes() {
+        let source = r#"
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                    if (x < 0) return;
+                    this.quadrant = "first";
+                }
+                sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);
+            print p.sum();
+            print p.quadrant;
+            print p.init(3, 4) == p;
+            print p.x;
+            var sum = p.sum;
+            print sum();
+            print Point(-1, 0).x;
+        "#;
+        assert_eq!(run(source), "3\nfirst\ntrue\n3\n7\n-1");
+        assert_eq!(run_error("class A { init(a) {} } A();"), "Expected 1 arguments but got 0.");
+        assert_eq!(run_error("class A {} print A().missing;"), "Undefined property 'missing'.");
+        assert_eq!(run_error("var a = 1; print a.b;"), "Only instances have properties.");
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let source = r#"
+            class A {
+                init(name) { this.name = name; }
+                greet() { return "A says hi to " + this.name; }
+            }
+            class B < A {
+                greet() {
+                    var parent = fun () { return super.greet(); };
+                    return "B and " + parent();
+                }
+            }
+            class C < B {}
+            print C("you").greet();
+            print C("you") == C("you");
+        "#;
+        let options = LanguageOptions { lambdas: true, ..LanguageOptions::default() };
+        let (output, error) = run_with(source, &options);
+        assert_eq!(error, None);
+        assert_eq!(output, "B and A says hi to you\nfalse");
+        assert_eq!(run_error("var A = 1; class B < A {}"), "Superclass must be a class.");
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = r#"
+            fun makeCounter() {
+                var count = 0;
+                fun increment() {
+                    count = count + 1;
+                    return count;
+                }
+                return increment;
+            }
+            var counter = makeCounter();
+            counter();
+            print counter();
+            print makeCounter()();
+
+            var closures = nil;
+            for (var i = 0; i < 3; i = i + 1) {
+                fun show() { print i; }
+                if (i == 0) closures = show;
+            }
+            closures();
+
+            var a = "global";
+            {
+                fun showA() { print a; }
+                showA();
+                var a = "block";
+                showA();
+                print a;
+            }
+        "#;
+        assert_eq!(run(source), "2\n1\n3\nglobal\nglobal\nblock");
+    }
+
+    #[test]
+    fn test_locals_declared_after_a_use_of_their_name() {
+        let source = r#"
+            fun f() { print a; var a = "local"; print a; }
+            var a = "global";
+            f();
+            {
+                fun show() { print a; }
+                show();
+                var a = "block";
+                show();
+                print a;
+            }
+        "#;
+        assert_eq!(run(source), "global
+local
+global
+global
+block");
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        assert_eq!(run_error("print missing;"), "Undefined variable 'missing'.");
+        assert_eq!(run_error("missing = 1;"), "Undefined variable 'missing'.");
+        assert_eq!(run_error("fun f() { print later; } f(); var later = 1;"), "Undefined variable 'later'.");
+        assert_eq!(run_error("fun f() { later = 2; } f(); var later = 1;"), "Undefined variable 'later'.");
+        assert_eq!(run_error("{ fun f() { print a; } f(); var a = 1; }"), "Undefined variable 'a'.");
+        assert_eq!(run_error("print Object;"), "Undefined variable 'Object'.");
+
+        let source = r#"
+            fun f() { print later; later = later + 1; }
+            var later = 1;
+            f();
+            print later;
+            print clock;
+            var clock = "mine";
+            print clock;
+        "#;
+        assert_eq!(run(source), "1
+2
+<native fn>
+mine");
+    }
+
+    #[test]
+    fn test_redeclaration_and_reserved_names() {
+        let source = r#"
+            var a = 1;
+            var a = a + 1;
+            print a;
+            var new = "new";
+            var constructor = "c";
+            class function { constructor() { return "method"; } }
+            print new + constructor + function().constructor();
+            print function;
+            fun this_is_fine(undefined) { return undefined; }
+            print this_is_fine(nil);
+        "#;
+        assert_eq!(run(source), "2\nnewcmethod\nfunction\nnil");
+    }
+
+    #[test]
+    fn test_calls_check_arity() {
+        assert_eq!(run_error("fun f(a, b) {} f(1);"), "Expected 2 arguments but got 1.");
+        assert_eq!(run_error(r#""text"();"#), "Can only call functions and classes.");
+    }
+
+    #[test]
+    fn test_language_extensions() {
+        let source = r#"
+            var total = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+                if (i == 2) continue;
+                if (i == 5) break;
+                total = total + i;
+            }
+            print total;
+            print total > 5 ? "big" : "small";
+            print (1, 2);
+            var twice = fun (f, x) { return f(f(x)); };
+            print twice(fun (x) { return x * 3; }, 2);
+        "#;
+        let (output, error) = run_with(source, &LanguageOptions::EXTENDED);
+        assert_eq!(error, None);
+        assert_eq!(output, "8\nbig\n2\n18");
+    }
+
+    #[test]
+    fn test_examples() {
+        let cases = [
+            ("hello.lox", "Hello, world!"),
+            ("arithmetic.lox", "30\n55"),
+            ("variables.lox", "Lox\n1\ntrue\nSuper Lox"),
+        ];
+        for (file, expected) in cases {
+            let path = format!("{}/../examples/{file}", env!("CARGO_MANIFEST_DIR"));
+            let source = std::fs::read_to_string(path).unwrap();
+            assert_eq!(run(&source), expected, "{file}");
+        }
+    }
+}
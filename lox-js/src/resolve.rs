@@ -0,0 +1,187 @@
+//! Scope resolution ahead of translation
+//!
+//! Lox resolves a name to the declarations before it, but a JavaScript `let`
+//! covers its whole block, so code earlier in the block would see the local
+//! declared after it (and fail on reading it before its `let` has run). This
+//! pass finds the locals whose names are used earlier in their scope, which
+//! the emitter gives fresh names, and the globals that are used before their
+//! first declaration, which it declares up front as unset so that reading
+//! them too early fails the way it does in Lox.
+
+use std::collections::HashSet;
+
+use lox_ast::{Expr, Program, Stmt};
+
+/// Globals the runtime defines before the program runs
+pub(crate) const NATIVES: &[&str] = &["clock"];
+
+/// What the emitter needs to know about the program's scopes
+#[derive(Debug, Default)]
+pub(crate) struct Resolution {
+    /// Local declarations whose name means something else earlier in their scope
+    renamed: HashSet<*const Stmt>,
+    /// Globals used before their first declaration, in the order they are declared
+    pub(crate) hoisted: Vec<String>,
+}
+
+impl Resolution {
+    pub(crate) fn new(program: &Program) -> Self {
+        let globals = Scope { declared: NATIVES.iter().map(|name| name.to_string()).collect(), ..Scope::default() };
+        let mut resolver = Resolver { scopes: vec![globals], resolution: Resolution::default() };
+        resolver.stmts(&program.statements);
+        resolver.resolution
+    }
+
+    /// Whether a local declaration needs a name of its own
+    pub(crate) fn is_renamed(&self, declaration: &Stmt) -> bool {
+        self.renamed.contains(&std::ptr::from_ref(declaration))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    declared: HashSet<String>,
+    /// Names used so far in the scope, or in scopes within it, that it does
+    /// not declare
+    used: HashSet<String>,
+}
+
+struct Resolver {
+    /// Scopes from the global one outwards
+    scopes: Vec<Scope>,
+    resolution: Resolution,
+}
+
+impl Resolver {
+    /// Declare `name` in the innermost scope; `declaration` is `None` for parameters
+    fn declare(&mut self, name: &str, declaration: Option<&Stmt>) {
+        let is_global = self.scopes.len() == 1;
+        let scope = self.scopes.last_mut().expect("the global scope is never popped");
+        if !scope.declared.insert(name.to_string()) || !scope.used.contains(name) {
+            return;
+        }
+        if is_global {
+            self.resolution.hoisted.push(name.to_string());
+        } else if let Some(declaration) = declaration {
+            self.resolution.renamed.insert(std::ptr::from_ref(declaration));
+        }
+    }
+
+    /// Note a use of `name` in every scope up to the one declaring it
+    fn use_name(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if scope.declared.contains(name) {
+                return;
+            }
+            scope.used.insert(name.to_string());
+        }
+    }
+
+    fn scoped(&mut self, params: &[String], body: &[Stmt]) {
+        self.scopes.push(Scope::default());
+        for param in params {
+            self.declare(param, None);
+        }
+        self.stmts(body);
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.expr(expr),
+            Stmt::VarDeclaration { name, initializer, .. } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.declare(name, Some(stmt));
+            }
+            Stmt::Block(stmts) => self.scoped(&[], stmts),
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.scoped(&[], std::slice::from_ref(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.scoped(&[], std::slice::from_ref(else_branch));
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.expr(condition);
+                self.scoped(&[], std::slice::from_ref(body));
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                self.scopes.push(Scope::default());
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.scoped(&[], std::slice::from_ref(body));
+                self.scopes.pop();
+            }
+            Stmt::Function { name, params, body, .. } => {
+                self.declare(name, Some(stmt));
+                self.scoped(params, body);
+            }
+            Stmt::Return { value } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } => {}
+            Stmt::Class { name, superclass, methods, .. } => {
+                if let Some(superclass) = superclass {
+                    self.use_name(superclass);
+                }
+                self.declare(name, Some(stmt));
+                for method in methods {
+                    if let Stmt::Function { params, body, .. } = method {
+                        self.scoped(params, body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) | Expr::This | Expr::Super { .. } => {}
+            Expr::Variable(name) => self.use_name(name),
+            Expr::Assignment { name, value } => {
+                self.use_name(name);
+                self.expr(value);
+            }
+            Expr::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { operand, .. } => self.expr(operand),
+            Expr::Grouping(inner) => self.expr(inner),
+            Expr::Call { callee, arguments } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expr(object),
+            Expr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            Expr::Lambda { params, body } => self.scoped(params, body),
+        }
+    }
+}
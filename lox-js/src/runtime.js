@@ -0,0 +1,132 @@
+"use strict";
+
+// Runtime support for Lox programs translated to JavaScript. Lox names cannot
+// contain `$`, so every helper starts with one to stay out of their way.
+
+class $RuntimeError extends Error {
+  constructor(message) {
+    super(message);
+    this.name = "RuntimeError";
+  }
+}
+
+// Base class of every Lox class; constructing an instance runs its `init`
+class $Instance {
+  constructor(...args) {
+    const init = $method(this, "init");
+    if (init !== undefined) init.apply(this, args);
+  }
+}
+
+const $has = (object, key) => Object.prototype.hasOwnProperty.call(object, key);
+
+// Method names are stored under a different key where JavaScript gives the name a meaning
+const $key = (name) => (name === "constructor" ? "constructor$" : name);
+
+// Find a method on a prototype or the ones it inherits from, unbound
+function $lookup(proto, name) {
+  const key = $key(name);
+  for (; proto !== $Instance.prototype; proto = Object.getPrototypeOf(proto)) {
+    if ($has(proto, key)) return proto[key];
+  }
+  return undefined;
+}
+
+const $method = (instance, name) => $lookup(Object.getPrototypeOf(instance), name);
+
+const $isClass = (value) => typeof value === "function" && value.prototype instanceof $Instance;
+
+// The value of a global that is used before its declaration has run
+const $unset = Symbol("unset");
+
+function $undefined(name) {
+  throw new $RuntimeError(`Undefined variable '${name}'.`);
+}
+
+// Read a global that may not be defined yet
+const $check = (value, name) => (value === $unset ? $undefined(name) : value);
+
+// The value to assign to a global that may not be defined yet
+const $assign = (current, name, value) => (current === $unset ? $undefined(name) : value);
+
+// Only `nil` and `false` are falsey
+const $truthy = (value) => value !== null && value !== false;
+
+function $stringify(value) {
+  if (value === null) return "nil";
+  if (typeof value === "number") return Object.is(value, -0) ? "-0" : String(value);
+  if ($isClass(value)) return $name(value);
+  if (typeof value === "function") return value.$native ? "<native fn>" : `<fn ${$name(value) || "lambda"}>`;
+  if (typeof value === "object") return `${$name(Object.getPrototypeOf(value).constructor)} instance`;
+  return String(value);
+}
+
+// The Lox name of a function or class, undoing the renaming of reserved words and
+// shadowing variables
+const $name = (f) => f.name.replace(/^bound /, "").replace(/\$\d*$/, "");
+
+const $print = (value) => console.log($stringify(value));
+
+function $numbers(a, b) {
+  if (typeof a !== "number" || typeof b !== "number") throw new $RuntimeError("Operands must be numbers.");
+}
+
+function $add(a, b) {
+  if (typeof a === "number" && typeof b === "number") return a + b;
+  if (typeof a === "string" && typeof b === "string") return a + b;
+  throw new $RuntimeError("Operands must be two numbers or two strings.");
+}
+const $sub = (a, b) => ($numbers(a, b), a - b);
+const $mul = (a, b) => ($numbers(a, b), a * b);
+const $div = (a, b) => ($numbers(a, b), a / b);
+const $gt = (a, b) => ($numbers(a, b), a > b);
+const $ge = (a, b) => ($numbers(a, b), a >= b);
+const $lt = (a, b) => ($numbers(a, b), a < b);
+const $le = (a, b) => ($numbers(a, b), a <= b);
+
+function $neg(a) {
+  if (typeof a !== "number") throw new $RuntimeError("Operand must be a number.");
+  return -a;
+}
+
+// `and` and `or` yield one of their operands, and only evaluate the right one when needed
+const $and = (left, right) => ($truthy(left) ? right() : left);
+const $or = (left, right) => ($truthy(left) ? left : right());
+
+function $call(callee, ...args) {
+  if (typeof callee !== "function") throw new $RuntimeError("Can only call functions and classes.");
+  const arity = $isClass(callee) ? (callee.prototype.init?.length ?? 0) : callee.length;
+  if (args.length !== arity) throw new $RuntimeError(`Expected ${arity} arguments but got ${args.length}.`);
+  return $isClass(callee) ? new callee(...args) : callee(...args);
+}
+
+function $get(object, name) {
+  if (!(object instanceof $Instance)) throw new $RuntimeError("Only instances have properties.");
+  if ($has(object, name)) return object[name];
+  const method = $method(object, name);
+  if (method === undefined) throw new $RuntimeError(`Undefined property '${name}'.`);
+  return method.bind(object);
+}
+
+function $set(object, name, value) {
+  if (!(object instanceof $Instance)) throw new $RuntimeError("Only instances have fields.");
+  Object.defineProperty(object, name, { value, writable: true, enumerable: true, configurable: true });
+  return value;
+}
+
+// `super.name` in a method of `cls`, bound to the current instance
+function $super(cls, instance, name) {
+  const method = $lookup(Object.getPrototypeOf(cls.prototype), name);
+  if (method === undefined) throw new $RuntimeError(`Undefined property '${name}'.`);
+  return method.bind(instance);
+}
+
+function $superclass(value) {
+  if (value !== $Instance && !$isClass(value)) throw new $RuntimeError("Superclass must be a class.");
+  return value;
+}
+
+const $native = (f) => Object.assign(f, { $native: true });
+
+// A program may declare its own `clock`, which replaces this one
+let clock = $native(() => Date.now() / 1000);
@@ -0,0 +1,196 @@
+//! Linter for Lox programs
+//!
+//! Walks a `lox_ast::Program` and reports [`Warning`]s, each tagged with the
+//! [`Rule`] that produced it. Rules can be turned on and off through
+//! [`LintConfig`], and [`lint_source`] additionally honours suppression
+//! comments in the program's source:
+//!
+//! ```lox
+//! // lox-lint: allow(unused-parameter, unused-this)
+//! class Greeter {
+//!   greet(name) { print "hello"; }
+//! }
+//! ```
+//!
+//! A suppression comment applies to the statement (or method) directly after
+//! it and everything inside that statement. `// lox-lint: allow-file(...)`
+//! applies to the whole file wherever it appears.
+//!
+//! # Example
+//!
+//! ```
+//! use lox_lint::{LintConfig, Rule, lint};
+//!
+//! let program = nom_lox::parse_program("fun f(x) { var y = 1; }").unwrap();
+//! let config = LintConfig::default().disable(Rule::UnusedParameter);
+//! let warnings = lint(&program, &config);
+//! assert_eq!(warnings.len(), 1);
+//! assert_eq!(warnings[0].rule, Rule::UnusedVariable);
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use lox_ast::{LexOptions, Program, Span};
+
+mod linter;
+mod suppress;
+
+/// A lint rule, identified in configuration and suppression comments by its ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// A local variable that is never read
+    UnusedVariable,
+    /// A function, method or lambda parameter that is never read
+    UnusedParameter,
+    /// A local variable or parameter with the same name as one in an enclosing scope
+    ShadowedVariable,
+    /// Statements after a `return`, `break` or `continue` in the same block
+    UnreachableCode,
+    /// Assigning a variable or property to itself, as in `x = x`
+    SelfAssignment,
+    /// A comparison between two literals, which is always true or always false
+    ConstantComparison,
+    /// A block with no statements
+    EmptyBlock,
+    /// A top-level `var` with the name of an earlier top-level declaration
+    TopLevelRedeclaration,
+    /// A method that never uses `this` or `super`
+    UnusedThis,
+    /// A method that overrides a superclass method with a different number of parameters
+    OverrideArity,
+}
+
+impl Rule {
+    /// Every rule, in the order they are documented
+    pub const ALL: &[Rule] = &[
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::ShadowedVariable,
+        Rule::UnreachableCode,
+        Rule::SelfAssignment,
+        Rule::ConstantComparison,
+        Rule::EmptyBlock,
+        Rule::TopLevelRedeclaration,
+        Rule::UnusedThis,
+        Rule::OverrideArity,
+    ];
+
+    /// The rule's ID, as used in `// lox-lint: allow(...)` comments
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::ShadowedVariable => "shadowed-variable",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::SelfAssignment => "self-assignment",
+            Rule::ConstantComparison => "constant-comparison",
+            Rule::EmptyBlock => "empty-block",
+            Rule::TopLevelRedeclaration => "top-level-redeclaration",
+            Rule::UnusedThis => "unused-this",
+            Rule::OverrideArity => "override-arity",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Rule::ALL.iter()
+            .copied()
+            .find(|rule| rule.id() == id)
+            .ok_or_else(|| format!("Unknown lint rule '{}'", id))
+    }
+}
+
+/// A problem found by the linter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub rule: Rule,
+    pub message: String,
+    /// The statement the warning is about, when linted with [`lint_source`]
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning[{}]: {}", self.rule, self.message)
+    }
+}
+
+/// Which rules to run; every rule is enabled by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintConfig {
+    disabled: HashSet<Rule>,
+    /// Lexical extensions the source was parsed with, so that [`lint_source`]
+    /// reads its strings and comments the same way the parser did
+    pub lex_options: LexOptions,
+}
+
+impl LintConfig {
+    /// Turn a rule on
+    pub fn enable(mut self, rule: Rule) -> Self {
+        self.disabled.remove(&rule);
+        self
+    }
+
+    /// Turn a rule off
+    pub fn disable(mut self, rule: Rule) -> Self {
+        self.disabled.insert(rule);
+        self
+    }
+
+    /// Check whether a rule is turned on
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+/// Lint a program, reporting a warning for every enabled rule it breaks
+pub fn lint(program: &Program, config: &LintConfig) -> Vec<Warning> {
+    linter::run(program, config, &suppress::Suppressions::default())
+}
+
+/// Lint a program parsed from `source`, honouring its `// lox-lint: allow(...)` comments
+/// and giving each warning the span of its statement
+///
+/// Unknown rule IDs in a suppression comment are ignored.
+pub fn lint_source(source: &str, program: &Program, config: &LintConfig) -> Vec<Warning> {
+    let suppressions = suppress::Suppressions::scan(source, &config.lex_options);
+    linter::run(program, config, &suppressions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_ids_round_trip() {
+        for rule in Rule::ALL {
+            assert_eq!(rule.id().parse::<Rule>(), Ok(*rule));
+        }
+        assert!("no-such-rule".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_config_enable_disable() {
+        let config = LintConfig::default().disable(Rule::EmptyBlock).disable(Rule::UnusedThis);
+        assert!(!config.is_enabled(Rule::EmptyBlock));
+        assert!(config.is_enabled(Rule::UnusedVariable));
+        assert!(config.enable(Rule::EmptyBlock).is_enabled(Rule::EmptyBlock));
+    }
+
+    #[test]
+    fn test_warning_display() {
+        let warning = Warning { rule: Rule::EmptyBlock, message: "empty block".to_string(), span: None };
+        assert_eq!(warning.to_string(), "warning[empty-block]: empty block");
+    }
+}
@@ -0,0 +1,555 @@
+//! The walk over the AST that applies every rule
+
+use std::collections::HashMap;
+
+use lox_ast::optimize::optimize_expr;
+use lox_ast::{BinaryOp, Expr, Program, Span, Stmt, Value};
+
+use crate::suppress::Suppressions;
+use crate::{LintConfig, Rule, Warning};
+
+/// What a name in scope was declared as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+}
+
+#[derive(Debug)]
+struct Binding {
+    name: String,
+    kind: Kind,
+    used: bool,
+    /// The statement declaring the binding, where unused warnings point
+    span: Option<Span>,
+    /// Where the binding was declared, such as `'f'`, for messages
+    owner: String,
+}
+
+/// A class seen so far, for checking overrides
+#[derive(Debug)]
+struct Class {
+    superclass: Option<String>,
+    arities: HashMap<String, usize>,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    suppressions: &'a Suppressions,
+    warnings: Vec<Warning>,
+    /// Scopes from the global one outwards; only local scopes report unused names
+    scopes: Vec<Vec<Binding>>,
+    /// Spans of the statements being walked, innermost last
+    statements: Vec<Option<Span>>,
+    /// Index in pre-order of the next statement the walk meets
+    next_statement: usize,
+    /// Names of the functions being walked, innermost last
+    functions: Vec<String>,
+    /// Whether each method being walked has used `this` or `super`
+    methods: Vec<bool>,
+    classes: HashMap<String, Class>,
+}
+
+/// Lint `program`, dropping warnings for disabled or suppressed rules
+pub(crate) fn run(program: &Program, config: &LintConfig, suppressions: &Suppressions) -> Vec<Warning> {
+    let mut linter = Linter {
+        config,
+        suppressions,
+        warnings: Vec::new(),
+        scopes: vec![Vec::new()],
+        statements: Vec::new(),
+        next_statement: 0,
+        functions: Vec::new(),
+        methods: Vec::new(),
+        classes: HashMap::new(),
+    };
+    linter.stmts(&program.statements);
+    linter.warnings
+}
+
+impl Linter<'_> {
+    /// Warn about the statement being walked
+    fn warn(&mut self, rule: Rule, message: String) {
+        self.warn_at(rule, message, self.span());
+    }
+
+    fn warn_at(&mut self, rule: Rule, message: String, span: Option<Span>) {
+        if self.config.is_enabled(rule) && !self.suppressions.allows(rule, span) {
+            self.warnings.push(Warning { rule, message, span });
+        }
+    }
+
+    /// The span of the statement being walked, if the source was scanned
+    fn span(&self) -> Option<Span> {
+        self.statements.last().copied().flatten()
+    }
+
+    /// Start walking a statement or method; pair with `end_statement`
+    fn begin_statement(&mut self) {
+        self.statements.push(self.suppressions.statement_span(self.next_statement));
+        self.next_statement += 1;
+    }
+
+    fn end_statement(&mut self) {
+        self.statements.pop();
+    }
+
+    /// Description of where the walk is, for messages
+    fn owner(&self) -> String {
+        match self.functions.last() {
+            Some(name) => format!("'{}'", name),
+            None => "the top level".to_string(),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self) {
+        let Some(bindings) = self.scopes.pop() else { return };
+        for binding in bindings.into_iter().filter(|binding| !binding.used) {
+            let (rule, message) = match binding.kind {
+                Kind::Variable => (
+                    Rule::UnusedVariable,
+                    format!("local variable '{}' in {} is never used", binding.name, binding.owner),
+                ),
+                Kind::Parameter => (
+                    Rule::UnusedParameter,
+                    format!("parameter '{}' of {} is never used", binding.name, binding.owner),
+                ),
+                Kind::Function | Kind::Class => continue,
+            };
+            self.warn_at(rule, message, binding.span);
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: Kind) {
+        let is_global = self.scopes.len() == 1;
+        if is_global {
+            if kind == Kind::Variable && self.scopes[0].iter().any(|binding| binding.name == name) {
+                self.warn(
+                    Rule::TopLevelRedeclaration,
+                    format!("'{}' is already declared at the top level", name),
+                );
+            }
+        } else if matches!(kind, Kind::Variable | Kind::Parameter)
+            && self.scopes[..self.scopes.len() - 1].iter().flatten().any(|binding| binding.name == name)
+        {
+            self.warn(
+                Rule::ShadowedVariable,
+                format!("'{}' in {} shadows a variable in an enclosing scope", name, self.owner()),
+            );
+        }
+
+        let binding = Binding {
+            name: name.to_string(),
+            kind,
+            used: false,
+            span: self.span(),
+            owner: self.owner(),
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(binding);
+        }
+    }
+
+    /// Mark the innermost binding of `name` as read
+    fn use_name(&mut self, name: &str) {
+        if let Some(binding) = self.scopes.iter_mut().rev().flatten().find(|binding| binding.name == name) {
+            binding.used = true;
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        let mut reported = false;
+        for (i, stmt) in stmts.iter().enumerate() {
+            if !reported && i > 0 && let Some(exit) = exit_keyword(&stmts[i - 1]) {
+                // Point at the first unreachable statement, which is walked next
+                let span = self.suppressions.statement_span(self.next_statement);
+                self.warn_at(
+                    Rule::UnreachableCode,
+                    format!("unreachable code after '{}' in {}", exit, self.owner()),
+                    span,
+                );
+                reported = true;
+            }
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.begin_statement();
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.expr(expr),
+            Stmt::VarDeclaration { name, initializer, .. } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.declare(name, Kind::Variable);
+            }
+            Stmt::Block(stmts) => {
+                if stmts.is_empty() {
+                    self.warn(Rule::EmptyBlock, format!("empty block in {}", self.owner()));
+                }
+                self.begin_scope();
+                self.stmts(stmts);
+                self.end_scope();
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.stmt(body);
+                self.end_scope();
+            }
+            Stmt::Function { name, params, body, .. } => {
+                self.declare(name, Kind::Function);
+                self.function(name, params, body);
+            }
+            Stmt::Return { value } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } => {}
+            Stmt::Class { name, superclass, methods, .. } => {
+                self.declare(name, Kind::Class);
+                if let Some(superclass) = superclass {
+                    self.use_name(superclass);
+                }
+                self.class(name, superclass.as_deref(), methods);
+            }
+        }
+        self.end_statement();
+    }
+
+    fn function(&mut self, name: &str, params: &[String], body: &[Stmt]) {
+        self.functions.push(name.to_string());
+        self.begin_scope();
+        for param in params {
+            self.declare(param, Kind::Parameter);
+        }
+        self.stmts(body);
+        self.end_scope();
+        self.functions.pop();
+    }
+
+    fn class(&mut self, name: &str, superclass: Option<&str>, methods: &[Stmt]) {
+        let arities: HashMap<String, usize> = methods
+            .iter()
+            .filter_map(|method| match method {
+                Stmt::Function { name, params, .. } => Some((name.clone(), params.len())),
+                _ => None,
+            })
+            .collect();
+
+        for method in methods {
+            let Stmt::Function { name: method_name, params, body, .. } = method else { continue };
+            self.begin_statement();
+            if let Some((owner, arity)) = superclass.and_then(|superclass| self.inherited(superclass, method_name))
+                && arity != params.len()
+            {
+                self.warn(
+                    Rule::OverrideArity,
+                    format!(
+                        "method '{}.{}' takes {} parameter(s) but overrides '{}.{}', which takes {}",
+                        name, method_name, params.len(), owner, method_name, arity
+                    ),
+                );
+            }
+
+            self.methods.push(false);
+            self.function(&format!("{}.{}", name, method_name), params, body);
+            if self.methods.pop() == Some(false) {
+                self.warn(
+                    Rule::UnusedThis,
+                    format!("method '{}.{}' never uses 'this'", name, method_name),
+                );
+            }
+            self.end_statement();
+        }
+
+        self.classes.insert(name.to_string(), Class {
+            superclass: superclass.map(str::to_string),
+            arities,
+        });
+    }
+
+    /// Find the nearest class from `class` upwards that defines `method`, and its arity
+    fn inherited(&self, class: &str, method: &str) -> Option<(String, usize)> {
+        let mut current = Some(class);
+        let mut seen = 0;
+        while let Some(name) = current
+            && seen <= self.classes.len()
+        {
+            let class = self.classes.get(name)?;
+            if let Some(arity) = class.arities.get(method) {
+                return Some((name.to_string(), *arity));
+            }
+            current = class.superclass.as_deref();
+            seen += 1;
+        }
+        None
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Variable(name) => self.use_name(name),
+            Expr::Binary { left, operator, right } => {
+                if is_comparison(operator)
+                    && is_literal(left)
+                    && is_literal(right)
+                    && let Expr::Literal(Value::Bool(result)) = optimize_expr(expr.clone())
+                {
+                    self.warn(
+                        Rule::ConstantComparison,
+                        format!("comparison '{}' between literals is always {}", operator, result),
+                    );
+                }
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { operand, .. } => self.expr(operand),
+            Expr::Grouping(inner) => self.expr(inner),
+            Expr::Assignment { name, value } => {
+                if matches!(ungroup(value), Expr::Variable(other) if other == name) {
+                    self.warn(Rule::SelfAssignment, format!("'{}' is assigned to itself", name));
+                }
+                self.expr(value);
+            }
+            Expr::Call { callee, arguments } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expr(object),
+            Expr::Set { object, name, value } => {
+                if let Expr::Get { object: other, name: other_name } = ungroup(value)
+                    && other_name == name
+                    && is_same_receiver(object, other)
+                {
+                    self.warn(Rule::SelfAssignment, format!("property '{}' is assigned to itself", name));
+                }
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::This | Expr::Super { .. } => {
+                if let Some(used) = self.methods.last_mut() {
+                    *used = true;
+                }
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            Expr::Lambda { params, body } => self.function("lambda", params, body),
+        }
+    }
+}
+
+/// The keyword of a statement after which nothing in the same block runs
+fn exit_keyword(stmt: &Stmt) -> Option<&'static str> {
+    match stmt {
+        Stmt::Return { .. } => Some("return"),
+        Stmt::Break => Some("break"),
+        Stmt::Continue => Some("continue"),
+        _ => None,
+    }
+}
+
+fn is_comparison(operator: &BinaryOp) -> bool {
+    matches!(
+        operator,
+        BinaryOp::Greater
+            | BinaryOp::GreaterEqual
+            | BinaryOp::Less
+            | BinaryOp::LessEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual
+    )
+}
+
+fn ungroup(mut expr: &Expr) -> &Expr {
+    while let Expr::Grouping(inner) = expr {
+        expr = inner;
+    }
+    expr
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(ungroup(expr), Expr::Literal(_))
+}
+
+/// Check whether two objects are certainly the same, i.e. the same variable or `this`
+fn is_same_receiver(a: &Expr, b: &Expr) -> bool {
+    match (ungroup(a), ungroup(b)) {
+        (Expr::This, Expr::This) => true,
+        (Expr::Variable(a), Expr::Variable(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_ast::Span;
+
+    use crate::{LintConfig, Rule, lint, lint_source};
+
+    /// The rules broken by `source`, in the order they were reported
+    fn rules(source: &str) -> Vec<Rule> {
+        let program = nom_lox::parse_program(source).unwrap();
+        lint_source(source, &program, &LintConfig::default()).into_iter().map(|w| w.rule).collect()
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let source = r#"
+            class Counter {
+              init(start) { this.count = start; }
+              increment() { this.count = this.count + 1; return this.count; }
+            }
+            fun count(n) {
+              var counter = Counter(0);
+              for (var i = 0; i < n; i = i + 1) counter.increment();
+              return counter;
+            }
+            print count(3).count;
+        "#;
+        assert_eq!(rules(source), vec![]);
+    }
+
+    #[test]
+    fn test_unused_variables_and_parameters() {
+        let source = "var global = 1; fun f(a, b) { var x = a; var y; y = 2; }";
+        let program = nom_lox::parse_program(source).unwrap();
+        let messages: Vec<String> = lint(&program, &LintConfig::default()).iter().map(|w| w.to_string()).collect();
+        assert_eq!(messages, vec![
+            "warning[unused-parameter]: parameter 'b' of 'f' is never used",
+            "warning[unused-variable]: local variable 'x' in 'f' is never used",
+            "warning[unused-variable]: local variable 'y' in 'f' is never used",
+        ]);
+    }
+
+    #[test]
+    fn test_closures_count_as_uses() {
+        assert_eq!(rules("fun outer() { var n = 0; fun inner() { return n; } return inner; }"), vec![]);
+    }
+
+    #[test]
+    fn test_shadowing_and_redeclaration() {
+        assert_eq!(rules("var a = 1; { var a = 2; print a; }"), vec![Rule::ShadowedVariable]);
+        assert_eq!(rules("fun f(x) { { var x = x; print x; } }"), vec![Rule::ShadowedVariable]);
+        assert_eq!(rules("var a = 1; var a = 2; fun g() {} var g;"), vec![
+            Rule::TopLevelRedeclaration,
+            Rule::TopLevelRedeclaration,
+        ]);
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(
+            rules("fun f() { return 1; print 2; print 3; }"),
+            vec![Rule::UnreachableCode],
+        );
+        assert_eq!(rules("fun f(x) { if (x) return 1; return 2; }"), vec![]);
+    }
+
+    #[test]
+    fn test_self_assignment_comparisons_and_empty_blocks() {
+        assert_eq!(rules("var x = 1; x = (x);"), vec![Rule::SelfAssignment]);
+        assert_eq!(rules("var o; o.p = o.p; o.p = o.q;"), vec![Rule::SelfAssignment]);
+        assert_eq!(rules("print 1 < 2; print \"a\" == nil; print 1 < \"a\"; var x; print x == 1;"), vec![
+            Rule::ConstantComparison,
+            Rule::ConstantComparison,
+        ]);
+        assert_eq!(rules("var x; while (x) {}"), vec![Rule::EmptyBlock]);
+    }
+
+    #[test]
+    fn test_methods() {
+        let source = r#"
+            class A { speak(words) { print this; print words; } }
+            class B < A {
+              speak() { print super.speak; }
+              helper() { print "no receiver"; }
+            }
+            class C < B { speak(a, b) { print this; print a + b; } }
+        "#;
+        assert_eq!(rules(source), vec![
+            Rule::OverrideArity,
+            Rule::UnusedThis,
+            Rule::OverrideArity,
+        ]);
+    }
+
+    #[test]
+    fn test_disabled_rules() {
+        let program = nom_lox::parse_program("fun f(a) { {} }").unwrap();
+        let config = LintConfig::default().disable(Rule::EmptyBlock).disable(Rule::UnusedParameter);
+        assert_eq!(lint(&program, &config), vec![]);
+    }
+
+    #[test]
+    fn test_suppression_comments() {
+        let source = r#"
+            // lox-lint: allow(unused-parameter)
+            fun f(a) { var unused; }
+            fun g(b) {
+              // lox-lint: allow(unused-variable)
+              var unused;
+            }
+        "#;
+        assert_eq!(rules(source), vec![Rule::UnusedVariable, Rule::UnusedParameter]);
+
+        assert_eq!(rules("// lox-lint: allow(empty-block)\n\nprint 1; {}"), vec![Rule::EmptyBlock]);
+        assert_eq!(rules("print 1;\n// lox-lint: allow(empty-block)\n{}"), vec![]);
+        assert_eq!(rules("{}\nprint 1;\n// lox-lint: allow-file(empty-block)\n{}"), vec![]);
+        assert_eq!(
+            rules("fun f() {\n  // lox-lint: allow(unreachable-code)\n  return;\n  print 1;\n}"),
+            vec![Rule::UnreachableCode],
+        );
+        assert_eq!(rules("fun f() {\n  return;\n  // lox-lint: allow(unreachable-code)\n  print 1;\n}"), vec![]);
+    }
+
+    #[test]
+    fn test_warning_spans() {
+        let source = "var a = 1;\nfun f(x) {\n  if (a) {}\n  return;\n  print x;\n}";
+        let program = nom_lox::parse_program(source).unwrap();
+        let located: Vec<(Rule, &str)> = lint_source(source, &program, &LintConfig::default())
+            .into_iter()
+            .map(|warning| {
+                let Span { start, end } = warning.span.unwrap();
+                (warning.rule, &source[start..end])
+            })
+            .collect();
+        assert_eq!(located, vec![
+            (Rule::EmptyBlock, "{}"),
+            (Rule::UnreachableCode, "print x;"),
+        ]);
+        assert!(lint(&program, &LintConfig::default()).iter().all(|warning| warning.span.is_none()));
+    }
+}
@@ -0,0 +1,332 @@
+//! `// lox-lint: allow(...)` suppression comments and statement locations
+//!
+//! The AST carries no source locations, so the source is scanned for the span
+//! of every statement instead. A statement starts after the statements before
+//! it and before the statements inside it, so the n-th span found is that of
+//! the n-th statement the linter meets in its pre-order walk of the program.
+//! Class fields are not statements to either of them.
+//!
+//! A suppression comment directly before a statement allows its rules within
+//! that statement's span; `// lox-lint: allow-file(...)` allows them anywhere.
+
+use std::collections::HashSet;
+
+use lox_ast::{LexOptions, Span};
+use lox_ast::lexical::{is_identifier_continue, is_identifier_start, scan_number, scan_string, skip_trivia, starts_number};
+
+use crate::Rule;
+
+/// The span of every statement, and the rules allowed for the whole file and within statements
+#[derive(Debug, Default)]
+pub(crate) struct Suppressions {
+    file: HashSet<Rule>,
+    /// Statement spans in pre-order
+    statements: Vec<Span>,
+    /// Rules allowed within the span of the statement after the comment naming them
+    allowed: Vec<(Span, HashSet<Rule>)>,
+}
+
+impl Suppressions {
+    /// Collect the statements and suppression comments in `source`
+    ///
+    /// Scanning stops at the first lexical error; the parser will have rejected
+    /// such a source anyway.
+    pub(crate) fn scan(source: &str, options: &LexOptions) -> Self {
+        let mut tokens = Vec::new();
+        let mut file = HashSet::new();
+        let mut pos = 0;
+        while let Ok(trivia) = skip_trivia(&source[pos..], options) {
+            let text = &source[pos..pos + trivia.len];
+            file.extend(directive_rules(text, "allow-file("));
+            pos += trivia.len;
+            let rest = &source[pos..];
+            let Some(c) = rest.chars().next() else { break };
+
+            let len = if c == '"' {
+                match scan_string(rest, options) {
+                    Ok((_, len)) => len,
+                    Err(_) => break,
+                }
+            } else if starts_number(rest) {
+                match scan_number(rest, options) {
+                    Ok((_, len)) => len,
+                    Err(_) => break,
+                }
+            } else if is_identifier_start(c) {
+                rest.find(|c: char| !is_identifier_continue(c)).unwrap_or(rest.len())
+            } else {
+                c.len_utf8()
+            };
+            tokens.push(Token { text: &rest[..len], span: Span::new(pos, pos + len), trivia: text });
+            pos += len;
+        }
+
+        let mut scanner = Scanner { tokens, pos: 0, statements: Vec::new(), allowed: Vec::new() };
+        while scanner.pos < scanner.tokens.len() {
+            scanner.statement();
+        }
+        Suppressions { file, statements: scanner.statements, allowed: scanner.allowed }
+    }
+
+    /// The span of the statement with this index in pre-order, if the source was scanned
+    pub(crate) fn statement_span(&self, index: usize) -> Option<Span> {
+        self.statements.get(index).copied()
+    }
+
+    /// Check whether `rule` is allowed for a warning about `span`
+    pub(crate) fn allows(&self, rule: Rule, span: Option<Span>) -> bool {
+        self.file.contains(&rule)
+            || span.is_some_and(|span| {
+                self.allowed.iter().any(|(allowed, rules)| {
+                    allowed.start <= span.start && span.end <= allowed.end && rules.contains(&rule)
+                })
+            })
+    }
+}
+
+struct Token<'a> {
+    text: &'a str,
+    span: Span,
+    /// The whitespace and comments before the token
+    trivia: &'a str,
+}
+
+/// Finds the statements among the tokens, following just enough of the
+/// grammar to tell where each starts and ends
+struct Scanner<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    statements: Vec<Span>,
+    allowed: Vec<(Span, HashSet<Rule>)>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<&str> {
+        self.tokens.get(self.pos + ahead).map(|token| token.text)
+    }
+
+    fn bump(&mut self) {
+        self.pos = (self.pos + 1).min(self.tokens.len());
+    }
+
+    /// Step over `text` if it is the next token
+    fn eat(&mut self, text: &str) {
+        if self.peek() == Some(text) {
+            self.bump();
+        }
+    }
+
+    /// Record the statement starting at the next token, with any rules allowed
+    /// by the comments before it, around `scan` reading the rest of it
+    fn record(&mut self, scan: impl FnOnce(&mut Self)) {
+        let Some(first) = self.tokens.get(self.pos) else { return };
+        let (start, rules) = (first.span.start, directive_rules(first.trivia, "allow("));
+        let index = self.statements.len();
+        self.statements.push(Span::new(start, start));
+
+        let before = self.pos;
+        scan(self);
+        if self.pos == before {
+            // Never get stuck on a token that starts nothing
+            self.bump();
+        }
+
+        let span = Span::new(start, self.tokens[self.pos - 1].span.end);
+        self.statements[index] = span;
+        if !rules.is_empty() {
+            self.allowed.push((span, rules));
+        }
+    }
+
+    fn statement(&mut self) {
+        self.record(|scanner| match scanner.peek() {
+            Some("{") => scanner.block(),
+            Some("if") => {
+                scanner.bump();
+                scanner.parenthesized();
+                scanner.statement();
+                if scanner.peek() == Some("else") {
+                    scanner.bump();
+                    scanner.statement();
+                }
+            }
+            Some("while") => {
+                scanner.bump();
+                scanner.parenthesized();
+                scanner.statement();
+            }
+            Some("for") => {
+                scanner.bump();
+                scanner.eat("(");
+                match scanner.peek() {
+                    Some(";") => scanner.bump(),
+                    _ => scanner.statement(),
+                }
+                scanner.expression(";");
+                scanner.eat(";");
+                scanner.expression(")");
+                scanner.eat(")");
+                scanner.statement();
+            }
+            Some("fun") if scanner.peek_at(1) != Some("(") => {
+                scanner.bump();
+                scanner.bump();
+                scanner.function();
+            }
+            Some("class") => {
+                while !matches!(scanner.peek(), None | Some("{")) {
+                    scanner.bump();
+                }
+                scanner.eat("{");
+                while !matches!(scanner.peek(), None | Some("}")) {
+                    scanner.member();
+                }
+                scanner.eat("}");
+            }
+            _ => {
+                scanner.expression(";");
+                scanner.eat(";");
+            }
+        });
+    }
+
+    /// A method, which is a statement, or a field of the typed dialect, which is not
+    fn member(&mut self) {
+        if self.peek_at(1) == Some("(") {
+            self.record(|scanner| {
+                scanner.bump();
+                scanner.function();
+            });
+        } else {
+            let before = self.pos;
+            self.expression(";");
+            self.eat(";");
+            if self.pos == before {
+                self.bump();
+            }
+        }
+    }
+
+    /// The statements of a block up to its closing brace
+    fn block(&mut self) {
+        self.eat("{");
+        while !matches!(self.peek(), None | Some("}")) {
+            self.statement();
+        }
+        self.eat("}");
+    }
+
+    /// The parameters, any return type and the body of a function or lambda
+    fn function(&mut self) {
+        self.parenthesized();
+        while !matches!(self.peek(), None | Some("{") | Some("}")) {
+            self.bump();
+        }
+        self.block();
+    }
+
+    fn parenthesized(&mut self) {
+        self.eat("(");
+        self.expression(")");
+        self.eat(")");
+    }
+
+    /// Step over an expression up to `end` outside of any parentheses,
+    /// recording the statements in the bodies of its lambdas
+    fn expression(&mut self, end: &str) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                "}" => return,
+                _ if depth == 0 && token == end => return,
+                ")" if depth == 0 => return,
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                "fun" if self.peek_at(1) == Some("(") => {
+                    self.bump();
+                    self.function();
+                    continue;
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+}
+
+/// Rules named in the `// lox-lint: <directive>...)` comments within some trivia,
+/// where `directive` is `allow(` or `allow-file(`
+fn directive_rules(trivia: &str, directive: &str) -> HashSet<Rule> {
+    trivia
+        .lines()
+        .filter_map(|line| {
+            let comment = line.trim().strip_prefix("//")?.trim().strip_prefix("lox-lint:")?;
+            let list = comment.trim().strip_prefix(directive)?.strip_suffix(')')?;
+            Some(list.split(',').filter_map(|id| id.trim().parse().ok()).collect::<Vec<Rule>>())
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directive_rules() {
+        let trivia = "\n  // lox-lint: allow(empty-block, unused-this)\n// lox-lint: allow(bogus)\n/// lox-lint: allow(self-assignment)\n// lox-lint: allow-file(unused-variable)\n";
+        assert_eq!(directive_rules(trivia, "allow("), HashSet::from([Rule::EmptyBlock, Rule::UnusedThis]));
+        assert_eq!(directive_rules(trivia, "allow-file("), HashSet::from([Rule::UnusedVariable]));
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let source = "var a = fun (x) { return x; };\nclass C { f: Number; m() { if (a) print 1; else {} } }\nfor (var i = 0; i < 1; i = i + 1) {}";
+        let suppressions = Suppressions::scan(source, &LexOptions::default());
+        let texts: Vec<&str> = suppressions.statements.iter().map(|span| &source[span.start..span.end]).collect();
+        assert_eq!(texts, vec![
+            "var a = fun (x) { return x; };",
+            "return x;",
+            "class C { f: Number; m() { if (a) print 1; else {} } }",
+            "m() { if (a) print 1; else {} }",
+            "if (a) print 1; else {}",
+            "print 1;",
+            "{}",
+            "for (var i = 0; i < 1; i = i + 1) {}",
+            "var i = 0;",
+            "{}",
+        ]);
+    }
+
+    #[test]
+    fn test_comments_attach_to_following_statement() {
+        let source = r#"
+            var a = "// lox-lint: allow(empty-block)";
+            class C {
+              // lox-lint: allow(unused-this)
+              m() { var f = fun (x) { return x; }; }
+            }
+            // lox-lint: allow(self-assignment)
+            print a;
+            {}
+            // lox-lint: allow-file(empty-block)
+        "#;
+        let suppressions = Suppressions::scan(source, &LexOptions::default());
+        let span = |text: &str| {
+            let start = source.find(text).unwrap();
+            Some(Span::new(start, start + text.len()))
+        };
+        assert_eq!(suppressions.allowed, vec![
+            (span("m() { var f = fun (x) { return x; }; }").unwrap(), HashSet::from([Rule::UnusedThis])),
+            (span("print a;").unwrap(), HashSet::from([Rule::SelfAssignment])),
+        ]);
+        assert!(suppressions.allows(Rule::UnusedThis, span("return x;")));
+        assert!(!suppressions.allows(Rule::UnusedThis, span("class C")));
+        assert!(!suppressions.allows(Rule::SelfAssignment, span("{}")));
+        assert!(suppressions.allows(Rule::EmptyBlock, None));
+    }
+}
@@ -0,0 +1,481 @@
+//! Loading and linking multi-file Lox programs
+//!
+//! With the `imports` language extension, a file can run another first:
+//!
+//! ```lox
+//! import "shapes/circle.lox";
+//! print Circle(2).area();
+//! ```
+//!
+//! A [`Loader`] starts from an entry file, resolves each import relative to
+//! the directory of the file containing it, and parses every file once with
+//! the backend it was given. Imports are only allowed at the top level, so
+//! linking is a matter of ordering: a file's imports, recursively, come before
+//! its own statements, and a file imported twice is only included the first
+//! time, however its path is spelled: paths are compared once symlinks and
+//! `..` are resolved. The resulting [`LinkedProgram`] records which file each
+//! top-level statement came from, and finds the file of any node inside one
+//! with [`LinkedProgram::stmt_file`] and [`LinkedProgram::expr_file`].
+//!
+//! A file that imports itself, directly or through others, is an error naming
+//! the whole chain: `Import cycle: main.lox -> a.lox -> main.lox`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use lox_ast::LanguageOptions;
+//! use lox_loader::Loader;
+//!
+//! let options = LanguageOptions { imports: true, ..LanguageOptions::default() };
+//! let loader = Loader::new(move |source| nom_lox::parse_program_with(source, &options));
+//! let linked = loader.load("examples/main.lox")?;
+//! for linked_stmt in &linked.statements {
+//!     println!("{}: {:?}", linked.path(linked_stmt.file).display(), linked_stmt.stmt);
+//! }
+//! # Ok::<(), lox_loader::LoadError>(())
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use lox_ast::{Expr, FileId, Program, SourceMap, Stmt};
+
+/// A top-level statement of a linked program and the file it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedStmt {
+    pub file: FileId,
+    pub stmt: Stmt,
+}
+
+/// The files of a program and their statements in the order they run
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedProgram {
+    /// Every file in the order it was loaded, named by its path resolved
+    /// against the importing file's directory
+    pub sources: SourceMap,
+    /// Every file's statements, imported files first, without the `import`
+    /// statements themselves
+    pub statements: Vec<LinkedStmt>,
+    /// Each file on its own, including its `import` statements, by `FileId`
+    programs: Vec<Program>,
+    entry: FileId,
+}
+
+impl LinkedProgram {
+    /// The entry file the program was loaded from
+    pub fn entry(&self) -> FileId {
+        self.entry
+    }
+
+    /// The path a file was read from
+    pub fn path(&self, id: FileId) -> &Path {
+        Path::new(self.sources.file(id).name())
+    }
+
+    /// A file parsed on its own, including its `import` statements
+    pub fn program(&self, id: FileId) -> &Program {
+        &self.programs[id.index()]
+    }
+
+    /// The file a statement of `statements`, at any depth, came from
+    ///
+    /// Nodes are matched by address, so a clone of one has no file.
+    pub fn stmt_file(&self, stmt: &Stmt) -> Option<FileId> {
+        self.file_containing(std::ptr::from_ref(stmt).cast())
+    }
+
+    /// The file an expression anywhere in `statements` came from
+    ///
+    /// Nodes are matched by address, so a clone of one has no file.
+    pub fn expr_file(&self, expr: &Expr) -> Option<FileId> {
+        self.file_containing(std::ptr::from_ref(expr).cast())
+    }
+
+    fn file_containing(&self, node: *const ()) -> Option<FileId> {
+        self.statements.iter().find(|linked| stmt_contains(&linked.stmt, node)).map(|linked| linked.file)
+    }
+
+    /// The whole program as a single `Program`, for backends that run or
+    /// translate one
+    pub fn to_program(&self) -> Program {
+        Program::new(self.statements.iter().map(|linked| linked.stmt.clone()).collect())
+    }
+}
+
+/// Why a program could not be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file could not be read
+    Io {
+        path: PathBuf,
+        /// The file whose import named it, or `None` for the entry file
+        imported_from: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// A file is not a valid program
+    Parse { path: PathBuf, message: String },
+    /// A file imports itself; `chain` starts and ends with that file
+    Cycle { chain: Vec<PathBuf> },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, imported_from: Some(importer), error } => {
+                write!(f, "Cannot read {} (imported from {}): {}", path.display(), importer.display(), error)
+            }
+            LoadError::Io { path, imported_from: None, error } => {
+                write!(f, "Cannot read {}: {}", path.display(), error)
+            }
+            LoadError::Parse { path, message } => write!(f, "Parse error in {}: {}", path.display(), message),
+            LoadError::Cycle { chain } => {
+                let chain: Vec<String> = chain.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "Import cycle: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+type ParseFn = Box<dyn Fn(&str) -> Result<Program, String>>;
+type ReadFn = Box<dyn Fn(&Path) -> io::Result<String>>;
+
+/// Loads a program and the files it imports
+pub struct Loader {
+    parse: ParseFn,
+    read: ReadFn,
+}
+
+impl Loader {
+    /// A loader reading files from disk and parsing them with `parse`, which
+    /// should accept the `imports` extension
+    pub fn new(parse: impl Fn(&str) -> Result<Program, String> + 'static) -> Self {
+        Loader { parse: Box::new(parse), read: Box::new(|path| std::fs::read_to_string(path)) }
+    }
+
+    /// Read files with `read` instead of from disk, e.g. from an editor's buffers
+    pub fn with_reader(mut self, read: impl Fn(&Path) -> io::Result<String> + 'static) -> Self {
+        self.read = Box::new(read);
+        self
+    }
+
+    /// Load `entry` and everything it imports
+    pub fn load(&self, entry: impl AsRef<Path>) -> Result<LinkedProgram, LoadError> {
+        let mut linker = Linker {
+            loader: self,
+            sources: SourceMap::new(),
+            programs: Vec::new(),
+            loaded: HashSet::new(),
+            stack: Vec::new(),
+            statements: Vec::new(),
+        };
+        let entry = linker.visit(normalize(entry.as_ref()), None)?.expect("the entry file is loaded first");
+        Ok(LinkedProgram { sources: linker.sources, statements: linker.statements, programs: linker.programs, entry })
+    }
+}
+
+/// The state of one `Loader::load`
+struct Linker<'a> {
+    loader: &'a Loader,
+    sources: SourceMap,
+    programs: Vec<Program>,
+    /// The canonical paths of the files loaded so far
+    loaded: HashSet<PathBuf>,
+    /// The chain of imports leading to the file being loaded, as canonical
+    /// paths and the paths they were reached by
+    stack: Vec<(PathBuf, PathBuf)>,
+    statements: Vec<LinkedStmt>,
+}
+
+impl Linker<'_> {
+    /// Load and link `path` unless it has been already, returning its id if it is new
+    fn visit(&mut self, path: PathBuf, imported_from: Option<&Path>) -> Result<Option<FileId>, LoadError> {
+        let key = canonicalize(&path);
+        if let Some(start) = self.stack.iter().position(|(open, _)| *open == key) {
+            let mut chain: Vec<PathBuf> = self.stack[start..].iter().map(|(_, path)| path.clone()).collect();
+            chain.push(path);
+            return Err(LoadError::Cycle { chain });
+        }
+        if self.loaded.contains(&key) {
+            return Ok(None);
+        }
+
+        let source = (self.loader.read)(&path).map_err(|error| LoadError::Io {
+            path: path.clone(),
+            imported_from: imported_from.map(Path::to_path_buf),
+            error,
+        })?;
+        let program = (self.loader.parse)(&source)
+            .map_err(|message| LoadError::Parse { path: path.clone(), message })?;
+        self.loaded.insert(key.clone());
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let imports: Vec<PathBuf> = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Import { path } => Some(normalize(&directory.join(path))),
+                _ => None,
+            })
+            .collect();
+        let own: Vec<Stmt> =
+            program.statements.iter().filter(|stmt| !matches!(stmt, Stmt::Import { .. })).cloned().collect();
+        let id = self.sources.add(path.to_string_lossy(), source);
+        self.programs.push(program);
+
+        self.stack.push((key, path));
+        for import in imports {
+            let importer = self.stack.last().map(|(_, path)| path.clone());
+            self.visit(import, importer.as_deref())?;
+        }
+        self.stack.pop();
+        self.statements.extend(own.into_iter().map(|stmt| LinkedStmt { file: id, stmt }));
+        Ok(Some(id))
+    }
+}
+
+/// The path identifying a file, the same however it is reached: the
+/// canonical path where the file system has one, the normalized path
+/// otherwise (e.g. for files that only exist in a custom reader)
+fn canonicalize(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| normalize(path))
+}
+
+/// Remove `.` and resolve `..` without touching the file system, for the
+/// names of files and for comparing paths that cannot be canonicalized
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn stmts_contain(stmts: &[Stmt], node: *const ()) -> bool {
+    stmts.iter().any(|stmt| stmt_contains(stmt, node))
+}
+
+/// Whether `node` is `stmt` or a statement or expression inside it
+fn stmt_contains(stmt: &Stmt, node: *const ()) -> bool {
+    if std::ptr::eq(std::ptr::from_ref(stmt).cast(), node) {
+        return true;
+    }
+    match stmt {
+        Stmt::Expression(expr) | Stmt::Print(expr) => expr_contains(expr, node),
+        Stmt::VarDeclaration { initializer, .. } => initializer.as_ref().is_some_and(|expr| expr_contains(expr, node)),
+        Stmt::Block(stmts) | Stmt::Function { body: stmts, .. } => stmts_contain(stmts, node),
+        Stmt::If { condition, then_branch, else_branch } => {
+            expr_contains(condition, node)
+                || stmt_contains(then_branch, node)
+                || else_branch.as_ref().is_some_and(|stmt| stmt_contains(stmt, node))
+        }
+        Stmt::While { condition, body } => expr_contains(condition, node) || stmt_contains(body, node),
+        Stmt::For { initializer, condition, increment, body } => {
+            initializer.as_ref().is_some_and(|stmt| stmt_contains(stmt, node))
+                || condition.as_ref().is_some_and(|expr| expr_contains(expr, node))
+                || increment.as_ref().is_some_and(|expr| expr_contains(expr, node))
+                || stmt_contains(body, node)
+        }
+        Stmt::Return { value } => value.as_ref().is_some_and(|expr| expr_contains(expr, node)),
+        Stmt::Class { fields, methods, .. } => stmts_contain(fields, node) || stmts_contain(methods, node),
+        Stmt::Break | Stmt::Continue | Stmt::Import { .. } => false,
+    }
+}
+
+/// Whether `node` is `expr` or an expression or statement inside it
+fn expr_contains(expr: &Expr, node: *const ()) -> bool {
+    if std::ptr::eq(std::ptr::from_ref(expr).cast(), node) {
+        return true;
+    }
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_) | Expr::This | Expr::Super { .. } => false,
+        Expr::Binary { left, right, .. } => expr_contains(left, node) || expr_contains(right, node),
+        Expr::Unary { operand, .. } => expr_contains(operand, node),
+        Expr::Grouping(inner) => expr_contains(inner, node),
+        Expr::Assignment { value, .. } => expr_contains(value, node),
+        Expr::Call { callee, arguments } => {
+            expr_contains(callee, node) || arguments.iter().any(|argument| expr_contains(argument, node))
+        }
+        Expr::Get { object, .. } => expr_contains(object, node),
+        Expr::Set { object, value, .. } => expr_contains(object, node) || expr_contains(value, node),
+        Expr::Conditional { condition, then_branch, else_branch } => {
+            expr_contains(condition, node) || expr_contains(then_branch, node) || expr_contains(else_branch, node)
+        }
+        Expr::Lambda { body, .. } => stmts_contain(body, node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use lox_ast::{Expr, LanguageOptions, Value};
+
+    const OPTIONS: LanguageOptions = LanguageOptions { imports: true, ..LanguageOptions::STRICT };
+
+    /// A loader over in-memory files that counts how often each is parsed
+    fn virtual_loader(files: &[(&str, &str)]) -> (Loader, Rc<RefCell<Vec<String>>>) {
+        let parsed = Rc::new(RefCell::new(Vec::new()));
+        let log = parsed.clone();
+        let files: HashMap<PathBuf, String> =
+            files.iter().map(|(path, source)| (PathBuf::from(path), source.to_string())).collect();
+        let loader = Loader::new(move |source| {
+            log.borrow_mut().push(source.to_string());
+            nom_lox::parse_program_with(source, &OPTIONS)
+        })
+        .with_reader(move |path| {
+            files.get(path).cloned().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file"))
+        });
+        (loader, parsed)
+    }
+
+    fn printed(linked: &LinkedProgram) -> Vec<(String, String)> {
+        linked
+            .statements
+            .iter()
+            .map(|linked_stmt| match &linked_stmt.stmt {
+                Stmt::Print(Expr::Literal(Value::String(text))) => {
+                    (linked.path(linked_stmt.file).display().to_string(), text.clone())
+                }
+                other => panic!("unexpected statement {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_imports_run_first_and_once() {
+        let (loader, parsed) = virtual_loader(&[
+            ("app/main.lox", "import \"lib/a.lox\"; import \"./lib/b.lox\"; print \"main\";"),
+            ("app/lib/a.lox", "import \"common.lox\"; print \"a\";"),
+            ("app/lib/b.lox", "import \"../lib/common.lox\"; print \"b\";"),
+            ("app/lib/common.lox", "print \"common\";"),
+        ]);
+        let linked = loader.load("app/main.lox").unwrap();
+        assert_eq!(
+            printed(&linked),
+            [
+                ("app/lib/common.lox", "common"),
+                ("app/lib/a.lox", "a"),
+                ("app/lib/b.lox", "b"),
+                ("app/main.lox", "main"),
+            ]
+            .map(|(path, text)| (path.to_string(), text.to_string()))
+        );
+        assert_eq!(parsed.borrow().len(), 4);
+        assert_eq!(linked.path(linked.entry()), Path::new("app/main.lox"));
+        assert_eq!(linked.program(linked.entry()).statements.len(), 3);
+        assert_eq!(linked.to_program().statements.len(), 4);
+    }
+
+    #[test]
+    fn test_cycles_are_reported_with_their_chain() {
+        let (loader, _) = virtual_loader(&[
+            ("main.lox", "import \"a.lox\";"),
+            ("a.lox", "import \"dir/b.lox\";"),
+            ("dir/b.lox", "import \"../a.lox\";"),
+        ]);
+        let error = loader.load("main.lox").unwrap_err();
+        assert_eq!(error.to_string(), "Import cycle: a.lox -> dir/b.lox -> a.lox");
+
+        let (loader, _) = virtual_loader(&[("self.lox", "import \"self.lox\";")]);
+        assert_eq!(loader.load("./self.lox").unwrap_err().to_string(), "Import cycle: self.lox -> self.lox");
+    }
+
+    #[test]
+    fn test_nested_nodes_know_their_file() {
+        let (loader, _) = virtual_loader(&[
+            ("main.lox", "import \"lib.lox\"; print twice(2);"),
+            ("lib.lox", "fun twice(n) { if (n > 0) { return n * 2; } return 0; }"),
+        ]);
+        let linked = loader.load("main.lox").unwrap();
+        let library = linked.sources.find("lib.lox").unwrap();
+        let Stmt::Function { body, .. } = &linked.statements[0].stmt else { panic!("expected a function") };
+        let Stmt::If { condition, then_branch, .. } = &body[0] else { panic!("expected an if") };
+        assert_eq!(linked.stmt_file(then_branch), Some(library));
+        assert_eq!(linked.expr_file(condition), Some(library));
+        let Stmt::Print(Expr::Call { arguments, .. }) = &linked.statements[1].stmt else { panic!("expected a call") };
+        assert_eq!(linked.expr_file(&arguments[0]), Some(linked.entry()));
+        assert_eq!(linked.expr_file(&arguments[0].clone()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_paths_are_compared_once_symlinks_are_resolved() {
+        use std::os::unix::fs::symlink;
+
+        let directory = std::env::temp_dir().join(format!("lox-loader-links-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("lib")).unwrap();
+        symlink(directory.join("lib"), directory.join("shared")).unwrap();
+        std::fs::write(directory.join("main.lox"), "import \"lib/common.lox\"; import \"shared/common.lox\";").unwrap();
+        std::fs::write(directory.join("lib/common.lox"), "print \"common\";").unwrap();
+        std::fs::write(directory.join("a.lox"), "import \"shared/b.lox\";").unwrap();
+        std::fs::write(directory.join("lib/b.lox"), "import \"../lib/b.lox\";").unwrap();
+
+        let parsed = Rc::new(RefCell::new(0));
+        let count = parsed.clone();
+        let loader = Loader::new(move |source| {
+            *count.borrow_mut() += 1;
+            nom_lox::parse_program_with(source, &OPTIONS)
+        });
+        let linked = loader.load(directory.join("main.lox"));
+        let parsed_once = *parsed.borrow();
+        let cycle = loader.load(directory.join("a.lox"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(linked.unwrap().statements.len(), 1);
+        assert_eq!(parsed_once, 2);
+        let Err(LoadError::Cycle { chain }) = cycle else { panic!("expected a cycle") };
+        let names: Vec<PathBuf> = chain.iter().map(|path| path.strip_prefix(&directory).unwrap().to_path_buf()).collect();
+        assert_eq!(names, ["shared/b.lox", "lib/b.lox"].map(PathBuf::from));
+        assert_eq!(*parsed.borrow(), 4);
+    }
+
+    #[test]
+    fn test_errors_name_the_file() {
+        let (loader, _) = virtual_loader(&[("main.lox", "import \"missing.lox\";"), ("bad.lox", "print ;")]);
+        assert_eq!(
+            loader.load("main.lox").unwrap_err().to_string(),
+            "Cannot read missing.lox (imported from main.lox): no such file"
+        );
+        assert!(loader.load("bad.lox").unwrap_err().to_string().starts_with("Parse error in bad.lox: "));
+
+        let (loader, _) = virtual_loader(&[("main.lox", "{ import \"a.lox\"; }")]);
+        let error = loader.load("main.lox").unwrap_err().to_string();
+        assert!(error.contains("'import' outside of the top level"), "{}", error);
+    }
+
+    #[test]
+    fn test_backends_and_files_on_disk() {
+        let directory = std::env::temp_dir().join(format!("lox-loader-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("lib")).unwrap();
+        std::fs::write(directory.join("main.lox"), "import \"lib/greet.lox\";\ngreet(\"disk\");\n").unwrap();
+        std::fs::write(directory.join("lib/greet.lox"), "fun greet(name) { print \"hi \" + name; }\n").unwrap();
+
+        let loader = Loader::new(|source| lalrpop_lox::parse_program_with(source, &OPTIONS));
+        let linked = loader.load(directory.join("main.lox")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(linked.sources.len(), 2);
+        assert_eq!(linked.path(linked.statements[0].file), directory.join("lib/greet.lox"));
+        assert!(matches!(linked.statements[0].stmt, Stmt::Function { .. }));
+        assert_eq!(linked.statements[1].file, linked.entry());
+    }
+}
@@ -0,0 +1,279 @@
+//! The interface every Lox parser implements
+//!
+//! Each backend crate exports a type implementing [`LoxParser`], so an
+//! application can hold a `Box<dyn LoxParser>` and pick the backend at
+//! runtime. The backends differ in how much of the language they handle and
+//! what else they offer, so each describes itself with [`Capabilities`]; a
+//! parse that asks for a language extension the backend does not implement
+//! fails with [`ParseError::Unsupported`] instead of a confusing syntax error.
+//! Every backend refuses input nested deeper than [`ParseOptions::max_depth`]
+//! with a "Nesting too deep" syntax error rather than overflowing the stack.
+//!
+//! ```
+//! use lox_parser_api::{Extension, Feature, LoxParser, ParseError, ParseOptions};
+//! use lox_ast::LanguageOptions;
+//!
+//! fn run(parser: &dyn LoxParser, source: &str) -> Result<usize, ParseError> {
+//!     let mut options = ParseOptions::default();
+//!     if parser.capabilities().supports(Feature::Extension(Extension::BreakContinue)) {
+//!         options.language.break_continue = true;
+//!     }
+//!     Ok(parser.parse(source, &options)?.statements.len())
+//! }
+//! ```
+
+use std::fmt;
+
+pub use lox_ast::{Diagnostic, LanguageOptions, LexOptions, Program};
+pub use lox_ast::depth::DEFAULT_MAX_DEPTH;
+
+/// A language extension that can be turned on per parse through `LanguageOptions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Extension {
+    BreakContinue,
+    Conditional,
+    Lambdas,
+    CommaOperator,
+    TypeAnnotations,
+    Imports,
+}
+
+impl Extension {
+    /// Every extension, in the order `LanguageOptions` declares them
+    pub const ALL: [Extension; 6] = [
+        Extension::BreakContinue,
+        Extension::Conditional,
+        Extension::Lambdas,
+        Extension::CommaOperator,
+        Extension::TypeAnnotations,
+        Extension::Imports,
+    ];
+
+    /// The name of the `LanguageOptions` field that turns the extension on
+    pub fn name(self) -> &'static str {
+        match self {
+            Extension::BreakContinue => "break_continue",
+            Extension::Conditional => "conditional",
+            Extension::Lambdas => "lambdas",
+            Extension::CommaOperator => "comma_operator",
+            Extension::TypeAnnotations => "type_annotations",
+            Extension::Imports => "imports",
+        }
+    }
+
+    /// Whether `options` turn the extension on
+    pub fn is_enabled(self, options: &LanguageOptions) -> bool {
+        match self {
+            Extension::BreakContinue => options.break_continue,
+            Extension::Conditional => options.conditional,
+            Extension::Lambdas => options.lambdas,
+            Extension::CommaOperator => options.comma_operator,
+            Extension::TypeAnnotations => options.type_annotations,
+            Extension::Imports => options.imports,
+        }
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Something a parser may or may not offer, for checking against its `Capabilities`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    FullGrammar,
+    Spans,
+    ErrorRecovery,
+    Streaming,
+    Extension(Extension),
+}
+
+/// What a parser handles beyond expressions, `print` and `var`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Functions, classes, blocks and control flow: the whole language of the book
+    pub full_grammar: bool,
+    /// Reports the span of each top-level statement
+    pub spans: bool,
+    /// Carries on after a syntax error and reports every error it finds
+    pub error_recovery: bool,
+    /// Updates a parse after an edit by reparsing only the statements it affects
+    pub streaming: bool,
+    /// The language extensions that may be turned on; the others must stay off
+    pub extensions: LanguageOptions,
+    /// The lexical extensions the parser was built with
+    pub lexical: LexOptions,
+}
+
+impl Capabilities {
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::FullGrammar => self.full_grammar,
+            Feature::Spans => self.spans,
+            Feature::ErrorRecovery => self.error_recovery,
+            Feature::Streaming => self.streaming,
+            Feature::Extension(extension) => extension.is_enabled(&self.extensions),
+        }
+    }
+
+    /// Check that `options` only turn on extensions the parser implements
+    pub fn check(&self, options: &ParseOptions) -> Result<(), ParseError> {
+        let unsupported: Vec<Extension> = Extension::ALL
+            .into_iter()
+            .filter(|extension| extension.is_enabled(&options.language) && !extension.is_enabled(&self.extensions))
+            .collect();
+        if unsupported.is_empty() { Ok(()) } else { Err(ParseError::Unsupported(unsupported)) }
+    }
+}
+
+/// Settings for one parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Language extensions to accept; strict book Lox by default
+    pub language: LanguageOptions,
+    /// How deeply the program may nest, as measured by
+    /// `lox_ast::depth::check_depth`; deeper input fails with "Nesting too deep"
+    pub max_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions { language: LanguageOptions::default(), max_depth: DEFAULT_MAX_DEPTH }
+    }
+}
+
+impl ParseOptions {
+    pub fn with_language(language: LanguageOptions) -> Self {
+        ParseOptions { language, ..ParseOptions::default() }
+    }
+
+    /// These options, but allowing nesting up to `max_depth` levels
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        ParseOptions { max_depth, ..self }
+    }
+}
+
+/// Why a parse failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The source is not a valid program; holds only the first error unless
+    /// the parser recovers from errors
+    Syntax(Vec<Diagnostic>),
+    /// The options turn on extensions the parser does not implement
+    Unsupported(Vec<Extension>),
+}
+
+impl ParseError {
+    pub fn syntax(diagnostic: Diagnostic) -> Self {
+        ParseError::Syntax(vec![diagnostic])
+    }
+
+    /// The syntax errors found, in source order
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            ParseError::Syntax(diagnostics) => diagnostics,
+            ParseError::Unsupported(_) => &[],
+        }
+    }
+
+    /// The error as a line of text, with the location of the first syntax error in `source`
+    pub fn describe(&self, source: &str) -> String {
+        match self {
+            ParseError::Syntax(diagnostics) => match diagnostics.first() {
+                Some(diagnostic) => diagnostic.describe(source),
+                None => self.to_string(),
+            },
+            ParseError::Unsupported(_) => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Syntax(diagnostics) => match diagnostics.first() {
+                Some(diagnostic) => write!(f, "{}", diagnostic),
+                None => write!(f, "Syntax error"),
+            },
+            ParseError::Unsupported(extensions) => {
+                let names: Vec<&str> = extensions.iter().map(|extension| extension.name()).collect();
+                write!(f, "Unsupported language extensions: {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A Lox parser
+pub trait LoxParser: Send + Sync {
+    /// A short name, such as `nom`
+    fn name(&self) -> &'static str;
+    /// The version of the library the parser is built on, as its manifest requires it
+    fn version(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+
+    /// Parse a complete program
+    ///
+    /// Fails with `ParseError::Unsupported` before parsing if `options` turn
+    /// on an extension the parser does not implement.
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox_ast::Span;
+
+    const NOTHING: Capabilities = Capabilities {
+        full_grammar: false,
+        spans: false,
+        error_recovery: false,
+        streaming: false,
+        extensions: LanguageOptions::STRICT,
+        lexical: LexOptions { escapes: false, number_extensions: false, block_comments: false, doc_comments: false },
+    };
+
+    #[test]
+    fn test_check_options() {
+        let capabilities = Capabilities {
+            extensions: LanguageOptions { lambdas: true, ..LanguageOptions::STRICT },
+            ..NOTHING
+        };
+        assert_eq!(capabilities.check(&ParseOptions::default()), Ok(()));
+        let lambdas = ParseOptions::with_language(LanguageOptions { lambdas: true, ..LanguageOptions::STRICT });
+        assert_eq!(capabilities.check(&lambdas), Ok(()));
+
+        let error = capabilities.check(&ParseOptions::with_language(LanguageOptions::EXTENDED)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported language extensions: break_continue, conditional, comma_operator, type_annotations, imports"
+        );
+        assert!(error.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_supports() {
+        let capabilities = Capabilities { full_grammar: true, extensions: LanguageOptions::EXTENDED, ..NOTHING };
+        assert!(capabilities.supports(Feature::FullGrammar));
+        assert!(!capabilities.supports(Feature::ErrorRecovery));
+        assert!(Extension::ALL.into_iter().all(|extension| capabilities.supports(Feature::Extension(extension))));
+        assert!(!NOTHING.supports(Feature::Extension(Extension::Imports)));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let source = "print 1\nprint 2;";
+        let error = ParseError::syntax(Diagnostic::error("Missing ';' at end of statement", Span::new(7, 7)));
+        assert_eq!(error.to_string(), "Missing ';' at end of statement");
+        assert_eq!(error.describe(source), "Missing ';' at end of statement at line 1, column 8");
+        assert_eq!(error.diagnostics().len(), 1);
+
+        let empty = ParseError::Syntax(vec![]);
+        assert_eq!(empty.to_string(), "Syntax error");
+        assert_eq!(empty.describe(source), "Syntax error");
+    }
+}
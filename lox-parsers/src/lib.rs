@@ -0,0 +1,234 @@
+//! Every Lox parser behind one entry point
+//!
+//! Each backend is a cargo feature: `nom`, `lalrpop`, `pest`, `winnow`,
+//! `pom`, `lelwel`, `combine` and `chumsky`. A binary compiles only the
+//! parsers it enables; `nom` is on by default. A [`Backend`] names one of the
+//! enabled parsers and can be read from configuration with `str::parse`.
+//! [`parse_with`] parses with a given backend, and [`parse`] with the default:
+//! the backend passed to [`set_default_backend`], or else the first enabled
+//! one in the order above.
+//!
+//! ```
+//! use lox_parsers::Backend;
+//!
+//! // A name read from configuration; here, whichever backend is enabled first
+//! let name = Backend::ALL[0].name();
+//! let backend: Backend = name.parse()?;
+//! let program = lox_parsers::parse_with(backend, "print 1 + 2;")?;
+//! assert_eq!(program.statements.len(), 1);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Language extensions go through the backend's [`LoxParser`], which refuses
+//! the ones it does not implement:
+//! `backend.parser().parse(source, &ParseOptions::with_language(options))`.
+
+#[cfg(not(any(
+    feature = "nom",
+    feature = "lalrpop",
+    feature = "pest",
+    feature = "winnow",
+    feature = "pom",
+    feature = "lelwel",
+    feature = "combine",
+    feature = "chumsky",
+)))]
+compile_error!("lox-parsers needs at least one backend feature: nom, lalrpop, pest, winnow, pom, lelwel, combine or chumsky");
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{PoisonError, RwLock};
+
+pub use lox_parser_api::{Capabilities, Extension, Feature, LoxParser, ParseError, ParseOptions};
+pub use lox_ast::{LanguageOptions, Program};
+
+/// The name of every backend, enabled in this build or not
+pub const BACKEND_NAMES: [&str; 8] = ["nom", "lalrpop", "pest", "winnow", "pom", "lelwel", "combine", "chumsky"];
+
+/// A parser backend compiled into this build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    #[cfg(feature = "nom")]
+    Nom,
+    #[cfg(feature = "lalrpop")]
+    Lalrpop,
+    #[cfg(feature = "pest")]
+    Pest,
+    #[cfg(feature = "winnow")]
+    Winnow,
+    #[cfg(feature = "pom")]
+    Pom,
+    #[cfg(feature = "lelwel")]
+    Lelwel,
+    #[cfg(feature = "combine")]
+    Combine,
+    #[cfg(feature = "chumsky")]
+    Chumsky,
+}
+
+impl Backend {
+    /// The enabled backends, in order of preference for the default
+    pub const ALL: &'static [Backend] = &[
+        #[cfg(feature = "nom")]
+        Backend::Nom,
+        #[cfg(feature = "lalrpop")]
+        Backend::Lalrpop,
+        #[cfg(feature = "pest")]
+        Backend::Pest,
+        #[cfg(feature = "winnow")]
+        Backend::Winnow,
+        #[cfg(feature = "pom")]
+        Backend::Pom,
+        #[cfg(feature = "lelwel")]
+        Backend::Lelwel,
+        #[cfg(feature = "combine")]
+        Backend::Combine,
+        #[cfg(feature = "chumsky")]
+        Backend::Chumsky,
+    ];
+
+    /// The backend's feature name, which is also what `str::parse` accepts
+    pub fn name(self) -> &'static str {
+        self.parser().name()
+    }
+
+    pub fn parser(self) -> &'static dyn LoxParser {
+        match self {
+            #[cfg(feature = "nom")]
+            Backend::Nom => &nom_lox::NomParser,
+            #[cfg(feature = "lalrpop")]
+            Backend::Lalrpop => &lalrpop_lox::LalrpopParser,
+            #[cfg(feature = "pest")]
+            Backend::Pest => &pest_lox::PestParser,
+            #[cfg(feature = "winnow")]
+            Backend::Winnow => &winnow_lox::WinnowParser,
+            #[cfg(feature = "pom")]
+            Backend::Pom => &pom_lox::PomParser,
+            #[cfg(feature = "lelwel")]
+            Backend::Lelwel => &lelwel_lox::LelwelParser,
+            #[cfg(feature = "combine")]
+            Backend::Combine => &combine_lox::CombineParser,
+            #[cfg(feature = "chumsky")]
+            Backend::Chumsky => &chumsky_lox::ChumskyParser,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = BackendError;
+
+    /// Look up an enabled backend by name, ignoring case
+    fn from_str(name: &str) -> Result<Self, BackendError> {
+        if let Some(&backend) = Backend::ALL.iter().find(|backend| backend.name().eq_ignore_ascii_case(name)) {
+            return Ok(backend);
+        }
+        match BACKEND_NAMES.into_iter().find(|known| known.eq_ignore_ascii_case(name)) {
+            Some(known) => Err(BackendError::NotEnabled(known)),
+            None => Err(BackendError::Unknown(name.to_string())),
+        }
+    }
+}
+
+/// A name that does not pick out an enabled backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// Not the name of any backend
+    Unknown(String),
+    /// A backend whose feature this build was compiled without
+    NotEnabled(&'static str),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Unknown(name) => {
+                let enabled: Vec<&str> = Backend::ALL.iter().map(|backend| backend.name()).collect();
+                write!(f, "Unknown parser backend '{}'; expected one of: {}", name, enabled.join(", "))
+            }
+            BackendError::NotEnabled(name) => write!(
+                f,
+                "Parser backend '{}' is not enabled in this build; turn on the `{}` feature of lox-parsers",
+                name, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+static DEFAULT_BACKEND: RwLock<Option<Backend>> = RwLock::new(None);
+
+/// Make `backend` the one [`parse`] uses, for the whole process
+pub fn set_default_backend(backend: Backend) {
+    *DEFAULT_BACKEND.write().unwrap_or_else(PoisonError::into_inner) = Some(backend);
+}
+
+/// The backend [`parse`] uses: the one last passed to [`set_default_backend`],
+/// or else the first in [`Backend::ALL`]
+pub fn default_backend() -> Backend {
+    DEFAULT_BACKEND.read().unwrap_or_else(PoisonError::into_inner).unwrap_or(Backend::ALL[0])
+}
+
+/// Parse a strict Lox program with `backend`
+pub fn parse_with(backend: Backend, source: &str) -> Result<Program, ParseError> {
+    backend.parser().parse(source, &ParseOptions::default())
+}
+
+/// Parse a strict Lox program with the [`default_backend`]
+pub fn parse(source: &str) -> Result<Program, ParseError> {
+    parse_with(default_backend(), source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_names() {
+        for &backend in Backend::ALL {
+            assert!(BACKEND_NAMES.contains(&backend.name()));
+            assert_eq!(backend.name().parse(), Ok(backend));
+            assert_eq!(backend.name().to_uppercase().parse(), Ok(backend));
+        }
+
+        let error = "yacc".parse::<Backend>().unwrap_err();
+        assert_eq!(error, BackendError::Unknown("yacc".to_string()));
+        assert!(error.to_string().starts_with("Unknown parser backend 'yacc'; expected one of: "));
+    }
+
+    #[cfg(not(feature = "chumsky"))]
+    #[test]
+    fn test_disabled_backends() {
+        let error = "chumsky".parse::<Backend>().unwrap_err();
+        assert_eq!(error, BackendError::NotEnabled("chumsky"));
+        assert_eq!(
+            error.to_string(),
+            "Parser backend 'chumsky' is not enabled in this build; turn on the `chumsky` feature of lox-parsers"
+        );
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let source = "var total = (1 + 2) * 3 - 4;\nprint total >= 5 and !false;\ntotal = -total;";
+        let expected = nom_lox::parse_program(source).unwrap();
+        for &backend in Backend::ALL {
+            assert_eq!(parse_with(backend, source), Ok(expected.clone()), "Backend {}", backend);
+            assert!(parse_with(backend, "print (1;").is_err(), "Backend {}", backend);
+        }
+    }
+
+    #[test]
+    fn test_default_backend() {
+        assert_eq!(default_backend(), Backend::ALL[0]);
+        let last = *Backend::ALL.last().unwrap();
+        set_default_backend(last);
+        assert_eq!(default_backend(), last);
+        assert_eq!(parse("print 1;").unwrap().statements.len(), 1);
+    }
+}
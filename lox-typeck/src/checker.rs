@@ -0,0 +1,693 @@
+//! The walk over the AST that infers and checks types
+
+use std::collections::{HashMap, HashSet};
+
+use lox_ast::{BinaryOp, Expr, Program, Stmt, TypeAnnotation, UnaryOp, Value};
+
+use crate::TypeError;
+use crate::types::{FunctionType, Type};
+
+#[derive(Debug)]
+struct Binding {
+    ty: Type,
+    /// The annotated type that assignments must respect
+    declared: Option<Type>,
+}
+
+#[derive(Debug, Default)]
+struct Class {
+    superclass: Option<String>,
+    fields: HashMap<String, Type>,
+    methods: HashMap<String, FunctionType>,
+}
+
+/// The function whose body is being checked
+struct Function {
+    name: String,
+    declared_return: Option<Type>,
+    /// Types of the values returned so far
+    returns: Vec<Type>,
+}
+
+struct Checker {
+    errors: Vec<TypeError>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Every class declared anywhere, so annotations may name classes declared later
+    class_names: HashSet<String>,
+    classes: HashMap<String, Class>,
+    /// Names assigned somewhere in the program; an unannotated variable with
+    /// such a name may change type, so it is not given its initializer's type
+    reassigned: HashSet<String>,
+    functions: Vec<Function>,
+    /// The classes whose methods are being checked, innermost last
+    current_class: Vec<String>,
+}
+
+/// Check `program`, reporting every type error found
+pub(crate) fn run(program: &Program) -> Vec<TypeError> {
+    let mut class_names = HashSet::new();
+    let mut reassigned = HashSet::new();
+    for stmt in &program.statements {
+        collect_stmt(stmt, &mut class_names, &mut reassigned);
+    }
+
+    let mut globals = HashMap::new();
+    globals.insert("clock".to_string(), Binding { ty: Type::function(Some(vec![]), Type::Number), declared: None });
+
+    let mut checker = Checker {
+        errors: Vec::new(),
+        scopes: vec![globals],
+        class_names,
+        classes: HashMap::new(),
+        reassigned,
+        functions: Vec::new(),
+        current_class: Vec::new(),
+    };
+    for stmt in &program.statements {
+        checker.stmt(stmt);
+    }
+    checker.errors
+}
+
+impl Checker {
+    fn error(&mut self, message: String) {
+        let message = match self.functions.last() {
+            Some(function) => format!("{} (in '{}')", message, function.name),
+            None => message,
+        };
+        self.errors.push(TypeError { message });
+    }
+
+    /// The type an annotation stands for, reporting unknown class names
+    fn resolve(&mut self, annotation: &TypeAnnotation) -> Type {
+        match annotation {
+            TypeAnnotation::Any => Type::Any,
+            TypeAnnotation::Nil => Type::Nil,
+            TypeAnnotation::Bool => Type::Bool,
+            TypeAnnotation::Number => Type::Number,
+            TypeAnnotation::String => Type::String,
+            TypeAnnotation::Class(name) if self.class_names.contains(name) => Type::Instance(name.clone()),
+            TypeAnnotation::Class(name) => {
+                self.error(format!("unknown type '{}'", name));
+                Type::Any
+            }
+            TypeAnnotation::Function { params, return_type } => {
+                let params = params.iter().map(|param| self.resolve(param)).collect();
+                Type::function(Some(params), self.resolve(return_type))
+            }
+        }
+    }
+
+    fn resolve_optional(&mut self, annotation: Option<&TypeAnnotation>) -> Option<Type> {
+        annotation.map(|annotation| self.resolve(annotation))
+    }
+
+    /// Check whether `sub` is `class` or inherits from it
+    fn is_subclass(&self, sub: &str, class: &str) -> bool {
+        let mut current = Some(sub);
+        let mut steps = 0;
+        while let Some(name) = current
+            && steps <= self.classes.len()
+        {
+            if name == class {
+                return true;
+            }
+            current = self.classes.get(name).and_then(|class| class.superclass.as_deref());
+            steps += 1;
+        }
+        false
+    }
+
+    /// Check whether a value of type `actual` may be used where `expected` is required
+    fn compatible(&self, actual: &Type, expected: &Type) -> bool {
+        match (actual, expected) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Instance(actual), Type::Instance(expected)) => self.is_subclass(actual, expected),
+            (Type::Function(actual), Type::Function(expected)) => {
+                let params = match (&actual.params, &expected.params) {
+                    (Some(actual), Some(expected)) => {
+                        actual.len() == expected.len()
+                            && actual.iter().zip(expected).all(|(a, e)| self.compatible(e, a))
+                    }
+                    _ => true,
+                };
+                params && self.compatible(&actual.return_type, &expected.return_type)
+            }
+            (actual, expected) => actual == expected,
+        }
+    }
+
+    /// Walk up from `class` to find a member with `find`
+    fn lookup<T: Clone>(&self, class: &str, find: impl Fn(&Class) -> Option<&T>) -> Option<T> {
+        let mut current = Some(class);
+        let mut steps = 0;
+        while let Some(name) = current
+            && steps <= self.classes.len()
+        {
+            let class = self.classes.get(name)?;
+            if let Some(member) = find(class) {
+                return Some(member.clone());
+            }
+            current = class.superclass.as_deref();
+            steps += 1;
+        }
+        None
+    }
+
+    fn method(&self, class: &str, name: &str) -> Option<FunctionType> {
+        self.lookup(class, |class| class.methods.get(name))
+    }
+
+    fn field(&self, class: &str, name: &str) -> Option<Type> {
+        self.lookup(class, |class| class.fields.get(name))
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, declared: Option<Type>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Binding { ty, declared });
+        }
+    }
+
+    fn lookup_variable(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// The type a variable is given from its initializer when it has no annotation
+    fn inferred(&self, name: &str, initializer: Type) -> Type {
+        if self.reassigned.contains(name) { Type::Any } else { initializer }
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => {
+                self.expr(expr);
+            }
+            Stmt::VarDeclaration { name, type_annotation, initializer, .. } => {
+                let declared = self.resolve_optional(type_annotation.as_ref());
+                let initial = initializer.as_ref().map(|initializer| self.expr(initializer));
+                if let (Some(declared), Some(initial)) = (&declared, &initial)
+                    && !self.compatible(initial, declared)
+                {
+                    self.error(format!("variable '{}' is declared {} but initialized with {}", name, declared, initial));
+                }
+                let ty = match &declared {
+                    Some(declared) => declared.clone(),
+                    None => self.inferred(name, initial.unwrap_or(Type::Nil)),
+                };
+                self.declare(name, ty, declared);
+            }
+            Stmt::Block(stmts) => self.block(stmts),
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.expr(condition);
+                self.stmt(body);
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                self.scopes.push(HashMap::new());
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+                self.stmt(body);
+                self.scopes.pop();
+            }
+            Stmt::Function { name, params, signature, body, .. } => {
+                let param_types = self.param_types(params.len(), signature.as_ref().map(|s| &s.params[..]));
+                let declared_return = self.resolve_optional(signature.as_ref().and_then(|s| s.return_type.as_ref()));
+                // Recursive calls see the declared return type, or Any until the body is checked
+                let provisional = Type::function(Some(param_types.clone()), declared_return.clone().unwrap_or(Type::Any));
+                self.declare(name, self.inferred(name, provisional), None);
+
+                let return_type = self.function(name, params, param_types.clone(), declared_return, body);
+                let ty = Type::function(Some(param_types), return_type);
+                self.declare(name, self.inferred(name, ty), None);
+            }
+            Stmt::Return { value } => {
+                let ty = value.as_ref().map_or(Type::Nil, |value| self.expr(value));
+                let declared = self.functions.last().and_then(|function| function.declared_return.clone());
+                if let Some(declared) = declared
+                    && !self.compatible(&ty, &declared)
+                {
+                    self.error(format!("returns {} but is declared to return {}", ty, declared));
+                }
+                if let Some(function) = self.functions.last_mut() {
+                    function.returns.push(ty);
+                }
+            }
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } => {}
+            Stmt::Class { name, superclass, fields, methods, .. } => {
+                self.class(name, superclass.as_deref(), fields, methods)
+            }
+        }
+    }
+
+    fn param_types(&mut self, count: usize, annotations: Option<&[Option<TypeAnnotation>]>) -> Vec<Type> {
+        (0..count)
+            .map(|i| match annotations.and_then(|annotations| annotations.get(i)).and_then(Option::as_ref) {
+                Some(annotation) => self.resolve(annotation),
+                None => Type::Any,
+            })
+            .collect()
+    }
+
+    /// Check a function body, returning its declared or inferred return type
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        param_types: Vec<Type>,
+        declared_return: Option<Type>,
+        body: &[Stmt],
+    ) -> Type {
+        self.functions.push(Function {
+            name: name.to_string(),
+            declared_return: declared_return.clone(),
+            returns: Vec::new(),
+        });
+        self.scopes.push(HashMap::new());
+        for (param, ty) in params.iter().zip(param_types) {
+            let declared = (ty != Type::Any).then(|| ty.clone());
+            self.declare(param, ty, declared);
+        }
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+
+        let falls_through = !always_returns(body);
+        if let Some(declared) = &declared_return
+            && falls_through
+            && !self.compatible(&Type::Nil, declared)
+        {
+            self.error(format!("may end without returning the declared {}", declared));
+        }
+        let function = self.functions.pop().expect("function pushed above");
+
+        declared_return.unwrap_or_else(|| {
+            let mut returns = function.returns.into_iter();
+            let first = match returns.next() {
+                Some(first) => first,
+                None => return Type::Nil,
+            };
+            let joined = returns.fold(first, Type::join);
+            if falls_through { joined.join(Type::Nil) } else { joined }
+        })
+    }
+
+    fn class(&mut self, name: &str, superclass: Option<&str>, fields: &[Stmt], methods: &[Stmt]) {
+        if let Some(superclass) = superclass {
+            match self.lookup_variable(superclass).map(|binding| binding.ty.clone()) {
+                Some(Type::Class(_) | Type::Any) | None => {}
+                Some(ty) => self.error(format!("class '{}' cannot inherit from {}", name, ty)),
+            }
+        }
+
+        // Register the fields and method signatures first, so methods can use each other
+        let mut class = Class { superclass: superclass.map(str::to_string), ..Class::default() };
+        for field in fields {
+            if let Stmt::VarDeclaration { name, type_annotation, .. } = field {
+                let ty = self.resolve_optional(type_annotation.as_ref()).unwrap_or(Type::Any);
+                class.fields.insert(name.clone(), ty);
+            }
+        }
+        for method in methods {
+            if let Stmt::Function { name, params, signature, .. } = method {
+                let param_types = self.param_types(params.len(), signature.as_ref().map(|s| &s.params[..]));
+                let return_type = match signature.as_ref().and_then(|s| s.return_type.as_ref()) {
+                    Some(annotation) => self.resolve(annotation),
+                    None => Type::Any,
+                };
+                class.methods.insert(name.clone(), FunctionType { params: Some(param_types), return_type });
+            }
+        }
+        self.classes.insert(name.to_string(), class);
+        self.declare(name, Type::Class(name.to_string()), None);
+
+        self.current_class.push(name.to_string());
+        for method in methods {
+            if let Stmt::Function { name: method, params, body, .. } = method
+                && let Some(signature) = self.method(name, method)
+            {
+                let param_types = signature.params.unwrap_or_default();
+                let declared_return = (signature.return_type != Type::Any).then_some(signature.return_type);
+                self.function(&format!("{}.{}", name, method), params, param_types, declared_return, body);
+            }
+        }
+        self.current_class.pop();
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(value) => match value {
+                Value::Nil => Type::Nil,
+                Value::Bool(_) => Type::Bool,
+                Value::Number(_) => Type::Number,
+                Value::String(_) => Type::String,
+            },
+            Expr::Variable(name) => self.lookup_variable(name).map_or(Type::Any, |binding| binding.ty.clone()),
+            Expr::Binary { left, operator, right } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                self.binary(operator, left, right)
+            }
+            Expr::Unary { operator, operand } => {
+                let operand = self.expr(operand);
+                match operator {
+                    UnaryOp::Not => Type::Bool,
+                    UnaryOp::Minus => {
+                        if operand.is_known_not(&Type::Number) {
+                            self.error(format!("operand of '-' must be a number, found {}", operand));
+                        }
+                        Type::Number
+                    }
+                }
+            }
+            Expr::Grouping(inner) => self.expr(inner),
+            Expr::Assignment { name, value } => {
+                let value = self.expr(value);
+                if let Some(declared) = self.lookup_variable(name).and_then(|binding| binding.declared.clone())
+                    && !self.compatible(&value, &declared)
+                {
+                    self.error(format!("cannot assign {} to '{}', which is declared {}", value, name, declared));
+                }
+                value
+            }
+            Expr::Call { callee, arguments } => {
+                let callee = self.expr(callee);
+                let arguments: Vec<Type> = arguments.iter().map(|argument| self.expr(argument)).collect();
+                self.call(callee, arguments)
+            }
+            Expr::Get { object, name } => {
+                let object = self.expr(object);
+                match object {
+                    Type::Any => Type::Any,
+                    Type::Instance(class) => {
+                        if let Some(field) = self.field(&class, name) {
+                            field
+                        } else if let Some(method) = self.method(&class, name) {
+                            Type::Function(Box::new(method))
+                        } else {
+                            Type::Any
+                        }
+                    }
+                    other => {
+                        self.error(format!("only instances have properties, but '.{}' is read from {}", name, other));
+                        Type::Any
+                    }
+                }
+            }
+            Expr::Set { object, name, value } => {
+                let object = self.expr(object);
+                let value = self.expr(value);
+                match &object {
+                    Type::Any => {}
+                    Type::Instance(class) => {
+                        if let Some(field) = self.field(class, name)
+                            && !self.compatible(&value, &field)
+                        {
+                            self.error(format!("cannot assign {} to field '{}.{}', which is declared {}", value, class, name, field));
+                        }
+                    }
+                    other => self.error(format!("only instances have fields, but '.{}' is set on {}", name, other)),
+                }
+                value
+            }
+            Expr::This => self.current_class.last().map_or(Type::Any, |class| Type::Instance(class.clone())),
+            Expr::Super { method } => {
+                let superclass = self.current_class.last()
+                    .and_then(|class| self.classes.get(class))
+                    .and_then(|class| class.superclass.clone());
+                superclass
+                    .and_then(|superclass| self.method(&superclass, method))
+                    .map_or(Type::Any, |method| Type::Function(Box::new(method)))
+            }
+            Expr::Conditional { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                let then_type = self.expr(then_branch);
+                let else_type = self.expr(else_branch);
+                then_type.join(else_type)
+            }
+            Expr::Lambda { params, body } => {
+                let param_types = vec![Type::Any; params.len()];
+                let return_type = self.function("lambda", params, param_types.clone(), None, body);
+                Type::function(Some(param_types), return_type)
+            }
+        }
+    }
+
+    fn binary(&mut self, operator: &BinaryOp, left: Type, right: Type) -> Type {
+        match operator {
+            BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide => {
+                self.require_numbers(operator, &left, &right);
+                Type::Number
+            }
+            BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::Less | BinaryOp::LessEqual => {
+                self.require_numbers(operator, &left, &right);
+                Type::Bool
+            }
+            BinaryOp::Add => {
+                let addable = |ty: &Type| matches!(ty, Type::Any | Type::Number | Type::String);
+                let mismatched = matches!(
+                    (&left, &right),
+                    (Type::Number, Type::String) | (Type::String, Type::Number)
+                );
+                if !addable(&left) || !addable(&right) || mismatched {
+                    self.error(format!(
+                        "operands of '+' must be two numbers or two strings, found {} and {}",
+                        left, right
+                    ));
+                    return Type::Any;
+                }
+                if left == Type::Any { right } else { left }
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual => Type::Bool,
+            BinaryOp::And | BinaryOp::Or => left.join(right),
+            BinaryOp::Comma => right,
+        }
+    }
+
+    fn require_numbers(&mut self, operator: &BinaryOp, left: &Type, right: &Type) {
+        if left.is_known_not(&Type::Number) || right.is_known_not(&Type::Number) {
+            self.error(format!("operands of '{}' must be numbers, found {} and {}", operator, left, right));
+        }
+    }
+
+    fn call(&mut self, callee: Type, arguments: Vec<Type>) -> Type {
+        let (params, result) = match callee {
+            Type::Any => return Type::Any,
+            Type::Function(function) => (function.params, function.return_type),
+            Type::Class(class) => {
+                let params = match self.method(&class, "init") {
+                    Some(init) => init.params,
+                    None => Some(Vec::new()),
+                };
+                (params, Type::Instance(class))
+            }
+            other => {
+                self.error(format!("can only call functions and classes, but this is {}", other));
+                return Type::Any;
+            }
+        };
+
+        if let Some(params) = params {
+            if params.len() != arguments.len() {
+                self.error(format!("expected {} argument(s) but got {}", params.len(), arguments.len()));
+            } else {
+                for (i, (argument, param)) in arguments.iter().zip(&params).enumerate() {
+                    if !self.compatible(argument, param) {
+                        self.error(format!("argument {} is {} but the parameter is declared {}", i + 1, argument, param));
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Check whether every path through `stmts` ends in a `return`
+fn always_returns(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::Block(stmts) => always_returns(stmts),
+        Stmt::If { then_branch, else_branch: Some(else_branch), .. } => {
+            always_returns(std::slice::from_ref(then_branch)) && always_returns(std::slice::from_ref(else_branch))
+        }
+        _ => false,
+    })
+}
+
+/// Record the classes declared and the names assigned anywhere under `stmt`
+fn collect_stmt(stmt: &Stmt, classes: &mut HashSet<String>, assigned: &mut HashSet<String>) {
+    let mut exprs: Vec<&Expr> = Vec::new();
+    let mut stmts: Vec<&Stmt> = vec![stmt];
+    while let Some(stmt) = stmts.pop() {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => exprs.push(expr),
+            Stmt::VarDeclaration { initializer, .. } => exprs.extend(initializer),
+            Stmt::Block(body) | Stmt::Function { body, .. } => stmts.extend(body),
+            Stmt::If { condition, then_branch, else_branch } => {
+                exprs.push(condition);
+                stmts.push(then_branch);
+                stmts.extend(else_branch.as_deref());
+            }
+            Stmt::While { condition, body } => {
+                exprs.push(condition);
+                stmts.push(body);
+            }
+            Stmt::For { initializer, condition, increment, body } => {
+                stmts.extend(initializer.as_deref());
+                exprs.extend(condition);
+                exprs.extend(increment);
+                stmts.push(body);
+            }
+            Stmt::Return { value } => exprs.extend(value),
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } => {}
+            Stmt::Class { name, methods, .. } => {
+                classes.insert(name.clone());
+                stmts.extend(methods);
+            }
+        }
+
+        while let Some(expr) = exprs.pop() {
+            match expr {
+                Expr::Literal(_) | Expr::Variable(_) | Expr::This | Expr::Super { .. } => {}
+                Expr::Binary { left, right, .. } => exprs.extend([&**left, &**right]),
+                Expr::Unary { operand: inner, .. } | Expr::Grouping(inner) | Expr::Get { object: inner, .. } => {
+                    exprs.push(inner)
+                }
+                Expr::Assignment { name, value } => {
+                    assigned.insert(name.clone());
+                    exprs.push(value);
+                }
+                Expr::Call { callee, arguments } => {
+                    exprs.push(callee);
+                    exprs.extend(arguments);
+                }
+                Expr::Set { object, value, .. } => exprs.extend([&**object, &**value]),
+                Expr::Conditional { condition, then_branch, else_branch } => {
+                    exprs.extend([&**condition, &**then_branch, &**else_branch])
+                }
+                Expr::Lambda { body, .. } => stmts.extend(body),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lox_ast::LanguageOptions;
+
+    use crate::check;
+
+    /// The messages of the type errors in `source`, parsed in the typed dialect
+    fn errors(source: &str) -> Vec<String> {
+        let options = LanguageOptions { type_annotations: true, lambdas: true, ..LanguageOptions::default() };
+        let program = nom_lox::parse_program_with(source, &options).unwrap();
+        check(&program).into_iter().map(|error| error.message).collect()
+    }
+
+    #[test]
+    fn test_untyped_program_without_errors() {
+        let source = r#"
+            var x = 1;
+            x = "now a string";
+            fun make(n) { return fun () { return n; }; }
+            class Counter { init() { this.count = 0; } inc() { this.count = this.count + 1; } }
+            var c = Counter();
+            c.inc();
+            print make(1)() + clock();
+        "#;
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_inferred_operand_errors() {
+        assert_eq!(errors(r#"print "a" - 1;"#), vec!["operands of '-' must be numbers, found String and Number"]);
+        assert_eq!(errors(r#"var s = "a"; print -s;"#), vec!["operand of '-' must be a number, found String"]);
+        assert_eq!(errors(r#"print "a" + 1;"#), vec![
+            "operands of '+' must be two numbers or two strings, found String and Number",
+        ]);
+        assert_eq!(errors("print nil < 1;"), vec!["operands of '<' must be numbers, found Nil and Number"]);
+        // Reassigned variables may hold anything, so they are not checked
+        assert_eq!(errors(r#"var s = "a"; s = 1; print s - 1;"#), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_eq!(errors("var n = 1; n();"), vec!["can only call functions and classes, but this is Number"]);
+        assert_eq!(errors(r#""text"();"#), vec!["can only call functions and classes, but this is String"]);
+        assert_eq!(errors("fun f(a, b) {} f(1);"), vec!["expected 2 argument(s) but got 1"]);
+        assert_eq!(errors("fun f() { return 1; } print f() - f();"), Vec::<String>::new());
+        assert_eq!(errors(r#"fun f() { return "s"; } print f() - 1;"#), vec![
+            "operands of '-' must be numbers, found String and Number",
+        ]);
+        assert_eq!(errors("class P { init(x) {} } P();"), vec!["expected 1 argument(s) but got 0"]);
+    }
+
+    #[test]
+    fn test_annotations() {
+        assert_eq!(errors("var x: Number = 1; fun add(a: Number, b: Number): Number { return a + b; } print add(x, 2);"), Vec::<String>::new());
+        assert_eq!(errors(r#"var x: Number = "one";"#), vec!["variable 'x' is declared Number but initialized with String"]);
+        assert_eq!(errors(r#"var x: Number = 1; x = "two";"#), vec!["cannot assign String to 'x', which is declared Number"]);
+        assert_eq!(errors(r#"fun f(a: Number) {} f("s");"#), vec!["argument 1 is String but the parameter is declared Number"]);
+        assert_eq!(errors(r#"fun f(a: String) { print a - 1; }"#), vec![
+            "operands of '-' must be numbers, found String and Number (in 'f')",
+        ]);
+        assert_eq!(errors(r#"fun f(): Number { return "s"; }"#), vec![
+            "returns String but is declared to return Number (in 'f')",
+        ]);
+        assert_eq!(errors("fun f(x): Number { if (x) return 1; }"), vec![
+            "may end without returning the declared Number (in 'f')",
+        ]);
+        assert_eq!(errors("var p: Pointt;"), vec!["unknown type 'Pointt'"]);
+        assert_eq!(errors("var f: fun(Number): Number = fun (n) { return n; }; print f(1) - 1;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_classes_and_fields() {
+        let source = r#"
+            class Shape { area(): Number { return 0; } }
+            class Square < Shape {
+              side: Number;
+              init(side: Number) { this.side = side; }
+              area(): Number { return this.side * this.side; }
+            }
+            var s: Shape = Square(2);
+            print s.area() + 1;
+        "#;
+        assert_eq!(errors(source), Vec::<String>::new());
+
+        let source = r#"
+            class Point { x: Number; init() { this.x = "zero"; } }
+            var p: Point = Point();
+            var name: String = p.x;
+            print p.missing;
+            var n = 1;
+            print n.field;
+        "#;
+        assert_eq!(errors(source), vec![
+            "cannot assign String to field 'Point.x', which is declared Number (in 'Point.init')",
+            "variable 'name' is declared String but initialized with Number",
+            "only instances have properties, but '.field' is read from Number",
+        ]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Gradual type checker for Lox
+//!
+//! Checks programs written in the typed dialect, where variables, parameters,
+//! return values and class fields may carry annotations (see
+//! `lox_ast::types`), as well as plain untyped Lox. Unannotated code is given
+//! types inferred from literals, operators, calls and initializers, and
+//! anything that cannot be inferred is `Any`, which is never reported. The
+//! checker only reads the program; annotations do not change how it runs.
+//!
+//! Errors are reported for operations that would fail at runtime, such as
+//! `"a" - 1`, calling a number, passing the wrong number of arguments or
+//! reading a property of something other than an instance, and for values
+//! that do not match an annotation.
+//!
+//! # Example
+//!
+//! ```
+//! use lox_ast::LanguageOptions;
+//!
+//! let options = LanguageOptions { type_annotations: true, ..LanguageOptions::default() };
+//! let source = r#"fun add(a: Number, b: Number): Number { return a + b; } add(1, "2");"#;
+//! let program = nom_lox::parse_program_with(source, &options).unwrap();
+//! let errors = lox_typeck::check(&program);
+//! assert_eq!(errors[0].to_string(), "type error: argument 2 is String but the parameter is declared Number");
+//! ```
+
+use std::fmt;
+
+use lox_ast::Program;
+
+mod checker;
+mod types;
+
+pub use types::{FunctionType, Type};
+
+/// A mismatch found by the checker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "type error: {}", self.message)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// Infer types throughout `program` and report every mismatch
+pub fn check(program: &Program) -> Vec<TypeError> {
+    checker::run(program)
+}
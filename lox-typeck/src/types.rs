@@ -0,0 +1,86 @@
+//! The types the checker infers
+
+use std::fmt;
+
+/// The static type of an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    /// Unknown; compatible with every other type
+    Any,
+    Nil,
+    Bool,
+    Number,
+    String,
+    Function(Box<FunctionType>),
+    /// The named class itself, which is called to make an instance
+    Class(String),
+    /// An instance of the named class
+    Instance(String),
+}
+
+/// The type of a function, method or lambda
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionType {
+    /// Parameter types, or `None` when even the arity is unknown
+    pub params: Option<Vec<Type>>,
+    pub return_type: Type,
+}
+
+impl Type {
+    pub fn function(params: Option<Vec<Type>>, return_type: Type) -> Self {
+        Type::Function(Box::new(FunctionType { params, return_type }))
+    }
+
+    /// The type of an expression that may evaluate to either `self` or `other`
+    pub fn join(self, other: Type) -> Type {
+        if self == other { self } else { Type::Any }
+    }
+
+    /// Check whether the type is known to be something other than `expected`
+    pub fn is_known_not(&self, expected: &Type) -> bool {
+        *self != Type::Any && self != expected
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Nil => write!(f, "Nil"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Number => write!(f, "Number"),
+            Type::String => write!(f, "String"),
+            Type::Function(function) => {
+                match &function.params {
+                    Some(params) => {
+                        let params: Vec<String> = params.iter().map(Type::to_string).collect();
+                        write!(f, "fun({})", params.join(", "))?;
+                    }
+                    None => write!(f, "fun(...)")?,
+                }
+                write!(f, ": {}", function.return_type)
+            }
+            Type::Class(name) => write!(f, "class {}", name),
+            Type::Instance(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        assert_eq!(Type::Number.join(Type::Number), Type::Number);
+        assert_eq!(Type::Number.join(Type::String), Type::Any);
+    }
+
+    #[test]
+    fn test_display() {
+        let function = Type::function(Some(vec![Type::Number, Type::Instance("Point".to_string())]), Type::Nil);
+        assert_eq!(function.to_string(), "fun(Number, Point): Nil");
+        assert_eq!(Type::function(None, Type::Any).to_string(), "fun(...): Any");
+        assert_eq!(Type::Class("Point".to_string()).to_string(), "class Point");
+    }
+}
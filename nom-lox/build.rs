@@ -0,0 +1,12 @@
+fn main() {
+    // The version of nom the manifest requires, which `version()` reports
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let manifest = std::fs::read_to_string("Cargo.toml").expect("the manifest is readable");
+    let requirement = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("nom = "))
+        .map(|entry| entry.split_once("version").map_or(entry, |(_, rest)| rest))
+        .and_then(|entry| entry.split('"').nth(1))
+        .expect("the manifest depends on nom");
+    println!("cargo:rustc-env=LIBRARY_VERSION={}", requirement);
+}
@@ -0,0 +1,270 @@
+//! Incremental reparsing of edited programs
+//!
+//! An editor that reparses the whole file on every keystroke repeats almost
+//! all of its work. `parse_program_spanned` records where each top-level
+//! statement ends, and `reparse_program` uses those spans to parse only the
+//! statements an edit can affect:
+//!
+//! - Statements that end before the edit are kept. The one just before it is
+//!   parsed again, since text after an `if` can give it an `else`.
+//! - Parsing then resumes from there until a statement ends at the same place,
+//!   relative to the text after the edit, as one in the previous program. The
+//!   rest of the input is unchanged from that point, so it would parse to the
+//!   same statements, which are kept with their spans moved.
+//!
+//! The result is always the program a full parse of the new text would give.
+//!
+//! ```
+//! use lox_ast::{LanguageOptions, TextEdit};
+//! use nom_lox::{parse_program_spanned, reparse_program};
+//!
+//! let options = LanguageOptions::default();
+//! let source = "var a = 1;\nprint a;\nprint a + 1;";
+//! let program = parse_program_spanned(source, &options).unwrap();
+//!
+//! let edit = TextEdit::new(17..18, "2");
+//! let edited = edit.apply(source);
+//! let program = reparse_program(&program, &edit, &edited, &options).unwrap();
+//! assert_eq!(program, parse_program_spanned(&edited, &options).unwrap());
+//! ```
+
+use std::ops::Range;
+
+use lox_ast::{LanguageOptions, Program, Span, Stmt, TextEdit};
+
+use lox_ast::depth::DEFAULT_MAX_DEPTH;
+
+use crate::parser::{State, declarations, nested};
+
+/// A program along with the span of each top-level statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedProgram {
+    pub program: Program,
+    /// The span of each statement in `program.statements`, from its first
+    /// token to its last; doc comments and other trivia before it are left out
+    pub spans: Vec<Span>,
+}
+
+impl SpannedProgram {
+    fn from_parts(parts: Vec<(Span, Stmt)>) -> Self {
+        let (spans, statements) = parts.into_iter().unzip();
+        SpannedProgram { program: Program::new(statements), spans }
+    }
+}
+
+/// Parse a complete program, recording the span of each top-level statement
+pub fn parse_program_spanned(input: &str, options: &LanguageOptions) -> Result<SpannedProgram, String> {
+    let parsed = nested(input, DEFAULT_MAX_DEPTH, || declarations(input, 0, State::new(options), |_| false));
+    match parsed.map_err(|error| error.describe(input))? {
+        Ok((parts, _)) => Ok(SpannedProgram::from_parts(parts)),
+        Err(()) => Err(describe_error(input, options)),
+    }
+}
+
+/// Update `previous`, the parse of a text, after `edit` turned that text into
+/// `input`
+///
+/// `options` must be the ones `previous` was parsed with.
+pub fn reparse_program(
+    previous: &SpannedProgram,
+    edit: &TextEdit,
+    input: &str,
+    options: &LanguageOptions,
+) -> Result<SpannedProgram, String> {
+    reparse(previous, edit, input, options).map(|(program, _)| program)
+}
+
+/// Reparse after an edit, also returning the indices of the statements that
+/// were parsed again
+fn reparse(
+    previous: &SpannedProgram,
+    edit: &TextEdit,
+    input: &str,
+    options: &LanguageOptions,
+) -> Result<(SpannedProgram, Range<usize>), String> {
+    let spans = &previous.spans;
+    let touched = spans.iter().position(|span| span.end >= edit.range.start).unwrap_or(spans.len());
+    let first = touched.saturating_sub(1);
+    let start = if first == 0 { 0 } else { spans[first - 1].end };
+
+    // Where the unchanged text after the edit begins
+    let resume = edit.range.start + edit.replacement.len();
+    let mut old = first;
+    let stop = |end: usize| {
+        if end < resume {
+            return false;
+        }
+        let old_end = end - edit.replacement.len() + edit.range.len();
+        while old < spans.len() && spans[old].end < old_end {
+            old += 1;
+        }
+        old < spans.len() && spans[old].end == old_end
+    };
+    let parsed = nested(input, DEFAULT_MAX_DEPTH, || declarations(input, start, State::new(options), stop));
+    let (reparsed, synchronized) = match parsed.map_err(|error| error.describe(input))? {
+        Ok(result) => result,
+        Err(()) => return Err(describe_error(input, options)),
+    };
+
+    let mut parts: Vec<(Span, Stmt)> =
+        spans[..first].iter().copied().zip(previous.program.statements[..first].iter().cloned()).collect();
+    let reparsed_range = first..first + reparsed.len();
+    parts.extend(reparsed);
+    if synchronized {
+        let shift = |span: &Span| Span::new(edit.shift(span.start), edit.shift(span.end));
+        parts.extend(spans[old + 1..].iter().map(shift).zip(previous.program.statements[old + 1..].iter().cloned()));
+    }
+    Ok((SpannedProgram::from_parts(parts), reparsed_range))
+}
+
+/// The error a full parse reports for `input`
+///
+/// The two parses accept the same programs, but should they ever disagree the
+/// caller still gets an error rather than a panic.
+fn describe_error(input: &str, options: &LanguageOptions) -> String {
+    match crate::parse_program_with(input, options) {
+        Err(message) => message,
+        Ok(_) => "Parse error: the program parses as a whole but not statement by statement".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const OPTIONS: LanguageOptions = LanguageOptions::EXTENDED;
+
+    fn parse(input: &str) -> SpannedProgram {
+        parse_program_spanned(input, &OPTIONS).unwrap()
+    }
+
+    /// Apply `edit` to `source` and reparse, checking the result against a full parse
+    fn edit(source: &str, previous: &SpannedProgram, edit: &TextEdit) -> (String, Range<usize>) {
+        let edited = edit.apply(source);
+        let (program, reparsed) = reparse(previous, edit, &edited, &OPTIONS).unwrap();
+        assert_eq!(program, parse(&edited), "{:?} -> {:?}", source, edited);
+        (edited, reparsed)
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "/// Doc\nvar a = 1;\n  print a; // done\n";
+        let program = parse(source);
+        assert_eq!(program.program, crate::parse_program_with(source, &OPTIONS).unwrap());
+        assert_eq!(program.spans, vec![Span::new(8, 18), Span::new(21, 29)]);
+    }
+
+    #[test]
+    fn test_only_affected_statements_are_reparsed() {
+        let source = "var a = 1;\nvar b = 2;\nvar c = 3;\nvar d = 4;\nvar e = 5;\n";
+        let program = parse(source);
+        let (_, reparsed) = edit(source, &program, &TextEdit::new(30..31, "30 + 1"));
+        assert_eq!(reparsed, 1..3);
+
+        // Adding and removing statements moves the ones after them
+        let (_, reparsed) = edit(source, &program, &TextEdit::new(22..22, "print c; print c;\n"));
+        assert_eq!(reparsed, 1..5);
+        let (_, reparsed) = edit(source, &program, &TextEdit::new(11..33, ""));
+        assert_eq!(reparsed, 0..2);
+
+        // An edit in the last statement, or after it, parses to the end
+        let (_, reparsed) = edit(source, &program, &TextEdit::new(55..55, "print e;"));
+        assert_eq!(reparsed, 4..6);
+    }
+
+    #[test]
+    fn test_edits_that_change_earlier_statements() {
+        let source = "if (x) print 1; elsewhere(1);\nprint 2;";
+        let program = parse(source);
+        // `elsewhere(1);` becomes the `else` branch of the `if`
+        let (edited, _) = edit(source, &program, &TextEdit::new(20..25, ""));
+        assert_eq!(parse(&edited).program.statements.len(), 2);
+
+        let source = "print 1;\nprint 2;\nprint 3;";
+        let program = parse(source);
+        // Opening a comment swallows statements after the edit
+        let (edited, _) = edit(source, &program, &TextEdit::new(9..9, "// "));
+        assert_eq!(parse(&edited).program.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_edits_report_the_full_parse_error() {
+        let source = "print 1;\nprint 2;";
+        let program = parse(source);
+        let edit = TextEdit::new(15..16, "");
+        let edited = edit.apply(source);
+        assert_eq!(
+            reparse_program(&program, &edit, &edited, &OPTIONS),
+            Err(crate::parse_program_with(&edited, &OPTIONS).unwrap_err())
+        );
+    }
+
+    /// Fragments random edits insert, chosen to open and close blocks,
+    /// strings and comments, and to join or split statements
+    const FRAGMENTS: &[&str] = &[
+        "print 1;", "var x = 2;", "x", "1", " + ", ";", "{", "}", "(", ")", "if (x) ", "else ", "else print 3;",
+        "fun f() { return 1; }", "// c\n", "\n", "\"", "class A { m() {} }", "while (x) ", "/// doc\n", " ",
+        "x = x ? 1 : 2;", "for (;;) break;", "fun (a) { return a; }",
+    ];
+
+    fn random_edit(rng: &mut StdRng, source: &str) -> TextEdit {
+        let mut start = rng.random_range(0..=source.len());
+        while !source.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (start + rng.random_range(0..12)).min(source.len());
+        if rng.random_bool(0.5) {
+            end = start;
+        }
+        while !source.is_char_boundary(end) {
+            end -= 1;
+        }
+        let replacement = if end > start && rng.random_bool(0.5) {
+            ""
+        } else {
+            FRAGMENTS[rng.random_range(0..FRAGMENTS.len())]
+        };
+        TextEdit::new(start..end, replacement)
+    }
+
+    #[test]
+    fn test_random_edits_match_full_parse() {
+        let mut sources = vec![
+            "var a = 1;\nif (a) print a; else { print -a; }\nfun f(x) { return x * 2; }\nprint f(a);\n".to_string(),
+            "class A < B { init() { this.x = 1; } }\nwhile (true) { break; }\n/// doc\nvar y = fun (a) { return a; };\n"
+                .to_string(),
+            "if (a) print 1;\nif (b) print 2; print 3;\nwhile (c) if (d) print 4;\n".to_string(),
+        ];
+        for example in ["arithmetic", "classes", "closures", "variables"] {
+            let path = format!("{}/../examples/{}.lox", env!("CARGO_MANIFEST_DIR"), example);
+            sources.push(std::fs::read_to_string(path).unwrap());
+        }
+
+        let mut rng = StdRng::seed_from_u64(0x10c5);
+        let mut checked = 0;
+        for original in &sources {
+            for _ in 0..40 {
+                let mut source = original.clone();
+                let mut program = parse(&source);
+                for _ in 0..25 {
+                    let edit = random_edit(&mut rng, &source);
+                    let edited = edit.apply(&source);
+                    let incremental = reparse_program(&program, &edit, &edited, &OPTIONS);
+                    match parse_program_spanned(&edited, &OPTIONS) {
+                        Ok(full) => {
+                            assert_eq!(incremental.as_ref(), Ok(&full), "{:?} with {:?}", source, edit);
+                            source = edited;
+                            program = full;
+                            checked += 1;
+                        }
+                        // Keep editing the last valid text
+                        Err(error) => assert_eq!(incremental, Err(error), "{:?} with {:?}", source, edit),
+                    }
+                }
+            }
+        }
+        assert!(checked > 500, "only {} edits kept the program valid", checked);
+    }
+}
@@ -13,16 +13,47 @@
 //! assert!(result.is_ok());
 //! ```
 
+mod incremental;
 mod parser;
 
-pub use parser::parse_program;
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use incremental::{SpannedProgram, parse_program_spanned, reparse_program};
+pub use parser::{
+    parse_program, parse_program_borrowed, parse_program_borrowed_with, parse_program_diagnostic, parse_program_with,
+    parse_program_with_max_depth,
+};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "nom";
-pub const PARSER_VERSION: &str = "7.1";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "Zero-copy parser combinator library with excellent performance";
 
+/// This parser behind the common `LoxParser` interface
+pub struct NomParser;
+
+impl LoxParser for NomParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: true,
+            spans: true,
+            error_recovery: false,
+            streaming: true,
+            extensions: LanguageOptions::EXTENDED,
+            lexical: parser::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parse_program_with_max_depth(source, &options.language, options.max_depth).map_err(|error| ParseError::syntax(*error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,7 +96,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -75,4 +106,156 @@ mod tests {
             _ => panic!("Expected variable declaration"),
         }
     }
+
+    #[test]
+    fn test_control_flow() {
+        let input = r#"
+            for (var i = 0; i < 3; i = i + 1) {
+                if (i == 1) print "one"; else print i;
+            }
+            while (false) {}
+        "#;
+        let result = parse_program(input).unwrap();
+
+        assert_eq!(result.statements.len(), 2);
+        match &result.statements[0] {
+            Stmt::For { initializer: Some(init), condition: Some(_), increment: Some(_), body } => {
+                assert!(matches!(**init, Stmt::VarDeclaration { .. }));
+                assert!(matches!(**body, Stmt::Block(ref stmts) if matches!(stmts[0], Stmt::If { .. })));
+            }
+            _ => panic!("Expected for loop"),
+        }
+        assert!(matches!(result.statements[1], Stmt::While { .. }));
+    }
+
+    #[test]
+    fn test_functions_and_classes() {
+        let input = r#"
+            fun add(a, b) { return a + b; }
+            class Point < Base {
+                init(x) { this.x = x; }
+                sum() { return super.sum() + add(this.x, 1); }
+            }
+        "#;
+        let result = parse_program(input).unwrap();
+
+        assert_eq!(result.statements.len(), 2);
+        match &result.statements[0] {
+            Stmt::Function { name, params, body, .. } => {
+                assert_eq!(name, "add");
+                assert_eq!(params, &["a", "b"]);
+                assert!(matches!(body[0], Stmt::Return { value: Some(Expr::Binary { .. }) }));
+            }
+            _ => panic!("Expected function declaration"),
+        }
+        match &result.statements[1] {
+            Stmt::Class { name, superclass, methods, .. } => {
+                assert_eq!(name, "Point");
+                assert_eq!(superclass.as_deref(), Some("Base"));
+                assert_eq!(methods.len(), 2);
+                match &methods[0] {
+                    Stmt::Function { body, .. } => assert!(matches!(
+                        body[0],
+                        Stmt::Expression(Expr::Set { ref object, .. }) if **object == Expr::This
+                    )),
+                    _ => panic!("Expected method"),
+                }
+            }
+            _ => panic!("Expected class declaration"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        let error = parse_program("a + b = c;").unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid assignment target at line 1, column 1; only a variable or a field such as 'point.x' can be assigned"
+        );
+    }
+
+    #[test]
+    fn test_language_extensions() {
+        let input = r#"
+            var f = fun (a, b) { return a > b ? a : b; };
+            while (true) { if (x) break; else continue; }
+            x = 1, 2;
+        "#;
+        let result = parse_program_with(input, &LanguageOptions::EXTENDED).unwrap();
+
+        assert_eq!(result.statements.len(), 3);
+        match &result.statements[0] {
+            Stmt::VarDeclaration { initializer: Some(Expr::Lambda { params, body }), .. } => {
+                assert_eq!(params, &["a", "b"]);
+                assert!(matches!(body[0], Stmt::Return { value: Some(Expr::Conditional { .. }) }));
+            }
+            _ => panic!("Expected lambda"),
+        }
+        match &result.statements[1] {
+            Stmt::While { body, .. } => match &**body {
+                Stmt::Block(stmts) => assert!(matches!(
+                    &stmts[0],
+                    Stmt::If { then_branch, else_branch: Some(else_branch), .. }
+                        if **then_branch == Stmt::Break && **else_branch == Stmt::Continue
+                )),
+                _ => panic!("Expected block"),
+            },
+            _ => panic!("Expected while loop"),
+        }
+        // The comma operator binds looser than assignment
+        assert!(matches!(
+            &result.statements[2],
+            Stmt::Expression(Expr::Binary { left, operator: BinaryOp::Comma, .. })
+                if matches!(**left, Expr::Assignment { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extensions_are_off_by_default() {
+        // `break` is an ordinary identifier in book Lox
+        assert!(parse_program("var break = 1;").is_ok());
+        assert!(parse_program("print a ? b : c;").is_err());
+        assert!(parse_program("var f = fun (a) { return a; };").is_err());
+        assert!(parse_program("print 1, 2;").is_err());
+        assert!(parse_program_with("var break = 1;", &LanguageOptions::EXTENDED).is_err());
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let options = LanguageOptions::EXTENDED;
+        let error = parse_program_with("print 1;\n  break;", &options).unwrap_err();
+        assert_eq!(error, "'break' outside of a loop at line 2, column 3");
+
+        // A function body inside a loop does not inherit it
+        let error = parse_program_with("while (true) { fun f() { continue; } }", &options).unwrap_err();
+        assert_eq!(error, "'continue' outside of a loop at line 1, column 26");
+
+        assert!(parse_program_with("for (;;) { { break; } }", &options).is_ok());
+    }
+
+    #[test]
+    fn test_borrowed_ast_points_into_source() {
+        use lox_ast::borrowed;
+        use std::borrow::Cow;
+
+        let source = String::from(r#"var greeting = "hello"; print greeting;"#);
+        let program = parse_program_borrowed(&source).unwrap();
+        let in_source = |text: &str| source.as_bytes().as_ptr_range().contains(&text.as_ptr());
+
+        match &program.statements[..] {
+            [
+                borrowed::Stmt::VarDeclaration {
+                    name,
+                    initializer: Some(borrowed::Expr::Literal(borrowed::Value::String(Cow::Borrowed(value)))),
+                    ..
+                },
+                borrowed::Stmt::Print(borrowed::Expr::Variable(variable)),
+            ] => {
+                assert_eq!((*name, *value, *variable), ("greeting", "hello", "greeting"));
+                assert!(in_source(name) && in_source(value) && in_source(variable));
+            }
+            other => panic!("Unexpected statements: {:?}", other),
+        }
+        assert_eq!(program.into_owned(), parse_program(&source).unwrap());
+    }
 }
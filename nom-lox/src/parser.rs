@@ -1,76 +1,189 @@
 //! Nom-based parser for the Lox language (nom 8.0)
 
+use std::borrow::Cow;
+
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until, take_while1},
-    character::complete::{
-        alpha1, alphanumeric1, char, multispace0, multispace1,
-    },
-    combinator::{map, opt, recognize, value},
-    multi::many0,
-    number::complete::double,
+    bytes::complete::{tag, take_while},
+    character::complete::{char, satisfy},
+    combinator::{map, not, opt, peek, recognize, value, verify},
+    multi::{many0, separated_list0},
+    error::{ErrorKind, ParseError},
     sequence::{delimited, pair, preceded, terminated},
     IResult, Parser,
 };
 
-use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+use lox_ast::{BinaryOp, Diagnostic, FunctionSignature, LanguageOptions, LexError, LexOptions, TypeAnnotation, UnaryOp};
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+use lox_ast::hints;
+use lox_ast::borrowed::{Expr, Program, Stmt, Value};
+use lox_ast::lexical::{
+    self, is_identifier_continue, is_identifier_start, scan_number, scan_string, skip_trivia, starts_number,
+};
+use lox_ast::span::Span;
+
+/// Lexical extensions enabled through this crate's cargo features
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 64 * 1024;
+
+/// Per-parse settings, passed down to every parser whose grammar depends on them
+///
+/// A parser that enters a loop or block hands its children a copy with the
+/// depth changed, so nothing needs putting back when it returns or fails.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct State {
+    /// Language extensions accepted
+    options: LanguageOptions,
+    /// Number of loops around the statement being parsed; function bodies start again from zero
+    loops: usize,
+    /// Number of blocks, including function bodies, around the declaration being parsed
+    blocks: usize,
+}
+
+impl State {
+    /// The state at the top level of a program accepting the extensions in `options`
+    pub(crate) fn new(options: &LanguageOptions) -> Self {
+        State { options: *options, loops: 0, blocks: 0 }
+    }
+
+    /// `parser` with this state given to it, as a plain nom parser
+    fn parser<'a, O>(self, parser: fn(State, &'a str) -> PResult<'a, O>) -> impl FnMut(&'a str) -> PResult<'a, O> {
+        move |input| parser(self, input)
+    }
+}
+
+/// Error type threaded through the parsers, able to carry a lexical error or a message
+#[derive(Debug, PartialEq)]
+pub struct Error<'a> {
+    input: &'a str,
+    kind: ErrorKind,
+    lex: Option<LexError>,
+    message: Option<&'static str>,
+}
+
+impl<'a> Error<'a> {
+    /// A fatal error that is reported with `message`
+    fn failure(input: &'a str, message: &'static str) -> nom::Err<Self> {
+        nom::Err::Failure(Self { input, kind: ErrorKind::Verify, lex: None, message: Some(message) })
+    }
+
+    /// A fatal lexical error
+    fn lexical(input: &'a str, kind: ErrorKind, lex: LexError) -> nom::Err<Self> {
+        nom::Err::Failure(Self { input, kind, lex: Some(lex), message: None })
+    }
+}
 
-/// Parse a line comment
-fn line_comment(input: &str) -> IResult<&str, ()> {
-    value((), pair(tag("//"), take_until("\n"))).parse(input)
+impl<'a> ParseError<&'a str> for Error<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        Self { input, kind, lex: None, message: None }
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keep whichever alternative got further, which is the likelier mistake
+    fn or(self, other: Self) -> Self {
+        if other.input.len() <= self.input.len() { other } else { self }
+    }
+}
+
+type PResult<'a, O> = IResult<&'a str, O, Error<'a>>;
+
+/// Parse whitespace and comments, returning the text of any doc comments among them
+fn trivia(input: &str) -> PResult<'_, Option<String>> {
+    match skip_trivia(input, &LEX_OPTIONS) {
+        Ok(trivia) => Ok((&input[trivia.len..], trivia.doc)),
+        Err(lex) => Err(Error::lexical(input, ErrorKind::Space, lex)),
+    }
 }
 
 /// Parse whitespace including comments
-fn whitespace(input: &str) -> IResult<&str, ()> {
-    value(
-        (),
-        many0(alt((value((), multispace1), line_comment))),
-    ).parse(input)
+fn whitespace(input: &str) -> PResult<'_, ()> {
+    value((), trivia).parse(input)
 }
 
-/// Parse an identifier
-fn identifier(input: &str) -> IResult<&str, String> {
-    map(
+/// Wrap a parser so that it skips the whitespace before it
+///
+/// Trailing whitespace is left alone, so that a doc comment after one
+/// declaration is still there for the next one to collect.
+fn token<'a, O, P>(parser: P) -> impl Parser<&'a str, Output = O, Error = Error<'a>>
+where
+    P: Parser<&'a str, Output = O, Error = Error<'a>>,
+{
+    preceded(whitespace, parser)
+}
+
+/// Parse a punctuation symbol, skipping the whitespace around it
+fn symbol<'a>(text: &'static str) -> impl Parser<&'a str, Output = &'a str, Error = Error<'a>> {
+    token(tag(text))
+}
+
+/// Parse a reserved word, requiring that it is not followed by an identifier character
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, Output = &'a str, Error = Error<'a>> {
+    token(terminated(tag(word), not(peek(satisfy(is_identifier_continue)))))
+}
+
+/// Parse an identifier that is not a reserved word
+fn identifier(state: State, input: &str) -> PResult<'_, &str> {
+    verify(
         recognize(pair(
-            alt((alpha1, tag("_"))),
-            many0(alt((alphanumeric1, tag("_")))),
+            satisfy(is_identifier_start),
+            take_while(is_identifier_continue),
         )),
-        |s: &str| s.to_string(),
+        |s: &str| !state.options.is_reserved(s),
     ).parse(input)
 }
 
-/// Parse a string literal
-fn string_literal(input: &str) -> IResult<&str, String> {
-    delimited(
-        char('"'),
-        map(
-            take_while1(|c| c != '"'),
-            |s: &str| s.to_string(),
-        ),
-        char('"'),
-    ).parse(input)
+/// Parse a string literal; once the opening quote is seen, failing to close it is fatal
+///
+/// The value borrows from the input unless escape sequences were decoded.
+fn string_literal(input: &str) -> PResult<'_, Cow<'_, str>> {
+    if !input.starts_with('"') {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Char)));
+    }
+
+    match scan_string(input, &LEX_OPTIONS) {
+        Ok((value, len)) => Ok((&input[len..], value)),
+        Err(lex) => Err(Error::lexical(input, ErrorKind::Char, lex)),
+    }
 }
 
-/// Parse a number literal
-fn number_literal(input: &str) -> IResult<&str, f64> {
-    double.parse(input)
+/// Parse a number literal; a malformed one such as `1.` or `1e10` is fatal
+fn number_literal(input: &str) -> PResult<'_, f64> {
+    if !starts_number(input) {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Digit)));
+    }
+
+    match scan_number(input, &LEX_OPTIONS) {
+        Ok((value, len)) => Ok((&input[len..], value)),
+        Err(lex) => Err(Error::lexical(input, ErrorKind::Digit, lex)),
+    }
 }
 
 /// Parse a boolean literal
-fn boolean_literal(input: &str) -> IResult<&str, bool> {
+fn boolean_literal(input: &str) -> PResult<'_, bool> {
     alt((
-        value(true, tag("true")),
-        value(false, tag("false")),
+        value(true, keyword("true")),
+        value(false, keyword("false")),
     )).parse(input)
 }
 
 /// Parse nil literal
-fn nil_literal(input: &str) -> IResult<&str, ()> {
-    value((), tag("nil")).parse(input)
+fn nil_literal(input: &str) -> PResult<'_, ()> {
+    value((), keyword("nil")).parse(input)
 }
 
 /// Parse a literal value
-fn literal(input: &str) -> IResult<&str, Value> {
+fn literal(input: &str) -> PResult<'_, Value<'_>> {
     alt((
         map(nil_literal, |_| Value::Nil),
         map(boolean_literal, Value::Bool),
@@ -79,57 +192,99 @@ fn literal(input: &str) -> IResult<&str, Value> {
     )).parse(input)
 }
 
-/// Parse a primary expression (literals, identifiers, groupings)
-fn primary(input: &str) -> IResult<&str, Expr> {
+/// Parse an anonymous function, if the extension is enabled
+fn lambda(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    if !state.options.lambdas {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Verify)));
+    }
+
+    map(
+        preceded(keyword("fun"), pair(state.parser(parameters), state.parser(function_body))),
+        |(params, body)| Expr::Lambda { params, body },
+    ).parse(input)
+}
+
+/// Parse a primary expression (literals, identifiers, `this`, `super`, lambdas, groupings)
+fn primary(state: State, input: &str) -> PResult<'_, Expr<'_>> {
     alt((
         map(literal, Expr::Literal),
-        map(identifier, Expr::Variable),
+        state.parser(lambda),
+        value(Expr::This, keyword("this")),
+        map(
+            preceded((keyword("super"), symbol(".")), state.parser(identifier)),
+            |method| Expr::Super { method },
+        ),
+        map(state.parser(identifier), Expr::Variable),
         delimited(
-            delimited(multispace0, char('('), multispace0),
-            map(expression, |e| Expr::Grouping(Box::new(e))),
-            delimited(multispace0, char(')'), multispace0),
+            symbol("("),
+            map(state.parser(expression), |e| Expr::Grouping(Box::new(e))),
+            symbol(")"),
         ),
     )).parse(input)
 }
 
+/// A call or property access following an expression
+#[derive(Clone)]
+enum Postfix<'a> {
+    Call(Vec<Expr<'a>>),
+    Get(&'a str),
+}
+
+/// Parse calls and property accesses, e.g. `a.b(c)(d).e`
+fn call(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = preceded(whitespace, state.parser(primary)).parse(input)?;
+
+    let (input, postfixes) = many0(alt((
+        map(
+            // Arguments bind tighter than the comma operator
+            delimited(symbol("("), separated_list0(symbol(","), state.parser(assignment)), symbol(")")),
+            Postfix::Call,
+        ),
+        map(preceded(symbol("."), state.parser(identifier)), Postfix::Get),
+    ))).parse(input)?;
+
+    Ok((input, postfixes.into_iter().fold(init, |acc, postfix| match postfix {
+        Postfix::Call(arguments) => Expr::Call {
+            callee: Box::new(acc),
+            arguments,
+        },
+        Postfix::Get(name) => Expr::Get {
+            object: Box::new(acc),
+            name,
+        },
+    })))
+}
+
 /// Parse unary expressions
-fn unary(input: &str) -> IResult<&str, Expr> {
+fn unary(state: State, input: &str) -> PResult<'_, Expr<'_>> {
     alt((
         map(
             pair(
-                delimited(
-                    multispace0,
-                    alt((
-                        value(UnaryOp::Not, char('!')),
-                        value(UnaryOp::Minus, char('-')),
-                    )),
-                    multispace0,
-                ),
-                unary,
+                token(alt((
+                    value(UnaryOp::Not, char('!')),
+                    value(UnaryOp::Minus, char('-')),
+                ))),
+                state.parser(unary),
             ),
             |(op, expr)| Expr::Unary {
                 operator: op,
                 operand: Box::new(expr),
             },
         ),
-        primary,
+        state.parser(call),
     )).parse(input)
 }
 
 /// Parse multiplication and division
-fn factor(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = unary(input)?;
+fn factor(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = unary(state, input)?;
 
     let (input, ops) = many0(pair(
-        delimited(
-            multispace0,
-            alt((
-                value(BinaryOp::Multiply, char('*')),
-                value(BinaryOp::Divide, char('/')),
-            )),
-            multispace0,
-        ),
-        unary,
+        token(alt((
+            value(BinaryOp::Multiply, char('*')),
+            value(BinaryOp::Divide, char('/')),
+        ))),
+        state.parser(unary),
     )).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (op, expr)| {
@@ -142,19 +297,15 @@ fn factor(input: &str) -> IResult<&str, Expr> {
 }
 
 /// Parse addition and subtraction
-fn term(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = factor(input)?;
+fn term(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = factor(state, input)?;
 
     let (input, ops) = many0(pair(
-        delimited(
-            multispace0,
-            alt((
-                value(BinaryOp::Add, char('+')),
-                value(BinaryOp::Subtract, char('-')),
-            )),
-            multispace0,
-        ),
-        factor,
+        token(alt((
+            value(BinaryOp::Add, char('+')),
+            value(BinaryOp::Subtract, char('-')),
+        ))),
+        state.parser(factor),
     )).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (op, expr)| {
@@ -167,21 +318,17 @@ fn term(input: &str) -> IResult<&str, Expr> {
 }
 
 /// Parse comparison operators
-fn comparison(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = term(input)?;
+fn comparison(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = term(state, input)?;
 
     let (input, ops) = many0(pair(
-        delimited(
-            multispace0,
-            alt((
-                value(BinaryOp::GreaterEqual, tag(">=")),
-                value(BinaryOp::Greater, char('>')),
-                value(BinaryOp::LessEqual, tag("<=")),
-                value(BinaryOp::Less, char('<')),
-            )),
-            multispace0,
-        ),
-        term,
+        token(alt((
+            value(BinaryOp::GreaterEqual, tag(">=")),
+            value(BinaryOp::Greater, char('>')),
+            value(BinaryOp::LessEqual, tag("<=")),
+            value(BinaryOp::Less, char('<')),
+        ))),
+        state.parser(term),
     )).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (op, expr)| {
@@ -194,19 +341,15 @@ fn comparison(input: &str) -> IResult<&str, Expr> {
 }
 
 /// Parse equality operators
-fn equality(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = comparison(input)?;
+fn equality(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = comparison(state, input)?;
 
     let (input, ops) = many0(pair(
-        delimited(
-            multispace0,
-            alt((
-                value(BinaryOp::NotEqual, tag("!=")),
-                value(BinaryOp::Equal, tag("==")),
-            )),
-            multispace0,
-        ),
-        comparison,
+        token(alt((
+            value(BinaryOp::NotEqual, tag("!=")),
+            value(BinaryOp::Equal, tag("==")),
+        ))),
+        state.parser(comparison),
     )).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (op, expr)| {
@@ -219,13 +362,10 @@ fn equality(input: &str) -> IResult<&str, Expr> {
 }
 
 /// Parse logical AND
-fn logical_and(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = equality(input)?;
+fn logical_and(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = equality(state, input)?;
 
-    let (input, ops) = many0(pair(
-        delimited(multispace0, tag("and"), multispace0),
-        equality,
-    )).parse(input)?;
+    let (input, ops) = many0(pair(keyword("and"), state.parser(equality))).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (_, expr)| {
         Expr::Binary {
@@ -237,13 +377,10 @@ fn logical_and(input: &str) -> IResult<&str, Expr> {
 }
 
 /// Parse logical OR
-fn logical_or(input: &str) -> IResult<&str, Expr> {
-    let (input, init) = logical_and(input)?;
+fn logical_or(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = logical_and(state, input)?;
 
-    let (input, ops) = many0(pair(
-        delimited(multispace0, tag("or"), multispace0),
-        logical_and,
-    )).parse(input)?;
+    let (input, ops) = many0(pair(keyword("or"), state.parser(logical_and))).parse(input)?;
 
     Ok((input, ops.into_iter().fold(init, |acc, (_, expr)| {
         Expr::Binary {
@@ -254,87 +391,493 @@ fn logical_or(input: &str) -> IResult<&str, Expr> {
     })))
 }
 
-/// Parse assignment
-fn assignment(input: &str) -> IResult<&str, Expr> {
+/// Parse a conditional expression, if the extension is enabled; it is right-associative
+fn conditional(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, condition) = logical_or(state, input)?;
+    if !state.options.conditional {
+        return Ok((input, condition));
+    }
+
+    let branches = (
+        preceded(symbol("?"), state.parser(expression)),
+        preceded(symbol(":"), state.parser(conditional)),
+    );
+    match opt(branches).parse(input)? {
+        (input, Some((then_branch, else_branch))) => Ok((input, Expr::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })),
+        (input, None) => Ok((input, condition)),
+    }
+}
+
+/// Parse assignment, whose target is only known to be a variable or property once `=` is seen
+fn assignment(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, _) = whitespace(input)?;
+    let (rest, target) = conditional(state, input)?;
+
+    let Ok((rest, _)) = token(terminated(char('='), not(char('=')))).parse(rest) else {
+        return Ok((rest, target));
+    };
+    let (rest, value) = assignment(state, rest)?;
+
+    match target {
+        Expr::Variable(name) => Ok((rest, Expr::Assignment {
+            name,
+            value: Box::new(value),
+        })),
+        Expr::Get { object, name } => Ok((rest, Expr::Set {
+            object,
+            name,
+            value: Box::new(value),
+        })),
+        _ => Err(Error::failure(input, "Invalid assignment target")),
+    }
+}
+
+/// Parse a full expression, including the comma operator if the extension is enabled
+fn expression(state: State, input: &str) -> PResult<'_, Expr<'_>> {
+    let (input, init) = assignment(state, input)?;
+    if !state.options.comma_operator {
+        return Ok((input, init));
+    }
+
+    let (input, rest) = many0(preceded(symbol(","), state.parser(assignment))).parse(input)?;
+
+    Ok((input, rest.into_iter().fold(init, |acc, expr| {
+        Expr::Binary {
+            left: Box::new(acc),
+            operator: BinaryOp::Comma,
+            right: Box::new(expr),
+        }
+    })))
+}
+
+/// Parse a print statement
+fn print_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        delimited(keyword("print"), state.parser(expression), symbol(";")),
+        Stmt::Print,
+    ).parse(input)
+}
+
+/// Parse a return statement
+fn return_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        delimited(keyword("return"), opt(state.parser(expression)), symbol(";")),
+        |value| Stmt::Return { value },
+    ).parse(input)
+}
+
+/// Parse `break;` or `continue;`, if the extension is enabled; either is an error outside a loop
+fn loop_control_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    if !state.options.break_continue {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Verify)));
+    }
+
+    let (input, _) = whitespace(input)?;
+    let (rest, (stmt, message)) = terminated(
+        alt((
+            value((Stmt::Break, "'break' outside of a loop"), keyword("break")),
+            value((Stmt::Continue, "'continue' outside of a loop"), keyword("continue")),
+        )),
+        symbol(";"),
+    ).parse(input)?;
+
+    if state.loops == 0 {
+        return Err(Error::failure(input, message));
+    }
+    Ok((rest, stmt))
+}
+
+/// Parse the body of a loop, inside which `break` and `continue` are allowed
+fn loop_body(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    statement(State { loops: state.loops + 1, ..state }, input)
+}
+
+/// Parse a block of declarations
+fn block(state: State, input: &str) -> PResult<'_, Vec<Stmt<'_>>> {
+    let inner = State { blocks: state.blocks + 1, ..state };
+    delimited(symbol("{"), many0(inner.parser(declaration)), symbol("}")).parse(input)
+}
+
+/// Parse an if statement with an optional else branch
+fn if_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        (
+            preceded(keyword("if"), delimited(symbol("("), state.parser(expression), symbol(")"))),
+            state.parser(statement),
+            opt(preceded(keyword("else"), state.parser(statement))),
+        ),
+        |(condition, then_branch, else_branch)| Stmt::If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+        },
+    ).parse(input)
+}
+
+/// Parse a while loop
+fn while_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        pair(
+            preceded(keyword("while"), delimited(symbol("("), state.parser(expression), symbol(")"))),
+            state.parser(loop_body),
+        ),
+        |(condition, body)| Stmt::While {
+            condition,
+            body: Box::new(body),
+        },
+    ).parse(input)
+}
+
+/// Parse a for loop, keeping its clauses rather than desugaring it
+fn for_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        (
+            preceded(keyword("for"), symbol("(")),
+            alt((
+                map(state.parser(var_declaration), Some),
+                map(state.parser(expr_stmt), Some),
+                value(None, symbol(";")),
+            )),
+            terminated(opt(state.parser(expression)), symbol(";")),
+            terminated(opt(state.parser(expression)), symbol(")")),
+            state.parser(loop_body),
+        ),
+        |(_, initializer, condition, increment, body)| Stmt::For {
+            initializer: initializer.map(Box::new),
+            condition,
+            increment,
+            body: Box::new(body),
+        },
+    ).parse(input)
+}
+
+/// Parse a type such as `Number`, `Point` or `fun(Number, String): Bool`
+fn type_annotation(state: State, input: &str) -> PResult<'_, TypeAnnotation> {
     alt((
         map(
             (
-                identifier,
-                delimited(multispace0, char('='), multispace0),
-                assignment,
+                keyword("fun"),
+                delimited(symbol("("), separated_list0(symbol(","), state.parser(type_annotation)), symbol(")")),
+                preceded(symbol(":"), state.parser(type_annotation)),
             ),
-            |(name, _, value)| Expr::Assignment {
-                name,
-                value: Box::new(value),
-            },
+            |(_, params, return_type)| TypeAnnotation::Function { params, return_type: Box::new(return_type) },
         ),
-        logical_or,
+        map(token(state.parser(identifier)), TypeAnnotation::from_name),
     )).parse(input)
 }
 
-/// Parse a full expression
-fn expression(input: &str) -> IResult<&str, Expr> {
-    assignment(input)
+/// Parse an optional `: Type`, which is only recognised in the typed dialect
+fn annotation(state: State, input: &str) -> PResult<'_, Option<TypeAnnotation>> {
+    if !state.options.type_annotations {
+        return Ok((input, None));
+    }
+    opt(preceded(symbol(":"), state.parser(type_annotation))).parse(input)
 }
 
-/// Parse a print statement
-fn print_stmt(input: &str) -> IResult<&str, Stmt> {
+/// Parse a variable declaration
+fn var_declaration(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
     map(
         (
-            delimited(multispace0, tag("print"), multispace0),
-            expression,
-            delimited(multispace0, char(';'), multispace0),
+            preceded(keyword("var"), token(state.parser(identifier))),
+            state.parser(annotation),
+            opt(preceded(symbol("="), state.parser(expression))),
+            symbol(";"),
         ),
-        |(_, expr, _)| Stmt::Print(expr),
+        |(name, type_annotation, initializer, _)| Stmt::VarDeclaration {
+            name,
+            type_annotation,
+            initializer,
+            doc: None,
+        },
     ).parse(input)
 }
 
-/// Parse a variable declaration
-fn var_declaration(input: &str) -> IResult<&str, Stmt> {
+/// Parse a parenthesised parameter list
+fn parameters(state: State, input: &str) -> PResult<'_, Vec<&str>> {
+    delimited(symbol("("), separated_list0(symbol(","), token(state.parser(identifier))), symbol(")")).parse(input)
+}
+
+/// Parse a parenthesised parameter list whose parameters may be annotated
+fn typed_parameters(state: State, input: &str) -> PResult<'_, Vec<(&str, Option<TypeAnnotation>)>> {
+    delimited(
+        symbol("("),
+        separated_list0(symbol(","), pair(token(state.parser(identifier)), state.parser(annotation))),
+        symbol(")"),
+    ).parse(input)
+}
+
+/// Parse the body of a function, where there is no loop for `break` or `continue` to leave
+fn function_body(state: State, input: &str) -> PResult<'_, Vec<Stmt<'_>>> {
+    block(State { loops: 0, ..state }, input)
+}
+
+/// Parse a function's name, parameters and body; used for `fun` declarations and methods
+fn function(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
     map(
         (
-            delimited(multispace0, tag("var"), multispace0),
-            delimited(multispace0, identifier, multispace0),
-            opt(preceded(delimited(multispace0, char('='), multispace0), expression)),
-            delimited(multispace0, char(';'), multispace0),
+            token(state.parser(identifier)),
+            state.parser(typed_parameters),
+            state.parser(annotation),
+            state.parser(function_body),
         ),
-        |(_, name, initializer, _)| Stmt::VarDeclaration { name, initializer },
+        |(name, params, return_type, body)| {
+            let (params, param_types) = params.into_iter().unzip();
+            Stmt::Function {
+                name,
+                params,
+                signature: FunctionSignature::from_parts(param_types, return_type),
+                body,
+                doc: None,
+            }
+        },
+    ).parse(input)
+}
+
+/// Parse a function declaration
+fn fun_declaration(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    preceded(keyword("fun"), state.parser(function)).parse(input)
+}
+
+/// Parse a field's type, `name: Type;`, which is only recognised in the typed dialect
+fn field(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    if !state.options.type_annotations {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Verify)));
+    }
+
+    map(
+        terminated(
+            pair(token(state.parser(identifier)), preceded(symbol(":"), state.parser(type_annotation))),
+            symbol(";"),
+        ),
+        |(name, annotation)| Stmt::VarDeclaration {
+            name,
+            type_annotation: Some(annotation),
+            initializer: None,
+            doc: None,
+        },
+    ).parse(input)
+}
+
+/// Parse a method or field, which may have its own doc comment
+fn method(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        pair(trivia, alt((state.parser(field), state.parser(function)))),
+        |(doc, method)| method.with_doc(doc),
+    ).parse(input)
+}
+
+/// Parse a class declaration
+fn class_declaration(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    map(
+        (
+            preceded(keyword("class"), token(state.parser(identifier))),
+            opt(preceded(symbol("<"), token(state.parser(identifier)))),
+            delimited(symbol("{"), many0(state.parser(method)), symbol("}")),
+        ),
+        |(name, superclass, members)| {
+            let (fields, methods) = members.into_iter().partition(|member| matches!(member, Stmt::VarDeclaration { .. }));
+            Stmt::Class { name, superclass, fields, methods, doc: None }
+        },
     ).parse(input)
 }
 
 /// Parse an expression statement
-fn expr_stmt(input: &str) -> IResult<&str, Stmt> {
+fn expr_stmt(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
     map(
-        terminated(expression, delimited(multispace0, char(';'), multispace0)),
+        terminated(state.parser(expression), symbol(";")),
         Stmt::Expression,
     ).parse(input)
 }
 
 /// Parse a statement
-fn statement(input: &str) -> IResult<&str, Stmt> {
+fn statement(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
     preceded(
         whitespace,
         alt((
-            print_stmt,
-            var_declaration,
-            expr_stmt,
+            state.parser(print_stmt),
+            state.parser(if_stmt),
+            state.parser(while_stmt),
+            state.parser(for_stmt),
+            state.parser(return_stmt),
+            state.parser(loop_control_stmt),
+            map(state.parser(block), Stmt::Block),
+            state.parser(expr_stmt),
         )),
     ).parse(input)
 }
 
-/// Parse a program (list of statements)
-pub fn program(input: &str) -> IResult<&str, Program> {
+/// Parse `import "path";`, if the extension is enabled; it is an error inside a block
+fn import_declaration(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
+    if !state.options.imports {
+        return Err(nom::Err::Error(Error::from_error_kind(input, ErrorKind::Verify)));
+    }
+
+    let (input, _) = whitespace(input)?;
+    let (rest, path) = delimited(keyword("import"), token(string_literal), symbol(";")).parse(input)?;
+    if state.blocks > 0 {
+        return Err(Error::failure(input, "'import' outside of the top level"));
+    }
+    Ok((rest, Stmt::Import { path }))
+}
+
+/// Parse a declaration, attaching any doc comment before it
+fn declaration(state: State, input: &str) -> PResult<'_, Stmt<'_>> {
     map(
-        terminated(many0(statement), whitespace),
+        pair(
+            trivia,
+            alt((
+                state.parser(class_declaration),
+                state.parser(fun_declaration),
+                state.parser(var_declaration),
+                state.parser(import_declaration),
+                state.parser(statement),
+            )),
+        ),
+        |(doc, stmt)| stmt.with_doc(doc),
+    ).parse(input)
+}
+
+/// Parse a program (list of declarations)
+fn program(state: State, input: &str) -> PResult<'_, Program<'_>> {
+    map(
+        terminated(many0(state.parser(declaration)), whitespace),
         Program::new,
     ).parse(input)
 }
 
-/// Parse a complete Lox program from a string
-pub fn parse_program(input: &str) -> Result<Program, String> {
-    match program(input) {
-        Ok(("", program)) => Ok(program),
-        Ok((remaining, _)) => Err(format!("Unexpected input: {}", remaining)),
-        Err(e) => Err(format!("Parse error: {:?}", e)),
+/// Parse a complete program in strict book Lox
+pub fn parse_program(input: &str) -> Result<lox_ast::Program, String> {
+    parse_program_with(input, &LanguageOptions::default())
+}
+
+/// Parse a complete program, accepting the language extensions in `options`
+pub fn parse_program_with(input: &str, options: &LanguageOptions) -> Result<lox_ast::Program, String> {
+    parse_program_borrowed_with(input, options).map(Program::into_owned)
+}
+
+/// Parse a complete program in strict book Lox into an AST that borrows from `input`
+pub fn parse_program_borrowed(input: &str) -> Result<Program<'_>, String> {
+    parse_program_borrowed_with(input, &LanguageOptions::default())
+}
+
+/// Parse a complete program into an AST that borrows from `input`, accepting the
+/// language extensions in `options`
+pub fn parse_program_borrowed_with<'src>(
+    input: &'src str,
+    options: &LanguageOptions,
+) -> Result<Program<'src>, String> {
+    nested(input, DEFAULT_MAX_DEPTH, || parse(input, State::new(options)))
+        .and_then(|result| result)
+        .map_err(|error| error.describe(input))
+}
+
+/// Parse a complete program, accepting the language extensions in `options`,
+/// and report a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str, options: &LanguageOptions) -> Result<lox_ast::Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, options, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(
+    input: &str,
+    options: &LanguageOptions,
+    max_depth: usize,
+) -> Result<lox_ast::Program, Box<Diagnostic>> {
+    nested(input, max_depth, || parse(input, State::new(options)))?.map(Program::into_owned)
+}
+
+/// Run `parse` on a stack with room for how deeply `input` nests, failing
+/// instead when that is deeper than `max_depth`
+pub(crate) fn nested<T>(input: &str, max_depth: usize, parse: impl FnOnce() -> T) -> Result<T, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    Ok(depth::with_stack(depth, STACK_PER_LEVEL, parse))
+}
+
+/// Parse the top-level declarations from byte offset `start` of `input`,
+/// each with its span, until `stop` returns true for the end of one
+///
+/// Returns whether `stop` ended the parse early. Fails when the input is not
+/// a valid program from `start` on; `parse` describes why.
+pub(crate) fn declarations(
+    input: &str,
+    start: usize,
+    state: State,
+    mut stop: impl FnMut(usize) -> bool,
+) -> Result<(Vec<(Span, lox_ast::Stmt)>, bool), ()> {
+    let offset = |rest: &str| input.len() - rest.len();
+    let mut rest = &input[start..];
+    let mut parsed = Vec::new();
+    loop {
+        match declaration(state, rest) {
+            Ok((after, stmt)) => {
+                // The declaration parsed, so its leading trivia did too
+                let (first_token, ()) = whitespace(rest).map_err(|_| ())?;
+                let end = offset(after);
+                parsed.push((Span::new(offset(first_token), end), stmt.into_owned()));
+                rest = after;
+                if stop(end) {
+                    return Ok((parsed, true));
+                }
+            }
+            // As in `program`, the declarations end where only trivia is left
+            Err(nom::Err::Error(_)) => {
+                return match whitespace(rest) {
+                    Ok(("", ())) => Ok((parsed, false)),
+                    _ => Err(()),
+                };
+            }
+            Err(_) => return Err(()),
+        }
+    }
+}
+
+/// Parse a complete program with `state`, describing any failure
+fn parse(input: &str, state: State) -> Result<Program<'_>, Box<Diagnostic>> {
+    let offset = |at: &str| input.len() - at.len();
+
+    let diagnostic = match program(state, input) {
+        Ok(("", program)) => return Ok(program),
+        Ok((remaining, _)) => {
+            // `many0` drops the error that stopped it, so parse the declaration
+            // again to find how far it got
+            let at = match declaration(state, remaining) {
+                Err(nom::Err::Error(error) | nom::Err::Failure(error)) => error.input,
+                _ => remaining,
+            };
+            let at = offset(at) + trivia(at).map_or(0, |(rest, _)| at.len() - rest.len());
+            Diagnostic::unexpected(input, at, &LEX_OPTIONS).with_unclosed_delimiter(input, &LEX_OPTIONS)
+        }
+        Err(nom::Err::Failure(Error { input: at, lex: Some(lex), .. })) => lex.offset(offset(at)).into(),
+        Err(nom::Err::Failure(Error { input: at, message: Some(message), .. })) => {
+            let at = offset(at);
+            Diagnostic::error(message, lexical::token_span(input, at, &LEX_OPTIONS))
+        }
+        Err(e) => Diagnostic::error(format!("Parse error: {:?}", e), Span::new(0, 0)),
+    };
+    Err(Box::new(hints::explain(input, diagnostic, &LEX_OPTIONS, &state.options)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_parse_inside_another_keeps_its_own_settings() {
+        let input = "while (true) { print 1; }\nbreak;";
+        let mut inner = Vec::new();
+        let outer = declarations(input, 0, State::new(&LanguageOptions::EXTENDED), |_| {
+            inner.push(parse_program_with("import \"a.lox\";", &LanguageOptions::STRICT).is_ok());
+            false
+        });
+
+        // The strict parses refuse imports, and the loop they ran inside did
+        // not let the extended parse accept a `break` after it
+        assert_eq!(inner, vec![false]);
+        assert_eq!(outer, Err(()));
     }
-}
\ No newline at end of file
+}
@@ -3,191 +3,30 @@
 //! This crate combines functionality from parser-comparison, parser-comparison-simple,
 //! and simple-test into a unified testing framework for all Lox parsers.
 
-use lox_ast::Program;
+use lox_ast::LexOptions;
+use lox_ast::export::{GraphFormat, render_side_by_side};
 use colored::*;
 
-/// Trait for unified parser testing
-pub trait LoxParser: Send + Sync {
-    fn name(&self) -> &'static str;
-    fn version(&self) -> &'static str;
-    fn description(&self) -> &'static str;
-    fn parse(&self, input: &str) -> ParseResult;
-}
-
-/// Result of parsing with success/failure information
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ParseResult {
-    pub success: bool,
-    pub program: Option<Program>,
-    pub error: Option<String>,
-    pub statement_count: usize,
-}
-
-/// Parser implementation for nom-lox
-pub struct NomParser;
-
-impl LoxParser for NomParser {
-    fn name(&self) -> &'static str { nom_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { nom_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { nom_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match nom_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-// ChumskyParser implementation commented out due to macOS linker issues
-// The chumsky-lox parser works but causes linker assertion failures on macOS
-// when included in the test framework
-
-/// Parser implementation for pest-lox
-pub struct PestParser;
-
-impl LoxParser for PestParser {
-    fn name(&self) -> &'static str { pest_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { pest_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { pest_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match pest_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-/// Parser implementation for winnow-lox
-pub struct WinnowParser;
-
-impl LoxParser for WinnowParser {
-    fn name(&self) -> &'static str { winnow_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { winnow_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { winnow_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match winnow_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-/// Parser implementation for lalrpop-lox
-pub struct LalrpopParser;
-
-impl LoxParser for LalrpopParser {
-    fn name(&self) -> &'static str { lalrpop_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { lalrpop_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { lalrpop_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match lalrpop_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-/// Parser implementation for pom-lox
-pub struct PomParser;
-
-impl LoxParser for PomParser {
-    fn name(&self) -> &'static str { pom_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { pom_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { pom_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match pom_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-/// Parser implementation for lelwel-lox
-pub struct LelwelParser;
-
-impl LoxParser for LelwelParser {
-    fn name(&self) -> &'static str { lelwel_lox::PARSER_NAME }
-    fn version(&self) -> &'static str { lelwel_lox::PARSER_VERSION }
-    fn description(&self) -> &'static str { lelwel_lox::PARSER_DESCRIPTION }
-
-    fn parse(&self, input: &str) -> ParseResult {
-        match lelwel_lox::parse_program(input) {
-            Ok(program) => ParseResult {
-                success: true,
-                statement_count: program.statements.len(),
-                program: Some(program),
-                error: None,
-            },
-            Err(e) => ParseResult {
-                success: false,
-                program: None,
-                error: Some(format!("{:?}", e)),
-                statement_count: 0,
-            },
-        }
-    }
-}
-
-/// Get all available parsers (only working ones to avoid linker issues)
+pub mod report;
+pub mod snapshot;
+pub mod validation;
+
+pub use lox_parser_api::{Capabilities, Extension, Feature, LoxParser, ParseError, ParseOptions};
+pub use nom_lox::NomParser;
+pub use chumsky_lox::ChumskyParser;
+pub use combine_lox::CombineParser;
+pub use pest_lox::PestParser;
+pub use winnow_lox::WinnowParser;
+pub use lalrpop_lox::LalrpopParser;
+pub use pom_lox::PomParser;
+pub use lelwel_lox::LelwelParser;
+
+/// Get all available parsers
 pub fn get_all_parsers() -> Vec<Box<dyn LoxParser>> {
     vec![
         Box::new(NomParser),
-        // Box::new(ChumskyParser),  // Disabled due to macOS linker issues
+        Box::new(ChumskyParser),
+        Box::new(CombineParser),
         Box::new(PestParser),
         Box::new(WinnowParser),
         Box::new(LalrpopParser),
@@ -200,7 +39,8 @@ pub fn get_all_parsers() -> Vec<Box<dyn LoxParser>> {
 pub fn get_working_parsers() -> Vec<Box<dyn LoxParser>> {
     vec![
         Box::new(NomParser),
-        // Box::new(ChumskyParser),  // Disabled due to macOS linker issues
+        Box::new(ChumskyParser),
+        Box::new(CombineParser),
         Box::new(PestParser),
         Box::new(WinnowParser),
         Box::new(LalrpopParser),
@@ -209,127 +49,299 @@ pub fn get_working_parsers() -> Vec<Box<dyn LoxParser>> {
     ]
 }
 
+/// The working parsers that declare `feature`; tests of it skip the others
+pub fn parsers_supporting(feature: Feature) -> Vec<Box<dyn LoxParser>> {
+    get_working_parsers().into_iter().filter(|parser| parser.capabilities().supports(feature)).collect()
+}
+
+/// The working parsers built with the lexical extension `extension` selects;
+/// tests of it skip the others
+pub fn parsers_lexing(extension: fn(&LexOptions) -> bool) -> Vec<Box<dyn LoxParser>> {
+    get_working_parsers().into_iter().filter(|parser| extension(&parser.capabilities().lexical)).collect()
+}
+
+/// The part of the language a test case exercises; the rows of the conformance matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub enum Category {
+    Literals,
+    Operators,
+    Statements,
+    Functions,
+    ControlFlow,
+    Classes,
+    /// Invalid programs, which pass when the parser rejects them
+    Errors,
+}
+
+impl Category {
+    pub const ALL: [Category; 7] = [
+        Category::Literals,
+        Category::Operators,
+        Category::Statements,
+        Category::Functions,
+        Category::ControlFlow,
+        Category::Classes,
+        Category::Errors,
+    ];
+
+    /// How the category is titled in reports
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::Literals => "Literals",
+            Category::Operators => "Operators and precedence",
+            Category::Statements => "Statements and assignment",
+            Category::Functions => "Functions",
+            Category::ControlFlow => "Control flow",
+            Category::Classes => "Classes",
+            Category::Errors => "Syntax errors",
+        }
+    }
+}
+
 /// Test case structure
 #[derive(Debug, Clone)]
 pub struct TestCase {
     pub name: &'static str,
+    pub category: Category,
     pub input: &'static str,
+    /// Ignored for `Category::Errors` cases, which must fail to parse
     pub expected_statements: usize,
     pub description: &'static str,
+    /// Parsers without all of these skip the case rather than fail it
+    pub requires: &'static [Feature],
 }
 
 /// Standard test cases covering all Lox language features
 pub const TEST_CASES: &[TestCase] = &[
     TestCase {
         name: "simple_literal",
+        category: Category::Literals,
         input: "42;",
         expected_statements: 1,
         description: "Simple number literal",
+        requires: &[],
     },
     TestCase {
         name: "string_literal",
+        category: Category::Literals,
         input: r#""Hello, world!";"#,
         expected_statements: 1,
         description: "String literal",
+        requires: &[],
     },
     TestCase {
         name: "print_statement",
+        category: Category::Statements,
         input: r#"print "Hello, world!";"#,
         expected_statements: 1,
         description: "Print statement with string",
+        requires: &[],
     },
     TestCase {
         name: "variable_declaration",
+        category: Category::Statements,
         input: "var x = 42;",
         expected_statements: 1,
         description: "Variable declaration with initializer",
+        requires: &[],
     },
     TestCase {
         name: "variable_assignment",
+        category: Category::Statements,
         input: "var x = 10; x = 20;",
         expected_statements: 2,
         description: "Variable declaration and assignment",
+        requires: &[],
     },
     TestCase {
         name: "arithmetic_expression",
+        category: Category::Operators,
         input: "1 + 2 * 3;",
         expected_statements: 1,
         description: "Arithmetic with operator precedence",
+        requires: &[],
     },
     TestCase {
         name: "comparison_expression",
+        category: Category::Operators,
         input: "5 > 3;",
         expected_statements: 1,
         description: "Comparison operation",
+        requires: &[],
     },
     TestCase {
         name: "logical_expression",
+        category: Category::Operators,
         input: "true and false;",
         expected_statements: 1,
         description: "Logical AND operation",
+        requires: &[],
     },
     TestCase {
         name: "grouped_expression",
+        category: Category::Operators,
         input: "(1 + 2) * 3;",
         expected_statements: 1,
         description: "Grouped expression with parentheses",
+        requires: &[],
     },
     TestCase {
         name: "boolean_literals",
+        category: Category::Literals,
         input: "true; false; nil;",
         expected_statements: 3,
         description: "Boolean and nil literals",
+        requires: &[],
     },
     TestCase {
         name: "unary_expressions",
+        category: Category::Operators,
         input: "-42; !true;",
         expected_statements: 2,
         description: "Unary minus and logical not",
+        requires: &[],
     },
     TestCase {
         name: "complex_arithmetic",
+        category: Category::Operators,
         input: "1 + 2 * 3 - 4 / 2;",
         expected_statements: 1,
         description: "Complex arithmetic with multiple operators",
+        requires: &[],
+    },
+    TestCase {
+        name: "keyword_prefixed_identifiers",
+        category: Category::Statements,
+        input: "var nilly = 1; var orchid = nilly; printer = orchid;",
+        expected_statements: 3,
+        description: "Identifiers that start with a reserved word",
+        requires: &[],
     },
     TestCase {
         name: "multiple_statements",
+        category: Category::Statements,
         input: "var a = 10; var b = 20; var sum = a + b; print sum;",
         expected_statements: 4,
         description: "Multiple statements with variables and operations",
+        requires: &[],
+    },
+    TestCase {
+        name: "function_declaration",
+        category: Category::Functions,
+        input: "fun add(a, b) { return a + b; } print add(1, 2);",
+        expected_statements: 2,
+        description: "Function declaration and call",
+        requires: &[Feature::FullGrammar],
+    },
+    TestCase {
+        name: "control_flow",
+        category: Category::ControlFlow,
+        input: "var i = 0; while (i < 3) { if (i == 1) print i; else print -i; i = i + 1; }",
+        expected_statements: 2,
+        description: "Loops, conditionals and blocks",
+        requires: &[Feature::FullGrammar],
+    },
+    TestCase {
+        name: "class_declaration",
+        category: Category::Classes,
+        input: "class Point { init(x) { this.x = x; } } print Point(1).x;",
+        expected_statements: 2,
+        description: "Class with an initializer and field access",
+        requires: &[Feature::FullGrammar],
+    },
+    TestCase {
+        name: "unclosed_paren",
+        category: Category::Errors,
+        input: "print (1 + 2;",
+        expected_statements: 0,
+        description: "Rejects an unclosed parenthesis",
+        requires: &[],
+    },
+    TestCase {
+        name: "missing_semicolon",
+        category: Category::Errors,
+        input: "var x = 1",
+        expected_statements: 0,
+        description: "Rejects a statement without ';'",
+        requires: &[],
+    },
+    TestCase {
+        name: "unterminated_string",
+        category: Category::Errors,
+        input: "print \"oops;",
+        expected_statements: 0,
+        description: "Rejects an unterminated string",
+        requires: &[],
+    },
+    TestCase {
+        name: "incomplete_expression",
+        category: Category::Errors,
+        input: "1 +;",
+        expected_statements: 0,
+        description: "Rejects a binary operator without a right operand",
+        requires: &[],
+    },
+    TestCase {
+        name: "invalid_assignment_target",
+        category: Category::Errors,
+        input: "a + 2 = 3;",
+        expected_statements: 0,
+        description: "Rejects assigning to something other than a variable",
+        requires: &[],
     },
 ];
 
+/// How a test case went for one parser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    /// The parser does not declare a feature the case requires
+    Skipped,
+}
+
 /// Run a single test case against a parser
-pub fn run_test_case(parser: &dyn LoxParser, test_case: &TestCase) -> bool {
-    let result = parser.parse(test_case.input);
-    result.success && result.statement_count == test_case.expected_statements
+pub fn run_test_case(parser: &dyn LoxParser, test_case: &TestCase) -> Outcome {
+    let capabilities = parser.capabilities();
+    if !test_case.requires.iter().all(|&feature| capabilities.supports(feature)) {
+        return Outcome::Skipped;
+    }
+    match (test_case.category, parser.parse(test_case.input, &ParseOptions::default())) {
+        (Category::Errors, Err(_)) => Outcome::Passed,
+        (Category::Errors, Ok(_)) => Outcome::Failed,
+        (_, Ok(program)) if program.statements.len() == test_case.expected_statements => Outcome::Passed,
+        _ => Outcome::Failed,
+    }
 }
 
 /// Run all test cases against a parser and return summary
 pub fn run_parser_tests(parser: &dyn LoxParser) -> TestSummary {
     let mut passed = 0;
     let mut failed = 0;
+    let mut skipped = 0;
     let mut results = Vec::new();
 
     for test_case in TEST_CASES {
-        let success = run_test_case(parser, test_case);
-        if success {
-            passed += 1;
-        } else {
-            failed += 1;
+        let outcome = run_test_case(parser, test_case);
+        match outcome {
+            Outcome::Passed => passed += 1,
+            Outcome::Failed => failed += 1,
+            Outcome::Skipped => skipped += 1,
         }
         results.push(TestResult {
             test_name: test_case.name.to_string(),
-            success,
+            category: test_case.category,
+            outcome,
             description: test_case.description.to_string(),
         });
     }
 
     TestSummary {
         parser_name: parser.name().to_string(),
+        parser_version: parser.version().to_string(),
         passed,
         failed,
+        skipped,
         total: TEST_CASES.len(),
         results,
     }
@@ -339,8 +351,12 @@ pub fn run_parser_tests(parser: &dyn LoxParser) -> TestSummary {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TestSummary {
     pub parser_name: String,
+    /// The version of its library the parser declares; reports prefer the one Cargo resolved
+    pub parser_version: String,
     pub passed: usize,
     pub failed: usize,
+    /// Cases needing a feature the parser does not declare; they count towards `total`
+    pub skipped: usize,
     pub total: usize,
     pub results: Vec<TestResult>,
 }
@@ -349,43 +365,52 @@ pub struct TestSummary {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TestResult {
     pub test_name: String,
-    pub success: bool,
+    pub category: Category,
+    pub outcome: Outcome,
     pub description: String,
 }
 
 impl TestSummary {
+    /// The number of cases that were run rather than skipped
+    pub fn run(&self) -> usize {
+        self.total - self.skipped
+    }
+
     /// Print a colored summary of the test results
     pub fn print_summary(&self) {
-        let success_rate = (self.passed as f64 / self.total as f64) * 100.0;
+        let success_rate = (self.passed as f64 / self.run() as f64) * 100.0;
 
         println!("\n=== {} Test Results ===", self.parser_name.bold());
 
-        if self.passed == self.total {
+        if self.passed == self.run() {
             println!("✅ {} All tests passed! ({}/{})",
                 "SUCCESS:".green().bold(),
                 self.passed.to_string().green().bold(),
-                self.total
+                self.run()
             );
         } else if self.passed > 0 {
             println!("⚠️  {} {}/{} tests passed ({:.1}%)",
                 "PARTIAL:".yellow().bold(),
                 self.passed.to_string().yellow().bold(),
-                self.total,
+                self.run(),
                 success_rate
             );
         } else {
             println!("❌ {} All tests failed ({}/{})",
                 "FAILED:".red().bold(),
                 self.failed.to_string().red().bold(),
-                self.total
+                self.run()
             );
         }
+        if self.skipped > 0 {
+            println!("⏭️  {} {} tests need features the parser does not declare", "SKIPPED:".dimmed(), self.skipped);
+        }
 
         // Show failed tests
         if self.failed > 0 {
             println!("\n{}", "Failed tests:".red().bold());
             for result in &self.results {
-                if !result.success {
+                if result.outcome == Outcome::Failed {
                     println!("  ❌ {}: {}", result.test_name.red(), result.description.dimmed());
                 }
             }
@@ -410,7 +435,7 @@ pub fn compare_all_parsers() -> Vec<TestSummary> {
     // Print overall comparison
     println!("\n{}", "=== Overall Comparison ===".bold().cyan());
     for summary in &summaries {
-        let status = if summary.passed == summary.total {
+        let status = if summary.passed == summary.run() {
             "✅ WORKING".green().bold()
         } else if summary.passed > 0 {
             "⚠️  PARTIAL".yellow().bold()
@@ -418,13 +443,32 @@ pub fn compare_all_parsers() -> Vec<TestSummary> {
             "❌ BROKEN".red().bold()
         };
 
-        println!("{:12} {}/{:2} tests passed - {}",
+        println!("{:12} {}/{:2} tests passed, {} skipped - {}",
             status,
             summary.passed,
-            summary.total,
+            summary.run(),
+            summary.skipped,
             summary.parser_name.bold()
         );
     }
 
     summaries
 }
+
+/// Render the trees two parsers build for `input` next to each other, with
+/// the subtrees where they disagree highlighted
+///
+/// Fails with the parser's error when either cannot parse `input`.
+pub fn render_parsers_side_by_side(
+    input: &str,
+    left: &dyn LoxParser,
+    right: &dyn LoxParser,
+    format: GraphFormat,
+) -> Result<String, String> {
+    let parse = |parser: &dyn LoxParser| {
+        parser.parse(input, &ParseOptions::default())
+            .map_err(|error| format!("{} parser failed: {}", parser.name(), error.describe(input)))
+    };
+    let (left_program, right_program) = (parse(left)?, parse(right)?);
+    Ok(render_side_by_side((left.name(), &left_program), (right.name(), &right_program), format))
+}
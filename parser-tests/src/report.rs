@@ -0,0 +1,217 @@
+//! The conformance matrix: how each backend does on each part of the language
+//!
+//! A [`ConformanceMatrix`] is built from the [`TestSummary`] of every parser,
+//! with a row per [`Category`] and a column per backend, and rendered as
+//! Markdown, HTML or JSON. Each backend is named with its parser's `version()`:
+//! the version of its parsing library that its manifest requires, rather than
+//! what Cargo resolved, so that `cargo update` leaves the checked-in reports alone.
+//!
+//! The rendered reports are checked in under `parser-tests/conformance/`;
+//! the tests fail when they are out of date and rewrite them when run with
+//! `UPDATE_CONFORMANCE=1`.
+
+use crate::{Category, Outcome, TestSummary};
+
+/// How one backend did on the cases of one category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Cell {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Cell {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Passed => self.passed += 1,
+            Outcome::Failed => self.failed += 1,
+            Outcome::Skipped => self.skipped += 1,
+        }
+    }
+
+    /// The number of cases that were run rather than skipped
+    pub fn run(&self) -> usize {
+        self.passed + self.failed
+    }
+
+    /// A symbol for the cell: ✅ all passed, ⚠️ some failed, ❌ all failed,
+    /// ➖ all skipped
+    pub fn symbol(&self) -> &'static str {
+        if self.run() == 0 {
+            "➖"
+        } else if self.failed == 0 {
+            "✅"
+        } else if self.passed > 0 {
+            "⚠️"
+        } else {
+            "❌"
+        }
+    }
+
+    fn text(&self) -> String {
+        if self.run() == 0 { self.symbol().to_string() } else { format!("{} {}/{}", self.symbol(), self.passed, self.run()) }
+    }
+
+    fn class(&self) -> &'static str {
+        if self.run() == 0 {
+            "skipped"
+        } else if self.failed == 0 {
+            "passed"
+        } else if self.passed > 0 {
+            "partial"
+        } else {
+            "failed"
+        }
+    }
+}
+
+/// A backend's column
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Column {
+    pub name: String,
+    pub version: String,
+    pub total: Cell,
+    /// The names of the cases it failed
+    pub failures: Vec<String>,
+}
+
+/// A category's row, with a cell per backend in column order
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Row {
+    pub category: Category,
+    pub title: String,
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConformanceMatrix {
+    pub backends: Vec<Column>,
+    pub rows: Vec<Row>,
+}
+
+impl ConformanceMatrix {
+    /// Tabulate `summaries`, a column per parser
+    pub fn new(summaries: &[TestSummary]) -> Self {
+        let backends = summaries
+            .iter()
+            .map(|summary| {
+                let mut total = Cell::default();
+                summary.results.iter().for_each(|result| total.add(result.outcome));
+                Column {
+                    name: summary.parser_name.clone(),
+                    version: summary.parser_version.clone(),
+                    total,
+                    failures: summary
+                        .results
+                        .iter()
+                        .filter(|result| result.outcome == Outcome::Failed)
+                        .map(|result| result.test_name.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+        let rows = Category::ALL
+            .into_iter()
+            .map(|category| Row {
+                category,
+                title: category.title().to_string(),
+                cells: summaries
+                    .iter()
+                    .map(|summary| {
+                        let mut cell = Cell::default();
+                        summary
+                            .results
+                            .iter()
+                            .filter(|result| result.category == category)
+                            .for_each(|result| cell.add(result.outcome));
+                        cell
+                    })
+                    .collect(),
+            })
+            .collect();
+        ConformanceMatrix { backends, rows }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Parser conformance\n\n");
+        markdown += "Generated from the shared test cases in `parser-tests`; do not edit by hand.\n";
+        markdown += "Regenerate with `UPDATE_CONFORMANCE=1 cargo test -p parser-tests --test conformance`.\n\n";
+
+        markdown += "| Feature |";
+        for backend in &self.backends {
+            markdown += &format!(" {} {} |", backend.name, backend.version);
+        }
+        markdown += "\n|---|";
+        markdown += &"---|".repeat(self.backends.len());
+        markdown += "\n";
+        for row in &self.rows {
+            markdown += &format!("| {} |", row.title);
+            for cell in &row.cells {
+                markdown += &format!(" {} |", cell.text());
+            }
+            markdown += "\n";
+        }
+        markdown += "| **Total** |";
+        for backend in &self.backends {
+            markdown += &format!(" **{}** |", backend.total.text());
+        }
+        markdown += "\n\n✅ all passed · ⚠️ some failed · ❌ all failed · ➖ skipped: the parser does not \
+                     declare the grammar the cases need\n";
+
+        if self.backends.iter().any(|backend| !backend.failures.is_empty()) {
+            markdown += "\n## Failures\n\n";
+            for backend in self.backends.iter().filter(|backend| !backend.failures.is_empty()) {
+                let failures: Vec<String> = backend.failures.iter().map(|name| format!("`{}`", name)).collect();
+                markdown += &format!("- **{}**: {}\n", backend.name, failures.join(", "));
+            }
+        }
+        markdown
+    }
+
+    /// A standalone HTML page with the matrix as a table
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Parser conformance</title>\n<style>\n\
+             table { border-collapse: collapse; font-family: sans-serif; }\n\
+             th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: center; }\n\
+             th:first-child, td:first-child { text-align: left; }\n\
+             .passed { background: #e6ffed; }\n\
+             .partial { background: #fff8c5; }\n\
+             .failed { background: #ffeef0; }\n\
+             .skipped { color: #888; }\n\
+             </style>\n</head>\n<body>\n<h1>Parser conformance</h1>\n<table>\n<tr><th>Feature</th>",
+        );
+        for backend in &self.backends {
+            html += &format!("<th>{}<br>{}</th>", escape_html(&backend.name), escape_html(&backend.version));
+        }
+        html += "</tr>\n";
+        for row in &self.rows {
+            html += &format!("<tr><td>{}</td>", escape_html(&row.title));
+            for cell in &row.cells {
+                html += &format!("<td class=\"{}\">{}</td>", cell.class(), cell.text());
+            }
+            html += "</tr>\n";
+        }
+        html += "<tr><th>Total</th>";
+        for backend in &self.backends {
+            html += &format!("<th class=\"{}\">{}</th>", backend.total.class(), backend.total.text());
+        }
+        html += "</tr>\n</table>\n";
+        for backend in self.backends.iter().filter(|backend| !backend.failures.is_empty()) {
+            let failures: Vec<String> =
+                backend.failures.iter().map(|name| format!("<code>{}</code>", escape_html(name))).collect();
+            html += &format!("<p><strong>{}</strong> failed {}</p>\n", escape_html(&backend.name), failures.join(", "));
+        }
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the matrix serializes") + "\n"
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,138 @@
+//! AST snapshots of the example programs
+//!
+//! Every `.lox` file in `examples/` and `parser-tests/fixtures/` is parsed
+//! by the [`REFERENCE`] backend and its tree, as written by
+//! `lox_ast::export::to_outline`, checked in under `parser-tests/snapshots/`.
+//! The tests compare each backend's tree with the snapshot and report a
+//! difference as a [`diff`] of the two outlines, so a parser that builds the
+//! wrong tree is caught even when it finds the right number of statements.
+//!
+//! When a snapshot no longer matches, the test writes what the reference
+//! backend produces now next to it as `<name>.txt.new` and prints the diff.
+//! Review it, then accept it by rerunning the tests with
+//! `UPDATE_SNAPSHOTS=1`, which rewrites the snapshots and removes the `.new`
+//! files.
+
+use std::path::{Path, PathBuf};
+
+use lox_ast::{Expr, Program, Stmt};
+
+/// The backend whose trees the snapshots hold
+pub const REFERENCE: &str = "nom";
+
+/// A program to snapshot
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// The path relative to the workspace root, such as `examples/hello.lox`
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    /// Where the snapshot of the source's tree is kept, such as
+    /// `parser-tests/snapshots/examples/hello.txt`
+    pub fn snapshot_path(&self) -> PathBuf {
+        let relative = Path::new(self.name.strip_prefix("parser-tests/").unwrap_or(&self.name)).with_extension("txt");
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots").join(relative)
+    }
+}
+
+/// The `.lox` files of `examples/` and `parser-tests/fixtures/`, sorted by name
+pub fn corpus() -> Vec<Source> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut sources = Vec::new();
+    for directory in ["examples", "parser-tests/fixtures"] {
+        let Ok(entries) = std::fs::read_dir(root.join(directory)) else { continue };
+        for path in entries.map(|entry| entry.unwrap().path()) {
+            if path.extension().is_some_and(|extension| extension == "lox") {
+                let file = path.file_name().unwrap().to_string_lossy();
+                let text = std::fs::read_to_string(&path).unwrap();
+                sources.push(Source { name: format!("{}/{}", directory, file), text });
+            }
+        }
+    }
+    sources.sort_by(|a, b| a.name.cmp(&b.name));
+    sources
+}
+
+/// Whether `program` uses more than expressions, `print` and `var`, so
+/// parsers without `Feature::FullGrammar` are not expected to handle it
+pub fn uses_full_grammar(program: &Program) -> bool {
+    fn extended(expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(_) | Expr::Variable(_) => false,
+            Expr::Binary { left, right, .. } => extended(left) || extended(right),
+            Expr::Unary { operand, .. } => extended(operand),
+            Expr::Grouping(inner) => extended(inner),
+            Expr::Assignment { value, .. } => extended(value),
+            _ => true,
+        }
+    }
+    program.statements.iter().any(|stmt| match stmt {
+        Stmt::Expression(expr) | Stmt::Print(expr) => extended(expr),
+        Stmt::VarDeclaration { initializer, type_annotation, .. } => {
+            type_annotation.is_some() || initializer.as_ref().is_some_and(extended)
+        }
+        _ => true,
+    })
+}
+
+/// A line diff of two outlines: unchanged lines start with two spaces,
+/// removed ones with `- ` and added ones with `+ `, and runs of more than
+/// three unchanged lines away from a change are cut to `  ...`
+pub fn diff(expected: &str, actual: &str) -> String {
+    let (old, new): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(('=', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    const CONTEXT: usize = 3;
+    let changed: Vec<usize> = (0..lines.len()).filter(|&index| lines[index].0 != '=').collect();
+    let near_change = |index: usize| changed.iter().any(|&change| change.abs_diff(index) <= CONTEXT);
+    let mut rendered = String::new();
+    let mut elided = false;
+    for (index, (kind, line)) in lines.iter().enumerate() {
+        let marker = match kind {
+            '+' => "+ ",
+            '-' => "- ",
+            _ if near_change(index) => "  ",
+            _ => {
+                if !elided {
+                    rendered.push_str("  ...\n");
+                    elided = true;
+                }
+                continue;
+            }
+        };
+        elided = false;
+        rendered.push_str(marker);
+        rendered.push_str(line);
+        rendered.push('\n');
+    }
+    rendered
+}
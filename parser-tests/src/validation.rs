@@ -0,0 +1,178 @@
+//! A checker for the JSON Schema of `lox_ast::schema`
+//!
+//! It understands only the keywords `json_schema` uses, which is enough for
+//! the tests to check every parser's output without a full JSON Schema
+//! implementation.
+
+use serde_json::Value as Json;
+
+/// Check `instance` against `schema`, returning every mismatch
+///
+/// Each message starts with the JSON pointer of the offending value. Only the
+/// keywords `json_schema` uses are understood: `$ref` to a local `$defs`
+/// entry, `type`, `const`, `enum`, `properties`, `required`,
+/// `additionalProperties`, `items`, `oneOf` and `anyOf`.
+pub fn validate(schema: &Json, instance: &Json) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    Validator { root: schema }.check(schema, instance, "", &mut errors);
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+struct Validator<'a> {
+    root: &'a Json,
+}
+
+impl Validator<'_> {
+    fn check(&self, schema: &Json, instance: &Json, path: &str, errors: &mut Vec<String>) {
+        let location = if path.is_empty() { "/" } else { path };
+
+        if let Some(target) = schema.get("$ref").and_then(Json::as_str) {
+            match target.strip_prefix("#/$defs/").and_then(|name| self.root.get("$defs")?.get(name)) {
+                Some(definition) => self.check(definition, instance, path, errors),
+                None => errors.push(format!("{}: unresolvable reference {}", location, target)),
+            }
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Json::Array(types) => types.iter().filter_map(Json::as_str).collect(),
+                other => other.as_str().into_iter().collect(),
+            };
+            if !types.iter().any(|name| has_type(instance, name)) {
+                errors.push(format!("{}: expected {}, found {}", location, types.join(" or "), describe(instance)));
+                return;
+            }
+        }
+
+        if let Some(expected) = schema.get("const")
+            && !same_value(expected, instance)
+        {
+            errors.push(format!("{}: expected {}, found {}", location, expected, instance));
+        }
+
+        if let Some(Json::Array(values)) = schema.get("enum")
+            && !values.iter().any(|value| same_value(value, instance))
+        {
+            errors.push(format!("{}: {} is not one of the allowed values", location, instance));
+        }
+
+        if let Json::Object(object) = instance {
+            let properties = schema.get("properties").and_then(Json::as_object);
+            if let Some(Json::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Json::as_str) {
+                    if !object.contains_key(name) {
+                        errors.push(format!("{}: missing property \"{}\"", location, name));
+                    }
+                }
+            }
+            for (name, value) in object {
+                let child = format!("{}/{}", path, escape_pointer(name));
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => self.check(property, value, &child, errors),
+                    None if schema.get("additionalProperties") == Some(&Json::Bool(false)) => {
+                        errors.push(format!("{}: unexpected property \"{}\"", location, name));
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if let (Some(items), Json::Array(elements)) = (schema.get("items"), instance) {
+            for (index, element) in elements.iter().enumerate() {
+                self.check(items, element, &format!("{}/{}", path, index), errors);
+            }
+        }
+
+        if let Some(Json::Array(alternatives)) = schema.get("oneOf") {
+            let results: Vec<Vec<String>> =
+                alternatives.iter().map(|alternative| self.errors(alternative, instance, path)).collect();
+            let matches = results.iter().filter(|result| result.is_empty()).count();
+            if matches == 0 {
+                errors.push(format!("{}: matches none of the alternatives", location));
+                errors.extend(self.closest(alternatives, instance, results));
+            } else if matches > 1 {
+                errors.push(format!("{}: matches {} alternatives instead of one", location, matches));
+            }
+        }
+
+        if let Some(Json::Array(alternatives)) = schema.get("anyOf") {
+            let results: Vec<Vec<String>> =
+                alternatives.iter().map(|alternative| self.errors(alternative, instance, path)).collect();
+            if results.iter().all(|result| !result.is_empty()) {
+                errors.push(format!("{}: matches none of the alternatives", location));
+                errors.extend(self.closest(alternatives, instance, results));
+            }
+        }
+    }
+
+    /// The mismatches of the alternative that came closest to matching,
+    /// preferring those whose `const` properties, such as `kind`, agree with
+    /// the instance
+    fn closest(&self, alternatives: &[Json], instance: &Json, results: Vec<Vec<String>>) -> Vec<String> {
+        let (tagged, untagged): (Vec<_>, Vec<_>) = alternatives
+            .iter()
+            .zip(results)
+            .partition(|(alternative, _)| self.constants_agree(alternative, instance));
+        let candidates = if tagged.is_empty() { untagged } else { tagged };
+        candidates.into_iter().map(|(_, errors)| errors).min_by_key(Vec::len).unwrap_or_default()
+    }
+
+    fn constants_agree(&self, schema: &Json, instance: &Json) -> bool {
+        let schema = match schema.get("$ref").and_then(Json::as_str) {
+            Some(target) => match target.strip_prefix("#/$defs/").and_then(|name| self.root.get("$defs")?.get(name)) {
+                Some(definition) => definition,
+                None => return false,
+            },
+            None => schema,
+        };
+        let Some(properties) = schema.get("properties").and_then(Json::as_object) else {
+            return true;
+        };
+        properties.iter().all(|(name, property)| match (property.get("const"), instance.get(name)) {
+            (Some(expected), Some(value)) => same_value(expected, value),
+            _ => true,
+        })
+    }
+
+    fn errors(&self, schema: &Json, instance: &Json, path: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.check(schema, instance, path, &mut errors);
+        errors
+    }
+}
+
+fn has_type(instance: &Json, name: &str) -> bool {
+    match name {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "number" => instance.is_number(),
+        "integer" => instance.is_i64() || instance.is_u64() || instance.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "string" => instance.is_string(),
+        "array" => instance.is_array(),
+        "object" => instance.is_object(),
+        _ => false,
+    }
+}
+
+/// Compare like JSON Schema, where `2` and `2.0` are the same number
+fn same_value(expected: &Json, instance: &Json) -> bool {
+    match (expected, instance) {
+        (Json::Number(a), Json::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => expected == instance,
+    }
+}
+
+fn describe(instance: &Json) -> &'static str {
+    match instance {
+        Json::Null => "null",
+        Json::Bool(_) => "a boolean",
+        Json::Number(_) => "a number",
+        Json::String(_) => "a string",
+        Json::Array(_) => "an array",
+        Json::Object(_) => "an object",
+    }
+}
+
+fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
@@ -0,0 +1,82 @@
+//! The trees of the example programs, checked in under `snapshots/`
+//!
+//! The reference backend's tree of each program must match its snapshot,
+//! and every other backend must build the same tree. A mismatch is reported
+//! as a diff of the two outlines. To accept a changed snapshot, review the
+//! `.txt.new` file written next to it and rerun with `UPDATE_SNAPSHOTS=1`.
+
+use lox_ast::export::to_outline;
+use lox_parser_api::{Feature, ParseOptions};
+use parser_tests::get_working_parsers;
+use parser_tests::snapshot::{REFERENCE, corpus, diff, uses_full_grammar};
+
+fn reference_outline(name: &str, source: &str) -> String {
+    let reference = get_working_parsers().into_iter().find(|parser| parser.name() == REFERENCE).unwrap();
+    let program = reference
+        .parse(source, &ParseOptions::default())
+        .unwrap_or_else(|error| panic!("{} cannot parse {}: {}", REFERENCE, name, error));
+    to_outline(&program)
+}
+
+#[test]
+fn test_reference_matches_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut stale = Vec::new();
+    for source in corpus() {
+        let outline = reference_outline(&source.name, &source.text);
+        let path = source.snapshot_path();
+        let pending = path.with_extension("txt.new");
+        if update {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &outline).unwrap();
+            let _ = std::fs::remove_file(&pending);
+            continue;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(snapshot) if snapshot == outline => {
+                let _ = std::fs::remove_file(&pending);
+            }
+            Ok(snapshot) => {
+                std::fs::write(&pending, &outline).unwrap();
+                stale.push(format!("{}:\n--- snapshot\n+++ {}\n{}", source.name, REFERENCE, diff(&snapshot, &outline)));
+            }
+            Err(_) => {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&pending, &outline).unwrap();
+                stale.push(format!("{}: no snapshot", source.name));
+            }
+        }
+    }
+    assert!(
+        stale.is_empty(),
+        "Snapshots are out of date; review the .txt.new files and rerun the tests with UPDATE_SNAPSHOTS=1\n\n{}",
+        stale.join("\n")
+    );
+}
+
+/// Every backend builds the reference tree of every program it is expected
+/// to parse, not just the same number of statements
+#[test]
+fn test_backends_match_reference() {
+    let mut failures = Vec::new();
+    for source in corpus() {
+        let expected = reference_outline(&source.name, &source.text);
+        let full_grammar = nom_lox::parse_program(&source.text).is_ok_and(|program| uses_full_grammar(&program));
+        for parser in get_working_parsers().into_iter().filter(|parser| parser.name() != REFERENCE) {
+            match parser.parse(&source.text, &ParseOptions::default()) {
+                Ok(program) => {
+                    let actual = to_outline(&program);
+                    if actual != expected {
+                        failures.push(format!(
+                            "{} on {}:\n--- {}\n+++ {}\n{}",
+                            parser.name(), source.name, REFERENCE, parser.name(), diff(&expected, &actual)
+                        ));
+                    }
+                }
+                Err(_) if full_grammar && !parser.capabilities().supports(Feature::FullGrammar) => {}
+                Err(error) => failures.push(format!("{} cannot parse {}: {}", parser.name(), source.name, error)),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "Backends disagree with {}:\n\n{}", REFERENCE, failures.join("\n"));
+}
@@ -0,0 +1,68 @@
+//! Tests for the block comment extension
+//!
+//! Run with `cargo test -p parser-tests --features block-comments`.
+
+#![cfg(feature = "block-comments")]
+
+use parser_tests::{ParseOptions, parsers_lexing};
+use lox_ast::{BinaryOp, Expr, Stmt, Value};
+
+fn number(value: f64) -> Box<Expr> {
+    Box::new(Expr::Literal(Value::Number(value)))
+}
+
+#[test]
+fn test_block_comments_are_skipped() {
+    let inputs = vec![
+        "/* leading */ print 1 + 2;",
+        "print 1 /* between */ + 2;",
+        "print 1 + 2; /* trailing */",
+        "/*\n  spanning\n  lines\n*/\nprint 1 + 2;",
+        "/* outer /* inner */ still outer */ print 1 + 2;",
+        "/* a // line comment inside */ print 1 + 2;",
+        "/**/ print 1 + 2;",
+    ];
+    let expected = vec![Stmt::Print(Expr::Binary {
+        left: number(1.0),
+        operator: BinaryOp::Add,
+        right: number(2.0),
+    })];
+
+    for parser in parsers_lexing(|lex| lex.block_comments) {
+        for input in &inputs {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} failed on '{}': {:?}",
+                parser.name(), input, result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements, expected,
+                "Parser {} parsed '{}' incorrectly",
+                parser.name(), input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_unterminated_block_comment_points_at_opening() {
+    let inputs = vec![
+        ("print 1;\n  /* never closed", "line 2, column 3"),
+        ("print 1;\n/* outer /* inner */ not closed", "line 2, column 1"),
+    ];
+
+    for parser in parsers_lexing(|lex| lex.block_comments) {
+        for (input, position) in &inputs {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_err(), "Parser {} accepted '{}'", parser.name(), input);
+            let error = result.unwrap_err().describe(input);
+            assert!(error.contains("Unterminated block comment"),
+                "Parser {} gave no unterminated-comment error for '{}': {}",
+                parser.name(), input, error
+            );
+            assert!(error.contains(position),
+                "Parser {} did not point at the opening of the comment in '{}': {}",
+                parser.name(), input, error
+            );
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! The conformance matrix checked in under `conformance/`
+//!
+//! The test fails when a report no longer matches what the parsers do, and
+//! rewrites the reports when run with `UPDATE_CONFORMANCE=1`; review the
+//! diff before committing it.
+
+use parser_tests::report::ConformanceMatrix;
+use parser_tests::{get_all_parsers, run_parser_tests};
+
+#[test]
+fn test_reports_are_up_to_date() {
+    let summaries: Vec<_> = get_all_parsers().iter().map(|parser| run_parser_tests(parser.as_ref())).collect();
+    let matrix = ConformanceMatrix::new(&summaries);
+
+    let update = std::env::var_os("UPDATE_CONFORMANCE").is_some();
+    let mut stale = Vec::new();
+    for (name, generated) in [("matrix.md", matrix.to_markdown()), ("matrix.html", matrix.to_html()), ("matrix.json", matrix.to_json())] {
+        let path = format!("{}/conformance/{}", env!("CARGO_MANIFEST_DIR"), name);
+        if update {
+            std::fs::write(&path, &generated).unwrap();
+        } else if std::fs::read_to_string(&path).unwrap_or_default() != generated {
+            stale.push(name);
+        }
+    }
+    assert!(stale.is_empty(), "{:?} are out of date; rerun the tests with UPDATE_CONFORMANCE=1", stale);
+}
+
+/// Every backend has a column, named with the version of the library it is
+/// built on
+#[test]
+fn test_every_backend_has_a_column() {
+    let summaries: Vec<_> = get_all_parsers().iter().map(|parser| run_parser_tests(parser.as_ref())).collect();
+    let matrix = ConformanceMatrix::new(&summaries);
+    let columns: Vec<_> = matrix.backends.iter().map(|column| (column.name.as_str(), column.version.as_str())).collect();
+    let parsers: Vec<_> = get_all_parsers().iter().map(|parser| (parser.name(), parser.version())).collect();
+    assert_eq!(columns, parsers);
+}
@@ -0,0 +1,89 @@
+//! Tests for the doc comment extension
+//!
+//! Run with `cargo test -p parser-tests --features doc-comments`.
+
+#![cfg(feature = "doc-comments")]
+
+use parser_tests::{LalrpopParser, LoxParser, NomParser, ParseOptions, parsers_lexing};
+use lox_ast::{Expr, Stmt, Value};
+
+#[test]
+fn test_doc_comments_attach_to_variable_declarations() {
+    let input = "/// The answer\n/// to everything\nvar answer = 42;\n// plain comment\nvar other;";
+    let expected = vec![
+        Stmt::VarDeclaration {
+            name: "answer".to_string(),
+            type_annotation: None,
+            initializer: Some(Expr::Literal(Value::Number(42.0))),
+            doc: Some("The answer\nto everything".to_string()),
+        },
+        Stmt::VarDeclaration {
+            name: "other".to_string(),
+            type_annotation: None,
+            initializer: None,
+            doc: None,
+        },
+    ];
+
+    for parser in parsers_lexing(|lex| lex.doc_comments) {
+        let result = parser.parse(input, &ParseOptions::default());
+        assert!(result.is_ok(), "Parser {} failed: {:?}", parser.name(), result.as_ref().err());
+        assert_eq!(result.unwrap().statements, expected,
+            "Parser {} attached doc comments incorrectly",
+            parser.name()
+        );
+    }
+}
+
+#[test]
+fn test_doc_comments_before_other_statements_are_dropped() {
+    let input = "/// Not a declaration\nprint 1;\n//// Four slashes is a plain comment\nvar x;";
+    let expected = vec![
+        Stmt::Print(Expr::Literal(Value::Number(1.0))),
+        Stmt::VarDeclaration {
+            name: "x".to_string(),
+            type_annotation: None,
+            initializer: None,
+            doc: None,
+        },
+    ];
+
+    for parser in parsers_lexing(|lex| lex.doc_comments) {
+        let result = parser.parse(input, &ParseOptions::default());
+        assert!(result.is_ok(), "Parser {} failed: {:?}", parser.name(), result.as_ref().err());
+        assert_eq!(result.unwrap().statements, expected,
+            "Parser {} kept a doc comment it should have dropped",
+            parser.name()
+        );
+    }
+}
+
+#[test]
+fn test_doc_comments_attach_to_functions_classes_and_methods() {
+    let input = "\
+/// Adds two numbers
+fun add(a, b) { return a + b; }
+
+/// A point in the plane
+class Point {
+  /// Distance from the origin
+  length() { return 0; }
+}
+";
+    // Only these parsers implement functions and classes
+    let parsers: Vec<Box<dyn LoxParser>> = vec![Box::new(NomParser), Box::new(LalrpopParser)];
+
+    for parser in parsers {
+        let result = parser.parse(input, &ParseOptions::default());
+        assert!(result.is_ok(), "Parser {} failed: {:?}", parser.name(), result.as_ref().err());
+        let statements = result.unwrap().statements;
+        assert_eq!(statements[0].doc(), Some("Adds two numbers"), "Parser {}", parser.name());
+        assert_eq!(statements[1].doc(), Some("A point in the plane"), "Parser {}", parser.name());
+        match &statements[1] {
+            Stmt::Class { methods, .. } => {
+                assert_eq!(methods[0].doc(), Some("Distance from the origin"), "Parser {}", parser.name());
+            }
+            other => panic!("Parser {} expected a class, got {:?}", parser.name(), other),
+        }
+    }
+}
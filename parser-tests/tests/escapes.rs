@@ -0,0 +1,66 @@
+//! Tests for the string escape sequence extension
+//!
+//! Run with `cargo test -p parser-tests --features escapes`.
+
+#![cfg(feature = "escapes")]
+
+use parser_tests::{ParseOptions, parsers_lexing};
+use lox_ast::{Expr, Stmt, Value};
+
+#[test]
+fn test_escape_sequences() {
+    let cases = vec![
+        (r#"print "a\nb";"#, "a\nb"),
+        (r#"print "tab\there";"#, "tab\there"),
+        (r#"print "say \"hi\"";"#, "say \"hi\""),
+        (r#"print "back\\slash";"#, "back\\slash"),
+        (r#"print "\u{41}\u{1F600}";"#, "A\u{1F600}"),
+        (r#"print "\\";"#, "\\"),
+    ];
+
+    for parser in parsers_lexing(|lex| lex.escapes) {
+        for (input, expected) in &cases {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} failed on '{}': {:?}",
+                parser.name(), input, result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements,
+                vec![Stmt::Print(Expr::Literal(Value::String(expected.to_string())))],
+                "Parser {} decoded '{}' incorrectly",
+                parser.name(), input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_invalid_escape_sequences() {
+    let inputs = vec![
+        r#"print "\q";"#,
+        r#"print "\u{110000}";"#,
+        r#"print "\u{}";"#,
+        r#"print "\u41";"#,
+    ];
+
+    for parser in parsers_lexing(|lex| lex.escapes) {
+        for input in &inputs {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_err(), "Parser {} accepted '{}'", parser.name(), input);
+            let error = result.unwrap_err().describe(input);
+            assert!(error.contains("Invalid escape sequence"),
+                "Parser {} gave an unexpected error for '{}': {}",
+                parser.name(), input, error
+            );
+        }
+    }
+}
+
+#[test]
+fn test_escaped_quote_does_not_close_string() {
+    for parser in parsers_lexing(|lex| lex.escapes) {
+        let result = parser.parse(r#"print "open \";"#, &ParseOptions::default());
+        assert!(result.is_err(), "Parser {} closed a string on an escaped quote", parser.name());
+        assert!(result.unwrap_err().to_string().contains("Unterminated string"));
+    }
+}
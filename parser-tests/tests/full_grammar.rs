@@ -0,0 +1,124 @@
+//! Tests of the whole book grammar, for the backends that declare it
+//!
+//! Declarations, control flow, functions and classes are checked against a
+//! tree built by hand, so each backend's grammar is pinned down on its own
+//! rather than only by agreeing with another backend.
+
+use parser_tests::{Feature, ParseOptions, parsers_supporting};
+use lox_ast::{BinaryOp, Expr, Stmt, Value};
+
+fn number(value: f64) -> Expr {
+    Expr::Literal(Value::Number(value))
+}
+
+fn variable(name: &str) -> Expr {
+    Expr::Variable(name.to_string())
+}
+
+fn binary(left: Expr, operator: BinaryOp, right: Expr) -> Expr {
+    Expr::Binary { left: Box::new(left), operator, right: Box::new(right) }
+}
+
+fn var(name: &str, initializer: Option<Expr>) -> Stmt {
+    Stmt::VarDeclaration { name: name.to_string(), type_annotation: None, initializer, doc: None }
+}
+
+fn function(name: &str, params: &[&str], body: Vec<Stmt>) -> Stmt {
+    Stmt::Function {
+        name: name.to_string(),
+        params: params.iter().map(|param| param.to_string()).collect(),
+        signature: None,
+        body,
+        doc: None,
+    }
+}
+
+#[test]
+fn test_declarations_and_control_flow() {
+    let input = r#"
+        fun add(a, b) { return a + b; }
+        for (var i = 0; i < 3; i = i + 1) if (i == 1) print "one"; else print i;
+        while (i) { i = nil; }
+        for (;;) return;
+    "#;
+    let expected = vec![
+        function("add", &["a", "b"], vec![Stmt::Return { value: Some(binary(variable("a"), BinaryOp::Add, variable("b"))) }]),
+        Stmt::For {
+            initializer: Some(Box::new(var("i", Some(number(0.0))))),
+            condition: Some(binary(variable("i"), BinaryOp::Less, number(3.0))),
+            increment: Some(Expr::Assignment {
+                name: "i".to_string(),
+                value: Box::new(binary(variable("i"), BinaryOp::Add, number(1.0))),
+            }),
+            body: Box::new(Stmt::If {
+                condition: binary(variable("i"), BinaryOp::Equal, number(1.0)),
+                then_branch: Box::new(Stmt::Print(Expr::Literal(Value::String("one".to_string())))),
+                else_branch: Some(Box::new(Stmt::Print(variable("i")))),
+            }),
+        },
+        Stmt::While {
+            condition: variable("i"),
+            body: Box::new(Stmt::Block(vec![Stmt::Expression(Expr::Assignment {
+                name: "i".to_string(),
+                value: Box::new(Expr::Literal(Value::Nil)),
+            })])),
+        },
+        Stmt::For { initializer: None, condition: None, increment: None, body: Box::new(Stmt::Return { value: None }) },
+    ];
+
+    for parser in parsers_supporting(Feature::FullGrammar) {
+        let program = parser.parse(input, &ParseOptions::default()).unwrap();
+        assert_eq!(program.statements, expected, "Parser {}", parser.name());
+    }
+}
+
+#[test]
+fn test_classes() {
+    let input = r#"
+        class Point < Base {
+            init(x) { this.x = x; }
+            sum() { return super.sum(1).total; }
+        }
+    "#;
+    let this_x = Expr::Set { object: Box::new(Expr::This), name: "x".to_string(), value: Box::new(variable("x")) };
+    let super_call = Expr::Call {
+        callee: Box::new(Expr::Super { method: "sum".to_string() }),
+        arguments: vec![number(1.0)],
+    };
+    let expected = vec![Stmt::Class {
+        name: "Point".to_string(),
+        superclass: Some("Base".to_string()),
+        fields: vec![],
+        methods: vec![
+            function("init", &["x"], vec![Stmt::Expression(this_x)]),
+            function("sum", &[], vec![Stmt::Return {
+                value: Some(Expr::Get { object: Box::new(super_call), name: "total".to_string() }),
+            }]),
+        ],
+        doc: None,
+    }];
+
+    for parser in parsers_supporting(Feature::FullGrammar) {
+        let program = parser.parse(input, &ParseOptions::default()).unwrap();
+        assert_eq!(program.statements, expected, "Parser {}", parser.name());
+    }
+}
+
+#[test]
+fn test_dangling_else_binds_to_nearest_if() {
+    let input = "if (a) if (b) print 1; else print 2;";
+    let expected = vec![Stmt::If {
+        condition: variable("a"),
+        then_branch: Box::new(Stmt::If {
+            condition: variable("b"),
+            then_branch: Box::new(Stmt::Print(number(1.0))),
+            else_branch: Some(Box::new(Stmt::Print(number(2.0)))),
+        }),
+        else_branch: None,
+    }];
+
+    for parser in parsers_supporting(Feature::FullGrammar) {
+        let program = parser.parse(input, &ParseOptions::default()).unwrap();
+        assert_eq!(program.statements, expected, "Parser {}", parser.name());
+    }
+}
@@ -6,10 +6,15 @@
 //! - simple-test functionality
 
 use parser_tests::{
-    get_working_parsers, run_parser_tests, compare_all_parsers,
-    NomParser, LalrpopParser, PomParser
+    get_all_parsers, get_working_parsers, run_parser_tests, compare_all_parsers, render_parsers_side_by_side,
+    Capabilities, LoxParser, NomParser, LalrpopParser, PomParser, ParseError, ParseOptions, TEST_CASES
 };
-use lox_ast::{Stmt, Expr, Value, BinaryOp};
+use lox_ast::export::GraphFormat;
+use lox_ast::{ArenaProgram, Program, Stmt, Expr, Value, BinaryOp, optimize};
+
+fn statement_count(result: &Result<Program, ParseError>) -> usize {
+    result.as_ref().map_or(0, |program| program.statements.len())
+}
 
 /// Test that all working parsers can handle basic cases
 #[test]
@@ -29,14 +34,14 @@ fn test_working_parsers_basic_functionality() {
         println!("Testing {} parser...", parser.name());
 
         for (name, input, expected_statements) in &basic_cases {
-            let result = parser.parse(input);
-            assert!(result.success,
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_ok(),
                 "Parser {} failed on test '{}': {:?}",
-                parser.name(), name, result.error
+                parser.name(), name, result.as_ref().err()
             );
-            assert_eq!(result.statement_count, *expected_statements,
+            assert_eq!(statement_count(&result), *expected_statements,
                 "Parser {} returned wrong statement count for '{}': expected {}, got {}",
-                parser.name(), name, expected_statements, result.statement_count
+                parser.name(), name, expected_statements, statement_count(&result)
             );
         }
     }
@@ -48,25 +53,54 @@ fn test_operator_precedence() {
     let parsers = get_working_parsers();
 
     for parser in parsers {
-        let result = parser.parse("1 + 2 * 3;");
-        if result.success {
-            if let Some(program) = result.program {
-                if let Some(Stmt::Expression(Expr::Binary { left, operator, right })) = program.statements.first() {
-                    // Should parse as 1 + (2 * 3), not (1 + 2) * 3
-                    assert_eq!(*operator, BinaryOp::Add);
-                    assert!(matches!(**left, Expr::Literal(Value::Number(1.0))));
-                    // Right side should be a multiplication
-                    if let Expr::Binary { operator: right_op, .. } = right.as_ref() {
-                        assert_eq!(*right_op, BinaryOp::Multiply);
-                    } else {
-                        panic!("Expected multiplication on right side for parser {}", parser.name());
-                    }
-                }
+        let result = parser.parse("1 + 2 * 3;", &ParseOptions::default());
+        if result.is_ok()
+            && let Ok(program) = result
+            && let Some(Stmt::Expression(Expr::Binary { left, operator, right })) = program.statements.first()
+        {
+            // Should parse as 1 + (2 * 3), not (1 + 2) * 3
+            assert_eq!(*operator, BinaryOp::Add);
+            assert!(matches!(**left, Expr::Literal(Value::Number(1.0))));
+            // Right side should be a multiplication
+            if let Expr::Binary { operator: right_op, .. } = right.as_ref() {
+                assert_eq!(*right_op, BinaryOp::Multiply);
+            } else {
+                panic!("Expected multiplication on right side for parser {}", parser.name());
             }
         }
     }
 }
 
+/// `1 - 2 - 3` is `(1 - 2) - 3` in every parser, and likewise for the other
+/// binary operators
+#[test]
+fn test_binary_operators_are_left_associative() {
+    let operators = [
+        ("-", BinaryOp::Subtract),
+        ("/", BinaryOp::Divide),
+        ("+", BinaryOp::Add),
+        ("*", BinaryOp::Multiply),
+        ("<", BinaryOp::Less),
+        (">=", BinaryOp::GreaterEqual),
+        ("==", BinaryOp::Equal),
+        ("!=", BinaryOp::NotEqual),
+        ("and", BinaryOp::And),
+        ("or", BinaryOp::Or),
+    ];
+    let number = |value| Box::new(Expr::Literal(Value::Number(value)));
+
+    for parser in get_all_parsers() {
+        for (symbol, operator) in &operators {
+            let source = format!("print 1 {} 2 {} 3;", symbol, symbol);
+            let left = Box::new(Expr::Binary { left: number(1.0), operator: operator.clone(), right: number(2.0) });
+            let expected = Stmt::Print(Expr::Binary { left, operator: operator.clone(), right: number(3.0) });
+            let program = parser.parse(&source, &ParseOptions::default())
+                .unwrap_or_else(|error| panic!("Parser {} failed on {}: {}", parser.name(), source, error));
+            assert_eq!(program.statements, [expected], "Parser {} on {}", parser.name(), source);
+        }
+    }
+}
+
 /// Test variable operations across working parsers
 #[test]
 fn test_variable_operations() {
@@ -80,11 +114,11 @@ fn test_variable_operations() {
     "#;
 
     for parser in parsers {
-        let result = parser.parse(test_input);
-        if result.success {
-            assert_eq!(result.statement_count, 4,
+        let result = parser.parse(test_input, &ParseOptions::default());
+        if result.is_ok() {
+            assert_eq!(statement_count(&result), 4,
                 "Parser {} should parse 4 statements, got {}",
-                parser.name(), result.statement_count
+                parser.name(), statement_count(&result)
             );
         }
     }
@@ -111,6 +145,52 @@ fn test_standard_cases_working_parsers() {
     }
 }
 
+/// Every parser's output converts to the arena representation and back unchanged
+#[test]
+fn test_arena_round_trip() {
+    for parser in get_working_parsers() {
+        for test_case in TEST_CASES {
+            let Some(program) = parser.parse(test_case.input, &ParseOptions::default()).ok() else { continue };
+            let arena = ArenaProgram::from_program(&program);
+            assert_eq!(arena.to_program(), program,
+                "Parser {} output changed in the arena round trip for {}",
+                parser.name(), test_case.name
+            );
+        }
+    }
+}
+
+/// Optimizing any parser's output reaches a fixed point in one pass
+#[test]
+fn test_optimize_is_idempotent() {
+    for parser in get_working_parsers() {
+        for test_case in TEST_CASES {
+            let Some(program) = parser.parse(test_case.input, &ParseOptions::default()).ok() else { continue };
+            let optimized = optimize(program);
+            assert_eq!(optimize(optimized.clone()), optimized,
+                "Parser {} output for {} was not fully optimized in one pass",
+                parser.name(), test_case.name
+            );
+        }
+    }
+}
+
+#[test]
+fn test_optimize_parsed_program() {
+    let program = nom_lox::parse_program(
+        r#"print 1 + 2 * 3; print "a" + "b"; print "a" + 1; if (false) print x;"#,
+    ).unwrap();
+    assert_eq!(optimize(program).statements, vec![
+        Stmt::Print(Expr::Literal(Value::Number(7.0))),
+        Stmt::Print(Expr::Literal(Value::String("ab".to_string()))),
+        Stmt::Print(Expr::Binary {
+            left: Box::new(Expr::Literal(Value::String("a".to_string()))),
+            operator: BinaryOp::Add,
+            right: Box::new(Expr::Literal(Value::Number(1.0))),
+        }),
+    ]);
+}
+
 /// Test individual parser implementations
 mod parser_specific_tests {
     use super::*;
@@ -155,10 +235,10 @@ mod error_handling_tests {
         let parsers = get_working_parsers();
 
         for parser in parsers {
-            let result = parser.parse("");
+            let result = parser.parse("", &ParseOptions::default());
             // Empty input should either succeed with 0 statements or fail gracefully
-            if result.success {
-                assert_eq!(result.statement_count, 0);
+            if result.is_ok() {
+                assert_eq!(statement_count(&result), 0);
             }
         }
     }
@@ -175,10 +255,10 @@ mod error_handling_tests {
 
         for parser in parsers {
             for invalid_input in &invalid_inputs {
-                let result = parser.parse(invalid_input);
+                let result = parser.parse(invalid_input, &ParseOptions::default());
                 // Should fail gracefully with error message
-                if !result.success {
-                    assert!(result.error.is_some(),
+                if let Err(error) = &result {
+                    assert!(!error.describe(invalid_input).is_empty(),
                         "Parser {} should provide error message for invalid input: {}",
                         parser.name(), invalid_input
                     );
@@ -186,6 +266,37 @@ mod error_handling_tests {
             }
         }
     }
+
+    #[test]
+    fn test_errors_point_at_the_offending_token() {
+        // Each error is on the second line, past where its statement starts
+        let cases = vec![
+            ("var a = 1;\nvar = 2;", "line 2, column 5"),
+            ("print 1;\nprint 1 2;", "line 2, column 9"),
+            ("print 1;\n1 2;", "line 2, column 3"),
+        ];
+
+        for parser in get_working_parsers() {
+            for (input, location) in &cases {
+                let result = parser.parse(input, &ParseOptions::default());
+                let error = result.expect_err(input).describe(input);
+                assert!(error.contains(location),
+                    "Parser {} did not point at the offending token of {:?}: {}",
+                    parser.name(), input, error
+                );
+            }
+        }
+    }
+
+    #[cfg(not(feature = "block-comments"))]
+    #[test]
+    fn test_block_comments_rejected_without_extension() {
+        // Without the extension `/*` is just a slash followed by a star
+        for parser in get_working_parsers() {
+            let result = parser.parse("/* comment */ print 1;", &ParseOptions::default());
+            assert!(result.is_err(), "Parser {} accepted a block comment", parser.name());
+        }
+    }
 }
 
 /// Test comprehensive language features
@@ -206,11 +317,11 @@ mod language_feature_tests {
 
         for parser in parsers {
             for (name, input) in &literals {
-                let result = parser.parse(input);
-                if result.success {
-                    assert_eq!(result.statement_count, 1,
+                let result = parser.parse(input, &ParseOptions::default());
+                if result.is_ok() {
+                    assert_eq!(statement_count(&result), 1,
                         "Parser {} failed on literal test '{}': expected 1 statement, got {}",
-                        parser.name(), name, result.statement_count
+                        parser.name(), name, statement_count(&result)
                     );
                 }
             }
@@ -236,11 +347,11 @@ mod language_feature_tests {
 
         for parser in parsers {
             for (name, input) in &operators {
-                let result = parser.parse(input);
-                if result.success {
-                    assert_eq!(result.statement_count, 1,
+                let result = parser.parse(input, &ParseOptions::default());
+                if result.is_ok() {
+                    assert_eq!(statement_count(&result), 1,
                         "Parser {} failed on operator test '{}': expected 1 statement, got {}",
-                        parser.name(), name, result.statement_count
+                        parser.name(), name, statement_count(&result)
                     );
                 }
             }
@@ -258,16 +369,347 @@ mod language_feature_tests {
 
         for parser in parsers {
             for (name, input) in &unary_ops {
-                let result = parser.parse(input);
-                if result.success {
-                    assert_eq!(result.statement_count, 1,
+                let result = parser.parse(input, &ParseOptions::default());
+                if result.is_ok() {
+                    assert_eq!(statement_count(&result), 1,
                         "Parser {} failed on unary test '{}': expected 1 statement, got {}",
-                        parser.name(), name, result.statement_count
+                        parser.name(), name, statement_count(&result)
                     );
                 }
             }
         }
     }
+
+    /// Every backend keeps groupings and builds each operator and assignment
+    /// where the grammar puts it, not just the right number of statements
+    #[test]
+    fn test_trees_keep_grouping_assignment_and_operators() {
+        let number = |value| Box::new(Expr::Literal(Value::Number(value)));
+        let variable = |name: &str| Box::new(Expr::Variable(name.to_string()));
+        let group = |inner| Box::new(Expr::Grouping(inner));
+        let not = |operand| Box::new(Expr::Unary { operator: lox_ast::UnaryOp::Not, operand });
+        let binary = |left, operator, right| Box::new(Expr::Binary { left, operator, right });
+        let assign = |name: &str, value| Box::new(Expr::Assignment { name: name.to_string(), value });
+        let cases = [
+            ("(1 + 2) * 3;", Stmt::Expression(*binary(group(binary(number(1.0), BinaryOp::Add, number(2.0))), BinaryOp::Multiply, number(3.0)))),
+            ("print (x);", Stmt::Print(*group(variable("x")))),
+            ("print ((1 < 2) == !(a or b));", Stmt::Print(*group(binary(
+                group(binary(number(1.0), BinaryOp::Less, number(2.0))),
+                BinaryOp::Equal,
+                not(group(binary(variable("a"), BinaryOp::Or, variable("b")))),
+            )))),
+            ("print a or b and c == d < e;", Stmt::Print(*binary(variable("a"), BinaryOp::Or, binary(
+                variable("b"),
+                BinaryOp::And,
+                binary(variable("c"), BinaryOp::Equal, binary(variable("d"), BinaryOp::Less, variable("e"))),
+            )))),
+            ("!!x == !y;", Stmt::Expression(*binary(not(not(variable("x"))), BinaryOp::Equal, not(variable("y"))))),
+            ("a = b = c;", Stmt::Expression(*assign("a", assign("b", variable("c"))))),
+            ("print a = 2;", Stmt::Print(*assign("a", number(2.0)))),
+        ];
+
+        for parser in get_all_parsers() {
+            for (input, expected) in &cases {
+                let program = parser.parse(input, &ParseOptions::default())
+                    .unwrap_or_else(|error| panic!("Parser {} failed on '{}': {:?}", parser.name(), input, error));
+                assert_eq!(program.statements, vec![expected.clone()],
+                    "Parser {} misparsed '{}'", parser.name(), input
+                );
+            }
+        }
+    }
+}
+
+/// Test that keywords end at a word boundary and are never accepted as identifiers
+mod keyword_boundary_tests {
+    use super::*;
+
+    fn var(name: &str) -> Expr {
+        Expr::Variable(name.to_string())
+    }
+
+    #[test]
+    fn test_keyword_prefixed_identifiers() {
+        let parsers = get_working_parsers();
+
+        let cases = vec![
+            ("var nilly = 1;", Stmt::VarDeclaration {
+                name: "nilly".to_string(),
+                type_annotation: None,
+                initializer: Some(Expr::Literal(Value::Number(1.0))),
+                doc: None,
+            }),
+            ("var orchid = trueValue;", Stmt::VarDeclaration {
+                name: "orchid".to_string(),
+                type_annotation: None,
+                initializer: Some(var("trueValue")),
+                doc: None,
+            }),
+            ("printer = falsehood;", Stmt::Expression(Expr::Assignment {
+                name: "printer".to_string(),
+                value: Box::new(var("falsehood")),
+            })),
+            ("variable = nilly;", Stmt::Expression(Expr::Assignment {
+                name: "variable".to_string(),
+                value: Box::new(var("nilly")),
+            })),
+            ("print andy;", Stmt::Print(var("andy"))),
+            ("print classy + fungus;", Stmt::Print(Expr::Binary {
+                left: Box::new(var("classy")),
+                operator: BinaryOp::Add,
+                right: Box::new(var("fungus")),
+            })),
+            ("var_x = whiles;", Stmt::Expression(Expr::Assignment {
+                name: "var_x".to_string(),
+                value: Box::new(var("whiles")),
+            })),
+        ];
+
+        for parser in parsers {
+            for (input, expected) in &cases {
+                let result = parser.parse(input, &ParseOptions::default());
+                assert!(result.is_ok(),
+                    "Parser {} failed on '{}': {:?}",
+                    parser.name(), input, result.as_ref().err()
+                );
+                let program = result.unwrap();
+                assert_eq!(program.statements, vec![expected.clone()],
+                    "Parser {} mis-tokenized '{}'",
+                    parser.name(), input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_reserved_words_rejected_as_identifiers() {
+        let parsers = get_working_parsers();
+
+        let inputs = vec![
+            "var class = 1;",
+            "var nil = 1;",
+            "print = 1;",
+            "var x = fun;",
+            "while = 1;",
+            "print return;",
+        ];
+
+        for parser in parsers {
+            for input in &inputs {
+                let result = parser.parse(input, &ParseOptions::default());
+                assert!(result.is_err(),
+                    "Parser {} accepted reserved word in '{}': {:?}",
+                    parser.name(), input, result.as_ref().ok()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_keyword_rejected_as_variable_name() {
+        let parsers = get_working_parsers();
+
+        for parser in parsers {
+            for keyword in lox_ast::KEYWORDS {
+                let input = format!("var {} = 1;", keyword);
+                let result = parser.parse(&input, &ParseOptions::default());
+                assert!(result.is_err(),
+                    "Parser {} accepted '{}' as a variable name",
+                    parser.name(), keyword
+                );
+            }
+        }
+    }
+}
+
+/// Test the string literal spec shared by every backend
+mod string_literal_tests {
+    use super::*;
+
+    fn print_string(value: &str) -> Stmt {
+        Stmt::Print(Expr::Literal(Value::String(value.to_string())))
+    }
+
+    #[test]
+    fn test_empty_string() {
+        for parser in get_working_parsers() {
+            let result = parser.parse(r#"print "";"#, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} rejected the empty string: {:?}",
+                parser.name(), result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements, vec![print_string("")]);
+        }
+    }
+
+    #[test]
+    fn test_multi_line_string() {
+        let input = "print \"first\nsecond\";\nprint \"third\";";
+
+        for parser in get_working_parsers() {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} rejected a multi-line string: {:?}",
+                parser.name(), result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements,
+                vec![print_string("first\nsecond"), print_string("third")],
+                "Parser {} mis-read a multi-line string",
+                parser.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unterminated_string_points_at_opening_quote() {
+        // The multi-line string on the first line must still count towards the line number
+        let input = "var a = \"one\ntwo\";\nprint \"oops;\nprint a;";
+
+        for parser in get_working_parsers() {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_err(), "Parser {} accepted an unterminated string", parser.name());
+            let error = result.unwrap_err().describe(input);
+            assert!(error.contains("Unterminated string"),
+                "Parser {} gave no unterminated-string error: {}",
+                parser.name(), error
+            );
+            assert!(error.contains("line 3, column 7"),
+                "Parser {} did not point at the opening quote: {}",
+                parser.name(), error
+            );
+        }
+    }
+
+    #[cfg(not(feature = "escapes"))]
+    #[test]
+    fn test_backslashes_are_literal_without_escapes() {
+        for parser in get_working_parsers() {
+            let result = parser.parse(r#"print "C:\dir\";"#, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} rejected a backslash: {:?}",
+                parser.name(), result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements, vec![print_string(r"C:\dir\")]);
+        }
+    }
+}
+
+/// Test the number literal spec shared by every backend
+mod number_literal_tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_numbers() {
+        let cases = vec![("0;", 0.0), ("42;", 42.0), ("3.25;", 3.25), ("10.0;", 10.0), ("007;", 7.0)];
+
+        for parser in get_working_parsers() {
+            for (input, expected) in &cases {
+                let result = parser.parse(input, &ParseOptions::default());
+                assert!(result.is_ok(),
+                    "Parser {} failed on '{}': {:?}",
+                    parser.name(), input, result.as_ref().err()
+                );
+                assert_eq!(result.unwrap().statements,
+                    vec![Stmt::Expression(Expr::Literal(Value::Number(*expected)))],
+                    "Parser {} mis-read '{}'",
+                    parser.name(), input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_numbers_have_targeted_errors() {
+        let mut cases = vec![
+            ("print 1.;", "Number literal cannot end with '.'"),
+            ("print 1.2.3;", "Number literal has more than one decimal point"),
+            ("print 1e10;", "Exponent notation is not supported"),
+            ("print .5;", "Number literal must start with a digit"),
+            ("print 12abc;", "Invalid character 'a' in number literal"),
+        ];
+        if cfg!(not(feature = "number-extensions")) {
+            cases.push(("print 0x1F;", "Invalid character 'x' in number literal"));
+            cases.push(("print 1_000;", "Invalid character '_' in number literal"));
+        }
+
+        for parser in get_working_parsers() {
+            for (input, message) in &cases {
+                let result = parser.parse(input, &ParseOptions::default());
+                assert!(result.is_err(), "Parser {} accepted '{}'", parser.name(), input);
+                let error = result.unwrap_err().describe(input);
+                assert!(error.contains(message),
+                    "Parser {} gave an untargeted error for '{}': {}",
+                    parser.name(), input, error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_numbers() {
+        for parser in get_working_parsers() {
+            // `inf` and `NaN` are ordinary identifiers in Lox
+            for name in ["inf", "NaN"] {
+                let result = parser.parse(&format!("print {};", name), &ParseOptions::default());
+                assert_eq!(result.ok().map(|p| p.statements),
+                    Some(vec![Stmt::Print(Expr::Variable(name.to_string()))]),
+                    "Parser {} did not read '{}' as a variable",
+                    parser.name(), name
+                );
+            }
+
+            // There is no unary plus, and negative numbers are negated literals
+            assert!(parser.parse("print +1;", &ParseOptions::default()).is_err(), "Parser {} accepted '+1'", parser.name());
+            let result = parser.parse("-1;", &ParseOptions::default());
+            assert_eq!(result.ok().map(|p| p.statements),
+                Some(vec![Stmt::Expression(Expr::Unary {
+                    operator: lox_ast::UnaryOp::Minus,
+                    operand: Box::new(Expr::Literal(Value::Number(1.0))),
+                })]),
+                "Parser {} mis-read '-1'",
+                parser.name()
+            );
+        }
+    }
+}
+
+/// Side-by-side renderings only highlight where two backends disagree
+#[test]
+fn test_render_parsers_side_by_side() {
+    let input = "var total = 1 + 2 * 3; print -total;";
+    for parser in get_working_parsers() {
+        for format in [GraphFormat::Dot, GraphFormat::Mermaid] {
+            let rendering = render_parsers_side_by_side(input, &NomParser, parser.as_ref(), format).unwrap();
+            assert!(rendering.contains(parser.name()));
+            assert!(!rendering.contains("changed"), "{} differs from nom:\n{}", parser.name(), rendering);
+        }
+    }
+
+    // A parser that leaves out grouping parentheses: the grouping node and
+    // everything under it differ
+    struct Ungrouped;
+    impl LoxParser for Ungrouped {
+        fn name(&self) -> &'static str { "ungrouped" }
+        fn version(&self) -> &'static str { "0" }
+        fn description(&self) -> &'static str { "nom without grouping nodes" }
+        fn capabilities(&self) -> Capabilities { NomParser.capabilities() }
+        fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+            let mut program = NomParser.parse(source, options)?;
+            for stmt in &mut program.statements {
+                if let Stmt::Print(Expr::Grouping(inner)) = stmt {
+                    *stmt = Stmt::Print(std::mem::replace(inner, Expr::Literal(Value::Nil)));
+                }
+            }
+            Ok(program)
+        }
+    }
+    let rendering = render_parsers_side_by_side("print (1 * 2);", &NomParser, &Ungrouped, GraphFormat::Mermaid).unwrap();
+    assert!(rendering.contains("l2[\"( )\"]:::changed"), "{}", rendering);
+    assert!(rendering.contains("r2[\"*\"]:::changed"), "{}", rendering);
+    assert!(rendering.contains("l1[\"Print\"]\n"), "{}", rendering);
+
+    let error = render_parsers_side_by_side("print", &NomParser, &LalrpopParser, GraphFormat::Dot).unwrap_err();
+    assert!(error.starts_with("nom parser failed"), "{}", error);
 }
 
 /// Integration test that runs the full comparison suite
@@ -277,11 +719,11 @@ fn test_full_parser_comparison() {
     let summaries = compare_all_parsers();
 
     // Should test all parsers
-    assert_eq!(summaries.len(), 6, "Should test all 6 working parsers");
+    assert_eq!(summaries.len(), 8, "Should test all 8 working parsers");
 
     // At least some parsers should work
     let working_count = summaries.iter().filter(|s| s.passed > 0).count();
-    assert!(working_count >= 6, "At least 6 parsers should pass some tests");
+    assert!(working_count >= 8, "At least 8 parsers should pass some tests");
 
     // Print summary
     println!("\n=== Test Summary ===");
@@ -0,0 +1,240 @@
+//! Tests for the language extensions chosen through `LanguageOptions`
+//!
+//! Only the nom and LALRPOP backends implement them so far; the others
+//! declare so in their capabilities and refuse options that turn them on.
+
+use lox_ast::{BinaryOp, Expr, FunctionSignature, LanguageOptions, Program, Stmt, TypeAnnotation, Value};
+use parser_tests::{Extension, Feature, ParseError, ParseOptions, get_working_parsers, parsers_supporting};
+
+type ParseWith = fn(&str, &LanguageOptions) -> Result<Program, String>;
+
+fn backends() -> Vec<(&'static str, ParseWith)> {
+    vec![
+        ("nom", nom_lox::parse_program_with),
+        ("lalrpop", lalrpop_lox::parse_program_with),
+    ]
+}
+
+fn number(value: f64) -> Box<Expr> {
+    Box::new(Expr::Literal(Value::Number(value)))
+}
+
+fn var(name: &str) -> Box<Expr> {
+    Box::new(Expr::Variable(name.to_string()))
+}
+
+#[test]
+fn test_backends_agree_on_extended_programs() {
+    let inputs = vec![
+        "var max = fun (a, b) { return a > b ? a : b; };",
+        "for (var i = 0; i < 10; i = i + 1) { if (i == 5) break; continue; }",
+        "while (true) { fun f() { return fun () { return 1; }; } break; }",
+        "a = b ? c ? 1 : 2 : d ? 3 : 4;",
+        "x = 1, y = 2, print_me(x, y);",
+        "print (1, 2);",
+        "var x: Number = a ? 1 : 2;",
+        "fun apply(f: fun(Number): Bool, n): Bool { return f(n); }",
+        "class Point < Shape { x: Number; /// The y field\n y: Any; init(x: Number) { this.x = x; } }",
+    ];
+
+    for input in &inputs {
+        let results: Vec<_> = backends()
+            .into_iter()
+            .map(|(name, parse)| (name, parse(input, &LanguageOptions::EXTENDED)))
+            .collect();
+        for (name, result) in &results {
+            assert!(result.is_ok(), "Parser {} failed on '{}': {:?}", name, input, result);
+        }
+        assert_eq!(results[0].1, results[1].1, "Parsers disagree on '{}'", input);
+    }
+}
+
+#[test]
+fn test_operator_precedence() {
+    // The conditional is right-associative and the comma operator binds loosest
+    let input = "a ? 1 : b ? 2 : 3, 4;";
+    let expected = vec![Stmt::Expression(Expr::Binary {
+        left: Box::new(Expr::Conditional {
+            condition: var("a"),
+            then_branch: number(1.0),
+            else_branch: Box::new(Expr::Conditional {
+                condition: var("b"),
+                then_branch: number(2.0),
+                else_branch: number(3.0),
+            }),
+        }),
+        operator: BinaryOp::Comma,
+        right: number(4.0),
+    })];
+
+    for (name, parse) in backends() {
+        let program = parse(input, &LanguageOptions::EXTENDED)
+            .unwrap_or_else(|e| panic!("Parser {} failed: {}", name, e));
+        assert_eq!(program.statements, expected, "Parser {}", name);
+    }
+}
+
+#[test]
+fn test_each_extension_is_independent() {
+    let cases = vec![
+        ("print a ? b : c;", LanguageOptions { conditional: true, ..LanguageOptions::STRICT }),
+        ("var f = fun () {};", LanguageOptions { lambdas: true, ..LanguageOptions::STRICT }),
+        ("a, b;", LanguageOptions { comma_operator: true, ..LanguageOptions::STRICT }),
+        ("var x: Number;", LanguageOptions { type_annotations: true, ..LanguageOptions::STRICT }),
+        ("class P { x: Number; }", LanguageOptions { type_annotations: true, ..LanguageOptions::STRICT }),
+    ];
+
+    for (name, parse) in backends() {
+        for (input, options) in &cases {
+            assert!(parse(input, options).is_ok(), "Parser {} rejected '{}'", name, input);
+            // Without its extension the same input is not valid book Lox
+            assert!(parse(input, &LanguageOptions::STRICT).is_err(),
+                "Parser {} accepted '{}' in strict mode", name, input
+            );
+        }
+
+        // In book Lox `break` is an ordinary identifier
+        let options = LanguageOptions { break_continue: true, ..LanguageOptions::STRICT };
+        let body = |program: Program| match program.statements.into_iter().next() {
+            Some(Stmt::While { body, .. }) => *body,
+            other => panic!("Parser {} expected a while loop, got {:?}", name, other),
+        };
+        assert_eq!(body(parse("while (true) break;", &options).unwrap()), Stmt::Break);
+        assert_eq!(
+            body(parse("while (true) break;", &LanguageOptions::STRICT).unwrap()),
+            Stmt::Expression(Expr::Variable("break".to_string()))
+        );
+    }
+}
+
+#[test]
+fn test_type_annotations() {
+    let options = LanguageOptions { type_annotations: true, ..LanguageOptions::STRICT };
+    let input = "fun scale(p: Point, by): Point { var k: Number = by; return p; }";
+    let number = TypeAnnotation::Number;
+    let point = TypeAnnotation::Class("Point".to_string());
+    let expected = vec![Stmt::Function {
+        name: "scale".to_string(),
+        params: vec!["p".to_string(), "by".to_string()],
+        signature: Some(FunctionSignature {
+            params: vec![Some(point.clone()), None],
+            return_type: Some(point),
+        }),
+        body: vec![
+            Stmt::VarDeclaration {
+                name: "k".to_string(),
+                type_annotation: Some(number),
+                initializer: Some(*var("by")),
+                doc: None,
+            },
+            Stmt::Return { value: Some(*var("p")) },
+        ],
+        doc: None,
+    }];
+
+    for (name, parse) in backends() {
+        let program = parse(input, &options).unwrap_or_else(|e| panic!("Parser {} failed: {}", name, e));
+        assert_eq!(program.statements, expected, "Parser {}", name);
+
+        // The conditional operator's `:` does not enable annotations
+        let conditional = LanguageOptions { conditional: true, ..LanguageOptions::STRICT };
+        assert!(parse("var x: Number;", &conditional).is_err(), "Parser {} accepted an annotation", name);
+    }
+}
+
+#[test]
+fn test_class_fields_are_kept_apart_from_methods() {
+    let options = LanguageOptions { type_annotations: true, ..LanguageOptions::STRICT };
+    let input = "class P { x: Number; m() {} y: String; }";
+    let field = |name: &str, annotation| Stmt::VarDeclaration {
+        name: name.to_string(),
+        type_annotation: Some(annotation),
+        initializer: None,
+        doc: None,
+    };
+    let expected = vec![Stmt::Class {
+        name: "P".to_string(),
+        superclass: None,
+        fields: vec![
+            field("x", TypeAnnotation::Number),
+            field("y", TypeAnnotation::String),
+        ],
+        methods: vec![Stmt::Function {
+            name: "m".to_string(),
+            params: vec![],
+            signature: None,
+            body: vec![],
+            doc: None,
+        }],
+        doc: None,
+    }];
+
+    for (name, parse) in backends() {
+        let program = parse(input, &options).unwrap_or_else(|e| panic!("Parser {} failed: {}", name, e));
+        assert_eq!(program.statements, expected, "Parser {}", name);
+    }
+}
+
+#[test]
+fn test_loop_control_outside_loop_is_an_error() {
+    let inputs = vec![
+        ("break;", "'break' outside of a loop"),
+        ("if (true) continue;", "'continue' outside of a loop"),
+        ("while (true) { var f = fun () { break; }; }", "'break' outside of a loop"),
+        ("for (;;) {} break;", "'break' outside of a loop"),
+    ];
+
+    for (name, parse) in backends() {
+        for (input, message) in &inputs {
+            let error = parse(input, &LanguageOptions::EXTENDED).unwrap_err();
+            assert!(error.contains(message),
+                "Parser {} gave an unexpected error for '{}': {}",
+                name, input, error
+            );
+        }
+    }
+}
+
+#[test]
+fn test_imports() {
+    let options = LanguageOptions { imports: true, ..LanguageOptions::STRICT };
+    let input = "import \"lib/shapes.lox\";\nimport \"util.lox\"; print 1;";
+    let expected = vec![
+        Stmt::Import { path: "lib/shapes.lox".to_string() },
+        Stmt::Import { path: "util.lox".to_string() },
+        Stmt::Print(*number(1.0)),
+    ];
+
+    for (name, parse) in backends() {
+        let program = parse(input, &options).unwrap_or_else(|e| panic!("Parser {} failed: {}", name, e));
+        assert_eq!(program.statements, expected, "Parser {}", name);
+
+        // Without the extension `import` is an ordinary name
+        assert!(parse(input, &LanguageOptions::STRICT).is_err(), "Parser {}", name);
+        assert!(parse("var import = 1;", &LanguageOptions::STRICT).is_ok(), "Parser {}", name);
+        assert!(parse("var import = 1;", &options).is_err(), "Parser {}", name);
+
+        for nested in ["{ import \"a.lox\"; }", "fun f() { import \"a.lox\"; }", "if (x) import \"a.lox\";"] {
+            assert!(parse(nested, &options).is_err(), "Parser {} accepted '{}'", name, nested);
+        }
+        assert!(parse("import a;", &options).is_err(), "Parser {}", name);
+    }
+}
+
+#[test]
+fn test_capabilities_match_the_backends() {
+    for extension in Extension::ALL {
+        let names: Vec<_> = parsers_supporting(Feature::Extension(extension)).iter().map(|parser| parser.name()).collect();
+        assert_eq!(names, ["nom", "lalrpop"], "Parsers supporting {}", extension);
+    }
+
+    let options = ParseOptions::with_language(LanguageOptions { lambdas: true, ..LanguageOptions::STRICT });
+    for parser in get_working_parsers() {
+        let result = parser.parse("var f = fun (a) { return a; };", &options);
+        if parser.capabilities().supports(Feature::Extension(Extension::Lambdas)) {
+            assert!(result.is_ok(), "Parser {} failed: {:?}", parser.name(), result);
+        } else {
+            assert_eq!(result, Err(ParseError::Unsupported(vec![Extension::Lambdas])), "Parser {}", parser.name());
+        }
+    }
+}
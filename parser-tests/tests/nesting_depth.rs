@@ -0,0 +1,59 @@
+//! Tests for the nesting depth limit every backend applies before parsing
+
+use parser_tests::{ParseError, ParseOptions, get_all_parsers};
+use lox_ast::Span;
+use lox_parser_api::DEFAULT_MAX_DEPTH;
+
+fn parens(depth: usize) -> String {
+    format!("print {}1{};", "(".repeat(depth), ")".repeat(depth))
+}
+
+#[test]
+fn test_nesting_too_deep_is_an_error() {
+    let inputs = vec![parens(100_000), format!("print {}1;", "!".repeat(100_000))];
+    let position = 6 + DEFAULT_MAX_DEPTH;
+
+    for parser in get_all_parsers() {
+        for input in &inputs {
+            let ParseError::Syntax(diagnostics) = parser.parse(input, &ParseOptions::default()).unwrap_err() else {
+                panic!("Parser {} gave no syntax error", parser.name());
+            };
+            assert_eq!(diagnostics[0].message, "Nesting too deep", "Parser {}", parser.name());
+            assert_eq!(diagnostics[0].primary.span, Span::new(position, position + 1), "Parser {}", parser.name());
+        }
+    }
+}
+
+#[test]
+fn test_nesting_up_to_the_limit_parses() {
+    let input = parens(DEFAULT_MAX_DEPTH);
+    for parser in get_all_parsers() {
+        let result = parser.parse(&input, &ParseOptions::default());
+        assert!(result.is_ok(), "Parser {} failed at the depth limit: {:?}", parser.name(), result.err());
+    }
+}
+
+#[test]
+fn test_max_depth_is_configurable() {
+    let input = parens(2_000);
+    for parser in get_all_parsers() {
+        let result = parser.parse(&input, &ParseOptions::default().with_max_depth(2_000));
+        assert!(result.is_ok(), "Parser {} failed below a raised limit: {:?}", parser.name(), result.err());
+
+        let error = parser.parse(&input, &ParseOptions::default().with_max_depth(10)).unwrap_err();
+        let ParseError::Syntax(diagnostics) = error else {
+            panic!("Parser {} gave no syntax error", parser.name());
+        };
+        assert_eq!(diagnostics[0].primary.span, Span::new(16, 17), "Parser {}", parser.name());
+    }
+}
+
+#[test]
+fn test_long_flat_expression_parses() {
+    // Deep in the tree but not in the source, so no limit applies
+    let input = format!("print 1{};", " + 1".repeat(100_000));
+    for parser in get_all_parsers() {
+        let program = parser.parse(&input, &ParseOptions::default()).unwrap();
+        assert_eq!(program.clone(), program, "Parser {}", parser.name());
+    }
+}
@@ -0,0 +1,57 @@
+//! Tests for the hex/binary literal and digit separator extension
+//!
+//! Run with `cargo test -p parser-tests --features number-extensions`.
+
+#![cfg(feature = "number-extensions")]
+
+use parser_tests::{ParseOptions, parsers_lexing};
+use lox_ast::{Expr, Stmt, Value};
+
+#[test]
+fn test_extended_number_literals() {
+    let cases = vec![
+        ("0xFF;", 255.0),
+        ("0x1f;", 31.0),
+        ("0b1010;", 10.0),
+        ("1_000_000;", 1_000_000.0),
+        ("0.000_5;", 0.0005),
+        ("0b1111_0000;", 240.0),
+    ];
+
+    for parser in parsers_lexing(|lex| lex.number_extensions) {
+        for (input, expected) in &cases {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_ok(),
+                "Parser {} failed on '{}': {:?}",
+                parser.name(), input, result.as_ref().err()
+            );
+            assert_eq!(result.unwrap().statements,
+                vec![Stmt::Expression(Expr::Literal(Value::Number(*expected)))],
+                "Parser {} mis-read '{}'",
+                parser.name(), input
+            );
+        }
+    }
+}
+
+#[test]
+fn test_malformed_extended_literals() {
+    let cases = vec![
+        ("print 1__000;", "Digit separator '_' must be between digits"),
+        ("print 1000_;", "Digit separator '_' must be between digits"),
+        ("print 0x;", "Expected digits after '0x'"),
+        ("print 0b102;", "Invalid character '2' in number literal"),
+    ];
+
+    for parser in parsers_lexing(|lex| lex.number_extensions) {
+        for (input, message) in &cases {
+            let result = parser.parse(input, &ParseOptions::default());
+            assert!(result.is_err(), "Parser {} accepted '{}'", parser.name(), input);
+            let error = result.unwrap_err().describe(input);
+            assert!(error.contains(message),
+                "Parser {} gave an unexpected error for '{}': {}",
+                parser.name(), input, error
+            );
+        }
+    }
+}
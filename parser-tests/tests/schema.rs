@@ -0,0 +1,104 @@
+//! Check every parser's JSON output against the published schema
+//!
+//! The schema in `lox_ast::schema` is written by hand from the serde
+//! encoding, so these tests make sure it describes what the parsers actually
+//! produce.
+
+use lox_ast::schema::json_schema;
+use lox_ast::{LanguageOptions, Program};
+use parser_tests::validation::validate;
+use parser_tests::{ParseOptions, TEST_CASES, get_all_parsers};
+use serde_json::{Value as Json, json};
+
+type ParseWith = fn(&str, &LanguageOptions) -> Result<Program, String>;
+
+fn assert_valid(schema: &serde_json::Value, program: &Program, context: &str) {
+    let document = serde_json::to_value(program).unwrap();
+    if let Err(errors) = validate(schema, &document) {
+        panic!("{}: output does not match the schema:\n{}", context, errors.join("\n"));
+    }
+}
+
+fn examples() -> Vec<(String, String)> {
+    let directory = format!("{}/../examples", env!("CARGO_MANIFEST_DIR"));
+    let mut examples: Vec<(String, String)> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .map(|path| (path.file_name().unwrap().to_string_lossy().into_owned(), std::fs::read_to_string(&path).unwrap()))
+        .collect();
+    examples.sort();
+    examples
+}
+
+#[test]
+fn test_parser_output_matches_schema() {
+    let schema = json_schema();
+    let mut corpus: Vec<(String, String)> =
+        TEST_CASES.iter().map(|case| (case.name.to_string(), case.input.to_string())).collect();
+    corpus.extend(examples());
+
+    for parser in get_all_parsers() {
+        let mut validated = 0;
+        for (name, input) in &corpus {
+            if let Ok(program) = parser.parse(input, &ParseOptions::default()) {
+                assert_valid(&schema, &program, &format!("{} parser on {}", parser.name(), name));
+                validated += 1;
+            }
+        }
+        assert!(validated > 0, "{} parser produced no programs to validate", parser.name());
+    }
+}
+
+#[test]
+fn test_extended_output_matches_schema() {
+    let schema = json_schema();
+    let options = LanguageOptions::EXTENDED;
+    let input = r#"
+        import "shapes.lox";
+        var max = fun (a, b) { return a > b ? a : b; };
+        for (var i = 0; i < 10; i = i + 1) { if (i == 5) break; continue; }
+        x = 1, y = 2;
+        var typed: Number = 1;
+        fun apply(f: fun(Number): Bool, n): Bool { return f(n); }
+        class Point < Shape { x: Number; init(x) { this.x = -x; super.init(); } }
+    "#;
+    let backends: [(&str, ParseWith); 2] =
+        [("nom", nom_lox::parse_program_with), ("lalrpop", lalrpop_lox::parse_program_with)];
+    for (name, parse) in backends {
+        let program = parse(input, &options).unwrap();
+        assert_valid(&schema, &program, &format!("{} parser on extended syntax", name));
+    }
+}
+
+#[test]
+fn test_mismatches_are_reported() {
+    let schema = json_schema();
+    assert_eq!(validate(&schema, &serde_json::to_value(Program::new(vec![])).unwrap()), Ok(()));
+    let check = |document: Json| validate(&schema, &document).unwrap_err();
+    let version = lox_ast::SCHEMA_VERSION;
+
+    let errors = check(json!({ "schema_version": 1, "statements": [] }));
+    assert_eq!(errors, vec![format!("/schema_version: expected {}, found 1", version)]);
+
+    let errors = check(json!({ "schema_version": version, "statements": [{ "kind": "Print" }] }));
+    assert!(errors.contains(&"/statements/0: missing property \"expression\"".to_string()), "{:?}", errors);
+
+    let errors = check(json!({
+        "schema_version": version,
+        "statements": [{ "kind": "Expression", "expression": {
+            "kind": "Binary",
+            "left": { "kind": "Literal", "value": [1] },
+            "operator": "Plus",
+            "right": { "kind": "This", "extra": true },
+        }}],
+    }));
+    let expected = [
+        "/statements/0/expression/left/value: expected null or boolean or number or string, found an array",
+        "/statements/0/expression/operator: \"Plus\" is not one of the allowed values",
+        "/statements/0/expression/right: unexpected property \"extra\"",
+    ];
+    for message in expected {
+        assert!(errors.contains(&message.to_string()), "{} not in {:?}", message, errors);
+    }
+}
@@ -2,21 +2,52 @@
 
 use pest::Parser;
 use pest::iterators::Pair;
-use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+use pest::error::{ErrorVariant, InputLocation};
+use lox_ast::{BinaryOp, Diagnostic, Expr, LanguageOptions, LexOptions, Program, Span, Stmt, UnaryOp, Value, hints};
+use lox_ast::lexical;
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+
+/// Lexical extensions enabled through this crate's cargo features
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 64 * 1024;
 
 #[derive(pest_derive::Parser)]
 #[grammar = "lox.pest"]
+#[cfg_attr(not(feature = "escapes"), grammar = "strings.pest")]
+#[cfg_attr(feature = "escapes", grammar = "strings_escapes.pest")]
+#[cfg_attr(not(feature = "block-comments"), grammar = "comments.pest")]
+#[cfg_attr(feature = "block-comments", grammar = "comments_block.pest")]
 pub struct LoxParser;
 
+/// Parse a complete Lox program from a string
+///
+/// Nesting is not limited here, so very deeply nested input overflows the
+/// stack; `parse_program_diagnostic` refuses such input instead.
 pub fn parse_program(input: &str) -> Result<Program, Box<pest::error::Error<Rule>>> {
-    let pairs = LoxParser::parse(Rule::program, input)?;
+    let pairs = LoxParser::parse(Rule::program, input).map_err(|e| lexical_error(input).unwrap_or(e))?;
     let program_pair = pairs.into_iter().next().unwrap();
 
+    // Pest skips comments implicitly, so doc comments are found by a separate scan of the source
+    let docs = if LEX_OPTIONS.doc_comments {
+        lexical::doc_comments(input, &LEX_OPTIONS).unwrap_or_default()
+    } else {
+        Default::default()
+    };
+
     let mut statements = Vec::new();
     for pair in program_pair.into_inner() {
         match pair.as_rule() {
             Rule::statement => {
-                statements.push(parse_statement(pair)?);
+                let doc = docs.get(&pair.as_span().start()).cloned();
+                statements.push(parse_statement(pair)?.with_doc(doc));
             }
             Rule::EOI => break,
             _ => {}
@@ -26,6 +57,54 @@ pub fn parse_program(input: &str) -> Result<Program, Box<pest::error::Error<Rule
     Ok(Program::new(statements))
 }
 
+/// Parse a complete program, reporting a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<Program, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        parse_program(input).map_err(|error| Box::new(match lexical::validate(input, &LEX_OPTIONS) {
+            Err(lex) => lex.into(),
+            Ok(()) => hints::explain(input, error_diagnostic(input, &error), &LEX_OPTIONS, &LanguageOptions::STRICT),
+        }))
+    })
+}
+
+/// Turn a pest error into a diagnostic; the expected rules become a note
+fn error_diagnostic(input: &str, error: &pest::error::Error<Rule>) -> Diagnostic {
+    let span = match error.location {
+        InputLocation::Pos(position) => Span::new(position, position),
+        InputLocation::Span((start, end)) => Span::new(start, end),
+    };
+    match &error.variant {
+        ErrorVariant::ParsingError { .. } => {
+            let expected = error.variant.message();
+            let mut chars = expected.chars();
+            let expected: String = chars.next().map(|first| first.to_ascii_uppercase()).into_iter().chain(chars).collect();
+            Diagnostic::unexpected(input, span.start, &LEX_OPTIONS)
+                .with_note(expected)
+                .with_unclosed_delimiter(input, &LEX_OPTIONS)
+        }
+        ErrorVariant::CustomError { message } => Diagnostic::error(message.clone(), span),
+    }
+}
+
+/// A lexical error in `input`, which describes a failed parse better than the expected-rules list
+fn lexical_error(input: &str) -> Option<pest::error::Error<Rule>> {
+    let error = lexical::validate(input, &LEX_OPTIONS).err()?;
+    let position = pest::Position::new(input, error.span.start)?;
+    Some(pest::error::Error::new_from_pos(
+        pest::error::ErrorVariant::CustomError {
+            message: error.describe(input),
+        },
+        position,
+    ))
+}
+
 fn parse_statement(pair: Pair<Rule>) -> Result<Stmt, Box<pest::error::Error<Rule>>> {
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
@@ -62,7 +141,7 @@ fn parse_var_declaration(pair: Pair<Rule>) -> Result<Stmt, Box<pest::error::Erro
         None
     };
 
-    Ok(Stmt::VarDeclaration { name, initializer })
+    Ok(Stmt::VarDeclaration { name, type_annotation: None, initializer, doc: None })
 }
 
 fn parse_expression_statement(pair: Pair<Rule>) -> Result<Stmt, Box<pest::error::Error<Rule>>> {
@@ -113,7 +192,7 @@ fn parse_binary_expr(pair: Pair<Rule>, default_op: BinaryOp) -> Result<Expr, Box
     let mut inner = pair.into_inner();
     let mut expr = parse_expression(inner.next().unwrap())?;
 
-    while let Some(next) = inner.next() {
+    for next in inner {
         expr = Expr::Binary {
             left: Box::new(expr),
             operator: default_op.clone(),
@@ -281,19 +360,25 @@ fn parse_primary(pair: Pair<Rule>) -> Result<Expr, Box<pest::error::Error<Rule>>
             Ok(Expr::Literal(Value::Bool(value)))
         }
         Rule::number => {
-            let value = inner.as_str().parse::<f64>().unwrap();
+            let value = lexical::parse_number(inner.as_str(), &LEX_OPTIONS)
+                .map_err(|e| literal_error(&inner, e))?;
             Ok(Expr::Literal(Value::Number(value)))
         }
         Rule::string => {
             let s = inner.as_str();
-            let value = s[1..s.len()-1].to_string(); // Remove quotes
+            let raw = &s[1..s.len()-1]; // Remove quotes
+            let value = if LEX_OPTIONS.escapes {
+                lexical::unescape(raw).map_err(|e| literal_error(&inner, e.offset(1)))?
+            } else {
+                raw.to_string()
+            };
             Ok(Expr::Literal(Value::String(value)))
         }
         Rule::identifier => {
             let name = inner.as_str().to_string();
             Ok(Expr::Variable(name))
         }
-        Rule::expression => parse_expression(inner),
+        Rule::expression => Ok(Expr::Grouping(Box::new(parse_expression(inner)?))),
         _ => Err(Box::new(pest::error::Error::new_from_pos(
             pest::error::ErrorVariant::CustomError {
                 message: "Unknown primary expression".to_string(),
@@ -302,3 +387,16 @@ fn parse_primary(pair: Pair<Rule>) -> Result<Expr, Box<pest::error::Error<Rule>>
         ))),
     }
 }
+
+/// Turn a lexical error inside a literal into a pest error pointing at the offending characters
+fn literal_error(literal: &Pair<Rule>, error: lox_ast::LexError) -> Box<pest::error::Error<Rule>> {
+    let source = literal.as_span().get_input();
+    let error = error.offset(literal.as_span().start());
+    let span = pest::Span::new(source, error.span.start, error.span.end).unwrap_or(literal.as_span());
+    Box::new(pest::error::Error::new_from_span(
+        pest::error::ErrorVariant::CustomError {
+            message: error.describe(source),
+        },
+        span,
+    ))
+}
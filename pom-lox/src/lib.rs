@@ -15,14 +15,40 @@
 
 mod parser;
 
-pub use parser::parse_program;
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use parser::{parse_program, parse_program_diagnostic, parse_program_with_max_depth};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "pom";
-pub const PARSER_VERSION: &str = "3.2";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "Simple, fast parser combinator library with clean API";
 
+/// This parser behind the common `LoxParser` interface
+pub struct PomParser;
+
+impl LoxParser for PomParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: false,
+            spans: false,
+            error_recovery: false,
+            streaming: false,
+            extensions: LanguageOptions::STRICT,
+            lexical: parser::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parse_program_with_max_depth(source, options.max_depth).map_err(|error| ParseError::syntax(*error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,7 +91,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -110,4 +136,10 @@ mod tests {
             _ => panic!("Expected binary expression"),
         }
     }
+
+    #[test]
+    fn test_errors_report_line_and_column() {
+        let error = parse_program("print 1;\nprint (1;").unwrap_err();
+        assert!(error.contains("line 2, column"), "{}", error);
+    }
 }
@@ -1,16 +1,78 @@
-use pom::parser::{Parser, is_a, none_of, sym, seq, end};
-use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+use pom::parser::{Parser, any, call, is_a, none_of, sym, seq, end};
+use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions, LexOptions, Diagnostic, hints};
+use lox_ast::lexical::{self, is_identifier_continue, is_identifier_start, is_keyword};
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+
+/// Lexical extensions enabled through this crate's cargo features
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 16 * 1024;
 
 pub fn parse_program(input: &str) -> Result<Program, String> {
-    match program().parse(input.as_bytes()) {
-        Ok(program) => Ok(program),
-        Err(e) => Err(format!("Parse error: {:?}", e)),
-    }
+    parse_program_diagnostic(input).map_err(|error| error.describe(input))
+}
+
+/// Parse a complete program, reporting a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str) -> Result<Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<Program, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        match program().parse(input.as_bytes()) {
+            Ok(program) => Ok(program),
+            // pom's repetition swallows the reason a token failed, so look for a lexical error first
+            Err(e) => match lexical::validate(input, &LEX_OPTIONS) {
+                Err(lex) => Err(Box::new(lex.into())),
+                Ok(()) => {
+                    let error = failed_statement(input.as_bytes()).unwrap_or(e);
+                    Err(Box::new(hints::explain(input, error_diagnostic(&error, input), &LEX_OPTIONS, &LanguageOptions::STRICT)))
+                }
+            },
+        }
+    })
+}
+
+/// The error that ended the program's statements early
+///
+/// pom's repetition drops the error that stopped it, so the statement it
+/// stopped at is parsed again to find how far that got.
+fn failed_statement(input: &[u8]) -> Option<pom::Error> {
+    let (_, stop) = statements().parse_at(input, 0).ok()?;
+    statement().parse_at(input, stop).err()
+}
+
+/// Turn a pom error into a diagnostic pointing into `input`
+///
+/// pom's own messages name the byte it did not expect, so only the ones this
+/// crate raises are kept.
+fn error_diagnostic(error: &pom::Error, input: &str) -> Diagnostic {
+    let position = match error {
+        // A statement that failed after its keyword; the reason is further in
+        pom::Error::Expect { inner, .. } => return error_diagnostic(inner, input),
+        pom::Error::Custom { message, position, .. } => {
+            return Diagnostic::error(message.clone(), lexical::token_span(input, *position, &LEX_OPTIONS));
+        }
+        pom::Error::Incomplete => input.len(),
+        pom::Error::Mismatch { position, .. }
+        | pom::Error::Conversion { position, .. } => *position,
+    };
+    Diagnostic::unexpected(input, position, &LEX_OPTIONS).with_unclosed_delimiter(input, &LEX_OPTIONS)
 }
 
 /// Parse a complete program
 fn program<'a>() -> Parser<'a, u8, Program> {
-    (ws() * statements() - ws() - end()).map(|statements| Program { statements })
+    (statements() - ws() - end()).map(|statements| Program { statements })
 }
 
 /// Parse a list of statements
@@ -18,29 +80,30 @@ fn statements<'a>() -> Parser<'a, u8, Vec<Stmt>> {
     statement().repeat(0..)
 }
 
-/// Parse a single statement
+/// Parse a single statement, attaching any doc comment before it
+///
+/// An expression statement is tried last, so that when nothing matches the
+/// error is the one from parsing the expression.
 fn statement<'a>() -> Parser<'a, u8, Stmt> {
-    ws() * (var_declaration() | assignment_statement() | expression_statement() | print_statement()) - ws()
-}
-
-/// Parse assignment statement: IDENTIFIER = EXPRESSION;
-fn assignment_statement<'a>() -> Parser<'a, u8, Stmt> {
-    (identifier() - ws() - sym(b'=') - ws() + expression() - ws() - sym(b';'))
-    .map(|(name, value)| Stmt::Expression(Expr::Assignment {
-        name,
-        value: Box::new(value),
-    }))
+    (trivia() + (var_declaration() | print_statement() | expression_statement()))
+    .map(|(doc, stmt)| stmt.with_doc(doc))
 }
 
 /// Parse variable declaration: var IDENTIFIER = EXPRESSION;
+///
+/// Past the keyword it can only be a declaration, so a failure there is not
+/// backtracked over.
 fn var_declaration<'a>() -> Parser<'a, u8, Stmt> {
-    (seq(b"var") * ws() * identifier() + (ws() * sym(b'=') * ws() * expression()).opt() - ws() - sym(b';'))
-    .map(|(name, initializer)| Stmt::VarDeclaration { name, initializer })
+    let declaration = ws() * identifier() + (ws() * sym(b'=') * ws() * expression()).opt() - ws() - sym(b';');
+    (keyword(b"var") * declaration.expect("declaration"))
+    .map(|(name, initializer)| Stmt::VarDeclaration { name, type_annotation: None, initializer, doc: None })
 }
 
 /// Parse print statement: print EXPRESSION;
+///
+/// As for a declaration, a failure after the keyword is not backtracked over.
 fn print_statement<'a>() -> Parser<'a, u8, Stmt> {
-    (seq(b"print") * ws() * expression() - ws() - sym(b';')).map(Stmt::Print)
+    (keyword(b"print") * (ws() * expression() - ws() - sym(b';')).expect("print statement")).map(Stmt::Print)
 }
 
 /// Parse expression statement: EXPRESSION;
@@ -48,14 +111,23 @@ fn expression_statement<'a>() -> Parser<'a, u8, Stmt> {
     (expression() - ws() - sym(b';')).map(Stmt::Expression)
 }
 
-/// Parse expressions - add logical operators
+/// Parse expressions
 fn expression<'a>() -> Parser<'a, u8, Expr> {
-    logical_or()
+    assignment()
+}
+
+/// Parse assignment: IDENTIFIER "=" assignment | logical_or
+///
+/// Assignment is right-associative; `==` is not mistaken for `=`.
+fn assignment<'a>() -> Parser<'a, u8, Expr> {
+    (identifier() - ws() - sym(b'=') - !sym(b'=') - ws() + call(assignment))
+    .map(|(name, value)| Expr::Assignment { name, value: Box::new(value) })
+    | logical_or()
 }
 
 /// Parse logical OR: logical_and ("or" logical_and)*
 fn logical_or<'a>() -> Parser<'a, u8, Expr> {
-    (logical_and() + (ws() * seq(b"or") + ws() * logical_and()).repeat(0..))
+    (logical_and() + (ws() * keyword(b"or") + ws() * logical_and()).repeat(0..))
     .map(|(first, rest)| {
         rest.into_iter().fold(first, |left, (_, right)| {
             Expr::Binary {
@@ -69,7 +141,7 @@ fn logical_or<'a>() -> Parser<'a, u8, Expr> {
 
 /// Parse logical AND: equality ("and" equality)*
 fn logical_and<'a>() -> Parser<'a, u8, Expr> {
-    (equality() + (ws() * seq(b"and") + ws() * equality()).repeat(0..))
+    (equality() + (ws() * keyword(b"and") + ws() * equality()).repeat(0..))
     .map(|(first, rest)| {
         rest.into_iter().fold(first, |left, (_, right)| {
             Expr::Binary {
@@ -167,70 +239,14 @@ fn unary<'a>() -> Parser<'a, u8, Expr> {
 
 /// Parse primary expressions
 fn primary<'a>() -> Parser<'a, u8, Expr> {
-    literal() | variable() | simple_grouped()
+    literal() | variable() | grouping()
 }
 
-/// Parse simple grouped expressions: allow arithmetic but avoid recursion
-fn simple_grouped<'a>() -> Parser<'a, u8, Expr> {
-    // Create a special parser for inside parentheses that doesn't call primary (to avoid recursion)
-    let inside_parens = term_no_grouping();
-    (sym(b'(') * ws() * inside_parens - ws() - sym(b')')).map(|expr| Expr::Grouping(Box::new(expr)))
+/// Parse grouped expressions: "(" expression ")"
+fn grouping<'a>() -> Parser<'a, u8, Expr> {
+    (sym(b'(') * ws() * call(expression) - ws() - sym(b')')).map(|expr| Expr::Grouping(Box::new(expr)))
 }
 
-/// Parse term without grouping to avoid recursion
-fn term_no_grouping<'a>() -> Parser<'a, u8, Expr> {
-    (factor_no_grouping() + (ws() * (sym(b'+') | sym(b'-')) + ws() * factor_no_grouping()).repeat(0..))
-    .map(|(first, rest)| {
-        rest.into_iter().fold(first, |left, (op, right)| {
-            let binary_op = if op == b'+' { BinaryOp::Add } else { BinaryOp::Subtract };
-            Expr::Binary {
-                left: Box::new(left),
-                operator: binary_op,
-                right: Box::new(right),
-            }
-        })
-    })
-}
-
-/// Parse factor without grouping to avoid recursion
-fn factor_no_grouping<'a>() -> Parser<'a, u8, Expr> {
-    (unary_no_grouping() + (ws() * (sym(b'*') | sym(b'/')) + ws() * unary_no_grouping()).repeat(0..))
-    .map(|(first, rest)| {
-        rest.into_iter().fold(first, |left, (op, right)| {
-            let binary_op = if op == b'*' { BinaryOp::Multiply } else { BinaryOp::Divide };
-            Expr::Binary {
-                left: Box::new(left),
-                operator: binary_op,
-                right: Box::new(right),
-            }
-        })
-    })
-}
-
-/// Parse unary without grouping to avoid recursion
-fn unary_no_grouping<'a>() -> Parser<'a, u8, Expr> {
-    ((sym(b'!') | sym(b'-')).repeat(0..) + primary_no_grouping())
-    .map(|(ops, expr)| {
-        ops.into_iter().rev().fold(expr, |acc, op| {
-            let unary_op = if op == b'!' {
-                UnaryOp::Not
-            } else {
-                UnaryOp::Minus
-            };
-            Expr::Unary {
-                operator: unary_op,
-                operand: Box::new(acc),
-            }
-        })
-    })
-}
-
-/// Parse primary without grouping to avoid recursion
-fn primary_no_grouping<'a>() -> Parser<'a, u8, Expr> {
-    literal() | variable()
-}
-
-
 /// Parse variable reference
 fn variable<'a>() -> Parser<'a, u8, Expr> {
     identifier().map(Expr::Variable)
@@ -238,43 +254,84 @@ fn variable<'a>() -> Parser<'a, u8, Expr> {
 
 /// Parse literals (simplified)
 fn literal<'a>() -> Parser<'a, u8, Expr> {
-    (seq(b"true").map(|_| Expr::Literal(Value::Bool(true)))) |
-    (seq(b"false").map(|_| Expr::Literal(Value::Bool(false)))) |
-    (seq(b"nil").map(|_| Expr::Literal(Value::Nil))) |
+    (keyword(b"true").map(|_| Expr::Literal(Value::Bool(true)))) |
+    (keyword(b"false").map(|_| Expr::Literal(Value::Bool(false)))) |
+    (keyword(b"nil").map(|_| Expr::Literal(Value::Nil))) |
     (number().map(|n| Expr::Literal(Value::Number(n)))) |
     (string().map(|s| Expr::Literal(Value::String(s))))
 }
 
-/// Parse number literals (simplified - just integers)
+/// Parse number literals
+///
+/// Everything that could belong to the literal is taken first, so that
+/// `lexical::parse_number` sees and rejects malformed input such as `1.2.3`.
 fn number<'a>() -> Parser<'a, u8, f64> {
-    is_a(|c: u8| (b'0'..=b'9').contains(&c)).repeat(1..)
-    .map(|digits| {
-        let number_str = String::from_utf8(digits).unwrap_or_default();
-        number_str.parse::<f64>().unwrap_or(0.0)
+    let digit = is_a(|c: u8| c.is_ascii_digit());
+    let rest = is_a(|c: u8| is_identifier_continue(c as char) || c == b'.');
+
+    (sym(b'.').opt() + digit + rest.repeat(0..)).collect()
+    .convert(|bytes| {
+        let text = String::from_utf8_lossy(bytes);
+        lexical::parse_number(&text, &LEX_OPTIONS).map_err(|e| e.to_string())
     })
 }
 
-/// Parse string literals
+/// Parse string literals, which may be empty or span several lines
 fn string<'a>() -> Parser<'a, u8, String> {
-    (sym(b'"') * none_of(b"\"").repeat(0..) - sym(b'"'))
-    .map(|chars| String::from_utf8(chars).unwrap_or_default())
+    let body = if LEX_OPTIONS.escapes {
+        (none_of(b"\"\\").discard() | (sym(b'\\') * any()).discard()).repeat(0..)
+    } else {
+        none_of(b"\"").discard().repeat(0..)
+    };
+
+    (sym(b'"') * body.collect() - sym(b'"'))
+    .convert(|bytes| {
+        let raw = String::from_utf8_lossy(bytes);
+        if LEX_OPTIONS.escapes {
+            lexical::unescape(&raw).map_err(|e| e.to_string())
+        } else {
+            Ok(raw.into_owned())
+        }
+    })
+}
+
+/// Parse a reserved word, requiring that it is not followed by an identifier character
+fn keyword<'a>(word: &'static [u8]) -> Parser<'a, u8, ()> {
+    (seq(word) - !is_a(|c: u8| is_identifier_continue(c as char))).discard()
 }
 
-/// Parse identifiers
+/// Parse identifiers, rejecting reserved words
 fn identifier<'a>() -> Parser<'a, u8, String> {
-    (is_a(|c: u8| (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || c == b'_') +
-     is_a(|c: u8| (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || (c >= b'0' && c <= b'9') || c == b'_').repeat(0..))
-    .map(|(first_char, rest_chars)| {
-        let mut name = String::new();
-        name.push(first_char as char);
-        for &c in &rest_chars {
-            name.push(c as char);
+    (is_a(|c: u8| is_identifier_start(c as char)) +
+     is_a(|c: u8| is_identifier_continue(c as char)).repeat(0..))
+    .collect()
+    .convert(|bytes| {
+        let name = String::from_utf8_lossy(bytes).into_owned();
+        if is_keyword(&name) {
+            Err(format!("reserved word '{}' cannot be used as an identifier", name))
+        } else {
+            Ok(name)
+        }
+    })
+}
+
+/// Parse whitespace and comments, returning the text of any doc comments among them
+fn trivia<'a>() -> Parser<'a, u8, Option<String>> {
+    Parser::new(|input: &'a [u8], start: usize| {
+        // Tokens only ever end on a character boundary, so the rest of the input is valid UTF-8
+        let rest = std::str::from_utf8(&input[start..]).unwrap_or_default();
+        match lexical::skip_trivia(rest, &LEX_OPTIONS) {
+            Ok(trivia) => Ok((trivia.doc, start + trivia.len)),
+            Err(e) => Err(pom::Error::Custom {
+                message: e.to_string(),
+                position: start + e.span.start,
+                inner: None,
+            }),
         }
-        name
     })
 }
 
-/// Parse whitespace
+/// Parse whitespace and comments
 fn ws<'a>() -> Parser<'a, u8, ()> {
-    is_a(|c: u8| c == b' ' || c == b'\t' || c == b'\r' || c == b'\n').repeat(0..).map(|_| ())
+    trivia().discard()
 }
\ No newline at end of file
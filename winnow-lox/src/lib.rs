@@ -15,14 +15,40 @@
 
 mod parser;
 
-pub use parser::parse_program;
-pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp};
+pub use parser::{parse_program, parse_program_borrowed, parse_program_diagnostic, parse_program_with_max_depth};
+pub use lox_ast::{Program, Stmt, Expr, Value, BinaryOp, UnaryOp, LanguageOptions};
+use lox_parser_api::{Capabilities, LoxParser, ParseError, ParseOptions};
 
 /// Parser information for identification
 pub const PARSER_NAME: &str = "winnow";
-pub const PARSER_VERSION: &str = "0.6";
+pub const PARSER_VERSION: &str = env!("LIBRARY_VERSION");
 pub const PARSER_DESCRIPTION: &str = "Modern successor to nom with better error handling";
 
+/// This parser behind the common `LoxParser` interface
+pub struct WinnowParser;
+
+impl LoxParser for WinnowParser {
+    fn name(&self) -> &'static str { PARSER_NAME }
+    fn version(&self) -> &'static str { PARSER_VERSION }
+    fn description(&self) -> &'static str { PARSER_DESCRIPTION }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            full_grammar: false,
+            spans: false,
+            error_recovery: false,
+            streaming: false,
+            extensions: LanguageOptions::STRICT,
+            lexical: parser::LEX_OPTIONS,
+        }
+    }
+
+    fn parse(&self, source: &str, options: &ParseOptions) -> Result<Program, ParseError> {
+        self.capabilities().check(options)?;
+        parse_program_with_max_depth(source, options.max_depth).map_err(|error| ParseError::syntax(*error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,7 +91,7 @@ mod tests {
 
         assert_eq!(result.statements.len(), 1);
         match &result.statements[0] {
-            Stmt::VarDeclaration { name, initializer } => {
+            Stmt::VarDeclaration { name, initializer, .. } => {
                 assert_eq!(name, "x");
                 assert!(matches!(
                     initializer,
@@ -75,4 +101,30 @@ mod tests {
             _ => panic!("Expected variable declaration"),
         }
     }
+
+    #[test]
+    fn test_borrowed_ast_points_into_source() {
+        use lox_ast::borrowed;
+        use std::borrow::Cow;
+
+        let source = String::from(r#"var greeting = "hello"; print greeting;"#);
+        let program = parse_program_borrowed(&source).unwrap();
+        let in_source = |text: &str| source.as_bytes().as_ptr_range().contains(&text.as_ptr());
+
+        match &program.statements[..] {
+            [
+                borrowed::Stmt::VarDeclaration {
+                    name,
+                    initializer: Some(borrowed::Expr::Literal(borrowed::Value::String(Cow::Borrowed(value)))),
+                    ..
+                },
+                borrowed::Stmt::Print(borrowed::Expr::Variable(variable)),
+            ] => {
+                assert_eq!((*name, *value, *variable), ("greeting", "hello", "greeting"));
+                assert!(in_source(name) && in_source(value) && in_source(variable));
+            }
+            other => panic!("Unexpected statements: {:?}", other),
+        }
+        assert_eq!(program.into_owned(), parse_program(&source).unwrap());
+    }
 }
@@ -1,61 +1,119 @@
 //! Winnow-based parser for the Lox language
 
+use std::borrow::Cow;
+
 use winnow::{
-    ascii::{digit1},
-    combinator::{alt, delimited, opt, preceded, repeat, terminated},
-    token::{take_while},
-    ModalResult, Parser, stream::AsChar,
+    combinator::{alt, cut_err, delimited, not, opt, repeat, terminated},
+    error::{ContextError, ErrMode, FromExternalError, ParseError, ParserError},
+    stream::Stream,
+    token::{one_of, take_while},
+    ModalResult, Parser,
+};
+use lox_ast::{BinaryOp, Diagnostic, LanguageOptions, LexOptions, UnaryOp, hints};
+use lox_ast::borrowed::{Expr, Program, Stmt, Value};
+use lox_ast::depth::{self, DEFAULT_MAX_DEPTH};
+use lox_ast::lexical::{
+    self, is_identifier_continue, is_identifier_start, is_keyword, scan_number, scan_string, skip_trivia,
+    starts_number,
 };
-use lox_ast::{BinaryOp, Expr, Program, Stmt, UnaryOp, Value};
+
+/// Lexical extensions enabled through this crate's cargo features
+pub(crate) const LEX_OPTIONS: LexOptions = LexOptions {
+    escapes: cfg!(feature = "escapes"),
+    number_extensions: cfg!(feature = "number-extensions"),
+    block_comments: cfg!(feature = "block-comments"),
+    doc_comments: cfg!(feature = "doc-comments"),
+};
+
+/// Stack a level of nesting may take while parsing, with room to spare in a
+/// debug build
+const STACK_PER_LEVEL: usize = 32 * 1024;
+
+/// Parse whitespace and comments, returning the text of any doc comments among them
+fn trivia(input: &mut &str) -> ModalResult<Option<String>> {
+    match skip_trivia(input, &LEX_OPTIONS) {
+        Ok(trivia) => {
+            input.next_slice(trivia.len);
+            Ok(trivia.doc)
+        }
+        Err(e) => {
+            // Leave the input at the unterminated comment so the error points there
+            input.next_slice(e.span.start);
+            Err(ErrMode::Cut(ContextError::from_external_error(input, e)))
+        }
+    }
+}
 
 /// Parse whitespace and comments
 fn ws(input: &mut &str) -> ModalResult<()> {
-    repeat::<_, _, (), _, _>(0.., alt((
-        take_while(1.., |c: char| c.is_whitespace()).void(),
-        ("//", take_while(0.., |c| c != '\n')).void(),
-    )))
-    .void()
-    .parse_next(input)
+    trivia.void().parse_next(input)
+}
+
+/// Parse a reserved word, requiring that it is not followed by an identifier character
+fn keyword<'a>(word: &'static str) -> impl Parser<&'a str, &'a str, ErrMode<ContextError>> {
+    terminated(word, not(one_of(is_identifier_continue)))
 }
 
-/// Parse an identifier
-fn identifier(input: &mut &str) -> ModalResult<String> {
-    take_while(1.., |c: char| c.is_alphanum() || c == '_')
-        .verify(|s: &str| s.chars().next().unwrap().is_alpha() || s.starts_with('_'))
-        .map(|s: &str| s.to_string())
+/// Parse an identifier that is not a reserved word
+fn identifier<'a>(input: &mut &'a str) -> ModalResult<&'a str> {
+    (one_of(is_identifier_start), take_while(0.., is_identifier_continue))
+        .take()
+        .verify(|s: &str| !is_keyword(s))
         .parse_next(input)
 }
 
-/// Parse a string literal
-fn string_literal(input: &mut &str) -> ModalResult<String> {
-    delimited(
-        '"',
-        take_while(0.., |c| c != '"').map(|s: &str| s.to_string()),
-        '"',
-    )
-    .parse_next(input)
+/// Parse a string literal; once the opening quote is seen, failing to close it is fatal
+///
+/// The value borrows from the input unless escape sequences were decoded.
+fn string_literal<'a>(input: &mut &'a str) -> ModalResult<Cow<'a, str>> {
+    if !input.starts_with('"') {
+        return Err(ErrMode::from_input(input));
+    }
+
+    match scan_string(input, &LEX_OPTIONS) {
+        Ok((value, len)) => {
+            input.next_slice(len);
+            Ok(value)
+        }
+        Err(e) => {
+            // Leave the input at the offending escape (or the opening quote) so the error points there
+            input.next_slice(e.span.start);
+            Err(ErrMode::Cut(ContextError::from_external_error(input, e)))
+        }
+    }
 }
 
-/// Parse a number literal
+/// Parse a number literal; a malformed one such as `1.` or `1e10` is fatal
 fn number_literal(input: &mut &str) -> ModalResult<f64> {
-    (digit1, opt(('.', digit1)))
-        .take()
-        .try_map(|s: &str| s.parse::<f64>())
-        .parse_next(input)
+    if !starts_number(input) {
+        return Err(ErrMode::from_input(input));
+    }
+
+    match scan_number(input, &LEX_OPTIONS) {
+        Ok((value, len)) => {
+            input.next_slice(len);
+            Ok(value)
+        }
+        Err(e) => {
+            // Leave the input at the offending character so the error points there
+            input.next_slice(e.span.start);
+            Err(ErrMode::Cut(ContextError::from_external_error(input, e)))
+        }
+    }
 }
 
 /// Parse a boolean literal
 fn boolean_literal(input: &mut &str) -> ModalResult<bool> {
-    alt(("true".value(true), "false".value(false))).parse_next(input)
+    alt((keyword("true").value(true), keyword("false").value(false))).parse_next(input)
 }
 
 /// Parse nil literal
 fn nil_literal(input: &mut &str) -> ModalResult<()> {
-    "nil".value(()).parse_next(input)
+    keyword("nil").value(()).parse_next(input)
 }
 
 /// Parse a literal value
-fn literal(input: &mut &str) -> ModalResult<Value> {
+fn literal<'a>(input: &mut &'a str) -> ModalResult<Value<'a>> {
     alt((
         nil_literal.map(|_| Value::Nil),
         boolean_literal.map(Value::Bool),
@@ -66,7 +124,7 @@ fn literal(input: &mut &str) -> ModalResult<Value> {
 }
 
 /// Parse a primary expression
-fn primary(input: &mut &str) -> ModalResult<Expr> {
+fn primary<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     alt((
         literal.map(Expr::Literal),
         identifier.map(Expr::Variable),
@@ -81,7 +139,7 @@ fn primary(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse unary expressions
-fn unary(input: &mut &str) -> ModalResult<Expr> {
+fn unary<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     alt((
         (
             alt((
@@ -101,7 +159,7 @@ fn unary(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse multiplication and division
-fn factor(input: &mut &str) -> ModalResult<Expr> {
+fn factor<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), BinaryOp, (), Expr)>) = (
         unary,
         repeat(
@@ -129,7 +187,7 @@ fn factor(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse addition and subtraction
-fn term(input: &mut &str) -> ModalResult<Expr> {
+fn term<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), BinaryOp, (), Expr)>) = (
         factor,
         repeat(
@@ -157,7 +215,7 @@ fn term(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse comparison operators
-fn comparison(input: &mut &str) -> ModalResult<Expr> {
+fn comparison<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), BinaryOp, (), Expr)>) = (
         term,
         repeat(
@@ -187,7 +245,7 @@ fn comparison(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse equality operators
-fn equality(input: &mut &str) -> ModalResult<Expr> {
+fn equality<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), BinaryOp, (), Expr)>) = (
         comparison,
         repeat(
@@ -215,10 +273,10 @@ fn equality(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse logical AND
-fn logical_and(input: &mut &str) -> ModalResult<Expr> {
+fn logical_and<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), &str, (), Expr)>) = (
         equality,
-        repeat(0.., (ws, "and", ws, equality)),
+        repeat(0.., (ws, keyword("and"), ws, equality)),
     )
         .parse_next(input)?;
 
@@ -232,10 +290,10 @@ fn logical_and(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse logical OR
-fn logical_or(input: &mut &str) -> ModalResult<Expr> {
+fn logical_or<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     let (init, ops): (Expr, Vec<((), &str, (), Expr)>) = (
         logical_and,
-        repeat(0.., (ws, "or", ws, logical_and)),
+        repeat(0.., (ws, keyword("or"), ws, logical_and)),
     )
         .parse_next(input)?;
 
@@ -249,7 +307,7 @@ fn logical_or(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse assignment
-fn assignment(input: &mut &str) -> ModalResult<Expr> {
+fn assignment<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     alt((
         (identifier, ws, '=', ws, assignment).map(|(name, _, _, _, value)| {
             Expr::Assignment {
@@ -263,58 +321,92 @@ fn assignment(input: &mut &str) -> ModalResult<Expr> {
 }
 
 /// Parse a full expression
-fn expression(input: &mut &str) -> ModalResult<Expr> {
+fn expression<'a>(input: &mut &'a str) -> ModalResult<Expr<'a>> {
     assignment.parse_next(input)
 }
 
-/// Parse a print statement
-fn print_stmt(input: &mut &str) -> ModalResult<Stmt> {
-    ("print", ws, expression, ws, ';')
-        .map(|(_, _, expr, _, _)| Stmt::Print(expr))
+/// Parse a print statement; after the keyword, a failure is reported where it happens
+fn print_stmt<'a>(input: &mut &'a str) -> ModalResult<Stmt<'a>> {
+    (keyword("print"), cut_err((ws, expression, ws, ';')))
+        .map(|(_, (_, expr, _, _))| Stmt::Print(expr))
         .parse_next(input)
 }
 
-/// Parse a variable declaration
-fn var_declaration(input: &mut &str) -> ModalResult<Stmt> {
+/// Parse a variable declaration; after the keyword, a failure is reported where it happens
+fn var_declaration<'a>(input: &mut &'a str) -> ModalResult<Stmt<'a>> {
     (
-        "var",
-        ws,
-        identifier,
-        opt((ws, '=', ws, expression)),
-        ws,
-        ';',
+        keyword("var"),
+        cut_err((ws, identifier, opt((ws, '=', ws, expression)), ws, ';')),
     )
-        .map(|(_, _, name, initializer, _, _)| Stmt::VarDeclaration {
+        .map(|(_, (_, name, initializer, _, _))| Stmt::VarDeclaration {
             name,
+            type_annotation: None,
             initializer: initializer.map(|(_, _, _, expr)| expr),
+            doc: None,
         })
         .parse_next(input)
 }
 
 /// Parse an expression statement
-fn expr_stmt(input: &mut &str) -> ModalResult<Stmt> {
-    terminated(expression, (ws, ';'))
+///
+/// It is the last kind of statement tried, so once the expression has parsed
+/// a missing `;` is reported where it should be rather than at the statement.
+fn expr_stmt<'a>(input: &mut &'a str) -> ModalResult<Stmt<'a>> {
+    terminated(expression, cut_err((ws, ';')))
         .map(Stmt::Expression)
         .parse_next(input)
 }
 
-/// Parse a statement
-fn statement(input: &mut &str) -> ModalResult<Stmt> {
-    preceded(
-        ws,
-        alt((print_stmt, var_declaration, expr_stmt)),
-    )
-    .parse_next(input)
+/// Parse a statement, attaching any doc comment before it
+fn statement<'a>(input: &mut &'a str) -> ModalResult<Stmt<'a>> {
+    (trivia, alt((print_stmt, var_declaration, expr_stmt)))
+        .map(|(doc, stmt)| stmt.with_doc(doc))
+        .parse_next(input)
 }
 
 /// Parse a program (list of statements)
-fn program(input: &mut &str) -> ModalResult<Program> {
+fn program<'a>(input: &mut &'a str) -> ModalResult<Program<'a>> {
     terminated(repeat(0.., statement), ws)
         .map(Program::new)
         .parse_next(input)
 }
 
 /// Parse a complete Lox program from a string
-pub fn parse_program(input: &str) -> Result<Program, winnow::error::ParseError<&str, winnow::error::ContextError>> {
+///
+/// Nesting is not limited here, so very deeply nested input overflows the
+/// stack; `parse_program_diagnostic` refuses such input instead.
+pub fn parse_program(input: &str) -> Result<lox_ast::Program, ParseError<&str, ContextError>> {
+    parse_program_borrowed(input).map(Program::into_owned)
+}
+
+/// Parse a complete Lox program into an AST that borrows from `input`
+///
+/// Nesting is not limited, as for `parse_program`.
+pub fn parse_program_borrowed(input: &str) -> Result<Program<'_>, ParseError<&str, ContextError>> {
     program.parse(input)
 }
+
+/// Parse a complete Lox program, reporting a failure as a `Diagnostic` pointing into `input`
+pub fn parse_program_diagnostic(input: &str) -> Result<lox_ast::Program, Box<Diagnostic>> {
+    parse_program_with_max_depth(input, DEFAULT_MAX_DEPTH)
+}
+
+/// Like `parse_program_diagnostic`, but allowing nesting up to `max_depth`
+/// levels instead of `DEFAULT_MAX_DEPTH`
+pub fn parse_program_with_max_depth(input: &str, max_depth: usize) -> Result<lox_ast::Program, Box<Diagnostic>> {
+    let depth = depth::check_depth(input, max_depth, &LEX_OPTIONS)?;
+    depth::with_stack(depth, STACK_PER_LEVEL, || {
+        parse_program(input).map_err(|error| Box::new(match lexical::validate(input, &LEX_OPTIONS) {
+            Err(lex) => lex.into(),
+            Ok(()) => {
+                let diagnostic = Diagnostic::unexpected(input, error.offset(), &LEX_OPTIONS)
+                    .with_unclosed_delimiter(input, &LEX_OPTIONS);
+                let diagnostic = match error.inner().to_string() {
+                    context if context.is_empty() => diagnostic,
+                    context => diagnostic.with_note(context),
+                };
+                hints::explain(input, diagnostic, &LEX_OPTIONS, &LanguageOptions::STRICT)
+            }
+        }))
+    })
+}